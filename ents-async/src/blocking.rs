@@ -0,0 +1,57 @@
+//! A runtime-independent `spawn_blocking`.
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+struct Shared<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// The result of a closure running on its own thread. Panics of the closure
+/// resume in the task awaiting it.
+pub struct Unblock<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// Run `f` on a new thread, resolving to its result once it returns
+pub fn unblock<F, T>(f: F) -> Unblock<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+    let worker = Arc::clone(&shared);
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        let mut shared = worker.lock().unwrap_or_else(|e| e.into_inner());
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    });
+    Unblock { shared }
+}
+
+impl<T> Future for Unblock<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
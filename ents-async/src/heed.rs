@@ -0,0 +1,64 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use ents::{DatabaseError, Transactional};
+use ents_heed::{HeedEnv, ReadTxn, Txn};
+
+use crate::{unblock, AsyncStore};
+
+/// An [`AsyncStore`] over a shared LMDB environment
+#[derive(Clone)]
+pub struct AsyncHeed {
+    env: Arc<HeedEnv>,
+}
+
+impl AsyncHeed {
+    pub fn new(env: Arc<HeedEnv>) -> Self {
+        Self { env }
+    }
+
+    pub fn env(&self) -> &Arc<HeedEnv> {
+        &self.env
+    }
+}
+
+impl AsyncStore for AsyncHeed {
+    type Txn<'a> = Txn<'a>;
+    type ReadTxn<'a> = ReadTxn<'a>;
+
+    fn write<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&Txn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static,
+    {
+        let env = Arc::clone(&self.env);
+        unblock(move || {
+            let txn = env.write_txn()?;
+            let result = f(&txn)?;
+            txn.commit()?;
+            Ok(result)
+        })
+    }
+
+    fn read<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&ReadTxn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static,
+    {
+        let env = Arc::clone(&self.env);
+        unblock(move || {
+            let txn = env.read_txn()?;
+            f(&txn)
+        })
+    }
+}
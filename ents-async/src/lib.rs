@@ -0,0 +1,156 @@
+//! Async access to ents stores.
+//!
+//! The backends are synchronous, and their transactions are tied to the
+//! thread that opened them, so an async service must not run them on its
+//! executor. [`AsyncStore`] instead runs each transaction as a closure on a
+//! blocking thread and returns a future of its result. The futures do not
+//! depend on any particular runtime.
+//!
+//! Adapters are provided for `ents-heed` ([`AsyncHeed`], feature `heed`) and
+//! `ents-sqlite` ([`AsyncSqlite`], feature `sqlite`).
+//!
+//! ```ignore
+//! let store = AsyncHeed::new(Arc::new(HeedEnv::open(path, None)?));
+//!
+//! let id = store.create(User::new("alice".into(), "a@b.c".into())).await?;
+//! let renamed = store
+//!     .write(move |txn| {
+//!         let user = txn.get_required_as::<User>(id)?;
+//!         txn.update(user, |u| u.username = "alicia".into())
+//!     })
+//!     .await?;
+//! ```
+
+use std::future::Future;
+
+use ents::{
+    DatabaseError, DatabaseResult, Edge, EdgeCursorOwned, EdgeQuery, EdgeValue,
+    Ent, EntExt, EntWithEdges, Id, QueryEdge, ReadTransactional, Transactional,
+};
+
+mod blocking;
+#[cfg(feature = "heed")]
+mod heed;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use blocking::{unblock, Unblock};
+#[cfg(feature = "heed")]
+pub use heed::AsyncHeed;
+#[cfg(feature = "sqlite")]
+pub use sqlite::AsyncSqlite;
+
+/// A store whose transactions run off the async executor.
+///
+/// Each call opens its own transaction, so consecutive calls are not atomic
+/// together; put operations that must commit together in one
+/// [`AsyncStore::write`] closure.
+///
+/// Every call also spawns an OS thread to run its closure on, which costs
+/// far more than a small read itself. Batch lookups into one
+/// [`AsyncStore::read`] closure instead of awaiting many single calls, and
+/// bound how many calls are in flight at once.
+pub trait AsyncStore: Clone + Send + Sync + 'static {
+    type Txn<'a>: Transactional;
+    type ReadTxn<'a>: ReadTransactional;
+
+    /// Run `f` in a write transaction on a blocking thread, committing when
+    /// it succeeds and rolling back when it fails
+    fn write<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&Self::Txn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static;
+
+    /// Run `f` in a read-only transaction on a blocking thread
+    fn read<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&Self::ReadTxn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static;
+
+    /// Load entity `id`. Resolves to None if the entity does not exist.
+    fn get(
+        &self,
+        id: Id,
+    ) -> impl Future<Output = DatabaseResult<Option<Box<dyn Ent>>>> + Send {
+        self.read(move |txn| txn.get(id))
+    }
+
+    /// Load entity `id` as a `T`. Resolves to None if the entity does not
+    /// exist or is of another type.
+    fn get_as<T: Ent>(
+        &self,
+        id: Id,
+    ) -> impl Future<Output = Result<Option<T>, DatabaseError>> + Send {
+        self.read(move |txn| Ok(txn.get(id)?.and_then(|e| e.into_ent::<T>())))
+    }
+
+    fn create<E: Ent + EntWithEdges>(
+        &self,
+        ent: E,
+    ) -> impl Future<Output = Result<Id, DatabaseError>> + Send {
+        self.write(move |txn| txn.create(ent))
+    }
+
+    fn delete<E: Ent + EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+        self.write(move |txn| txn.delete::<E>(id))
+    }
+
+    fn create_edge(
+        &self,
+        edge: EdgeValue,
+    ) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+        self.write(move |txn| txn.create_edge(edge))
+    }
+
+    fn hide_edge(
+        &self,
+        edge: EdgeValue,
+    ) -> impl Future<Output = Result<bool, DatabaseError>> + Send {
+        self.write(move |txn| txn.hide_edge(&edge))
+    }
+
+    /// One page of the edges named `names` leaving `source`, in ascending
+    /// order after `cursor`
+    fn find_edges(
+        &self,
+        source: Id,
+        names: Vec<Vec<u8>>,
+        cursor: Option<EdgeCursorOwned>,
+    ) -> impl Future<Output = Result<Vec<Edge>, DatabaseError>> + Send {
+        self.read(move |txn| {
+            let names: Vec<&[u8]> = names.iter().map(Vec::as_slice).collect();
+            let query = EdgeQuery::asc(&names)
+                .with_cursor_opt(cursor.as_ref().map(|c| c.as_cursor()));
+            txn.find_edges(source, query)
+        })
+    }
+
+    /// One page of the edges named `names` pointing at `dest`, in ascending
+    /// order after `cursor`
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        names: Vec<Vec<u8>>,
+        cursor: Option<EdgeCursorOwned>,
+    ) -> impl Future<Output = Result<Vec<Edge>, DatabaseError>> + Send {
+        self.read(move |txn| {
+            let names: Vec<&[u8]> = names.iter().map(Vec::as_slice).collect();
+            let query = EdgeQuery::asc(&names)
+                .with_cursor_opt(cursor.as_ref().map(|c| c.as_cursor()));
+            txn.find_edges_to(dest, query)
+        })
+    }
+}
@@ -0,0 +1,179 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use ents::bloom::IdFilter;
+use ents::dangling::IncomingEdgePolicy;
+use ents::decode::UnknownTypePolicy;
+use ents::format::JsonFormat;
+use ents::history::HistoryRetention;
+use ents::ids::IdProvider;
+use ents::{DatabaseError, Transactional};
+use ents_sqlite::{ReadTxn, Txn};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+use crate::{unblock, AsyncStore};
+
+/// An [`AsyncStore`] over a sqlite connection pool
+#[derive(Clone)]
+pub struct AsyncSqlite {
+    pool: Pool<SqliteConnectionManager>,
+    json_format: JsonFormat,
+    ids: Option<Arc<dyn IdProvider>>,
+    unknown_types: UnknownTypePolicy,
+    incoming_edges: IncomingEdgePolicy,
+    edge_integrity: bool,
+    capture_changes: bool,
+    history: Option<HistoryRetention>,
+    id_filter: Option<Arc<IdFilter>>,
+}
+
+impl AsyncSqlite {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self {
+            pool,
+            json_format: JsonFormat::COMPACT,
+            ids: None,
+            unknown_types: UnknownTypePolicy::Strict,
+            incoming_edges: IncomingEdgePolicy::Remove,
+            edge_integrity: false,
+            capture_changes: false,
+            history: None,
+            id_filter: None,
+        }
+    }
+
+    /// Assign ids of new entities from `ids`
+    pub fn with_id_provider(mut self, ids: Arc<dyn IdProvider>) -> Self {
+        self.ids = Some(ids);
+        self
+    }
+
+    /// Serialize entities written through this store with `format`
+    pub fn with_json_format(mut self, format: JsonFormat) -> Self {
+        self.json_format = format;
+        self
+    }
+
+    /// Handle stored entities of unregistered types per `policy`
+    pub fn with_unknown_types(mut self, policy: UnknownTypePolicy) -> Self {
+        self.unknown_types = policy;
+        self
+    }
+
+    /// Handle the edges pointing at deleted entities per `policy`
+    pub fn with_incoming_edges(mut self, policy: IncomingEdgePolicy) -> Self {
+        self.incoming_edges = policy;
+        self
+    }
+
+    /// Refuse to create edges whose source or destination does not exist
+    pub fn with_edge_integrity(mut self, enabled: bool) -> Self {
+        self.edge_integrity = enabled;
+        self
+    }
+
+    /// Append the changes of every write to the `changes` table
+    pub fn with_change_capture(mut self, enabled: bool) -> Self {
+        self.capture_changes = enabled;
+        self
+    }
+
+    /// Keep the versions of an entity that updates replace, bounded by
+    /// `retention`
+    pub fn with_history(mut self, retention: HistoryRetention) -> Self {
+        self.history = Some(retention);
+        self
+    }
+
+    /// Skip the lookup of ids `filter` has not seen, see
+    /// [`ents_sqlite::rebuild_id_filter`]
+    pub fn with_id_filter(mut self, filter: Arc<IdFilter>) -> Self {
+        self.id_filter = Some(filter);
+        self
+    }
+
+    pub fn pool(&self) -> &Pool<SqliteConnectionManager> {
+        &self.pool
+    }
+}
+
+impl AsyncStore for AsyncSqlite {
+    type Txn<'a> = Txn<'a>;
+    type ReadTxn<'a> = ReadTxn<'a>;
+
+    fn write<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&Txn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        let json_format = self.json_format;
+        let ids = self.ids.clone();
+        let unknown_types = self.unknown_types;
+        let incoming_edges = self.incoming_edges;
+        let edge_integrity = self.edge_integrity;
+        let capture_changes = self.capture_changes;
+        let history = self.history;
+        let id_filter = self.id_filter.clone();
+        unblock(move || {
+            let mut conn = pool.get().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let tx = conn.transaction().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let mut txn = Txn::new(tx)
+                .with_json_format(json_format)
+                .with_unknown_types(unknown_types)
+                .with_incoming_edges(incoming_edges)
+                .with_edge_integrity(edge_integrity)
+                .with_change_capture(capture_changes);
+            if let Some(ids) = ids {
+                txn = txn.with_id_provider(ids);
+            }
+            if let Some(retention) = history {
+                txn = txn.with_history(retention);
+            }
+            if let Some(filter) = id_filter {
+                txn = txn.with_id_filter(filter);
+            }
+            let result = f(&txn)?;
+            txn.commit()?;
+            Ok(result)
+        })
+    }
+
+    fn read<F, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, DatabaseError>> + Send
+    where
+        F: for<'a> FnOnce(&ReadTxn<'a>) -> Result<R, DatabaseError>
+            + Send
+            + 'static,
+        R: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        let unknown_types = self.unknown_types;
+        let id_filter = self.id_filter.clone();
+        unblock(move || {
+            let mut conn = pool.get().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let tx = conn.transaction().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let mut txn = ReadTxn::new(tx).with_unknown_types(unknown_types);
+            if let Some(filter) = id_filter {
+                txn = txn.with_id_filter(filter);
+            }
+            f(&txn)
+        })
+    }
+}
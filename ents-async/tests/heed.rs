@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use ents::{DatabaseError, EdgeCursorOwned, EdgeValue, Transactional};
+use ents_async::{AsyncHeed, AsyncStore};
+use ents_heed::HeedEnv;
+use ents_test_suite::{TestEntity, User};
+use tempfile::tempdir;
+
+mod common;
+use common::block_on;
+
+#[test]
+fn test_async_heed() {
+    let dir = tempdir().unwrap();
+    let store =
+        AsyncHeed::new(Arc::new(HeedEnv::open(dir.path(), None).unwrap()));
+
+    block_on(async {
+        let alice = store
+            .create(User::new("alice".to_string(), "a@x.com".to_string()))
+            .await?;
+        let bob = store
+            .create(User::new("bob".to_string(), "b@x.com".to_string()))
+            .await?;
+        store
+            .create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+            .await?;
+
+        let user = store.get_as::<User>(alice).await?.unwrap();
+        assert_eq!(user.username, "alice");
+        assert!(store.get_as::<TestEntity>(alice).await?.is_none());
+
+        let edges = store
+            .find_edges(alice, vec![b"follows".to_vec()], None)
+            .await?;
+        assert_eq!(edges.len(), 1);
+        let after = store
+            .find_edges(
+                alice,
+                vec![b"follows".to_vec()],
+                Some(EdgeCursorOwned::from_edge(&edges[0])),
+            )
+            .await?;
+        assert!(after.is_empty());
+        let incoming = store.find_edges_to(bob, vec![], None).await?;
+        assert_eq!(incoming[0].source, alice);
+
+        // A failing closure rolls its writes back
+        let failed = store
+            .write(move |txn| {
+                txn.delete::<User>(bob)?;
+                Err::<(), _>(DatabaseError::Frozen)
+            })
+            .await;
+        assert!(failed.is_err());
+        assert!(store.get(bob).await?.is_some());
+
+        store.delete::<User>(bob).await?;
+        assert!(store.get(bob).await?.is_none());
+        Ok::<_, DatabaseError>(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_concurrent_futures() {
+    let dir = tempdir().unwrap();
+    let store =
+        AsyncHeed::new(Arc::new(HeedEnv::open(dir.path(), None).unwrap()));
+
+    // Futures started together complete independently of polling order
+    let pending: Vec<_> = (0..8)
+        .map(|i| store.create(TestEntity::new(format!("e{}", i), i)))
+        .collect();
+    let ids: Vec<_> = pending
+        .into_iter()
+        .rev()
+        .map(|f| block_on(f).unwrap())
+        .collect();
+    for id in ids {
+        assert!(block_on(store.get(id)).unwrap().is_some());
+    }
+}
+
+#[test]
+#[should_panic(expected = "boom")]
+fn test_panic_resumes_in_caller() {
+    let dir = tempdir().unwrap();
+    let store =
+        AsyncHeed::new(Arc::new(HeedEnv::open(dir.path(), None).unwrap()));
+    let _ = block_on(
+        store.read(|_| -> Result<(), DatabaseError> { panic!("boom") }),
+    );
+}
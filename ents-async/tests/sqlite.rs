@@ -0,0 +1,53 @@
+use ents::{DatabaseError, EdgeValue, ReadTransactional};
+use ents_async::{AsyncSqlite, AsyncStore};
+use ents_test_suite::User;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use tempfile::tempdir;
+
+mod common;
+use common::block_on;
+
+#[test]
+fn test_async_sqlite() {
+    let dir = tempdir().unwrap();
+    let pool =
+        Pool::new(SqliteConnectionManager::file(dir.path().join("db.sqlite")))
+            .unwrap();
+    ents_sqlite::init_schema(&pool.get().unwrap()).unwrap();
+    let store = AsyncSqlite::new(pool);
+
+    block_on(async {
+        let alice = store
+            .create(User::new("alice".to_string(), "a@x.com".to_string()))
+            .await?;
+        let bob = store
+            .create(User::new("bob".to_string(), "b@x.com".to_string()))
+            .await?;
+        store
+            .create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+            .await?;
+
+        assert_eq!(store.get_as::<User>(bob).await?.unwrap().username, "bob");
+        let edges = store
+            .find_edges(alice, vec![b"follows".to_vec()], None)
+            .await?;
+        assert_eq!(edges.len(), 1);
+        assert!(
+            store
+                .hide_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+                .await?
+        );
+        assert!(store.find_edges(alice, vec![], None).await?.is_empty());
+
+        let users = store
+            .write(|txn| txn.list_ids_by_type("User", None, 10))
+            .await?;
+        assert_eq!(users, vec![alice, bob]);
+
+        store.delete::<User>(alice).await?;
+        assert!(store.get(alice).await?.is_none());
+        Ok::<_, DatabaseError>(())
+    })
+    .unwrap();
+}
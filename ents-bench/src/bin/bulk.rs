@@ -0,0 +1,140 @@
+//! Compares `bulk_load` with creating entities one transaction at a time.
+//!
+//! ```text
+//! bulk [--backend heed|sqlite] [--entities N]
+//! ```
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use ents::{EdgeValue, Ent, Id, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::TestEntity;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+/// Edge name linking each loaded entity to the next
+const NEXT_EDGE: &[u8] = b"next";
+
+fn entity(i: usize) -> TestEntity {
+    TestEntity::new(format!("entity{}", i), i as i32)
+}
+
+/// Entities with ids 1..=n, sorted by id
+fn entities(n: usize) -> impl Iterator<Item = Box<dyn Ent>> {
+    (1..=n).map(|i| {
+        let mut ent = entity(i);
+        ent.set_id(i as Id);
+        Box::new(ent) as Box<dyn Ent>
+    })
+}
+
+/// A chain over the entities of [`entities`], sorted by source
+fn edges(n: usize) -> impl Iterator<Item = EdgeValue> {
+    (1..n as Id).map(|i| EdgeValue::new(i, NEXT_EDGE.to_vec(), i + 1))
+}
+
+fn time<F: FnOnce() -> anyhow::Result<()>>(f: F) -> anyhow::Result<Duration> {
+    let start = Instant::now();
+    f()?;
+    Ok(start.elapsed())
+}
+
+fn run_heed(dir: &Path, n: usize) -> anyhow::Result<(Duration, Duration)> {
+    let env = HeedEnv::open(dir.join("create"), None)?;
+    let create = time(|| {
+        let mut last = None;
+        for i in 1..=n {
+            let txn = env.write_txn()?;
+            let id = txn.create(entity(i))?;
+            if let Some(prev) = last {
+                txn.create_edge(EdgeValue::new(prev, NEXT_EDGE.to_vec(), id))?;
+            }
+            txn.commit()?;
+            last = Some(id);
+        }
+        Ok(())
+    })?;
+
+    let env = HeedEnv::open(dir.join("bulk"), None)?;
+    let bulk = time(|| {
+        let txn = env.write_txn()?;
+        txn.bulk_load(entities(n), edges(n))?;
+        txn.commit()?;
+        Ok(())
+    })?;
+    Ok((create, bulk))
+}
+
+fn sqlite_pool(path: &Path) -> anyhow::Result<Pool<SqliteConnectionManager>> {
+    let pool = Pool::new(SqliteConnectionManager::file(path))?;
+    ents_sqlite::init_schema(&*pool.get()?)?;
+    Ok(pool)
+}
+
+fn run_sqlite(dir: &Path, n: usize) -> anyhow::Result<(Duration, Duration)> {
+    let pool = sqlite_pool(&dir.join("create.sqlite3"))?;
+    let create = time(|| {
+        let mut last = None;
+        for i in 1..=n {
+            let mut conn = pool.get()?;
+            let txn = ents_sqlite::Txn::new(conn.transaction()?);
+            let id = txn.create(entity(i))?;
+            if let Some(prev) = last {
+                txn.create_edge(EdgeValue::new(prev, NEXT_EDGE.to_vec(), id))?;
+            }
+            txn.commit()?;
+            last = Some(id);
+        }
+        Ok(())
+    })?;
+
+    let pool = sqlite_pool(&dir.join("bulk.sqlite3"))?;
+    let bulk = time(|| {
+        let mut conn = pool.get()?;
+        let txn = ents_sqlite::Txn::new(conn.transaction()?);
+        txn.bulk_load(entities(n), edges(n))?;
+        txn.commit()?;
+        Ok(())
+    })?;
+    Ok((create, bulk))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut backend = "heed".to_string();
+    let mut n = 10_000;
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--backend" => backend = value,
+            "--entities" => n = value.parse()?,
+            other => bail!("unknown argument: {}", other),
+        }
+    }
+
+    let dir = tempfile::tempdir()?;
+    let (create, bulk) = match backend.as_str() {
+        "heed" => run_heed(dir.path(), n)?,
+        "sqlite" => run_sqlite(dir.path(), n)?,
+        other => bail!("unknown backend: {}", other),
+    };
+
+    println!(
+        "{} entities and {} edges on {}",
+        n,
+        n.saturating_sub(1),
+        backend
+    );
+    println!("create loop: {:?}", create);
+    println!("bulk_load:   {:?}", bulk);
+    println!(
+        "speedup:     {:.1}x",
+        create.as_secs_f64() / bulk.as_secs_f64()
+    );
+    Ok(())
+}
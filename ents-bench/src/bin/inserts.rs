@@ -0,0 +1,66 @@
+//! Measures `create` throughput and space used on heed with and without
+//! append inserts.
+//!
+//! ```text
+//! inserts [--entities N] [--batch N]
+//! ```
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use ents::Transactional;
+use ents_heed::HeedEnv;
+use ents_test_suite::TestEntity;
+
+/// Create `n` entities, `batch` per write transaction
+fn run(
+    dir: &Path,
+    append: bool,
+    n: usize,
+    batch: usize,
+) -> anyhow::Result<(Duration, f64)> {
+    let env = HeedEnv::open(dir, None)?.with_append_inserts(append);
+    let start = Instant::now();
+    let mut created = 0;
+    while created < n {
+        let txn = env.write_txn()?;
+        for i in created..n.min(created + batch) {
+            txn.create(TestEntity::new(format!("entity{}", i), i as i32))?;
+        }
+        txn.commit()?;
+        created = n.min(created + batch);
+    }
+    Ok((start.elapsed(), env.map_usage()?))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut n = 100_000;
+    let mut batch = 1000;
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--entities" => n = value.parse()?,
+            "--batch" => batch = value.parse::<usize>()?.max(1),
+            other => bail!("unknown argument: {}", other),
+        }
+    }
+
+    let dir = tempfile::tempdir()?;
+    let put = run(&dir.path().join("put"), false, n, batch)?;
+    let append = run(&dir.path().join("append"), true, n, batch)?;
+
+    println!("{} entities in batches of {}", n, batch);
+    for (label, (time, usage)) in [("put", put), ("append", append)] {
+        println!("{:<8} {:?}, {:.2}% of the map", label, time, usage * 100.0);
+    }
+    println!(
+        "speedup: {:.2}x",
+        put.0.as_secs_f64() / append.0.as_secs_f64()
+    );
+    Ok(())
+}
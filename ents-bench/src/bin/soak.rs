@@ -0,0 +1,107 @@
+//! Long-running load test against a heed or sqlite store.
+//!
+//! ```text
+//! soak [--backend heed|sqlite] [--path DIR] [--duration SECS]
+//!      [--report-every SECS] [--mix READ,WRITE,QUERY] [--users N]
+//!      [--seed N]
+//! ```
+//!
+//! Without `--path` the store is created in a temporary directory.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use ents_bench::{run_soak, HeedRunner, OpMix, SoakConfig, SqliteRunner};
+use ents_heed::HeedEnv;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+struct Args {
+    backend: String,
+    path: Option<String>,
+    config: SoakConfig,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = Args {
+        backend: "heed".to_string(),
+        path: None,
+        config: SoakConfig::default(),
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| anyhow!("missing value for {}", flag))
+        };
+        match flag.as_str() {
+            "--backend" => args.backend = value()?,
+            "--path" => args.path = Some(value()?),
+            "--duration" => {
+                args.config.duration = Duration::from_secs(value()?.parse()?);
+            }
+            "--report-every" => {
+                args.config.report_interval =
+                    Duration::from_secs(value()?.parse()?);
+            }
+            "--mix" => args.config.mix = parse_mix(&value()?)?,
+            "--users" => args.config.preload.users = value()?.parse()?,
+            "--seed" => {
+                let seed = value()?.parse()?;
+                args.config.seed = seed;
+                args.config.preload.seed = seed;
+            }
+            other => bail!("unknown argument: {}", other),
+        }
+    }
+
+    Ok(args)
+}
+
+fn parse_mix(s: &str) -> anyhow::Result<OpMix> {
+    let parts = s
+        .split(',')
+        .map(|p| p.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("mix must be READ,WRITE,QUERY weights")?;
+    match parts.as_slice() {
+        [read, write, query] => Ok(OpMix {
+            read: *read,
+            write: *write,
+            query: *query,
+        }),
+        _ => bail!("mix must be READ,WRITE,QUERY weights"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = parse_args()?;
+    let temp_dir = tempfile::tempdir()?;
+    let path = args
+        .path
+        .map(Into::into)
+        .unwrap_or_else(|| temp_dir.path().to_path_buf());
+    let print = |report: &ents_bench::IntervalReport| print!("{}", report);
+
+    let total = match args.backend.as_str() {
+        "heed" => {
+            let env = Arc::new(HeedEnv::open(&path, None)?);
+            run_soak(&mut HeedRunner::new(env), &args.config, print)?
+        }
+        "sqlite" => {
+            std::fs::create_dir_all(&path)?;
+            let manager =
+                SqliteConnectionManager::file(path.join("soak.sqlite3"));
+            let pool = Pool::new(manager)?;
+            ents_sqlite::init_schema(&*pool.get()?)?;
+            run_soak(&mut SqliteRunner::new(pool), &args.config, print)?
+        }
+        other => bail!("unknown backend: {}", other),
+    };
+
+    println!("== total ==");
+    print!("{}", total);
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Load and soak testing tools for ents backends.
+//!
+//! [`run_soak`] drives a weighted mix of reads, writes and edge queries
+//! against any backend exposed as a [`TestCaseRunner`], reporting latency
+//! percentiles and error counts for every reporting interval.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ents::{EdgeQuery, EdgeValue, Id, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::generator::{self, GraphSpec, FOLLOWS_EDGE};
+use ents_test_suite::{TestCaseRunner, User};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Kind of operation issued by the soak loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Read,
+    Write,
+    Query,
+}
+
+impl OpKind {
+    pub const ALL: [OpKind; 3] = [OpKind::Read, OpKind::Write, OpKind::Query];
+
+    fn index(self) -> usize {
+        match self {
+            OpKind::Read => 0,
+            OpKind::Write => 1,
+            OpKind::Query => 2,
+        }
+    }
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpKind::Read => write!(f, "read"),
+            OpKind::Write => write!(f, "write"),
+            OpKind::Query => write!(f, "query"),
+        }
+    }
+}
+
+/// Relative weights of each operation kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpMix {
+    pub read: u32,
+    pub write: u32,
+    pub query: u32,
+}
+
+impl Default for OpMix {
+    fn default() -> Self {
+        Self {
+            read: 70,
+            write: 20,
+            query: 10,
+        }
+    }
+}
+
+impl OpMix {
+    fn pick(&self, rng: &mut StdRng) -> OpKind {
+        let total = self.read + self.write + self.query;
+        let roll = rng.random_range(0..total.max(1));
+        if roll < self.read {
+            OpKind::Read
+        } else if roll < self.read + self.write {
+            OpKind::Write
+        } else {
+            OpKind::Query
+        }
+    }
+}
+
+/// Latency samples of one operation kind over an interval
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+    errors: u64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+    }
+
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// Number of successful operations
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Fraction of operations that failed
+    pub fn error_rate(&self) -> f64 {
+        let total = self.samples.len() as u64 + self.errors;
+        if total == 0 {
+            0.0
+        } else {
+            self.errors as f64 / total as f64
+        }
+    }
+
+    /// Latency at the given percentile (0-100), using the nearest-rank method
+    pub fn percentile(&mut self, p: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        self.samples.sort_unstable();
+        let rank = ((p / 100.0) * self.samples.len() as f64).ceil() as usize;
+        Some(self.samples[rank.clamp(1, self.samples.len()) - 1])
+    }
+
+    /// Fold another set of samples into this one
+    pub fn merge(&mut self, other: &LatencyStats) {
+        self.samples.extend_from_slice(&other.samples);
+        self.errors += other.errors;
+    }
+}
+
+/// Statistics of a reporting interval, or of a whole run
+#[derive(Debug, Clone, Default)]
+pub struct IntervalReport {
+    /// Time since the start of the run at the end of the interval
+    pub elapsed: Duration,
+    pub stats: [LatencyStats; 3],
+}
+
+impl IntervalReport {
+    pub fn get(&mut self, kind: OpKind) -> &mut LatencyStats {
+        &mut self.stats[kind.index()]
+    }
+
+    fn merge(&mut self, other: &IntervalReport) {
+        self.elapsed = other.elapsed;
+        for (mine, theirs) in self.stats.iter_mut().zip(&other.stats) {
+            mine.merge(theirs);
+        }
+    }
+}
+
+impl fmt::Display for IntervalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut report = self.clone();
+        for kind in OpKind::ALL {
+            let stats = report.get(kind);
+            let micros = |d: Option<Duration>| {
+                d.map(|d| d.as_micros()).unwrap_or_default()
+            };
+            writeln!(
+                f,
+                "[{:>6.1}s] {:<5} ops={:<8} p50={}us p95={}us p99={}us errors={} ({:.2}%)",
+                self.elapsed.as_secs_f64(),
+                kind,
+                stats.count(),
+                micros(stats.percentile(50.0)),
+                micros(stats.percentile(95.0)),
+                micros(stats.percentile(99.0)),
+                stats.errors(),
+                stats.error_rate() * 100.0,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parameters of a soak run
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    pub duration: Duration,
+    pub report_interval: Duration,
+    pub mix: OpMix,
+    /// Initial graph written before the timed run starts
+    pub preload: GraphSpec,
+    pub seed: u64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(60),
+            report_interval: Duration::from_secs(10),
+            mix: OpMix::default(),
+            preload: GraphSpec::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// Run the soak loop, calling `report` at the end of every interval, and
+/// return the aggregate statistics of the whole run
+pub fn run_soak<C, F>(
+    runner: &mut C,
+    config: &SoakConfig,
+    mut report: F,
+) -> anyhow::Result<IntervalReport>
+where
+    C: TestCaseRunner,
+    F: FnMut(&IntervalReport),
+{
+    let graph = generator::generate(runner, &config.preload)?;
+    let mut users = graph.users;
+    anyhow::ensure!(!users.is_empty(), "preload must create some users");
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut total = IntervalReport::default();
+    let mut interval = IntervalReport::default();
+    let start = Instant::now();
+    let mut next_report = config.report_interval;
+
+    while start.elapsed() < config.duration {
+        let kind = config.mix.pick(&mut rng);
+        let user = users[rng.random_range(0..users.len())];
+
+        let op_start = Instant::now();
+        let result = match kind {
+            OpKind::Read => runner.execute(|txn| read_op(txn, user)),
+            OpKind::Write => {
+                let n = rng.random::<u32>();
+                runner.execute(|txn| write_op(txn, user, n)).map(|id| {
+                    users.push(id);
+                })
+            }
+            OpKind::Query => runner.execute(|txn| query_op(txn, user)),
+        };
+        match result {
+            Ok(()) => interval.get(kind).record(op_start.elapsed()),
+            Err(_) => interval.get(kind).record_error(),
+        }
+
+        if start.elapsed() >= next_report {
+            interval.elapsed = start.elapsed();
+            report(&interval);
+            total.merge(&interval);
+            interval = IntervalReport::default();
+            next_report += config.report_interval;
+        }
+    }
+
+    interval.elapsed = start.elapsed();
+    total.merge(&interval);
+    Ok(total)
+}
+
+fn read_op<T: Transactional>(txn: T, user: Id) -> anyhow::Result<()> {
+    anyhow::ensure!(txn.get(user)?.is_some(), "user {} not found", user);
+    Ok(())
+}
+
+/// Create a new user following an existing one
+fn write_op<T: Transactional>(txn: T, user: Id, n: u32) -> anyhow::Result<Id> {
+    let id = txn.create(User::new(
+        format!("soak_{}", n),
+        format!("soak_{}@example.com", n),
+    ))?;
+    txn.create_edge(EdgeValue::new(id, FOLLOWS_EDGE.to_vec(), user))?;
+    txn.commit()?;
+    Ok(id)
+}
+
+fn query_op<T: Transactional>(txn: T, user: Id) -> anyhow::Result<()> {
+    txn.find_edges(user, EdgeQuery::asc(&[FOLLOWS_EDGE]))?;
+    Ok(())
+}
+
+/// Runs soak operations against a heed environment
+pub struct HeedRunner {
+    env: Arc<HeedEnv>,
+}
+
+impl HeedRunner {
+    pub fn new(env: Arc<HeedEnv>) -> Self {
+        Self { env }
+    }
+}
+
+impl TestCaseRunner for HeedRunner {
+    type Tx = ents_heed::Txn<'static>;
+
+    fn execute<F, R>(&mut self, f: F) -> anyhow::Result<R>
+    where
+        F: FnOnce(Self::Tx) -> anyhow::Result<R>,
+    {
+        let txn = self.env.write_txn()?;
+        // The txn is consumed by the closure before `self.env` can be
+        // dropped, so extending its lifetime is sound here.
+        let txn = unsafe {
+            std::mem::transmute::<ents_heed::Txn<'_>, ents_heed::Txn<'static>>(
+                txn,
+            )
+        };
+        f(txn)
+    }
+}
+
+/// Runs soak operations against a pool of sqlite connections
+pub struct SqliteRunner {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl SqliteRunner {
+    /// Wrap a pool whose database was set up with [`ents_sqlite::init_schema`]
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+}
+
+impl TestCaseRunner for SqliteRunner {
+    type Tx = ents_sqlite::Txn<'static>;
+
+    fn execute<F, R>(&mut self, f: F) -> anyhow::Result<R>
+    where
+        F: FnOnce(Self::Tx) -> anyhow::Result<R>,
+    {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let txn = ents_sqlite::Txn::new(tx);
+        // The txn is consumed by the closure while `conn` is still alive, so
+        // extending its lifetime is sound here.
+        let txn = unsafe {
+            std::mem::transmute::<ents_sqlite::Txn<'_>, ents_sqlite::Txn<'static>>(
+                txn,
+            )
+        };
+        f(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.percentile(50.0), None);
+
+        for ms in (1..=100).rev() {
+            stats.record(Duration::from_millis(ms));
+        }
+        stats.record_error();
+
+        assert_eq!(stats.percentile(50.0), Some(Duration::from_millis(50)));
+        assert_eq!(stats.percentile(95.0), Some(Duration::from_millis(95)));
+        assert_eq!(stats.percentile(99.0), Some(Duration::from_millis(99)));
+        assert_eq!(stats.percentile(100.0), Some(Duration::from_millis(100)));
+        assert!((stats.error_rate() - 1.0 / 101.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_short_soak_against_heed() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Arc::new(HeedEnv::open(dir.path(), None).unwrap());
+        let mut runner = HeedRunner::new(env);
+
+        let config = SoakConfig {
+            duration: Duration::from_millis(200),
+            report_interval: Duration::from_millis(50),
+            preload: GraphSpec {
+                users: 20,
+                posts: 10,
+                ..GraphSpec::default()
+            },
+            ..SoakConfig::default()
+        };
+        let mut intervals = 0;
+        let mut total =
+            run_soak(&mut runner, &config, |_| intervals += 1).unwrap();
+
+        assert!(intervals >= 2);
+        for kind in OpKind::ALL {
+            assert!(total.get(kind).count() > 0);
+            assert_eq!(total.get(kind).errors(), 0);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Inspects ents stores without writing a program.
+//!
+//! ```text
+//! ents-cli <path> get <id>
+//! ents-cli <path> edges <id> [--incoming]
+//! ents-cli <path> stats [--top N]
+//! ents-cli <path> verify
+//! ```
+//!
+//! A directory is opened as a heed environment and a file as a sqlite
+//! database. The tool is built without the application's entity types, so
+//! entities are shown in their stored form. Nothing is written: transactions
+//! are dropped without committing.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context};
+use ents::decode::{decode_ent, DynamicEnt, UnknownTypePolicy};
+use ents::stats::{edge_name, GraphStats};
+use ents::{
+    Edge, EdgeCursor, EdgeQuery, EntExt, Id, QueryEdge, ReadTransactional,
+};
+use ents_heed::HeedEnv;
+use ents_sqlite::{ReadTxn, Txn};
+use r2d2::Pool;
+use r2d2_sqlite::rusqlite::Connection;
+use r2d2_sqlite::SqliteConnectionManager;
+
+/// Number of edges fetched per query
+const PAGE_SIZE: usize = 1000;
+
+const USAGE: &str = "usage: ents-cli <path> get <id>
+       ents-cli <path> edges <id> [--incoming]
+       ents-cli <path> stats [--top N]
+       ents-cli <path> verify";
+
+enum Command {
+    Get(Id),
+    Edges { id: Id, incoming: bool },
+    Stats { top: usize },
+    Verify,
+}
+
+fn parse_args() -> anyhow::Result<(PathBuf, Command)> {
+    let mut args = std::env::args().skip(1);
+    let path = PathBuf::from(args.next().ok_or_else(|| anyhow!(USAGE))?);
+    let name = args.next().ok_or_else(|| anyhow!(USAGE))?;
+    let mut id = || -> anyhow::Result<Id> {
+        let id = args.next().ok_or_else(|| anyhow!(USAGE))?;
+        id.parse().with_context(|| format!("invalid id {}", id))
+    };
+    let command = match name.as_str() {
+        "get" => Command::Get(id()?),
+        "edges" => {
+            let id = id()?;
+            let incoming = match args.next().as_deref() {
+                None => false,
+                Some("--incoming") => true,
+                Some(flag) => bail!("unknown flag {}\n{}", flag, USAGE),
+            };
+            Command::Edges { id, incoming }
+        }
+        "stats" => {
+            let top = match args.next().as_deref() {
+                None => 10,
+                Some("--top") => args
+                    .next()
+                    .ok_or_else(|| anyhow!("--top needs a value"))?
+                    .parse()?,
+                Some(flag) => bail!("unknown flag {}\n{}", flag, USAGE),
+            };
+            Command::Stats { top }
+        }
+        "verify" => Command::Verify,
+        _ => bail!("unknown command {}\n{}", name, USAGE),
+    };
+    if let Some(extra) = args.next() {
+        bail!("unexpected argument {}\n{}", extra, USAGE);
+    }
+    Ok((path, command))
+}
+
+fn get<T: ReadTransactional>(txn: &T, id: Id) -> anyhow::Result<()> {
+    let ent = txn
+        .get(id)?
+        .ok_or_else(|| anyhow!("entity {} not found", id))?;
+    let json = match ent.as_ent::<DynamicEnt>() {
+        Some(dynamic) => dynamic.to_json(),
+        None => serde_json::to_value(&ent)?,
+    };
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+fn edges<T: QueryEdge>(txn: &T, id: Id, incoming: bool) -> anyhow::Result<()> {
+    let mut last: Option<Edge> = None;
+    loop {
+        let cursor = last.as_ref().map(|edge| match incoming {
+            true => EdgeCursor::from_incoming_edge(edge),
+            false => EdgeCursor::from_edge(edge),
+        });
+        let query = EdgeQuery::asc(&[])
+            .include_hidden()
+            .with_limit(PAGE_SIZE)
+            .with_cursor_opt(cursor);
+        let page = match incoming {
+            true => txn.find_edges_to(id, query)?,
+            false => txn.find_edges(id, query)?,
+        };
+        for edge in &page {
+            println!(
+                "{}\t{}\t{}\t{}{}",
+                edge.source,
+                edge_name(&edge.sort_key),
+                edge.dest,
+                edge.discriminator,
+                if edge.hidden { "\thidden" } else { "" }
+            );
+        }
+        if page.len() < PAGE_SIZE {
+            return Ok(());
+        }
+        last = page.last().cloned();
+    }
+}
+
+fn verify_heed(env: &HeedEnv) -> anyhow::Result<()> {
+    let report = env.check_consistency()?;
+    println!("entities: {}", report.entities);
+    println!("edges: {}", report.edges);
+    println!("undecodable entities: {:?}", report.undecodable_entities);
+    println!("malformed edges: {}", report.malformed_edges);
+    println!("dangling edges: {}", report.dangling_edges);
+    println!(
+        "reverse index mismatches: {}",
+        report.reverse_index_mismatches
+    );
+    if !report.is_consistent() {
+        bail!("store is inconsistent");
+    }
+    Ok(())
+}
+
+fn verify_sqlite(conn: &mut Connection) -> anyhow::Result<()> {
+    let integrity: String =
+        conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+
+    let mut undecodable = Vec::new();
+    let mut entities = 0;
+    {
+        let mut stmt = conn.prepare("SELECT id, data FROM entities")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            entities += 1;
+            let data_json: String = row.get(1)?;
+            if decode_ent(&data_json, UnknownTypePolicy::Lenient).is_err() {
+                undecodable.push(row.get::<_, i64>(0)? as Id);
+            }
+        }
+    }
+    let edges: i64 =
+        conn.query_row("SELECT COUNT(*) FROM edges", [], |row| row.get(0))?;
+    let txn = Txn::new(conn.transaction()?);
+    let dangling = txn.find_dangling_edges(usize::MAX)?.len();
+
+    println!("integrity check: {}", integrity);
+    println!("entities: {}", entities);
+    println!("edges: {}", edges);
+    println!("undecodable entities: {:?}", undecodable);
+    println!("dangling edges: {}", dangling);
+    if integrity != "ok" || !undecodable.is_empty() {
+        bail!("store is inconsistent");
+    }
+    Ok(())
+}
+
+fn print_stats(stats: GraphStats) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    Ok(())
+}
+
+fn run_heed(env: &HeedEnv, command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Get(id) => get(&env.read_txn()?, id),
+        Command::Edges { id, incoming } => {
+            edges(&env.read_txn()?, id, incoming)
+        }
+        Command::Stats { top } => {
+            print_stats(env.write_txn()?.graph_stats(top)?)
+        }
+        Command::Verify => verify_heed(env),
+    }
+}
+
+fn run_sqlite(
+    pool: &Pool<SqliteConnectionManager>,
+    command: Command,
+) -> anyhow::Result<()> {
+    let mut conn = pool.get()?;
+    match command {
+        Command::Get(id) => {
+            let txn = ReadTxn::new(conn.transaction()?)
+                .with_unknown_types(UnknownTypePolicy::Lenient);
+            get(&txn, id)
+        }
+        Command::Edges { id, incoming } => {
+            edges(&ReadTxn::new(conn.transaction()?), id, incoming)
+        }
+        Command::Stats { top } => {
+            print_stats(Txn::new(conn.transaction()?).graph_stats(top)?)
+        }
+        Command::Verify => verify_sqlite(&mut conn),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let (path, command) = parse_args()?;
+    if path.is_dir() {
+        let env = HeedEnv::open(&path, None)?
+            .with_unknown_types(UnknownTypePolicy::Lenient);
+        run_heed(&env, command)
+    } else if path.is_file() {
+        let pool = Pool::new(SqliteConnectionManager::file(&path))?;
+        run_sqlite(&pool, command)
+    } else {
+        bail!(
+            "{} is neither a heed directory nor a sqlite file",
+            path.display()
+        )
+    }
+}
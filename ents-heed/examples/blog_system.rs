@@ -8,7 +8,7 @@
 use ents::{
     DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent, EntExt,
     EntMutationError, EntWithEdges, Id, NullEdgeProvider, QueryEdge,
-    Transactional,
+    ReadTransactional, Transactional,
 };
 use ents_heed::HeedEnv;
 use serde::{Deserialize, Serialize};
@@ -33,10 +33,7 @@ impl Ent for Author {
         self.last_updated
     }
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -77,10 +74,7 @@ impl Ent for BlogPost {
         self.last_updated
     }
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -5,7 +5,8 @@
 //! Run with: cargo run --example simple_blog
 
 use ents::{
-    Ent, EntMutationError, EntWithEdges, Id, NullEdgeProvider, Transactional,
+    Ent, EntMutationError, EntWithEdges, Id, NullEdgeProvider,
+    ReadTransactional, Transactional,
 };
 use ents_heed::HeedEnv;
 use serde::{Deserialize, Serialize};
@@ -32,10 +33,7 @@ impl Ent for BlogPost {
         self.last_updated
     }
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
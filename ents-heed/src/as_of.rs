@@ -0,0 +1,128 @@
+//! As-of reads, see [`ents::as_of`].
+//!
+//! The change log is read backwards from its last record until one
+//! committed at or before the requested time, so a read costs the changes
+//! committed since then, whichever entities they touched.
+
+use ents::as_of::{edges_as_of, entity_as_of};
+use ents::cdc::ChangeRecord;
+use ents::{DatabaseError, Edge, EdgeQuery, Ent, Id};
+use heed::RoTxn;
+
+use crate::{find_edges_internal, HeedEnv, ReadTxn, Txn};
+
+impl HeedEnv {
+    /// The captured changes committed after `at`, newest first
+    fn changes_after(
+        &self,
+        txn: &RoTxn<'_>,
+        at: u64,
+    ) -> Result<Vec<ChangeRecord>, DatabaseError> {
+        let Some(change_log) = self.change_log else {
+            return Ok(Vec::new());
+        };
+        let iter =
+            change_log.rev_iter(txn).map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let mut records = Vec::new();
+        for result in iter {
+            let (seq, json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let record = ChangeRecord::decode(seq, json)?;
+            if record.committed_at.is_none_or(|committed| committed <= at) {
+                break;
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn get_as_of_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+        at: u64,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        Ok(entity_as_of(
+            id,
+            self.get_internal(txn, id)?,
+            self.history_internal(txn, id)?,
+            &self.changes_after(txn, at)?,
+            at,
+        ))
+    }
+
+    fn find_edges_as_of_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        source: Id,
+        query: EdgeQuery,
+        at: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let current = find_edges_internal(
+            txn,
+            &self.edges,
+            source,
+            EdgeQuery::asc(query.edge_names)
+                .include_hidden()
+                .with_limit(usize::MAX),
+        )?;
+        Ok(edges_as_of(
+            source,
+            current,
+            &self.changes_after(txn, at)?,
+            &query,
+        ))
+    }
+}
+
+impl Txn<'_> {
+    /// Entity `id` as it was at `at`, in microseconds, as far as its
+    /// history and the change log reach back
+    pub fn get_as_of(
+        &self,
+        id: Id,
+        at: u64,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.env.get_as_of_internal(&self.txn.borrow(), id, at)
+    }
+
+    /// The edges from `source` matching `query` as they were at `at`
+    pub fn find_edges_as_of(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+        at: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.env.find_edges_as_of_internal(
+            &self.txn.borrow(),
+            source,
+            query,
+            at,
+        )
+    }
+}
+
+impl ReadTxn<'_> {
+    /// Entity `id` as it was at `at`, in microseconds
+    pub fn get_as_of(
+        &self,
+        id: Id,
+        at: u64,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.env.get_as_of_internal(&self.txn, id, at)
+    }
+
+    /// The edges from `source` matching `query` as they were at `at`
+    pub fn find_edges_as_of(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+        at: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.env
+            .find_edges_as_of_internal(&self.txn, source, query, at)
+    }
+}
@@ -0,0 +1,309 @@
+//! Hot backups, consistency checking and backup verification.
+//!
+//! [`HeedEnv::backup_to`] copies a live environment through a read
+//! transaction, so writers keep going while the copy runs and the backup
+//! holds exactly the data committed when it started.
+//!
+//! ```ignore
+//! env.backup_to("/backups/2024-06-01", true)?;
+//! assert!(env.verify_backup("/backups/2024-06-01")?.snapshot.is_consistent());
+//!
+//! // Later, with nothing open at the target directory
+//! HeedEnv::restore("/backups/2024-06-01", "/var/lib/app/db")?;
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use ents::sample::Reservoir;
+use ents::{DatabaseError, Id};
+use heed::CompactionOption;
+
+use crate::{parse_edge_key, reverse_edge_key, HeedEnv, StoreFeature};
+
+/// Number of entities whose contents are compared by `verify_backup`
+const BACKUP_SAMPLE_SIZE: usize = 1000;
+
+/// Result of a consistency check over a whole environment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    pub entities: u64,
+    pub edges: u64,
+    /// Entities whose stored JSON no longer deserializes, including those of
+    /// unknown types unless the environment accepts them
+    pub undecodable_entities: Vec<Id>,
+    /// Edge keys too short to hold source, dest and discriminator
+    pub malformed_edges: u64,
+    /// Edges whose source or dest entity does not exist. Edges from the
+    /// system id 0 are not counted. Informational: dangling edges are legal.
+    pub dangling_edges: u64,
+    /// Edges missing from, or stale entries in, the reverse index. Always 0
+    /// when the reverse index is not enabled.
+    pub reverse_index_mismatches: u64,
+}
+
+impl ConsistencyReport {
+    /// Whether every entity and edge could be read back
+    pub fn is_consistent(&self) -> bool {
+        self.undecodable_entities.is_empty()
+            && self.malformed_edges == 0
+            && self.reverse_index_mismatches == 0
+    }
+}
+
+/// Result of comparing a snapshot against the live store
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    /// Consistency check of the snapshot itself
+    pub snapshot: ConsistencyReport,
+    pub live_entities: u64,
+    pub live_edges: u64,
+    /// Number of snapshot entities compared against the live store
+    pub sampled: usize,
+    /// Sampled entities missing from the live store
+    pub missing_in_live: Vec<Id>,
+    /// Sampled entities whose contents differ from the live store
+    pub mismatched: Vec<Id>,
+}
+
+impl BackupReport {
+    pub fn counts_match(&self) -> bool {
+        self.snapshot.entities == self.live_entities
+            && self.snapshot.edges == self.live_edges
+    }
+
+    /// Whether the snapshot is consistent and matches the live store.
+    ///
+    /// Writes made to the live store after the snapshot was taken show up
+    /// as differences, so verify before resuming writes.
+    pub fn is_ok(&self) -> bool {
+        self.snapshot.is_consistent()
+            && self.counts_match()
+            && self.missing_in_live.is_empty()
+            && self.mismatched.is_empty()
+    }
+}
+
+impl HeedEnv {
+    /// Reads every entity and edge, reporting anything that cannot be
+    /// decoded and edges pointing at missing entities.
+    pub fn check_consistency(
+        &self,
+    ) -> Result<ConsistencyReport, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let mut report = ConsistencyReport::default();
+        let reverse_index =
+            self.feature_enabled_in(&txn, StoreFeature::ReverseIndex)?;
+        let mut indexed_edges = 0;
+
+        let iter =
+            self.entities.iter(&txn).map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        for result in iter {
+            let (id, data_json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            report.entities += 1;
+            if self.decode(id, data_json).is_err() {
+                report.undecodable_entities.push(id);
+            }
+        }
+
+        let iter = self.edges.iter(&txn).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        for result in iter {
+            let (key, _) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            report.edges += 1;
+            if key.len() < 24 {
+                report.malformed_edges += 1;
+                continue;
+            }
+
+            if reverse_index {
+                let indexed = self
+                    .edges_by_dest
+                    .get(&txn, &reverse_edge_key(key))
+                    .map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })?
+                    .is_some();
+                if indexed {
+                    indexed_edges += 1;
+                } else {
+                    report.reverse_index_mismatches += 1;
+                }
+            }
+
+            let (source, _, dest, _) = parse_edge_key(key);
+            let exists = |id: Id| {
+                self.entities
+                    .get(&txn, &id)
+                    .map(|v| v.is_some())
+                    .map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })
+            };
+            if (source != 0 && !exists(source)?) || !exists(dest)? {
+                report.dangling_edges += 1;
+            }
+        }
+
+        if reverse_index {
+            let entries = self.edges_by_dest.len(&txn).map_err(|e| {
+                DatabaseError::Other {
+                    source: Box::new(e),
+                }
+            })?;
+            report.reverse_index_mismatches += entries - indexed_edges;
+        }
+
+        Ok(report)
+    }
+
+    /// Opens the snapshot at `snapshot_path` read-only, checks its
+    /// consistency, and compares its counts and a sample of its entities
+    /// against this (live) environment.
+    pub fn verify_backup<P: AsRef<Path>>(
+        &self,
+        snapshot_path: P,
+    ) -> Result<BackupReport, DatabaseError> {
+        let snapshot = HeedEnv::open_read_only(snapshot_path)?;
+        let live = self.check_consistency()?;
+        let mut report = BackupReport {
+            snapshot: snapshot.check_consistency()?,
+            live_entities: live.entities,
+            live_edges: live.edges,
+            ..BackupReport::default()
+        };
+
+        let snapshot_txn =
+            snapshot.env.read_txn().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let live_txn =
+            self.env.read_txn().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let mut reservoir =
+            Reservoir::new(BACKUP_SAMPLE_SIZE, report.live_entities);
+        let iter = snapshot.entities.iter(&snapshot_txn).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        for result in iter {
+            let (id, data_json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            reservoir.offer((id, checksum(data_json.as_bytes())));
+        }
+
+        for (id, expected) in reservoir.into_vec() {
+            report.sampled += 1;
+            let live_json = self.entities.get(&live_txn, &id).map_err(|e| {
+                DatabaseError::Other {
+                    source: Box::new(e),
+                }
+            })?;
+            match live_json {
+                None => report.missing_in_live.push(id),
+                Some(json) if checksum(json.as_bytes()) != expected => {
+                    report.mismatched.push(id)
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl HeedEnv {
+    /// Copies the environment into the directory `dir` while writers keep
+    /// running. With `compact`, free pages are left out and the copy may be
+    /// much smaller than the data file, at the cost of more CPU. Without
+    /// it, the copy briefly takes the writer lock to start, so it must not
+    /// be called while this thread holds a write transaction.
+    ///
+    /// Fails if `dir` already holds an environment.
+    pub fn backup_to<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        compact: bool,
+    ) -> Result<(), DatabaseError> {
+        let dir = dir.as_ref();
+        let target = dir.join(DATA_FILE);
+        if target.exists() {
+            return Err(exists_error(dir));
+        }
+        fs::create_dir_all(dir).map_err(io_error)?;
+        let option = match compact {
+            true => CompactionOption::Enabled,
+            false => CompactionOption::Disabled,
+        };
+        // Renamed into place once complete, so a failed copy never looks
+        // like a backup
+        let partial = dir.join(PARTIAL_FILE);
+        self.env.copy_to_path(&partial, option).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        fs::rename(&partial, &target).map_err(io_error)
+    }
+
+    /// Restores the backup in `backup_dir`, written by
+    /// [`backup_to`](Self::backup_to), as the environment at `path`. Open
+    /// it with [`HeedEnv::open`] afterwards.
+    ///
+    /// Fails if `path` already holds an environment; remove it first, with
+    /// every handle to it closed.
+    pub fn restore<P: AsRef<Path>, Q: AsRef<Path>>(
+        backup_dir: P,
+        path: Q,
+    ) -> Result<(), DatabaseError> {
+        let source = backup_dir.as_ref().join(DATA_FILE);
+        let path = path.as_ref();
+        let target = path.join(DATA_FILE);
+        if target.exists() {
+            return Err(exists_error(path));
+        }
+        fs::create_dir_all(path).map_err(io_error)?;
+        let partial = path.join(PARTIAL_FILE);
+        fs::copy(&source, &partial).map_err(io_error)?;
+        fs::rename(&partial, &target).map_err(io_error)
+    }
+}
+
+/// Name of the LMDB data file in an environment directory
+const DATA_FILE: &str = "data.mdb";
+
+/// Name of a data file while it is being written
+const PARTIAL_FILE: &str = "data.mdb.partial";
+
+fn exists_error(dir: &Path) -> DatabaseError {
+    DatabaseError::Other {
+        source: format!("{} already holds an environment", dir.display())
+            .into(),
+    }
+}
+
+fn io_error(e: std::io::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
+
+/// FNV-1a hash, enough to spot differing contents
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,112 @@
+use ents::bulk::BulkLoadReport;
+use ents::{DatabaseError, EdgeValue, Ent, Id};
+use heed::PutFlags;
+
+use crate::resize::write_error;
+use crate::{
+    encode_edge_value, make_edge_key, reverse_edge_key, type_index_key,
+    StoreFeature, Txn,
+};
+
+impl Txn<'_> {
+    /// Write `entities` under their own ids and `edges` as given, appending
+    /// to the end of the databases while the input is sorted. See
+    /// [`ents::bulk`] for what is skipped compared to `create`.
+    pub fn bulk_load<I, J>(
+        &self,
+        entities: I,
+        edges: J,
+    ) -> Result<BulkLoadReport, DatabaseError>
+    where
+        I: IntoIterator<Item = Box<dyn Ent>>,
+        J: IntoIterator<Item = EdgeValue>,
+    {
+        let type_index = self.feature_maintained(StoreFeature::TypeIndex)?;
+        let reverse = self.feature_maintained(StoreFeature::ReverseIndex)?;
+        let mut wtxn = self.txn.borrow_mut();
+        let mut report = BulkLoadReport::default();
+
+        let mut last_id: Option<Id> = self
+            .env
+            .entities
+            .last(&wtxn)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(|(id, _)| id);
+        let mut max_id: Option<Id> = None;
+        for ent in entities {
+            let id = ent.id();
+            max_id = max_id.max(Some(id));
+            let data_json = self.env.json_format.to_string(&*ent)?;
+            let flags = if last_id.is_none_or(|last| id > last) {
+                last_id = Some(id);
+                PutFlags::APPEND
+            } else {
+                PutFlags::empty()
+            };
+            self.env
+                .entities
+                .put_with_flags(&mut wtxn, flags, &id, &data_json)
+                .map_err(write_error)?;
+            self.env.note_stored(id);
+            if type_index {
+                self.env
+                    .entities_by_type
+                    .put(
+                        &mut wtxn,
+                        &type_index_key(ent.typetag_name(), id),
+                        &[],
+                    )
+                    .map_err(write_error)?;
+            }
+            report.entities += 1;
+        }
+        if let Some(max) = max_id {
+            self.env.raise_watermark(&mut wtxn, max)?;
+        }
+
+        let mut last_key: Option<Vec<u8>> = self
+            .env
+            .edges
+            .last(&wtxn)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(|(key, _)| key.to_vec());
+        for edge in edges {
+            let key = make_edge_key(
+                edge.source,
+                &edge.sort_key,
+                edge.dest,
+                edge.discriminator,
+            );
+            let flags = if last_key.as_ref().is_none_or(|last| key > *last) {
+                PutFlags::APPEND
+            } else {
+                PutFlags::empty()
+            };
+            self.env
+                .edges
+                .put_with_flags(
+                    &mut wtxn,
+                    flags,
+                    &key,
+                    &encode_edge_value(0, &edge.payload),
+                )
+                .map_err(write_error)?;
+            if reverse {
+                self.env
+                    .edges_by_dest
+                    .put(&mut wtxn, &reverse_edge_key(&key), &[])
+                    .map_err(write_error)?;
+            }
+            if flags == PutFlags::APPEND {
+                last_key = Some(key);
+            }
+            report.edges += 1;
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,113 @@
+//! The change capture log.
+//!
+//! Once [`StoreFeature::ChangeCapture`] is enabled, every write transaction
+//! appends its changes to the `changes` database as part of its commit,
+//! each under the next sequence number. The last sequence handed out is
+//! kept in `meta`, so trimming the log never lets a number be reused.
+//!
+//! ```ignore
+//! env.enable_feature(StoreFeature::ChangeCapture)?;
+//! let records = env.changes_since(last_seen, 100)?;
+//! if let Some(last) = records.last() {
+//!     env.trim_changes(last.seq)?;
+//! }
+//! ```
+//!
+//! [`StoreFeature::ChangeCapture`]: crate::StoreFeature::ChangeCapture
+
+use std::ops::Bound;
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::cdc::{CapturedChange, ChangeRecord};
+use ents::clock::now_micros;
+use ents::DatabaseError;
+use heed::{RoTxn, RwTxn};
+
+use crate::resize::write_error;
+use crate::HeedEnv;
+
+/// Meta key holding the last sequence number handed out, big endian
+const SEQ_KEY: &str = "change_seq";
+
+impl HeedEnv {
+    /// Up to `limit` captured changes with a sequence number above `after`,
+    /// oldest first. Pass 0 to read from the start of the log.
+    pub fn changes_since(
+        &self,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<ChangeRecord>, DatabaseError> {
+        let Some(change_log) = self.change_log else {
+            return Ok(Vec::new());
+        };
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let iter = change_log
+            .range(&txn, &(Bound::Excluded(after), Bound::Unbounded))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let mut records = Vec::new();
+        for result in iter.take(limit) {
+            let (seq, json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            records.push(ChangeRecord::decode(seq, json)?);
+        }
+        Ok(records)
+    }
+
+    /// Drop the captured changes up to and including `through`, e.g. once
+    /// every consumer has processed them. Returns how many were dropped.
+    pub fn trim_changes(&self, through: u64) -> Result<u64, DatabaseError> {
+        let Some(change_log) = self.change_log else {
+            return Ok(0);
+        };
+        let mut wtxn = self.fenced_write_txn()?;
+        self.check_writable(&wtxn)?;
+        let removed = change_log
+            .delete_range(&mut wtxn, &(..=through))
+            .map_err(write_error)?;
+        wtxn.commit().map_err(write_error)?;
+        Ok(removed as u64)
+    }
+
+    /// Append `changes` to the log under the next sequence numbers
+    pub(crate) fn append_changes(
+        &self,
+        wtxn: &mut RwTxn<'_>,
+        changes: &[CapturedChange],
+    ) -> Result<(), DatabaseError> {
+        let Some(change_log) = self.change_log else {
+            return Ok(());
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut seq = self.last_seq_in(wtxn)?;
+        let committed_at = now_micros();
+        for change in changes {
+            seq += 1;
+            change_log
+                .put(wtxn, &seq, &ChangeRecord::encode(change, committed_at)?)
+                .map_err(write_error)?;
+        }
+        let mut value = [0u8; 8];
+        BigEndian::write_u64(&mut value, seq);
+        self.meta.put(wtxn, SEQ_KEY, &value).map_err(write_error)
+    }
+
+    fn last_seq_in(&self, txn: &RoTxn<'_>) -> Result<u64, DatabaseError> {
+        let value =
+            self.meta
+                .get(txn, SEQ_KEY)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        Ok(value
+            .filter(|v| v.len() == 8)
+            .map(BigEndian::read_u64)
+            .unwrap_or(0))
+    }
+}
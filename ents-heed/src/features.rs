@@ -0,0 +1,363 @@
+//! Optional store features, persisted in the `meta` database.
+//!
+//! Features are enabled once per store and stay enabled. Enabling a feature
+//! performs whatever backfill it needs over the existing data, so a store
+//! that turns a feature on late ends up in the same state as one that had it
+//! from the start. New stores start with [`StoreFeature::ReverseIndex`]
+//! active unless opened with [`HeedEnvOptions::reverse_index`] off.
+//!
+//! Large stores can enable a feature online: [`HeedEnv::start_backfill`]
+//! marks the feature as backfilling, after which new writes already maintain
+//! it, and [`HeedEnv::backfill_step`] processes the existing data in small
+//! write transactions, recording its position in `meta` so an interrupted
+//! backfill resumes where it stopped. Readers only rely on a feature once it
+//! is active.
+//!
+//! [`HeedEnvOptions::reverse_index`]: crate::HeedEnvOptions::reverse_index
+
+use std::ops::Bound;
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::DatabaseError;
+use heed::types::{Bytes, Str};
+use heed::{Database, RoTxn, RwTxn};
+
+use crate::resize::write_error;
+use crate::{entity_type, reverse_edge_key, type_index_key, HeedEnv, Txn};
+
+/// Meta value of an enabled feature
+const FEATURE_ACTIVE: &[u8] = b"active";
+
+/// Meta value of a feature whose backfill is in progress
+const FEATURE_BACKFILLING: &[u8] = b"backfilling";
+
+/// Mark `feature` active in the `meta` of a new store, which has no data
+/// to backfill
+pub(crate) fn activate_new(
+    meta: &Database<Str, Bytes>,
+    wtxn: &mut RwTxn<'_>,
+    feature: StoreFeature,
+) -> Result<(), DatabaseError> {
+    meta.put(wtxn, feature.meta_key(), FEATURE_ACTIVE)
+        .map_err(write_error)
+}
+
+/// Number of items processed per transaction by `enable_feature_online`
+const DEFAULT_BACKFILL_BATCH: usize = 1000;
+
+/// An optional subsystem of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StoreFeature {
+    /// Maintain the `edges_by_dest` index of edges keyed by destination
+    ReverseIndex,
+    /// Maintain the `entities_by_type` index of entity ids keyed by type
+    TypeIndex,
+    /// Append committed changes to the `changes` log, see
+    /// [`HeedEnv::changes_since`]
+    ChangeCapture,
+}
+
+impl StoreFeature {
+    /// Every known feature
+    pub const ALL: &'static [StoreFeature] = &[
+        StoreFeature::ReverseIndex,
+        StoreFeature::TypeIndex,
+        StoreFeature::ChangeCapture,
+    ];
+
+    fn meta_key(self) -> &'static str {
+        match self {
+            StoreFeature::ReverseIndex => "feature:reverse_index",
+            StoreFeature::TypeIndex => "feature:type_index",
+            StoreFeature::ChangeCapture => "feature:change_capture",
+        }
+    }
+
+    fn backfill_key(self) -> &'static str {
+        match self {
+            StoreFeature::ReverseIndex => "backfill:reverse_index",
+            StoreFeature::TypeIndex => "backfill:type_index",
+            StoreFeature::ChangeCapture => "backfill:change_capture",
+        }
+    }
+}
+
+/// Lifecycle of a store feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureState {
+    Disabled,
+    /// New writes maintain the feature; existing data is being backfilled
+    Backfilling,
+    Active,
+}
+
+/// Outcome of one backfill step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BackfillProgress {
+    /// Items processed by this step
+    pub processed: usize,
+    /// Whether the backfill finished and the feature is now active
+    pub done: bool,
+}
+
+impl HeedEnv {
+    pub(crate) fn feature_state_in(
+        &self,
+        txn: &RoTxn<'_>,
+        feature: StoreFeature,
+    ) -> Result<FeatureState, DatabaseError> {
+        let value = self.meta.get(txn, feature.meta_key()).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(match value {
+            Some(FEATURE_ACTIVE) => FeatureState::Active,
+            Some(FEATURE_BACKFILLING) => FeatureState::Backfilling,
+            _ => FeatureState::Disabled,
+        })
+    }
+
+    pub(crate) fn feature_enabled_in(
+        &self,
+        txn: &RoTxn<'_>,
+        feature: StoreFeature,
+    ) -> Result<bool, DatabaseError> {
+        Ok(self.feature_state_in(txn, feature)? == FeatureState::Active)
+    }
+
+    /// Current state of `feature`
+    pub fn feature_state(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<FeatureState, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.feature_state_in(&txn, feature)
+    }
+
+    /// Whether `feature` is enabled (and fully backfilled) for this store
+    pub fn is_feature_enabled(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<bool, DatabaseError> {
+        Ok(self.feature_state(feature)? == FeatureState::Active)
+    }
+
+    /// The features enabled for this store
+    pub fn enabled_features(&self) -> Result<Vec<StoreFeature>, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let mut enabled = Vec::new();
+        for &feature in StoreFeature::ALL {
+            if self.feature_enabled_in(&txn, feature)? {
+                enabled.push(feature);
+            }
+        }
+        Ok(enabled)
+    }
+
+    /// Enable `feature`, backfilling it from the existing data in a single
+    /// write transaction. Enabling an enabled feature is a no-op.
+    pub fn enable_feature(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<(), DatabaseError> {
+        self.start_backfill(feature)?;
+        while !self.backfill_step(feature, usize::MAX)?.done {}
+        Ok(())
+    }
+
+    /// Enable `feature` without blocking writers for long, backfilling in
+    /// batches of a thousand items per write transaction.
+    pub fn enable_feature_online(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<(), DatabaseError> {
+        self.start_backfill(feature)?;
+        while !self.backfill_step(feature, DEFAULT_BACKFILL_BATCH)?.done {}
+        Ok(())
+    }
+
+    /// Mark `feature` as backfilling so new writes maintain it. Does nothing
+    /// if a backfill is already running or the feature is active.
+    pub fn start_backfill(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<(), DatabaseError> {
+        let mut wtxn = self.fenced_write_txn()?;
+        self.check_writable(&wtxn)?;
+        if self.feature_state_in(&wtxn, feature)? != FeatureState::Disabled {
+            return Ok(());
+        }
+
+        match feature {
+            StoreFeature::ReverseIndex => {
+                self.edges_by_dest.clear(&mut wtxn).map_err(|e| {
+                    DatabaseError::Other {
+                        source: Box::new(e),
+                    }
+                })?;
+            }
+            StoreFeature::TypeIndex => {
+                self.entities_by_type.clear(&mut wtxn).map_err(|e| {
+                    DatabaseError::Other {
+                        source: Box::new(e),
+                    }
+                })?;
+            }
+            // Only changes committed from now on are captured
+            StoreFeature::ChangeCapture => {}
+        }
+
+        self.meta
+            .delete(&mut wtxn, feature.backfill_key())
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        self.meta
+            .put(&mut wtxn, feature.meta_key(), FEATURE_BACKFILLING)
+            .map_err(write_error)?;
+        wtxn.commit().map_err(write_error)
+    }
+
+    /// Backfill up to `batch_size` items of a backfilling feature in one
+    /// write transaction, activating the feature once everything is done.
+    pub fn backfill_step(
+        &self,
+        feature: StoreFeature,
+        batch_size: usize,
+    ) -> Result<BackfillProgress, DatabaseError> {
+        let mut wtxn = self.fenced_write_txn()?;
+        self.check_writable(&wtxn)?;
+        match self.feature_state_in(&wtxn, feature)? {
+            FeatureState::Active => {
+                return Ok(BackfillProgress {
+                    processed: 0,
+                    done: true,
+                })
+            }
+            FeatureState::Disabled => {
+                return Err(DatabaseError::Other {
+                    source: Box::new(std::io::Error::other(format!(
+                        "No backfill running for {:?}",
+                        feature
+                    ))),
+                })
+            }
+            FeatureState::Backfilling => {}
+        }
+
+        let cursor = self
+            .meta
+            .get(&wtxn, feature.backfill_key())
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(<[u8]>::to_vec);
+
+        let batch = match feature {
+            StoreFeature::ReverseIndex => {
+                let start = match &cursor {
+                    Some(cursor) => Bound::Excluded(cursor.as_slice()),
+                    None => Bound::Unbounded,
+                };
+                let iter = self
+                    .edges
+                    .range(&wtxn, &(start, Bound::Unbounded))
+                    .map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })?;
+                let mut keys = Vec::new();
+                for result in iter.take(batch_size) {
+                    let (key, _) =
+                        result.map_err(|e| DatabaseError::Other {
+                            source: Box::new(e),
+                        })?;
+                    keys.push(key.to_vec());
+                }
+                for key in &keys {
+                    self.edges_by_dest
+                        .put(&mut wtxn, &reverse_edge_key(key), &[])
+                        .map_err(write_error)?;
+                }
+                keys
+            }
+            StoreFeature::TypeIndex => {
+                let start = match &cursor {
+                    Some(cursor) => {
+                        Bound::Excluded(BigEndian::read_u64(cursor))
+                    }
+                    None => Bound::Unbounded,
+                };
+                let iter = self
+                    .entities
+                    .range(&wtxn, &(start, Bound::Unbounded))
+                    .map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })?;
+                let mut entries = Vec::new();
+                for result in iter.take(batch_size) {
+                    let (id, data_json) =
+                        result.map_err(|e| DatabaseError::Other {
+                            source: Box::new(e),
+                        })?;
+                    entries.push((id, entity_type(data_json)?));
+                }
+                for (id, type_name) in &entries {
+                    if let Some(type_name) = type_name {
+                        self.entities_by_type
+                            .put(
+                                &mut wtxn,
+                                &type_index_key(type_name, *id),
+                                &[],
+                            )
+                            .map_err(write_error)?;
+                    }
+                }
+                entries
+                    .iter()
+                    .map(|(id, _)| id.to_be_bytes().to_vec())
+                    .collect()
+            }
+            StoreFeature::ChangeCapture => Vec::new(),
+        };
+
+        let done = batch.len() < batch_size;
+        if done {
+            self.meta
+                .delete(&mut wtxn, feature.backfill_key())
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            self.meta
+                .put(&mut wtxn, feature.meta_key(), FEATURE_ACTIVE)
+                .map_err(write_error)?;
+        } else if let Some(last) = batch.last() {
+            self.meta
+                .put(&mut wtxn, feature.backfill_key(), last)
+                .map_err(write_error)?;
+        }
+
+        wtxn.commit().map_err(write_error)?;
+        Ok(BackfillProgress {
+            processed: batch.len(),
+            done,
+        })
+    }
+}
+
+impl Txn<'_> {
+    /// Whether writes must maintain `feature`, i.e. it is active or being
+    /// backfilled
+    pub(crate) fn feature_maintained(
+        &self,
+        feature: StoreFeature,
+    ) -> Result<bool, DatabaseError> {
+        let state = self.env.feature_state_in(&self.txn.borrow(), feature)?;
+        Ok(state != FeatureState::Disabled)
+    }
+}
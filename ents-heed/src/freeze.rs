@@ -0,0 +1,70 @@
+//! Store-wide read-only mode for maintenance windows.
+//!
+//! [`HeedEnv::freeze`] records a flag in `meta` from inside a write
+//! transaction, so it waits for the current writer to finish and every later
+//! write transaction, from this or any other process, sees the flag and fails
+//! with [`DatabaseError::Frozen`]. Readers are unaffected. [`HeedEnv::thaw`]
+//! clears the flag.
+
+use ents::DatabaseError;
+use heed::RoTxn;
+
+use crate::resize::write_error;
+use crate::HeedEnv;
+
+/// Meta key set while the store is frozen
+const FROZEN_KEY: &str = "frozen";
+
+impl HeedEnv {
+    /// Reject new write transactions until [`HeedEnv::thaw`] is called
+    pub fn freeze(&self) -> Result<(), DatabaseError> {
+        self.set_frozen(true)
+    }
+
+    /// Accept write transactions again
+    pub fn thaw(&self) -> Result<(), DatabaseError> {
+        self.set_frozen(false)
+    }
+
+    /// Whether the store is frozen
+    pub fn is_frozen(&self) -> Result<bool, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.frozen_in(&txn)
+    }
+
+    pub(crate) fn frozen_in(
+        &self,
+        txn: &RoTxn<'_>,
+    ) -> Result<bool, DatabaseError> {
+        let value = self.meta.get(txn, FROZEN_KEY).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(value.is_some())
+    }
+
+    /// Fail with [`DatabaseError::Frozen`] if the store is frozen
+    pub(crate) fn check_writable(
+        &self,
+        txn: &RoTxn<'_>,
+    ) -> Result<(), DatabaseError> {
+        if self.frozen_in(txn)? {
+            return Err(DatabaseError::Frozen);
+        }
+        Ok(())
+    }
+
+    fn set_frozen(&self, frozen: bool) -> Result<(), DatabaseError> {
+        let mut wtxn = self.fenced_write_txn()?;
+        let result = if frozen {
+            self.meta.put(&mut wtxn, FROZEN_KEY, &[])
+        } else {
+            self.meta.delete(&mut wtxn, FROZEN_KEY).map(|_| ())
+        };
+        result.map_err(write_error)?;
+        wtxn.commit().map_err(write_error)
+    }
+}
@@ -0,0 +1,200 @@
+//! Prior versions of entities, see [`ents::history`].
+//!
+//! `history` is keyed by (id, version), both big endian, so the versions of
+//! an entity are one prefix scan in version order. Each value is the time
+//! the version was replaced in microseconds followed by its JSON.
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::clock::now_micros;
+use ents::history::{EntityVersion, HistoryRetention};
+use ents::{DatabaseError, Id};
+use heed::RoTxn;
+
+use crate::resize::write_error;
+use crate::{HeedEnv, ReadTxn, Txn};
+
+/// A stored (key, value) pair of `history`
+type VersionEntry = (Vec<u8>, Vec<u8>);
+
+fn history_key(id: Id, version: u64) -> [u8; 16] {
+    let mut key = [0; 16];
+    BigEndian::write_u64(&mut key[..8], id);
+    BigEndian::write_u64(&mut key[8..], version);
+    key
+}
+
+impl HeedEnv {
+    /// Keep the versions of an entity that updates replace, bounded by
+    /// `retention`
+    pub fn with_history(mut self, retention: HistoryRetention) -> Self {
+        self.history_retention = Some(retention);
+        self
+    }
+
+    /// (key, value) of every stored version of `id`, oldest first
+    fn version_entries(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+    ) -> Result<Vec<VersionEntry>, DatabaseError> {
+        let iter =
+            self.history
+                .prefix_iter(txn, &id.to_be_bytes())
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        iter.map(|result| {
+            result
+                .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })
+        })
+        .collect()
+    }
+
+    fn decode_version(
+        &self,
+        id: Id,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        let data_json = std::str::from_utf8(&value[8..]).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(self.decode(id, data_json)?.map(|ent| EntityVersion {
+            version: BigEndian::read_u64(&key[8..]),
+            recorded_at: BigEndian::read_u64(&value[..8]),
+            ent,
+        }))
+    }
+
+    pub(crate) fn history_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+    ) -> Result<Vec<EntityVersion>, DatabaseError> {
+        let mut versions = Vec::new();
+        for (key, value) in self.version_entries(txn, id)?.iter().rev() {
+            versions.extend(self.decode_version(id, key, value)?);
+        }
+        Ok(versions)
+    }
+
+    fn version_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+        version: u64,
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        let key = history_key(id, version);
+        let value =
+            self.history
+                .get(txn, &key)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        match value {
+            Some(value) => self.decode_version(id, &key, value),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Txn<'_> {
+    /// Prior versions of entity `id`, newest first. Empty unless the
+    /// environment keeps history.
+    pub fn get_history(
+        &self,
+        id: Id,
+    ) -> Result<Vec<EntityVersion>, DatabaseError> {
+        self.env.history_internal(&self.txn.borrow(), id)
+    }
+
+    /// Version `version` of entity `id`, counting from 1 for the entity as
+    /// created, if it is still kept
+    pub fn get_version(
+        &self,
+        id: Id,
+        version: u64,
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        self.env.version_internal(&self.txn.borrow(), id, version)
+    }
+
+    /// Store the current state of `id` as its next version and prune the
+    /// versions the retention no longer keeps
+    pub(crate) fn archive_version(&self, id: Id) -> Result<(), DatabaseError> {
+        let Some(retention) = self.env.history_retention else {
+            return Ok(());
+        };
+        let mut wtxn = self.txn.borrow_mut();
+        let Some(data_json) = self
+            .env
+            .entities
+            .get(&wtxn, &id)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(str::to_string)
+        else {
+            return Ok(());
+        };
+        let mut entries = self.env.version_entries(&wtxn, id)?;
+        let version = entries
+            .last()
+            .map_or(1, |(key, _)| BigEndian::read_u64(&key[8..]) + 1);
+        let now = now_micros();
+        let mut value = vec![0; 8];
+        BigEndian::write_u64(&mut value, now);
+        value.extend_from_slice(data_json.as_bytes());
+        let key = history_key(id, version);
+        self.env
+            .history
+            .put(&mut wtxn, &key, &value)
+            .map_err(write_error)?;
+        entries.push((key.to_vec(), value));
+
+        for (index, (key, value)) in entries.iter().rev().enumerate() {
+            if !retention.keeps(index, BigEndian::read_u64(&value[..8]), now) {
+                self.env
+                    .history
+                    .delete(&mut wtxn, key)
+                    .map_err(write_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop every version of `id`
+    pub(crate) fn delete_history(&self, id: Id) -> Result<(), DatabaseError> {
+        let mut wtxn = self.txn.borrow_mut();
+        for (key, _) in self.env.version_entries(&wtxn, id)? {
+            self.env
+                .history
+                .delete(&mut wtxn, &key)
+                .map_err(write_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadTxn<'_> {
+    /// Prior versions of entity `id`, newest first
+    pub fn get_history(
+        &self,
+        id: Id,
+    ) -> Result<Vec<EntityVersion>, DatabaseError> {
+        self.env.history_internal(&self.txn, id)
+    }
+
+    /// Version `version` of entity `id`, if it is still kept
+    pub fn get_version(
+        &self,
+        id: Id,
+        version: u64,
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        self.env.version_internal(&self.txn, id, version)
+    }
+}
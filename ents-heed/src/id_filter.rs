@@ -0,0 +1,62 @@
+//! Short-circuiting lookups of missing ids, see [`ents::bloom`].
+//!
+//! A rebuild scans the keys of `entities` inside a write transaction, so
+//! no create in this process can commit between the scan and the swap.
+
+use std::sync::Arc;
+
+use ents::bloom::IdFilter;
+use ents::{DatabaseError, Id};
+use heed::types::DecodeIgnore;
+
+use crate::HeedEnv;
+
+impl HeedEnv {
+    /// Answer lookups of ids `filter` has not seen without reading the
+    /// store. The filter passes every id until
+    /// [`rebuild_id_filter`](Self::rebuild_id_filter) first builds it.
+    pub fn with_id_filter(mut self, filter: Arc<IdFilter>) -> Self {
+        self.id_filter = Some(filter);
+        self
+    }
+
+    /// Rebuild the id filter from the stored ids, returning how many it
+    /// holds. Run it periodically: deleted ids stay in the filter until
+    /// then, and creates by other processes are missed.
+    pub fn rebuild_id_filter(&self) -> Result<usize, DatabaseError> {
+        let Some(filter) = &self.id_filter else {
+            return Ok(0);
+        };
+        let wtxn = self.fenced_write_txn()?;
+        let ids = self
+            .entities
+            .remap_data_type::<DecodeIgnore>()
+            .iter(&wtxn)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(|result| {
+                result.map(|(id, _)| id).map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })
+            })
+            .collect::<Result<Vec<Id>, _>>()?;
+        filter.rebuild(&ids);
+        wtxn.abort();
+        Ok(ids.len())
+    }
+
+    /// Whether `id` may be stored, per the id filter if there is one
+    pub(crate) fn may_contain(&self, id: Id) -> bool {
+        self.id_filter
+            .as_ref()
+            .is_none_or(|filter| filter.may_contain(id))
+    }
+
+    /// Add a stored `id` to the id filter
+    pub(crate) fn note_stored(&self, id: Id) {
+        if let Some(filter) = &self.id_filter {
+            filter.insert(id);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Coordinating several processes that open the same store.
+//!
+//! LMDB itself is safe to share between processes: its write lock
+//! serializes writers across all of them and readers see committed data.
+//! What is not shared is the state this crate keeps in the process. Two
+//! processes generating snowflake ids with the same node id hand out the
+//! same ids, and watchers only hear about commits made by their own
+//! process.
+//!
+//! [`HeedEnvOptions::lease_node_id`] picks a node id no other process that
+//! leases has, and [`HeedEnvOptions::single_writer`] makes the opening
+//! process the only one allowed to open the store for writing. Both use
+//! advisory file locks next to `data.mdb`, which the operating system
+//! releases when the environment is dropped or the process exits, crashed
+//! or not.
+//!
+//! ```ignore
+//! // The writer
+//! let env = HeedEnvOptions::new()
+//!     .lease_node_id(true)
+//!     .single_writer(true)
+//!     .open(path)?;
+//!
+//! // Any number of other processes
+//! let snapshot = HeedEnv::open_read_only(path)?;
+//! ```
+//!
+//! While a process holds the writer role, opening the store with
+//! `single_writer` elsewhere fails with [`DatabaseError::Overloaded`].
+//! Processes that open the store without either option are not
+//! coordinated; give them distinct [`HeedEnvOptions::node_id`]s by hand or
+//! open them read-only.
+//!
+//! A crashed writer loses its lock with the process, but a hung one keeps
+//! it. Each time a process takes the writer role it stores a larger
+//! fencing token in `meta`, and each of its commits renews the heartbeat
+//! stored next to it. A standby opened with
+//! [`HeedEnvOptions::writer_lease_expiry`] takes the role over, lock or
+//! not, once the heartbeat is older than the expiry. Commits of the
+//! superseded writer then see a token other than their own and fail with
+//! [`DatabaseError::Fenced`] instead of writing alongside the standby, as
+//! do its maintenance writes such as freezing, trimming the change log or
+//! backfilling a feature. [`HeedEnv::upgrade_store`] refuses to run while
+//! a process holds the writer role.
+//!
+//! ```ignore
+//! let standby = HeedEnvOptions::new()
+//!     .single_writer(true)
+//!     .writer_lease_expiry(Duration::from_secs(30))
+//!     .open(path)?;
+//! ```
+//!
+//! A writer that may sit idle for longer than the expiry keeps its role
+//! with [`HeedEnv::renew_writer_lease`].
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::Path;
+use std::time::Duration;
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::clock::now_micros;
+use ents::DatabaseError;
+use heed::types::{Bytes, Str};
+use heed::{Database, RoTxn, RwTxn};
+
+use crate::options::MAX_NODE_ID;
+use crate::resize::write_error;
+use crate::{HeedEnv, HeedEnvOptions};
+
+/// File whose lock is the writer role
+const WRITER_LOCK: &str = "writer.lock";
+
+/// Meta key holding the fencing token of the writer role followed by the
+/// time its holder last committed or renewed it, both big endian
+const WRITER_LEASE_KEY: &str = "writer_lease";
+
+/// Advisory locks a process holds on a store, released on drop
+#[derive(Debug, Default)]
+pub(crate) struct ProcessLease {
+    node: Option<(u16, File)>,
+    writer: Option<WriterRole>,
+}
+
+#[derive(Debug)]
+struct WriterRole {
+    /// None when the role was taken over from a holder of the lock
+    lock: Option<File>,
+    token: u64,
+}
+
+impl ProcessLease {
+    /// Take the locks `options` asks for in the store directory `path`
+    pub(crate) fn acquire(
+        path: &Path,
+        options: &HeedEnvOptions,
+    ) -> Result<Self, DatabaseError> {
+        let mut lease = Self::default();
+        if !options.lease_node_id && !options.single_writer {
+            return Ok(lease);
+        }
+        fs::create_dir_all(path).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        if options.single_writer {
+            let lock = try_lock(&path.join(WRITER_LOCK))?;
+            if lock.is_none() && options.writer_lease_expiry.is_none() {
+                return Err(writer_taken(path));
+            }
+            // The token is assigned by `claim_writer` once the store is open
+            lease.writer = Some(WriterRole { lock, token: 0 });
+        }
+        if options.lease_node_id {
+            for node_id in 0..=MAX_NODE_ID {
+                let lock = path.join(format!("node-{}.lock", node_id));
+                if let Some(file) = try_lock(&lock)? {
+                    lease.node = Some((node_id, file));
+                    break;
+                }
+            }
+            if lease.node.is_none() {
+                return Err(DatabaseError::Overloaded {
+                    reason: format!(
+                        "all {} node ids of {} are leased",
+                        MAX_NODE_ID as u32 + 1,
+                        path.display()
+                    ),
+                });
+            }
+        }
+        Ok(lease)
+    }
+
+    pub(crate) fn node_id(&self) -> Option<u16> {
+        self.node.as_ref().map(|(node_id, _)| *node_id)
+    }
+
+    pub(crate) fn is_writer(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Store the next fencing token for the writer role acquired by
+    /// `acquire`. Without the lock the role is only taken over once the
+    /// heartbeat of its holder is older than `expiry`.
+    pub(crate) fn claim_writer(
+        &mut self,
+        env: &HeedEnv,
+        path: &Path,
+        expiry: Option<Duration>,
+    ) -> Result<(), DatabaseError> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let mut wtxn =
+            env.env.write_txn().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let (token, heartbeat) = read_writer_lease(&env.meta, &wtxn)?;
+        if writer.lock.is_none() {
+            let expired = expiry.is_some_and(|expiry| {
+                now_micros().saturating_sub(heartbeat)
+                    > expiry.as_micros() as u64
+            });
+            if !expired {
+                return Err(writer_taken(path));
+            }
+        }
+        writer.token = token + 1;
+        write_writer_lease(&env.meta, &mut wtxn, writer.token)?;
+        wtxn.commit().map_err(write_error)
+    }
+
+    /// Fail with [`DatabaseError::Fenced`] if another process took the
+    /// writer role over, otherwise renew its heartbeat in `wtxn`
+    pub(crate) fn fence(
+        &self,
+        meta: &Database<Str, Bytes>,
+        wtxn: &mut RwTxn<'_>,
+    ) -> Result<(), DatabaseError> {
+        let Some(writer) = &self.writer else {
+            return Ok(());
+        };
+        let (current, _) = read_writer_lease(meta, wtxn)?;
+        if current != writer.token {
+            return Err(DatabaseError::Fenced {
+                token: writer.token,
+                current,
+            });
+        }
+        write_writer_lease(meta, wtxn, writer.token)
+    }
+}
+
+/// Hold the writer lock of the store at `path` while writing to it
+/// without a [`HeedEnv`], failing while a process holds the writer role
+pub(crate) fn lock_writer(path: &Path) -> Result<File, DatabaseError> {
+    try_lock(&path.join(WRITER_LOCK))?.ok_or_else(|| writer_taken(path))
+}
+
+fn writer_taken(path: &Path) -> DatabaseError {
+    DatabaseError::Overloaded {
+        reason: format!(
+            "another process holds the writer role of {}",
+            path.display()
+        ),
+    }
+}
+
+/// The fencing token and heartbeat stored in `meta`, zeros if none are
+fn read_writer_lease(
+    meta: &Database<Str, Bytes>,
+    txn: &RoTxn<'_>,
+) -> Result<(u64, u64), DatabaseError> {
+    let value =
+        meta.get(txn, WRITER_LEASE_KEY)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+    Ok(value.map_or((0, 0), |value| {
+        (
+            BigEndian::read_u64(&value[..8]),
+            BigEndian::read_u64(&value[8..]),
+        )
+    }))
+}
+
+/// Store `token` with the current time as its heartbeat
+fn write_writer_lease(
+    meta: &Database<Str, Bytes>,
+    wtxn: &mut RwTxn<'_>,
+    token: u64,
+) -> Result<(), DatabaseError> {
+    let mut value = [0; 16];
+    BigEndian::write_u64(&mut value[..8], token);
+    BigEndian::write_u64(&mut value[8..], now_micros());
+    meta.put(wtxn, WRITER_LEASE_KEY, &value)
+        .map_err(write_error)
+}
+
+/// Exclusively lock the file at `path`, None if another handle holds it
+fn try_lock(path: &Path) -> Result<Option<File>, DatabaseError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(DatabaseError::Other {
+            source: Box::new(e),
+        }),
+    }
+}
+
+impl HeedEnv {
+    /// The node id leased with [`HeedEnvOptions::lease_node_id`]
+    pub fn leased_node_id(&self) -> Option<u16> {
+        self.lease.node_id()
+    }
+
+    /// Whether this process holds the writer role taken with
+    /// [`HeedEnvOptions::single_writer`]
+    pub fn is_single_writer(&self) -> bool {
+        self.lease.is_writer()
+    }
+
+    /// The fencing token of the writer role this process holds. Each
+    /// process taking the role gets a larger one.
+    pub fn fencing_token(&self) -> Option<u64> {
+        self.lease.writer.as_ref().map(|writer| writer.token)
+    }
+
+    /// Renew the heartbeat of the writer role without committing anything
+    /// else, failing with [`DatabaseError::Fenced`] if it was taken over.
+    /// Commits renew it too.
+    pub fn renew_writer_lease(&self) -> Result<(), DatabaseError> {
+        self.fenced_write_txn()?.commit().map_err(write_error)
+    }
+
+    /// Begin a write transaction outside of [`Txn`](crate::Txn), failing
+    /// with [`DatabaseError::Fenced`] if another process took the writer
+    /// role over. Every write bypassing `Txn`, whose commit fences itself,
+    /// begins here.
+    pub(crate) fn fenced_write_txn(&self) -> Result<RwTxn<'_>, DatabaseError> {
+        let mut wtxn =
+            self.env.write_txn().map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        self.lease.fence(&self.meta, &mut wtxn)?;
+        Ok(wtxn)
+    }
+}
@@ -5,35 +5,116 @@
 //!
 //! # Storage Layout
 //!
-//! The implementation uses three LMDB databases:
+//! The implementation uses these LMDB databases:
 //! - `entities`: Maps entity IDs to serialized entity JSON
-//! - `edges`: Maps composite keys (source, sort_key, dest) to empty values
-//! - `meta`: Stores metadata like the next entity ID
+//! - `edges`: Maps composite keys (source, sort_key, dest, discriminator) to
+//!   edge values. An empty value is a plain edge; otherwise the first byte
+//!   holds edge flags (e.g. hidden) and the rest is the edge payload
+//! - `edges_by_dest`: Reverse index keyed by (dest, source, sort_key,
+//!   discriminator), maintained once [`StoreFeature::ReverseIndex`] is
+//!   enabled. It serves `find_edges_to` and the inbound edge cleanup of
+//!   `delete` with a prefix scan instead of a scan of every edge
+//! - `entities_by_type`: Index keyed by (typetag name, 0, id), maintained
+//!   once [`StoreFeature::TypeIndex`] is enabled
+//! - `meta`: Stores metadata such as the format version, the enabled store
+//!   features and the largest id handed out
+//! - `changes`: Log of committed changes keyed by sequence number, appended
+//!   to once [`StoreFeature::ChangeCapture`] is enabled
+//! - `deleted`: Soft-deleted entities by ID, each the time of deletion in
+//!   microseconds followed by the entity JSON
+//! - `history`: Prior versions of entities keyed by (id, version), written
+//!   once [`HeedEnv::with_history`] is set, see [`ents::history`]
 
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::fs;
+use std::ops::Bound;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 use byteorder::{BigEndian, ByteOrder};
+use ents::acyclic;
+use ents::bloom::IdFilter;
+use ents::cascade;
+use ents::dangling::{self, IncomingEdgePolicy};
+use ents::decode::{decode_ent, UnknownTypePolicy};
+use ents::format::JsonFormat;
+use ents::history::HistoryRetention;
+use ents::ids::{IdProvider, SnowflakeIds};
+use ents::sample::Reservoir;
+use ents::stats::{GraphStats, GraphStatsCollector};
+use ents::unique::{self, UniqueKey};
+use ents::watch::{ChangeKind, ChangeLog, EdgeChange, EntityChange, WatchHub};
 use ents::{
     DatabaseError, Edge, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
-    EntWithEdges, Id, QueryEdge, SortOrder, Transactional,
+    EntExt as _, EntWithEdges, EntityCursor, EntityPage, Id, QueryEdge,
+    ReadTransactional, SortOrder, Transactional,
 };
 use heed::types::{Bytes, Str};
-use heed::{Database, Env, EnvOpenOptions, RwTxn};
-use snowflaked::Generator;
+use heed::{
+    Database, Env, EnvFlags, EnvOpenOptions, PutFlags, RoTxn, RwTxn, WithTls,
+};
 
-/// Maximum number of edges returned by find_edges
-const MAX_EDGES: usize = 100;
+use crate::lease::ProcessLease;
+use crate::resize::write_error;
+
+mod as_of;
+mod backup;
+mod bulk;
+mod cdc;
+mod features;
+mod freeze;
+mod history;
+mod id_filter;
+mod lease;
+mod options;
+mod resize;
+mod throttle;
+mod trash;
+mod version;
+mod watermark;
+
+pub use backup::{BackupReport, ConsistencyReport};
+pub use features::{BackfillProgress, FeatureState, StoreFeature};
+pub use options::{Durability, HeedEnvOptions};
+pub use resize::AutoResize;
+pub use throttle::WriteThrottle;
+pub use version::FORMAT_VERSION;
+pub use watermark::IdAudit;
+
+/// Edge flag marking a hidden (soft-deleted) edge
+const EDGE_FLAG_HIDDEN: u8 = 0x01;
 
 /// LMDB environment wrapper that manages the databases.
 pub struct HeedEnv {
     env: Env,
     entities: Database<heed::types::U64<BigEndian>, Str>,
     edges: Database<Bytes, Bytes>,
-    id_generator: Mutex<Generator>,
+    edges_by_dest: Database<Bytes, Bytes>,
+    entities_by_type: Database<Bytes, Bytes>,
+    meta: Database<Str, Bytes>,
+    uniques: Database<Bytes, heed::types::U64<BigEndian>>,
+    /// None for read-only environments created before change capture
+    change_log: Option<Database<heed::types::U64<BigEndian>, Str>>,
+    deleted: Database<heed::types::U64<BigEndian>, Bytes>,
+    history: Database<Bytes, Bytes>,
+    ids: Box<dyn IdProvider>,
+    watchers: WatchHub,
+    throttle: Option<WriteThrottle>,
+    queued_writers: AtomicUsize,
+    append_inserts: bool,
+    json_format: JsonFormat,
+    unknown_types: UnknownTypePolicy,
+    incoming_edges: IncomingEdgePolicy,
+    edge_integrity: bool,
+    allowed_types: Option<BTreeSet<String>>,
+    auto_resize: Option<AutoResize>,
+    history_retention: Option<HistoryRetention>,
+    id_filter: Option<Arc<IdFilter>>,
+    lease: ProcessLease,
 }
 
 impl HeedEnv {
@@ -42,20 +123,31 @@ impl HeedEnv {
     /// # Arguments
     /// * `path` - Directory path for the LMDB environment
     /// * `map_size` - Maximum size of the database in bytes (default: 1GB)
+    ///
+    /// See [`HeedEnvOptions`] for the other settings.
     pub fn open<P: AsRef<Path>>(
         path: P,
         map_size: Option<usize>,
     ) -> Result<Self, DatabaseError> {
-        let path = path.as_ref();
+        let mut options = HeedEnvOptions::new();
+        if let Some(map_size) = map_size {
+            options = options.map_size(map_size);
+        }
+        options.open(path)
+    }
+
+    pub(crate) fn open_with(
+        path: &Path,
+        options: &HeedEnvOptions,
+    ) -> Result<Self, DatabaseError> {
         fs::create_dir_all(path).map_err(|e| DatabaseError::Other {
             source: Box::new(e),
         })?;
 
         let env = unsafe {
-            EnvOpenOptions::new()
-                .map_size(map_size.unwrap_or(1024 * 1024 * 1024)) // 1GB default
-                .max_dbs(2)
-                .open(path)
+            let mut env_options = EnvOpenOptions::new();
+            options.apply(&mut env_options);
+            env_options.max_dbs(9).open(path)
         }
         .map_err(|e| DatabaseError::Other {
             source: Box::new(e),
@@ -66,6 +158,14 @@ impl HeedEnv {
             source: Box::new(e),
         })?;
 
+        // A store without entities is new and gets the current version
+        let fresh = env
+            .open_database::<Bytes, Bytes>(&wtxn, Some("entities"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .is_none();
+
         let entities: Database<heed::types::U64<BigEndian>, Str> = env
             .create_database(&mut wtxn, Some("entities"))
             .map_err(|e| DatabaseError::Other {
@@ -78,43 +178,576 @@ impl HeedEnv {
                 source: Box::new(e),
             })?;
 
-        wtxn.commit().map_err(|e| DatabaseError::Other {
+        let edges_by_dest: Database<Bytes, Bytes> = env
+            .create_database(&mut wtxn, Some("edges_by_dest"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let entities_by_type: Database<Bytes, Bytes> = env
+            .create_database(&mut wtxn, Some("entities_by_type"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let meta: Database<Str, Bytes> = env
+            .create_database(&mut wtxn, Some("meta"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let uniques: Database<Bytes, heed::types::U64<BigEndian>> = env
+            .create_database(&mut wtxn, Some("uniques"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let change_log: Database<heed::types::U64<BigEndian>, Str> = env
+            .create_database(&mut wtxn, Some("changes"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let deleted: Database<heed::types::U64<BigEndian>, Bytes> = env
+            .create_database(&mut wtxn, Some("deleted"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let history: Database<Bytes, Bytes> = env
+            .create_database(&mut wtxn, Some("history"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        if fresh {
+            version::write_version(&meta, &mut wtxn, FORMAT_VERSION)?;
+            if options.reverse_index {
+                features::activate_new(
+                    &meta,
+                    &mut wtxn,
+                    StoreFeature::ReverseIndex,
+                )?;
+            }
+        } else {
+            version::check_version(&meta, &wtxn)?;
+        }
+        wtxn.commit().map_err(write_error)?;
+
+        let heed_env = Self {
+            env,
+            entities,
+            edges,
+            edges_by_dest,
+            entities_by_type,
+            meta,
+            uniques,
+            change_log: Some(change_log),
+            deleted,
+            history,
+            ids: Box::new(SnowflakeIds::new(options.node_id)),
+            watchers: WatchHub::new(),
+            throttle: None,
+            queued_writers: AtomicUsize::new(0),
+            append_inserts: false,
+            json_format: JsonFormat::COMPACT,
+            unknown_types: UnknownTypePolicy::Strict,
+            incoming_edges: IncomingEdgePolicy::Remove,
+            edge_integrity: false,
+            allowed_types: options.allowed_types.clone(),
+            auto_resize: None,
+            history_retention: None,
+            id_filter: None,
+            lease: ProcessLease::default(),
+        };
+        if options.verify_ids {
+            let audit = heed_env.audit_ids()?;
+            if !audit.is_consistent() {
+                return Err(DatabaseError::Other {
+                    source: format!(
+                        "stored ids up to {:?} exceed the id watermark {:?}",
+                        audit.max_stored, audit.watermark
+                    )
+                    .into(),
+                });
+            }
+        }
+        Ok(heed_env)
+    }
+
+    /// Opens an existing LMDB environment read-only, e.g. a backup snapshot.
+    ///
+    /// Write transactions on the returned environment fail.
+    pub fn open_read_only<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<Self, DatabaseError> {
+        let env = unsafe {
+            let mut options = EnvOpenOptions::new();
+            options.max_dbs(9).flags(EnvFlags::READ_ONLY);
+            options.open(path.as_ref())
+        }
+        .map_err(|e| DatabaseError::Other {
             source: Box::new(e),
         })?;
 
-        // Initialize snowflake ID generator
-        // Using node_id 0, can be configured if needed for distributed systems
-        let id_generator = Generator::new(0);
+        let rtxn = env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let missing = |name: &str| DatabaseError::Other {
+            source: Box::new(std::io::Error::other(format!(
+                "Missing database: {}",
+                name
+            ))),
+        };
+
+        let entities: Database<heed::types::U64<BigEndian>, Str> = env
+            .open_database(&rtxn, Some("entities"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("entities"))?;
+
+        let edges: Database<Bytes, Bytes> = env
+            .open_database(&rtxn, Some("edges"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("edges"))?;
+
+        let edges_by_dest: Database<Bytes, Bytes> = env
+            .open_database(&rtxn, Some("edges_by_dest"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("edges_by_dest"))?;
+
+        let entities_by_type: Database<Bytes, Bytes> = env
+            .open_database(&rtxn, Some("entities_by_type"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("entities_by_type"))?;
+
+        let meta: Database<Str, Bytes> = env
+            .open_database(&rtxn, Some("meta"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("meta"))?;
+        version::check_version(&meta, &rtxn)?;
+
+        let uniques: Database<Bytes, heed::types::U64<BigEndian>> = env
+            .open_database(&rtxn, Some("uniques"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("uniques"))?;
+
+        let change_log: Option<Database<heed::types::U64<BigEndian>, Str>> =
+            env.open_database(&rtxn, Some("changes")).map_err(|e| {
+                DatabaseError::Other {
+                    source: Box::new(e),
+                }
+            })?;
+
+        let deleted: Database<heed::types::U64<BigEndian>, Bytes> = env
+            .open_database(&rtxn, Some("deleted"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("deleted"))?;
+
+        let history: Database<Bytes, Bytes> = env
+            .open_database(&rtxn, Some("history"))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .ok_or_else(|| missing("history"))?;
+
+        rtxn.commit().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
 
         Ok(Self {
             env,
             entities,
             edges,
-            id_generator: Mutex::new(id_generator),
+            edges_by_dest,
+            entities_by_type,
+            meta,
+            uniques,
+            change_log,
+            deleted,
+            history,
+            ids: Box::new(SnowflakeIds::default()),
+            watchers: WatchHub::new(),
+            throttle: None,
+            queued_writers: AtomicUsize::new(0),
+            append_inserts: false,
+            json_format: JsonFormat::COMPACT,
+            unknown_types: UnknownTypePolicy::Strict,
+            incoming_edges: IncomingEdgePolicy::Remove,
+            edge_integrity: false,
+            allowed_types: None,
+            auto_resize: None,
+            history_retention: None,
+            id_filter: None,
+            lease: ProcessLease::default(),
         })
     }
 
-    /// Begins a read-write transaction.
+    /// Write new entities with `MDB_APPEND` when their id sorts after every
+    /// stored id, which snowflake ids almost always do. Appending skips the
+    /// B-tree search and fills pages instead of splitting them; out of order
+    /// ids fall back to a regular put.
+    pub fn with_append_inserts(mut self, enabled: bool) -> Self {
+        self.append_inserts = enabled;
+        self
+    }
+
+    /// Assign ids of new entities from `ids` instead of snowflake ids for
+    /// instance 0
+    pub fn with_id_provider(mut self, ids: impl IdProvider + 'static) -> Self {
+        self.ids = Box::new(ids);
+        self
+    }
+
+    /// Serialize entities written through this environment with `format`
+    pub fn with_json_format(mut self, format: JsonFormat) -> Self {
+        self.json_format = format;
+        self
+    }
+
+    /// Handle stored entities of unregistered types per `policy`
+    pub fn with_unknown_types(mut self, policy: UnknownTypePolicy) -> Self {
+        self.unknown_types = policy;
+        self
+    }
+
+    /// Handle the edges pointing at deleted entities per `policy`
+    pub fn with_incoming_edges(mut self, policy: IncomingEdgePolicy) -> Self {
+        self.incoming_edges = policy;
+        self
+    }
+
+    /// Refuse to create edges whose source or destination does not exist,
+    /// see [`ents::dangling::check_endpoints`]
+    pub fn with_edge_integrity(mut self, enabled: bool) -> Self {
+        self.edge_integrity = enabled;
+        self
+    }
+
+    /// Begins a read-only transaction. Readers see a consistent snapshot and
+    /// do not wait for the writer.
+    pub fn read_txn(&self) -> Result<ReadTxn<'_>, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        Ok(ReadTxn { txn, env: self })
+    }
+
+    fn get_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if !self.may_contain(id) {
+            return Ok(None);
+        }
+        match self
+            .entities
+            .get(txn, &id)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })? {
+            Some(data_json) => self.decode(id, data_json),
+            None => Ok(None),
+        }
+    }
+
+    /// Deserializes stored entity `id` per the type allowlist and the
+    /// unknown type policy
+    fn decode(
+        &self,
+        id: Id,
+        data_json: &str,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if let Some(allowed) = &self.allowed_types {
+            let type_name = entity_type(data_json)?;
+            if !type_name.is_some_and(|t| allowed.contains(&t)) {
+                return Ok(None);
+            }
+        }
+        let mut ent = decode_ent(data_json, self.unknown_types)?;
+        if let Some(ent) = &mut ent {
+            ent.set_id(id);
+        }
+        Ok(ent)
+    }
+
+    /// Lists the ids of `type_name` entities from the type index when it is
+    /// active, or by scanning every entity otherwise.
+    fn list_ids_by_type_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let mut ids = Vec::new();
+        if limit == 0 {
+            return Ok(ids);
+        }
+        if self.feature_enabled_in(txn, StoreFeature::TypeIndex)? {
+            let mut prefix = type_name.as_bytes().to_vec();
+            prefix.push(0);
+            let start = type_index_key(type_name, after.unwrap_or(0));
+            let lower = match after {
+                Some(_) => Bound::Excluded(start.as_slice()),
+                None => Bound::Included(start.as_slice()),
+            };
+            let iter = self
+                .entities_by_type
+                .range(txn, &(lower, Bound::Unbounded))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            for result in iter {
+                let (key, _) = result.map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+                if !key.starts_with(&prefix) {
+                    break;
+                }
+                ids.push(BigEndian::read_u64(&key[prefix.len()..]));
+                if ids.len() == limit {
+                    break;
+                }
+            }
+        } else {
+            let lower = match after {
+                Some(after) => Bound::Excluded(after),
+                None => Bound::Unbounded,
+            };
+            let iter = self
+                .entities
+                .range(txn, &(lower, Bound::Unbounded))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            for result in iter {
+                let (id, data_json) =
+                    result.map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })?;
+                if entity_type(data_json)?.as_deref() == Some(type_name) {
+                    ids.push(id);
+                    if ids.len() == limit {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// A page of every entity in id order, see
+    /// [`Transactional::scan_entities`]
+    fn scan_entities_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        if limit == 0 {
+            return Ok((Vec::new(), cursor));
+        }
+        let lower = match cursor {
+            Some(cursor) => Bound::Excluded(cursor.after),
+            None => Bound::Unbounded,
+        };
+        let mut iter = self
+            .entities
+            .range(txn, &(lower, Bound::Unbounded))
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let mut ents = Vec::new();
+        let mut last = None;
+        for result in iter.by_ref().take(limit) {
+            let (id, data_json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            ents.extend(self.decode(id, data_json)?);
+            last = Some(id);
+        }
+        // The page ends the scan unless another entity follows it
+        let next = match iter.next() {
+            Some(result) => {
+                result.map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+                last.map(|after| EntityCursor { after })
+            }
+            None => None,
+        };
+        Ok((ents, next))
+    }
+
+    /// Keys of the edges pointing at `dest`, found with the reverse index
+    /// when it is active or by scanning every edge otherwise.
+    fn edge_keys_to(
+        &self,
+        txn: &RoTxn<'_>,
+        dest: Id,
+    ) -> Result<Vec<Vec<u8>>, DatabaseError> {
+        let mut keys = Vec::new();
+        if self.feature_enabled_in(txn, StoreFeature::ReverseIndex)? {
+            let iter = self
+                .edges_by_dest
+                .prefix_iter(txn, &dest.to_be_bytes())
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            for result in iter {
+                let (reverse, _) =
+                    result.map_err(|e| DatabaseError::Other {
+                        source: Box::new(e),
+                    })?;
+                keys.push(forward_edge_key(reverse));
+            }
+        } else {
+            let iter =
+                self.edges.iter(txn).map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            for result in iter {
+                let (key, _) = result.map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+                if parse_edge_key(key).2 == dest {
+                    keys.push(key.to_vec());
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Collects up to `limit` edges whose destination does not exist
+    fn dangling_edges(
+        &self,
+        txn: &RoTxn<'_>,
+        limit: usize,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let mut dangling = Vec::new();
+        if limit == 0 {
+            return Ok(dangling);
+        }
+        let iter = self.edges.iter(txn).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        for result in iter {
+            let (key, value) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let (source, sort_key, dest, discriminator) = parse_edge_key(key);
+            let exists = self
+                .entities
+                .get(txn, &dest)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?
+                .is_some();
+            if !exists {
+                dangling.push(
+                    Edge::new(source, sort_key.to_vec(), dest)
+                        .with_discriminator(discriminator)
+                        .with_hidden(edge_flags(value) & EDGE_FLAG_HIDDEN != 0)
+                        .with_payload(edge_payload(value)),
+                );
+                if dangling.len() == limit {
+                    break;
+                }
+            }
+        }
+        Ok(dangling)
+    }
+
+    /// Collects the edges pointing at `dest`
+    fn find_edges_to_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let keys = self.edge_keys_to(txn, dest)?;
+
+        let mut edges = Vec::new();
+        for key in keys {
+            let (source, sort_key, _, discriminator) = parse_edge_key(&key);
+            if !query.edge_names.is_empty()
+                && !query.edge_names.contains(&sort_key)
+            {
+                continue;
+            }
+            let value = self.edges.get(txn, &key).map_err(|e| {
+                DatabaseError::Other {
+                    source: Box::new(e),
+                }
+            })?;
+            let value = value.unwrap_or_default();
+            let hidden = edge_flags(value) & EDGE_FLAG_HIDDEN != 0;
+            if hidden && !query.include_hidden {
+                continue;
+            }
+            edges.push(
+                Edge::new(source, sort_key.to_vec(), dest)
+                    .with_discriminator(discriminator)
+                    .with_hidden(hidden)
+                    .with_payload(edge_payload(value)),
+            );
+        }
+
+        Ok(paginate(edges, &query, |e| e.source))
+    }
+
+    /// Begins a read-write transaction. Fails with
+    /// [`DatabaseError::Frozen`] while the store is frozen, and with
+    /// [`DatabaseError::Overloaded`] when the write throttle rejects it.
     pub fn write_txn(&self) -> Result<Txn<'_>, DatabaseError> {
+        let writer = self.admit_writer()?;
         let txn = self.env.write_txn().map_err(|e| DatabaseError::Other {
             source: Box::new(e),
         })?;
+        drop(writer);
+        self.check_writable(&txn)?;
         Ok(Txn {
             txn: RefCell::new(txn),
             env: self,
+            changes: ChangeLog::default(),
+            on_commit: RefCell::new(Vec::new()),
         })
     }
 
-    /// Allocates the next entity ID using snowflake algorithm.
+    /// Subscribe to committed changes of the entity `id`
+    pub fn watch(&self, id: Id) -> Receiver<EntityChange> {
+        self.watchers.watch(id)
+    }
+
+    /// Subscribe to committed changes of every entity of type `E`
+    pub fn watch_type<E: Ent>(&self) -> Receiver<EntityChange> {
+        self.watchers.watch_type::<E>()
+    }
+
+    /// Subscribe to committed additions and removals of `name` edges from
+    /// `source`
+    pub fn watch_edges(&self, source: Id, name: &[u8]) -> Receiver<EdgeChange> {
+        self.watchers.watch_edges(source, name)
+    }
+
+    /// Allocates the next entity ID from the id provider.
     fn next_id(&self) -> Result<Id, DatabaseError> {
-        let mut generator =
-            self.id_generator.lock().map_err(|e| DatabaseError::Other {
-                source: Box::new(std::io::Error::other(format!(
-                    "Failed to lock ID generator: {}",
-                    e
-                ))),
-            })?;
-        Ok(generator.generate())
+        self.ids.next_id()
     }
 }
 
@@ -125,248 +758,880 @@ impl HeedEnv {
 pub struct Txn<'env> {
     txn: RefCell<RwTxn<'env>>,
     env: &'env HeedEnv,
+    changes: ChangeLog,
+    on_commit: RefCell<Vec<Box<dyn FnOnce() + 'env>>>,
 }
 
 impl<'env> Txn<'env> {
-    /// Inserts an entity and returns its assigned ID.
-    fn insert<E: Ent>(&self, ent: &E) -> Result<Id, DatabaseError> {
-        let id = self.env.next_id()?;
+    /// Run `f` once this transaction commits, after watchers were notified.
+    /// Callbacks run in the order they were added and are dropped without
+    /// running if the transaction is rolled back or its commit fails.
+    pub fn on_commit(&self, f: impl FnOnce() + 'env) {
+        self.on_commit.borrow_mut().push(Box::new(f));
+    }
+
+    /// Creates `ent` with its edges and unique keys, under `id` or else a
+    /// new ID
+    fn create_as<E: EntWithEdges>(
+        &self,
+        mut ent: E,
+        id: Option<Id>,
+    ) -> Result<Id, DatabaseError> {
+        let keys = ent.unique_keys();
+        unique::check_available(self, &keys, None)?;
+        let id = self.insert(&mut ent, id)?;
+        ent.setup_edges(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.put_unique_keys(&keys, id)?;
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Created));
+        Ok(id)
+    }
+
+    /// Inserts an entity under `id` or else a new ID, which is set on `ent`
+    /// before it is serialized so the stored bytes carry it.
+    fn insert<E: Ent>(
+        &self,
+        ent: &mut E,
+        id: Option<Id>,
+    ) -> Result<Id, DatabaseError> {
+        let id = match id {
+            Some(id) => id,
+            None => self.env.next_id()?,
+        };
+        ent.set_id(id);
+        let indexed = self.feature_maintained(StoreFeature::TypeIndex)?;
+        let mut wtxn = self.txn.borrow_mut();
+
+        let data_json = self.env.json_format.to_string(ent)?;
+
+        let flags = if self.env.append_inserts
+            && self
+                .env
+                .entities
+                .last(&wtxn)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?
+                .is_none_or(|(last, _)| id > last)
+        {
+            PutFlags::APPEND
+        } else {
+            PutFlags::empty()
+        };
+        self.env
+            .entities
+            .put_with_flags(&mut wtxn, flags, &id, &data_json)
+            .map_err(write_error)?;
+        self.env.raise_watermark(&mut wtxn, id)?;
+        self.env.note_stored(id);
+
+        if indexed {
+            self.env
+                .entities_by_type
+                .put(&mut wtxn, &type_index_key(ent.typetag_name(), id), &[])
+                .map_err(write_error)?;
+        }
+
+        Ok(id)
+    }
+
+    /// Internal update that writes entity with optional CAS check.
+    fn update_internal(
+        &self,
+        id: Id,
+        ent: Box<dyn Ent>,
+        expected_last_updated: Option<u64>,
+    ) -> Result<bool, DatabaseError> {
+        // If CAS check is needed, verify current last_updated
+        if let Some(expected) = expected_last_updated {
+            if let Some(current) = self.get(id)? {
+                if current.last_updated() != expected {
+                    return Ok(false);
+                }
+            } else {
+                return Ok(false);
+            }
+        }
+
+        let data_json = self.env.json_format.to_string(&*ent)?;
+
+        self.archive_version(id)?;
+        self.env
+            .entities
+            .put(&mut self.txn.borrow_mut(), &id, &data_json)
+            .map_err(write_error)?;
+        self.env.note_stored(id);
+
+        Ok(true)
+    }
+
+    /// Whether `ent` serializes to exactly the stored bytes of its id
+    fn is_stored(&self, ent: &dyn Ent) -> Result<bool, DatabaseError> {
+        let data_json = self.env.json_format.to_string(ent)?;
+        let txn = self.txn.borrow();
+        let stored = self.env.entities.get(&txn, &ent.id()).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(stored == Some(data_json.as_str()))
+    }
+
+    /// Apply `mutator` to `ent0` and write it back, unless
+    /// `skip_unchanged` is set and the result matches the stored entity.
+    fn write_update<T, F, B>(
+        &self,
+        mut ent0: B,
+        mutator: F,
+        skip_unchanged: bool,
+    ) -> Result<bool, DatabaseError>
+    where
+        T: EntWithEdges,
+        F: FnOnce(&mut T),
+        B: BorrowMut<T>,
+    {
+        let ent = ent0.borrow_mut();
+        let draft0 = T::EdgeProvider::draft(ent);
+        let keys0 = ent.unique_keys();
+        let expected_last_updated = ent.last_updated();
+
+        mutator(ent);
+        // Nothing to write if the entity still serializes to the stored
+        // bytes, which also means nobody updated it since it was loaded
+        if skip_unchanged
+            && T::EdgeProvider::draft(ent) == draft0
+            && self.is_stored(ent)?
+        {
+            return Ok(true);
+        }
+        ent.mark_updated().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+
+        let draft1 = T::EdgeProvider::draft(ent);
+        let keys1 = ent.unique_keys();
+        if keys0 != keys1 {
+            unique::check_available(self, &keys1, Some(ent.id()))?;
+        }
+
+        // Optimization: if drafts are equal, no edge changes needed
+        if draft0 == draft1 {
+            let updated = self.update_internal(
+                ent.id(),
+                dyn_clone::clone_box(ent),
+                Some(expected_last_updated),
+            )?;
+            if updated {
+                self.move_unique_keys(&keys0, &keys1, ent.id())?;
+                self.changes.record(EntityChange::new::<T>(
+                    ent.id(),
+                    ChangeKind::Updated,
+                ));
+            }
+            return Ok(updated);
+        }
+
+        let edge0 = draft0.check(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let edge1 = draft1.check(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+
+        let updated = self.update_internal(
+            ent.id(),
+            dyn_clone::clone_box(ent),
+            Some(expected_last_updated),
+        )?;
+
+        if updated {
+            // Remove old edges if they existed
+            for edge in edge0 {
+                self.delete_edge(&edge)?;
+            }
+
+            // Create new edges if they exist
+            for edge in edge1 {
+                self.create_edge(edge)?;
+            }
+
+            self.move_unique_keys(&keys0, &keys1, ent.id())?;
+
+            self.changes
+                .record(EntityChange::new::<T>(ent.id(), ChangeKind::Updated));
+        }
+
+        Ok(updated)
+    }
+
+    /// Records `id` as the holder of `keys`
+    fn put_unique_keys(
+        &self,
+        keys: &[UniqueKey],
+        id: Id,
+    ) -> Result<(), DatabaseError> {
         let mut wtxn = self.txn.borrow_mut();
+        for key in keys {
+            self.env
+                .uniques
+                .put(&mut wtxn, &key.encode(), &id)
+                .map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    /// Releases the keys of `old` that are not in `new` and claims the rest
+    fn move_unique_keys(
+        &self,
+        old: &[UniqueKey],
+        new: &[UniqueKey],
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        if old == new {
+            return Ok(());
+        }
+        let released: Vec<UniqueKey> =
+            old.iter().filter(|k| !new.contains(k)).cloned().collect();
+        self.delete_unique_keys(&released)?;
+        self.put_unique_keys(new, id)
+    }
 
-        let data_json =
-            serde_json::to_string(&(ent as &dyn Ent)).map_err(|e| {
+    /// Removes entity `id` from the type index
+    fn delete_type_index(&self, id: Id) -> Result<(), DatabaseError> {
+        let mut wtxn = self.txn.borrow_mut();
+        let type_name =
+            match self.env.entities.get(&wtxn, &id).map_err(|e| {
                 DatabaseError::Other {
                     source: Box::new(e),
                 }
+            })? {
+                Some(data_json) => entity_type(data_json)?,
+                None => None,
+            };
+        if let Some(type_name) = type_name {
+            self.env
+                .entities_by_type
+                .delete(&mut wtxn, &type_index_key(&type_name, id))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        }
+        Ok(())
+    }
+
+    fn delete_unique_keys(
+        &self,
+        keys: &[UniqueKey],
+    ) -> Result<(), DatabaseError> {
+        let mut wtxn = self.txn.borrow_mut();
+        for key in keys {
+            self.env
+                .uniques
+                .delete(&mut wtxn, &key.encode())
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        }
+        Ok(())
+    }
+
+    fn entity_exists(&self, id: Id) -> Result<bool, DatabaseError> {
+        if !self.env.may_contain(id) {
+            return Ok(false);
+        }
+        Ok(self
+            .env
+            .entities
+            .get(&self.txn.borrow(), &id)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .is_some())
+    }
+
+    fn delete_edge(&self, edge: &EdgeValue) -> Result<(), DatabaseError> {
+        let key = make_edge_key(
+            edge.source,
+            &edge.sort_key,
+            edge.dest,
+            edge.discriminator,
+        );
+        self.delete_edge_key(&key)
+    }
+
+    /// Writes a visible edge with its payload, keeping the reverse index in
+    /// sync.
+    fn put_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        let reverse = self.feature_maintained(StoreFeature::ReverseIndex)?;
+        let key = make_edge_key(
+            edge.source,
+            &edge.sort_key,
+            edge.dest,
+            edge.discriminator,
+        );
+        let mut wtxn = self.txn.borrow_mut();
+        // Re-creating an edge replaces its payload but keeps it hidden or
+        // visible as it was, so it is not reported as added
+        let existing = self
+            .env
+            .edges
+            .get(&wtxn, &key)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(edge_flags);
+        self.env
+            .edges
+            .put(
+                &mut wtxn,
+                &key,
+                &encode_edge_value(existing.unwrap_or(0), &edge.payload),
+            )
+            .map_err(write_error)?;
+        if existing.is_some() {
+            return Ok(());
+        }
+        if reverse {
+            self.env
+                .edges_by_dest
+                .put(&mut wtxn, &reverse_edge_key(&key), &[])
+                .map_err(write_error)?;
+        }
+        self.changes.record_edge(EdgeChange::Added(edge));
+        Ok(())
+    }
+
+    /// Deletes an edge key, keeping the reverse index in sync.
+    fn delete_edge_key(&self, key: &[u8]) -> Result<(), DatabaseError> {
+        let reverse = self.feature_maintained(StoreFeature::ReverseIndex)?;
+        let mut wtxn = self.txn.borrow_mut();
+        let existed = self.env.edges.delete(&mut wtxn, key).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        if existed {
+            self.changes
+                .record_edge(EdgeChange::Removed(edge_value_from_key(key)));
+        }
+        if reverse {
+            self.env
+                .edges_by_dest
+                .delete(&mut wtxn, &reverse_edge_key(key))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Computes graph-wide statistics over this transaction's snapshot.
+    pub fn graph_stats(
+        &self,
+        top_n: usize,
+    ) -> Result<GraphStats, DatabaseError> {
+        let txn = self.txn.borrow();
+        let mut collector = GraphStatsCollector::new();
+
+        let iter =
+            self.env
+                .entities
+                .iter(&txn)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        for result in iter {
+            let (id, _) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
             })?;
+            collector.observe_entity(id);
+        }
+
+        let iter =
+            self.env
+                .edges
+                .iter(&txn)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        for result in iter {
+            let (key, _) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let (source, sort_key, dest, discriminator) = parse_edge_key(key);
+            collector.observe_edge(
+                &Edge::new(source, sort_key.to_vec(), dest)
+                    .with_discriminator(discriminator),
+            );
+        }
+
+        Ok(collector.finish(top_n))
+    }
+
+    /// Returns a uniform random sample of up to `n` entities of the given
+    /// type, using reservoir sampling over a full scan.
+    pub fn sample_entities(
+        &self,
+        type_name: &str,
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<Box<dyn Ent>>, DatabaseError> {
+        let txn = self.txn.borrow();
+        let mut reservoir = Reservoir::new(n, seed);
+
+        let iter =
+            self.env
+                .entities
+                .iter(&txn)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        for result in iter {
+            let (id, data_json) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            if entity_type(data_json)?.as_deref() == Some(type_name) {
+                reservoir.offer((id, data_json));
+            }
+        }
+
+        reservoir
+            .into_vec()
+            .into_iter()
+            .filter_map(|(id, data_json)| {
+                self.env.decode(id, data_json).transpose()
+            })
+            .collect()
+    }
+
+    /// Returns a uniform random sample of up to `n` edges whose sort key
+    /// starts with `name`, using reservoir sampling over a full scan.
+    pub fn sample_edges(
+        &self,
+        name: &[u8],
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let txn = self.txn.borrow();
+        let mut reservoir = Reservoir::new(n, seed);
+
+        let iter =
+            self.env
+                .edges
+                .iter(&txn)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        for result in iter {
+            let (key, value) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            let (source, sort_key, dest, discriminator) = parse_edge_key(key);
+            if sort_key.starts_with(name) {
+                reservoir.offer(
+                    Edge::new(source, sort_key.to_vec(), dest)
+                        .with_discriminator(discriminator)
+                        .with_hidden(edge_flags(value) & EDGE_FLAG_HIDDEN != 0)
+                        .with_payload(edge_payload(value)),
+                );
+            }
+        }
+
+        Ok(reservoir.into_vec())
+    }
+
+    /// Returns up to `limit` edges, hidden ones included, whose destination
+    /// does not exist, e.g. those left by
+    /// [`IncomingEdgePolicy::Orphan`]. Scans every edge.
+    pub fn find_dangling_edges(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.env.dangling_edges(&self.txn.borrow(), limit)
+    }
+
+    /// Deletes every edge whose destination does not exist and returns how
+    /// many were deleted. Scans every edge.
+    pub fn remove_dangling_edges(&self) -> Result<u64, DatabaseError> {
+        let dangling = self.find_dangling_edges(usize::MAX)?;
+        for edge in &dangling {
+            self.delete_edge_key(&make_edge_key(
+                edge.source,
+                &edge.sort_key,
+                edge.dest,
+                edge.discriminator,
+            ))?;
+        }
+        Ok(dangling.len() as u64)
+    }
+
+    /// Sets or clears the hidden flag of an existing edge.
+    fn set_edge_hidden(
+        &self,
+        edge: &EdgeValue,
+        hidden: bool,
+    ) -> Result<bool, DatabaseError> {
+        let key = make_edge_key(
+            edge.source,
+            &edge.sort_key,
+            edge.dest,
+            edge.discriminator,
+        );
+        let mut wtxn = self.txn.borrow_mut();
+        let mut value = match self.env.edges.get(&wtxn, &key).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })? {
+            Some(value) => value.to_vec(),
+            None => return Ok(false),
+        };
+
+        // Nothing changes, so nothing is written or reported
+        if (edge_flags(&value) & EDGE_FLAG_HIDDEN != 0) == hidden {
+            return Ok(true);
+        }
+        if value.is_empty() {
+            value.push(0);
+        }
+        if hidden {
+            value[0] |= EDGE_FLAG_HIDDEN;
+        } else {
+            value[0] &= !EDGE_FLAG_HIDDEN;
+        }
 
         self.env
-            .entities
-            .put(&mut wtxn, &id, &data_json)
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+            .edges
+            .put(&mut wtxn, &key, &value)
+            .map_err(write_error)?;
+        self.changes.record_edge(if hidden {
+            EdgeChange::Removed(edge.clone())
+        } else {
+            EdgeChange::Added(edge.clone())
+        });
+        Ok(true)
+    }
+}
 
-        Ok(id)
+impl<'env> ReadTransactional for Txn<'env> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        let txn = self.txn.borrow();
+        self.env.get_internal(&txn, id)
     }
 
-    /// Internal update that writes entity with optional CAS check.
-    fn update_internal(
+    fn get_deleted(
         &self,
         id: Id,
-        ent: Box<dyn Ent>,
-        expected_last_updated: Option<u64>,
-    ) -> Result<bool, DatabaseError> {
-        // If CAS check is needed, verify current last_updated
-        if let Some(expected) = expected_last_updated {
-            if let Some(current) = self.get(id)? {
-                if current.last_updated() != expected {
-                    return Ok(false);
-                }
-            } else {
-                return Ok(false);
-            }
-        }
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.env.get_deleted_internal(&self.txn.borrow(), id)
+    }
 
-        let data_json =
-            serde_json::to_string(&ent).map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.env.list_deleted_internal(
+            &self.txn.borrow(),
+            deleted_before,
+            after,
+            limit,
+        )
+    }
 
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let txn = self.txn.borrow();
         self.env
-            .entities
-            .put(&mut self.txn.borrow_mut(), &id, &data_json)
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+            .list_ids_by_type_internal(&txn, type_name, after, limit)
+    }
 
-        Ok(true)
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        self.env
+            .scan_entities_internal(&self.txn.borrow(), cursor, limit)
     }
 
-    fn delete_edge(
+    fn find_unique(
         &self,
-        source: Id,
-        sort_key: &[u8],
-        dest: Id,
-    ) -> Result<(), DatabaseError> {
-        let key = make_edge_key(source, sort_key, dest);
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
         self.env
-            .edges
-            .delete(&mut self.txn.borrow_mut(), &key)
+            .uniques
+            .get(&self.txn.borrow(), &key.encode())
             .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
-            })?;
-        Ok(())
+            })
     }
 }
 
 impl<'env> Transactional for Txn<'env> {
-    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
-        let txn = self.txn.borrow();
-        match self.env.entities.get(&txn, &id).map_err(|e| {
-            DatabaseError::Other {
-                source: Box::new(e),
-            }
-        })? {
-            Some(data_json) => {
-                let mut ent = serde_json::from_str::<Box<dyn Ent>>(data_json)
-                    .map_err(|e| DatabaseError::Other {
-                    source: Box::new(e),
-                })?;
-                ent.set_id(id);
-                Ok(Some(ent))
-            }
-            None => Ok(None),
-        }
+    fn create<E: Ent + EntWithEdges>(
+        &self,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        self.create_as(ent, None)
     }
 
-    fn create<E: Ent + EntWithEdges>(
+    fn create_with_id<E: EntWithEdges>(
         &self,
-        mut ent: E,
+        id: Id,
+        ent: E,
     ) -> Result<Id, DatabaseError> {
-        let id = self.insert(&ent)?;
-        ent.set_id(id);
-        ent.setup_edges(self).map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })?;
-        Ok(id)
+        if self.entity_exists(id)? {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        self.create_as(ent, Some(id))
     }
 
     fn delete<E: Ent + EntWithEdges>(
         &self,
         id: Id,
     ) -> Result<(), DatabaseError> {
-        // Delete edges where this entity is the destination
-        // We need to scan all edges and delete matching ones
-        let to_delete: Vec<Vec<u8>> = {
-            let txn = self.txn.borrow();
-            let iter = self.env.edges.iter(&txn).map_err(|e| {
+        // Unique keys are released from the stored entity, so it has to be
+        // an `E`
+        let stored = match self.get(id)? {
+            Some(ent) => Some(ent.into_ent::<E>().ok_or_else(|| {
                 DatabaseError::Other {
-                    source: Box::new(e),
+                    source: format!(
+                        "entity {} is not a {}",
+                        id,
+                        std::any::type_name::<E>()
+                    )
+                    .into(),
                 }
-            })?;
+            })?),
+            None => None,
+        };
 
-            let mut keys = Vec::new();
-            for result in iter {
-                let (key, _) = result.map_err(|e| DatabaseError::Other {
-                    source: Box::new(e),
-                })?;
-                let (_, _, dest) = parse_edge_key(key);
-                if dest == id {
-                    keys.push(key.to_vec());
-                }
+        // Refuse before the cascade writes anything
+        if matches!(self.env.incoming_edges, IncomingEdgePolicy::Restrict) {
+            let incoming = self.env.edge_keys_to(&self.txn.borrow(), id)?;
+            if !incoming.is_empty() {
+                return Err(DatabaseError::Other {
+                    source: format!(
+                        "entity {} has {} incoming edges",
+                        id,
+                        incoming.len()
+                    )
+                    .into(),
+                });
             }
-            keys
-        };
+        }
 
-        for key in to_delete {
-            self.env
-                .edges
-                .delete(&mut self.txn.borrow_mut(), &key)
-                .map_err(|e| DatabaseError::Other {
-                    source: Box::new(e),
-                })?;
+        cascade::enforce::<E, _>(self, id, |edge| self.delete_edge(edge))?;
+
+        // Delete edges where this entity is the destination. Without the
+        // reverse index this scans every edge.
+        if matches!(self.env.incoming_edges, IncomingEdgePolicy::Remove) {
+            let to_delete = self.env.edge_keys_to(&self.txn.borrow(), id)?;
+            for key in to_delete {
+                self.delete_edge_key(&key)?;
+            }
+        }
+
+        if let Some(ent) = stored {
+            self.delete_unique_keys(&ent.unique_keys())?;
+        }
+
+        if self.feature_maintained(StoreFeature::TypeIndex)? {
+            self.delete_type_index(id)?;
         }
 
         // Delete the entity
-        self.env
+        let existed = self
+            .env
             .entities
             .delete(&mut self.txn.borrow_mut(), &id)
             .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
             })?;
+        if existed {
+            self.delete_history(id)?;
+            self.changes
+                .record(EntityChange::new::<E>(id, ChangeKind::Deleted));
+        }
 
         Ok(())
     }
 
     fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
-        let key = make_edge_key(edge.source, &edge.sort_key, edge.dest);
-        self.env
-            .edges
-            .put(&mut self.txn.borrow_mut(), &key, &[])
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
-        Ok(())
+        if self.env.edge_integrity {
+            dangling::check_endpoints(&edge, |id| self.entity_exists(id))?;
+        }
+        acyclic::check_edge(self, &edge)?;
+        self.put_edge(edge)
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.set_edge_hidden(edge, true)
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        acyclic::check_edge(self, edge)?;
+        self.set_edge_hidden(edge, false)
     }
 
     fn update<T: EntWithEdges, F: FnOnce(&mut T), B: BorrowMut<T>>(
         &self,
-        mut ent0: B,
+        ent: B,
         mutator: F,
     ) -> Result<bool, DatabaseError> {
-        let ent = ent0.borrow_mut();
-        let draft0 = T::EdgeProvider::draft(ent);
-        let expected_last_updated = ent.last_updated();
+        self.write_update(ent, mutator, true)
+    }
 
-        mutator(ent);
-        ent.mark_updated().map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })?;
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        match self.get_as::<E>(id)? {
+            Some(ent) => self.write_update(ent, |_: &mut E| {}, false),
+            None => Ok(false),
+        }
+    }
 
-        let draft1 = T::EdgeProvider::draft(ent);
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        self.move_to_trash::<E>(id)
+    }
 
-        // Optimization: if drafts are equal, no edge changes needed
-        if draft0 == draft1 {
-            return self.update_internal(
-                ent.id(),
-                dyn_clone::clone_box(ent),
-                Some(expected_last_updated),
-            );
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.restore_from_trash::<E>(id)
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        self.env
+            .lease
+            .fence(&self.env.meta, &mut self.txn.borrow_mut())?;
+        if self.feature_maintained(StoreFeature::ChangeCapture)? {
+            self.env.append_changes(
+                &mut self.txn.borrow_mut(),
+                &self.changes.captured(),
+            )?;
+        }
+        self.txn.into_inner().commit().map_err(write_error)?;
+        self.changes.publish_to(&self.env.watchers);
+        for f in self.on_commit.into_inner() {
+            f();
         }
+        Ok(())
+    }
+}
 
-        let edge0 = draft0.check(self).map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })?;
-        let edge1 = draft1.check(self).map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })?;
+impl<'env> QueryEdge for Txn<'env> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let txn = self.txn.borrow();
+        find_edges_internal(&txn, &self.env.edges, source, query)
+    }
 
-        let updated = self.update_internal(
-            ent.id(),
-            dyn_clone::clone_box(ent),
-            Some(expected_last_updated),
-        )?;
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let txn = self.txn.borrow();
+        self.env.find_edges_to_internal(&txn, dest, query)
+    }
+}
 
-        if updated {
-            // Remove old edges if they existed
-            for edge in edge0 {
-                self.delete_edge(edge.source, &edge.sort_key, edge.dest)?;
-            }
+/// A read-only transaction over a snapshot of the environment.
+pub struct ReadTxn<'env> {
+    txn: RoTxn<'env, WithTls>,
+    env: &'env HeedEnv,
+}
 
-            // Create new edges if they exist
-            for edge in edge1 {
-                self.create_edge(edge)?;
-            }
-        }
+impl<'env> ReadTransactional for ReadTxn<'env> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.env.get_internal(&self.txn, id)
+    }
 
-        Ok(updated)
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.env.get_deleted_internal(&self.txn, id)
     }
 
-    fn commit(self) -> Result<(), DatabaseError> {
-        self.txn
-            .into_inner()
-            .commit()
-            .map_err(|e| DatabaseError::Other {
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.env
+            .list_deleted_internal(&self.txn, deleted_before, after, limit)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.env
+            .list_ids_by_type_internal(&self.txn, type_name, after, limit)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        self.env.scan_entities_internal(&self.txn, cursor, limit)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        self.env.uniques.get(&self.txn, &key.encode()).map_err(|e| {
+            DatabaseError::Other {
                 source: Box::new(e),
-            })
+            }
+        })
     }
 }
 
-impl<'env> QueryEdge for Txn<'env> {
+impl<'env> QueryEdge for ReadTxn<'env> {
     fn find_edges(
         &self,
         source: Id,
         query: EdgeQuery,
     ) -> Result<Vec<Edge>, DatabaseError> {
-        let txn = self.txn.borrow();
-        find_edges_internal(&txn, &self.env.edges, source, query)
+        find_edges_internal(&self.txn, &self.env.edges, source, query)
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.env.find_edges_to_internal(&self.txn, dest, query)
     }
 }
 
-/// Creates a composite key for an edge: source (8 bytes) + sort_key + dest (8 bytes)
-fn make_edge_key(source: Id, sort_key: &[u8], dest: Id) -> Vec<u8> {
-    let mut key = Vec::with_capacity(8 + sort_key.len() + 8);
+/// Creates a composite key for an edge:
+/// source (8 bytes) + sort_key + dest (8 bytes) + discriminator (8 bytes)
+fn make_edge_key(
+    source: Id,
+    sort_key: &[u8],
+    dest: Id,
+    discriminator: u64,
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + sort_key.len() + 16);
     let mut buf = [0u8; 8];
 
     BigEndian::write_u64(&mut buf, source);
@@ -377,25 +1642,106 @@ fn make_edge_key(source: Id, sort_key: &[u8], dest: Id) -> Vec<u8> {
     BigEndian::write_u64(&mut buf, dest);
     key.extend_from_slice(&buf);
 
+    BigEndian::write_u64(&mut buf, discriminator);
+    key.extend_from_slice(&buf);
+
     key
 }
 
-/// Parses a composite edge key into (source, sort_key, dest)
-fn parse_edge_key(key: &[u8]) -> (Id, &[u8], Id) {
+/// Parses a composite edge key into (source, sort_key, dest, discriminator)
+fn parse_edge_key(key: &[u8]) -> (Id, &[u8], Id, u64) {
     let source = BigEndian::read_u64(&key[0..8]);
-    let dest = BigEndian::read_u64(&key[key.len() - 8..]);
-    let sort_key = &key[8..key.len() - 8];
-    (source, sort_key, dest)
+    let dest = BigEndian::read_u64(&key[key.len() - 16..key.len() - 8]);
+    let discriminator = BigEndian::read_u64(&key[key.len() - 8..]);
+    let sort_key = &key[8..key.len() - 16];
+    (source, sort_key, dest, discriminator)
+}
+
+fn edge_value_from_key(key: &[u8]) -> EdgeValue {
+    let (source, sort_key, dest, discriminator) = parse_edge_key(key);
+    EdgeValue::new(source, sort_key.to_vec(), dest)
+        .with_discriminator(discriminator)
+}
+
+/// Converts an `edges` key into its `edges_by_dest` key:
+/// dest (8 bytes) + source (8 bytes) + sort_key + discriminator (8 bytes)
+fn reverse_edge_key(key: &[u8]) -> Vec<u8> {
+    let (source, sort_key, dest, discriminator) = parse_edge_key(key);
+    let mut reverse = Vec::with_capacity(key.len());
+    reverse.extend_from_slice(&dest.to_be_bytes());
+    reverse.extend_from_slice(&source.to_be_bytes());
+    reverse.extend_from_slice(sort_key);
+    reverse.extend_from_slice(&discriminator.to_be_bytes());
+    reverse
+}
+
+/// Converts an `edges_by_dest` key back into its `edges` key
+fn forward_edge_key(reverse: &[u8]) -> Vec<u8> {
+    let dest = &reverse[0..8];
+    let source = &reverse[8..16];
+    let sort_key = &reverse[16..reverse.len() - 8];
+    let discriminator = &reverse[reverse.len() - 8..];
+    let mut key = Vec::with_capacity(reverse.len());
+    key.extend_from_slice(source);
+    key.extend_from_slice(sort_key);
+    key.extend_from_slice(dest);
+    key.extend_from_slice(discriminator);
+    key
+}
+
+/// Creates a type index key: type name + 0 + id (8 bytes)
+fn type_index_key(type_name: &str, id: Id) -> Vec<u8> {
+    let mut key = Vec::with_capacity(type_name.len() + 9);
+    key.extend_from_slice(type_name.as_bytes());
+    key.push(0);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+/// Reads the typetag name of a serialized entity without deserializing it
+/// into a concrete type
+fn entity_type(data_json: &str) -> Result<Option<String>, DatabaseError> {
+    #[derive(serde::Deserialize)]
+    struct Tagged {
+        #[serde(rename = "type")]
+        type_name: Option<String>,
+    }
+
+    let tagged: Tagged =
+        serde_json::from_str(data_json).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    Ok(tagged.type_name)
+}
+
+/// Reads the flags byte of an edge value
+fn edge_flags(value: &[u8]) -> u8 {
+    value.first().copied().unwrap_or(0)
+}
+
+/// Reads the payload of an edge value
+fn edge_payload(value: &[u8]) -> &[u8] {
+    value.get(1..).unwrap_or_default()
+}
+
+/// Encodes an edge value: empty for a plain edge, otherwise the flags byte
+/// followed by the payload
+fn encode_edge_value(flags: u8, payload: &[u8]) -> Vec<u8> {
+    if flags == 0 && payload.is_empty() {
+        return Vec::new();
+    }
+    let mut value = Vec::with_capacity(1 + payload.len());
+    value.push(flags);
+    value.extend_from_slice(payload);
+    value
 }
 
 fn find_edges_internal(
-    txn: &heed::RoTxn<'_>,
+    txn: &RoTxn<'_>,
     edges_db: &Database<Bytes, Bytes>,
     source: Id,
     query: EdgeQuery,
 ) -> Result<Vec<Edge>, DatabaseError> {
-    let mut results = Vec::new();
-
     // Create the prefix for this source
     let mut prefix = [0u8; 8];
     BigEndian::write_u64(&mut prefix, source);
@@ -411,43 +1757,69 @@ fn find_edges_internal(
     let mut all_edges: Vec<Edge> = Vec::new();
 
     for result in iter {
-        let (key, _) = result.map_err(|e| DatabaseError::Other {
+        let (key, value) = result.map_err(|e| DatabaseError::Other {
             source: Box::new(e),
         })?;
 
-        let (src, sort_key, dest) = parse_edge_key(key);
+        let (src, sort_key, dest, discriminator) = parse_edge_key(key);
         if src != source {
             break; // Past our prefix
         }
 
+        let hidden = edge_flags(value) & EDGE_FLAG_HIDDEN != 0;
+        if hidden && !query.include_hidden {
+            continue;
+        }
+
         // Apply edge name filter if specified
         if !query.edge_names.is_empty() && !query.edge_names.contains(&sort_key)
         {
             continue;
         }
 
-        all_edges.push(Edge::new(src, sort_key.to_vec(), dest));
+        all_edges.push(
+            Edge::new(src, sort_key.to_vec(), dest)
+                .with_discriminator(discriminator)
+                .with_hidden(hidden)
+                .with_payload(edge_payload(value)),
+        );
     }
 
-    // Sort based on order
-    match query.order {
-        SortOrder::Asc => {
-            all_edges.sort_by(|a, b| {
-                (&a.sort_key, a.dest).cmp(&(&b.sort_key, b.dest))
-            });
-        }
-        SortOrder::Desc => {
-            all_edges.sort_by(|a, b| {
-                (&b.sort_key, b.dest).cmp(&(&a.sort_key, a.dest))
-            });
-        }
+    Ok(paginate(all_edges, &query, |e| e.dest))
+}
+
+/// Sort edges by (sort_key, endpoint, discriminator) in query order, then
+/// return the page after the query's cursor
+fn paginate(
+    mut edges: Vec<Edge>,
+    query: &EdgeQuery,
+    endpoint: fn(&Edge) -> Id,
+) -> Vec<Edge> {
+    edges.sort_by(|a, b| {
+        (a.sort_key.as_slice(), endpoint(a), a.discriminator).cmp(&(
+            b.sort_key.as_slice(),
+            endpoint(b),
+            b.discriminator,
+        ))
+    });
+    if query.order == SortOrder::Desc {
+        edges.reverse();
     }
 
-    // Apply cursor filter
-    for edge in all_edges {
+    let limit = query.max_edges();
+    let mut results = Vec::new();
+    for edge in edges {
+        if results.len() >= limit {
+            break;
+        }
         if let Some(ref cursor) = query.cursor {
-            let edge_key = (edge.sort_key.as_slice(), edge.dest);
-            let cursor_key = (cursor.sort_key, cursor.destination);
+            let edge_key = (
+                edge.sort_key.as_slice(),
+                endpoint(&edge),
+                edge.discriminator,
+            );
+            let cursor_key =
+                (cursor.sort_key, cursor.destination, cursor.discriminator);
 
             match query.order {
                 SortOrder::Asc => {
@@ -464,13 +1836,9 @@ fn find_edges_internal(
         }
 
         results.push(edge);
-
-        if results.len() >= MAX_EDGES {
-            break;
-        }
     }
 
-    Ok(results)
+    results
 }
 
 #[cfg(test)]
@@ -483,23 +1851,26 @@ mod tests {
         let sort_key = b"test_edge";
         let dest = 67890u64;
 
-        let key = make_edge_key(source, sort_key, dest);
-        let (parsed_source, parsed_sort_key, parsed_dest) =
+        let key = make_edge_key(source, sort_key, dest, 3);
+        let (parsed_source, parsed_sort_key, parsed_dest, parsed_disc) =
             parse_edge_key(&key);
 
         assert_eq!(parsed_source, source);
         assert_eq!(parsed_sort_key, sort_key);
         assert_eq!(parsed_dest, dest);
+        assert_eq!(parsed_disc, 3);
     }
 
     #[test]
     fn test_edge_key_ordering() {
         // Verify that keys sort correctly
-        let key1 = make_edge_key(1, b"a", 10);
-        let key2 = make_edge_key(1, b"a", 20);
-        let key3 = make_edge_key(1, b"b", 10);
-        let key4 = make_edge_key(2, b"a", 10);
+        let key0 = make_edge_key(1, b"a", 10, 0);
+        let key1 = make_edge_key(1, b"a", 10, 1);
+        let key2 = make_edge_key(1, b"a", 20, 0);
+        let key3 = make_edge_key(1, b"b", 10, 0);
+        let key4 = make_edge_key(2, b"a", 10, 0);
 
+        assert!(key0 < key1); // Parallel edges, different discriminator
         assert!(key1 < key2); // Same source and type, different dest
         assert!(key2 < key3); // Same source, different type
         assert!(key3 < key4); // Different source
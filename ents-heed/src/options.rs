@@ -0,0 +1,213 @@
+//! Settings for opening an environment.
+//!
+//! [`HeedEnv::open`] covers the common case of a single process with the
+//! default map size. [`HeedEnvOptions`] also sets the snowflake node id,
+//! which must differ between processes writing to the same store so their
+//! ids do not collide, the LMDB reader table size, how commits are
+//! flushed to disk and which entity types are read at all. Processes can
+//! also lease node ids and the writer role instead, see
+//! [`HeedEnvOptions::lease_node_id`].
+//!
+//! ```ignore
+//! let env = HeedEnvOptions::new()
+//!     .node_id(7)
+//!     .map_size(16 << 30)
+//!     .max_readers(512)
+//!     .durability(Durability::NoMetaSync)
+//!     .open(path)?;
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Duration;
+
+use ents::DatabaseError;
+use heed::EnvFlags;
+
+use crate::lease::ProcessLease;
+use crate::HeedEnv;
+
+/// Default map size: 1GB
+const DEFAULT_MAP_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Largest snowflake node id
+pub(crate) const MAX_NODE_ID: u16 = 1023;
+
+/// How commits are flushed to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Flush data and metadata on every commit
+    #[default]
+    Full,
+    /// Skip the metadata flush. A system crash may lose the last commit,
+    /// but the store stays consistent.
+    NoMetaSync,
+    /// Leave flushing to the operating system. A system crash may lose
+    /// recent commits or corrupt the store; process crashes are safe.
+    NoSync,
+}
+
+impl Durability {
+    fn flags(self) -> EnvFlags {
+        match self {
+            Durability::Full => EnvFlags::empty(),
+            Durability::NoMetaSync => EnvFlags::NO_META_SYNC,
+            Durability::NoSync => EnvFlags::NO_SYNC,
+        }
+    }
+}
+
+/// Builder for [`HeedEnv`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeedEnvOptions {
+    pub(crate) map_size: usize,
+    pub(crate) max_readers: Option<u32>,
+    pub(crate) node_id: u16,
+    pub(crate) durability: Durability,
+    pub(crate) verify_ids: bool,
+    pub(crate) allowed_types: Option<BTreeSet<String>>,
+    pub(crate) lease_node_id: bool,
+    pub(crate) single_writer: bool,
+    pub(crate) writer_lease_expiry: Option<Duration>,
+    pub(crate) reverse_index: bool,
+}
+
+impl Default for HeedEnvOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeedEnvOptions {
+    pub const fn new() -> Self {
+        Self {
+            map_size: DEFAULT_MAP_SIZE,
+            max_readers: None,
+            node_id: 0,
+            durability: Durability::Full,
+            verify_ids: false,
+            allowed_types: None,
+            lease_node_id: false,
+            single_writer: false,
+            writer_lease_expiry: None,
+            reverse_index: true,
+        }
+    }
+
+    /// Snowflake instance of generated ids, at most 1023
+    pub const fn node_id(mut self, node_id: u16) -> Self {
+        self.node_id = node_id;
+        self
+    }
+
+    /// Maximum size of the database in bytes
+    pub const fn map_size(mut self, map_size: usize) -> Self {
+        self.map_size = map_size;
+        self
+    }
+
+    /// Maximum number of concurrent read transactions, across processes.
+    /// LMDB defaults to 126.
+    pub const fn max_readers(mut self, max_readers: u32) -> Self {
+        self.max_readers = Some(max_readers);
+        self
+    }
+
+    pub const fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Refuse to open a store holding entities above its id watermark,
+    /// see [`HeedEnv::audit_ids`]
+    pub const fn verify_ids(mut self, verify: bool) -> Self {
+        self.verify_ids = verify;
+        self
+    }
+
+    /// Only read entities whose typetag name is in `types`. Others look
+    /// absent to `get` and scans, and are skipped after reading their type
+    /// tag instead of being deserialized, e.g. for an exporter that needs a
+    /// few types of a large store.
+    pub fn allow_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_types = Some(types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Lease a node id no other leasing process holds instead of using
+    /// [`node_id`](Self::node_id), see [`HeedEnv::leased_node_id`]
+    pub const fn lease_node_id(mut self, lease: bool) -> Self {
+        self.lease_node_id = lease;
+        self
+    }
+
+    /// Fail to open while another process opened the store with
+    /// `single_writer`, and keep others from doing so until the returned
+    /// environment is dropped
+    pub const fn single_writer(mut self, single: bool) -> Self {
+        self.single_writer = single;
+        self
+    }
+
+    /// With [`single_writer`](Self::single_writer), take the writer role
+    /// over from a holder that has neither committed nor renewed it for
+    /// `expiry`, e.g. a hung process. The holder's later commits fail with
+    /// [`DatabaseError::Fenced`], see [`HeedEnv::fencing_token`].
+    pub const fn writer_lease_expiry(mut self, expiry: Duration) -> Self {
+        self.writer_lease_expiry = Some(expiry);
+        self
+    }
+
+    /// Create new stores with [`StoreFeature::ReverseIndex`] active, so
+    /// deletes and `find_edges_to` look edges up by destination instead of
+    /// scanning every edge. On by default; stores created without it turn
+    /// it on with [`HeedEnv::enable_feature`].
+    ///
+    /// [`StoreFeature::ReverseIndex`]: crate::StoreFeature::ReverseIndex
+    pub const fn reverse_index(mut self, enabled: bool) -> Self {
+        self.reverse_index = enabled;
+        self
+    }
+
+    /// Opens or creates the environment at `path`
+    pub fn open<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<HeedEnv, DatabaseError> {
+        if self.node_id > MAX_NODE_ID {
+            return Err(DatabaseError::Other {
+                source: format!(
+                    "node id {} exceeds {}",
+                    self.node_id, MAX_NODE_ID
+                )
+                .into(),
+            });
+        }
+        let mut lease = ProcessLease::acquire(path.as_ref(), self)?;
+        let mut options = self.clone();
+        if let Some(node_id) = lease.node_id() {
+            options.node_id = node_id;
+        }
+        let mut env = HeedEnv::open_with(path.as_ref(), &options)?;
+        lease.claim_writer(&env, path.as_ref(), self.writer_lease_expiry)?;
+        env.lease = lease;
+        Ok(env)
+    }
+
+    /// Applies the settings to LMDB's options
+    pub(crate) fn apply(&self, options: &mut heed::EnvOpenOptions) {
+        options.map_size(self.map_size);
+        if let Some(max_readers) = self.max_readers {
+            options.max_readers(max_readers);
+        }
+        // SAFETY: the sync flags only weaken durability after a system
+        // crash, as documented on `Durability`
+        unsafe {
+            options.flags(self.durability.flags());
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Running out of map space.
+//!
+//! LMDB reserves a fixed-size map when the environment opens, and a write
+//! that needs more room fails with `MDB_MAP_FULL`. Such writes surface as
+//! [`DatabaseError::EntCapacityReached`], and the transaction must be rolled
+//! back.
+//!
+//! [`HeedEnv::set_map_size`] grows the map in place. It takes `&mut self`,
+//! which guarantees that no transaction of the environment is open, as LMDB
+//! requires. [`HeedEnv::write`] builds on it: with an [`AutoResize`]
+//! installed, a transaction that fills the map is rolled back, the map
+//! grown, and the transaction run again from the start.
+//!
+//! ```ignore
+//! let mut env = HeedEnv::open(path, Some(64 << 20))?
+//!     .with_auto_resize(AutoResize::new().with_max_map_size(16 << 30));
+//! let id = env.write(|txn| txn.create(user.clone()))?;
+//! ```
+
+use ents::{DatabaseError, Transactional};
+use heed::MdbError;
+
+use crate::{HeedEnv, Txn};
+
+/// How [`HeedEnv::write`] grows a full map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoResize {
+    /// Factor the map size is multiplied by on each resize
+    pub growth_factor: usize,
+    /// Largest map size to grow to. Must be a multiple of the OS page size.
+    pub max_map_size: Option<usize>,
+}
+
+impl Default for AutoResize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoResize {
+    pub const fn new() -> Self {
+        Self {
+            growth_factor: 2,
+            max_map_size: None,
+        }
+    }
+
+    pub const fn with_growth_factor(mut self, factor: usize) -> Self {
+        self.growth_factor = factor;
+        self
+    }
+
+    pub const fn with_max_map_size(mut self, size: usize) -> Self {
+        self.max_map_size = Some(size);
+        self
+    }
+
+    /// The size to grow a full map of `current` bytes to, if it may grow
+    fn next_size(&self, current: usize) -> Option<usize> {
+        let mut next = current.saturating_mul(self.growth_factor);
+        if let Some(max) = self.max_map_size {
+            next = next.min(max);
+        }
+        (next > current).then_some(next)
+    }
+}
+
+impl HeedEnv {
+    /// Grow the map and retry in [`HeedEnv::write`] when it fills up
+    pub fn with_auto_resize(mut self, resize: AutoResize) -> Self {
+        self.auto_resize = Some(resize);
+        self
+    }
+
+    /// Size of the map in bytes
+    pub fn map_size(&self) -> usize {
+        self.env.info().map_size
+    }
+
+    /// Change the size of the map. `size` must be a multiple of the OS page
+    /// size and cannot drop below the space in use.
+    pub fn set_map_size(&mut self, size: usize) -> Result<(), DatabaseError> {
+        // SAFETY: transactions borrow the environment, so none is open
+        // while we hold it mutably
+        unsafe { self.env.resize(size) }.map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+    }
+
+    /// Run `f` in a write transaction and commit it. If the map fills up
+    /// and auto resize is enabled, the transaction is rolled back, the map
+    /// grown and `f` run again, so `f` must not depend on earlier attempts.
+    pub fn write<F, R>(&mut self, mut f: F) -> Result<R, DatabaseError>
+    where
+        F: FnMut(&Txn<'_>) -> Result<R, DatabaseError>,
+    {
+        loop {
+            let result = self.write_txn().and_then(|txn| {
+                let result = f(&txn)?;
+                txn.commit()?;
+                Ok(result)
+            });
+            match result {
+                Err(DatabaseError::EntCapacityReached) => {
+                    let next = self
+                        .auto_resize
+                        .and_then(|resize| resize.next_size(self.map_size()))
+                        .ok_or(DatabaseError::EntCapacityReached)?;
+                    self.set_map_size(next)?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Converts the error of an LMDB write, recognizing a full map
+pub(crate) fn write_error(e: heed::Error) -> DatabaseError {
+    match e {
+        heed::Error::Mdb(MdbError::MapFull) => {
+            DatabaseError::EntCapacityReached
+        }
+        e => DatabaseError::Other {
+            source: Box::new(e),
+        },
+    }
+}
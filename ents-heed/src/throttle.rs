@@ -0,0 +1,159 @@
+//! Backpressure for write transactions.
+//!
+//! A [`WriteThrottle`] installed with [`HeedEnv::with_write_throttle`] is
+//! checked by [`HeedEnv::write_txn`] before it waits for the write lock.
+//! While too many writers are already waiting, or more of the map is in use
+//! than allowed, a new writer backs off and checks again until `max_delay`
+//! has passed, then fails with [`DatabaseError::Overloaded`]. A zero
+//! `max_delay` rejects immediately. LMDB has no write-ahead log, so queue
+//! depth and map usage are the pressure signals.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?.with_write_throttle(
+//!     WriteThrottle::new()
+//!         .with_max_queued_writers(8)
+//!         .with_max_map_usage(0.9)
+//!         .with_max_delay(Duration::from_millis(50)),
+//! );
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ents::DatabaseError;
+
+use crate::HeedEnv;
+
+/// Pause between pressure checks of a delayed writer
+const RECHECK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Thresholds above which new write transactions are delayed or rejected
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WriteThrottle {
+    /// Writers allowed to wait for the write lock at once
+    pub max_queued_writers: Option<usize>,
+    /// Fraction of the map size that may be in use, between 0 and 1
+    pub max_map_usage: Option<f64>,
+    /// How long a writer waits for the pressure to drop before failing
+    pub max_delay: Duration,
+}
+
+impl WriteThrottle {
+    pub const fn new() -> Self {
+        Self {
+            max_queued_writers: None,
+            max_map_usage: None,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    pub const fn with_max_queued_writers(mut self, max: usize) -> Self {
+        self.max_queued_writers = Some(max);
+        self
+    }
+
+    pub const fn with_max_map_usage(mut self, max: f64) -> Self {
+        self.max_map_usage = Some(max);
+        self
+    }
+
+    pub const fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
+
+/// A writer counted as waiting for the write lock until dropped
+pub(crate) struct QueuedWriter<'a> {
+    queued: &'a AtomicUsize,
+    /// Writers that were already waiting when this one arrived
+    ahead: usize,
+}
+
+impl Drop for QueuedWriter<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl HeedEnv {
+    /// Delay or reject write transactions under pressure
+    pub fn with_write_throttle(mut self, throttle: WriteThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Number of writers waiting for the write lock
+    pub fn queued_writers(&self) -> usize {
+        self.queued_writers.load(Ordering::SeqCst)
+    }
+
+    /// Fraction of the map size used by the databases, not counting free
+    /// pages
+    pub fn map_usage(&self) -> Result<f64, DatabaseError> {
+        let used = self.env.non_free_pages_size().map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(used as f64 / self.env.info().map_size as f64)
+    }
+
+    /// Queue a new writer, waiting out or rejecting it while the throttle
+    /// reports pressure
+    pub(crate) fn admit_writer(
+        &self,
+    ) -> Result<QueuedWriter<'_>, DatabaseError> {
+        let deadline = self
+            .throttle
+            .map(|throttle| Instant::now() + throttle.max_delay);
+        loop {
+            let writer = QueuedWriter {
+                queued: &self.queued_writers,
+                ahead: self.queued_writers.fetch_add(1, Ordering::SeqCst),
+            };
+            let Some(reason) = self.pressure(&writer)? else {
+                return Ok(writer);
+            };
+            drop(writer);
+
+            let now = Instant::now();
+            match deadline {
+                Some(deadline) if now < deadline => {
+                    thread::sleep(RECHECK_INTERVAL.min(deadline - now));
+                }
+                _ => return Err(DatabaseError::Overloaded { reason }),
+            }
+        }
+    }
+
+    /// Why `writer` should not proceed yet, if it should not
+    fn pressure(
+        &self,
+        writer: &QueuedWriter<'_>,
+    ) -> Result<Option<String>, DatabaseError> {
+        let Some(throttle) = self.throttle else {
+            return Ok(None);
+        };
+        if let Some(max) = throttle.max_queued_writers {
+            if writer.ahead >= max {
+                return Ok(Some(format!(
+                    "{} writers already queued (max {})",
+                    writer.ahead, max
+                )));
+            }
+        }
+        if let Some(max) = throttle.max_map_usage {
+            let usage = self.map_usage()?;
+            if usage > max {
+                return Ok(Some(format!(
+                    "map {:.1}% full (max {:.1}%)",
+                    usage * 100.0,
+                    max * 100.0
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
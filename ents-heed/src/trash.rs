@@ -0,0 +1,160 @@
+//! Soft-deleted entities.
+//!
+//! `soft_delete` moves the stored JSON of an entity from `entities` to
+//! `deleted`, prefixed with the time of deletion, and drops it from the
+//! type index. Edges and unique keys are left alone, so `restore` only
+//! has to move the JSON back. Purging restores the entity and deletes it
+//! like any other.
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::clock::now_micros;
+use ents::watch::{ChangeKind, EntityChange};
+use ents::{DatabaseError, Ent, EntWithEdges, Id, ReadTransactional};
+use heed::RoTxn;
+
+use crate::resize::write_error;
+use crate::{type_index_key, HeedEnv, StoreFeature, Txn};
+
+impl Txn<'_> {
+    pub(crate) fn move_to_trash<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        if self.get_as::<E>(id)?.is_none() {
+            return Ok(false);
+        }
+        if self.feature_maintained(StoreFeature::TypeIndex)? {
+            self.delete_type_index(id)?;
+        }
+        let mut wtxn = self.txn.borrow_mut();
+        let data_json = self
+            .env
+            .entities
+            .get(&wtxn, &id)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .unwrap_or_default()
+            .to_string();
+        let mut value = vec![0; 8];
+        BigEndian::write_u64(&mut value, now_micros());
+        value.extend_from_slice(data_json.as_bytes());
+        self.env
+            .deleted
+            .put(&mut wtxn, &id, &value)
+            .map_err(write_error)?;
+        self.env
+            .entities
+            .delete(&mut wtxn, &id)
+            .map_err(write_error)?;
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Deleted));
+        Ok(true)
+    }
+
+    pub(crate) fn restore_from_trash<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        let Some(data_json) = self.env.trashed_json(&self.txn.borrow(), id)?
+        else {
+            return Ok(false);
+        };
+        let Some(ent) = self.env.decode(id, &data_json)? else {
+            return Ok(false);
+        };
+        if ent.downcast_ref::<E>().is_none() {
+            return Ok(false);
+        }
+        if self.entity_exists(id)? {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        let indexed = self.feature_maintained(StoreFeature::TypeIndex)?;
+        let mut wtxn = self.txn.borrow_mut();
+        self.env
+            .entities
+            .put(&mut wtxn, &id, &data_json)
+            .map_err(write_error)?;
+        self.env.note_stored(id);
+        self.env
+            .deleted
+            .delete(&mut wtxn, &id)
+            .map_err(write_error)?;
+        if indexed {
+            self.env
+                .entities_by_type
+                .put(&mut wtxn, &type_index_key(ent.typetag_name(), id), &[])
+                .map_err(write_error)?;
+        }
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Created));
+        Ok(true)
+    }
+}
+
+impl HeedEnv {
+    pub(crate) fn get_deleted_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        match self.trashed_json(txn, id)? {
+            Some(data_json) => self.decode(id, &data_json),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn list_deleted_internal(
+        &self,
+        txn: &RoTxn<'_>,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let mut ids = Vec::new();
+        let start = match after {
+            Some(Id::MAX) => return Ok(ids),
+            Some(after) => after + 1,
+            None => 0,
+        };
+        let iter = self.deleted.range(txn, &(start..)).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        for result in iter {
+            if ids.len() >= limit {
+                break;
+            }
+            let (id, value) = result.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            if BigEndian::read_u64(&value[..8]) < deleted_before {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// JSON of the soft-deleted entity `id`
+    pub(crate) fn trashed_json(
+        &self,
+        txn: &RoTxn<'_>,
+        id: Id,
+    ) -> Result<Option<String>, DatabaseError> {
+        let value =
+            self.deleted
+                .get(txn, &id)
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        value
+            .map(|value| match std::str::from_utf8(&value[8..]) {
+                Ok(data_json) => Ok(data_json.to_string()),
+                Err(e) => Err(DatabaseError::Other {
+                    source: Box::new(e),
+                }),
+            })
+            .transpose()
+    }
+}
@@ -0,0 +1,236 @@
+//! The on-disk format version.
+//!
+//! `meta` records the version of the layout a store was written with under
+//! `format_version`. A new store is stamped with [`FORMAT_VERSION`]; stores
+//! created before the stamp existed are version 0. [`HeedEnv::open`]
+//! refuses a store of any other version with
+//! [`DatabaseError::UnsupportedFormat`]: a newer one needs a newer library,
+//! an older one needs [`HeedEnv::upgrade_store`] first.
+//!
+//! ```ignore
+//! match HeedEnv::open(path, None) {
+//!     Err(DatabaseError::UnsupportedFormat { found, supported })
+//!         if found < supported =>
+//!     {
+//!         HeedEnv::upgrade_store(path, None)?;
+//!         HeedEnv::open(path, None)?
+//!     }
+//!     other => other?,
+//! }
+//! ```
+//!
+//! A change to key encoding or value layout bumps [`FORMAT_VERSION`] and
+//! appends the step rewriting the previous layout to `UPGRADES`.
+//!
+//! Unversioned stores come in two edge key layouts that their data does not
+//! tell apart: keys written before parallel edges end at the destination,
+//! later ones carry a discriminator after it. [`HeedEnv::upgrade_store`]
+//! takes an unversioned store to have discriminators; upgrade one written
+//! before them with [`HeedEnv::upgrade_pre_discriminator_store`] instead.
+
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::DatabaseError;
+use heed::types::{Bytes, Str};
+use heed::{Database, Env, EnvOpenOptions, RoTxn, RwTxn};
+
+use crate::lease::lock_writer;
+use crate::resize::write_error;
+use crate::{HeedEnv, HeedEnvOptions};
+
+/// The format version this library reads and writes
+pub const FORMAT_VERSION: u32 = 3;
+
+/// Meta key holding the format version, big endian
+const VERSION_KEY: &str = "format_version";
+
+/// Rewrites a store of one version into the next
+type Upgrade = fn(&Env, &mut RwTxn<'_>) -> Result<(), DatabaseError>;
+
+/// `UPGRADES[v]` turns a version `v` store into a version `v + 1` one
+const UPGRADES: [Upgrade; FORMAT_VERSION as usize] =
+    [stamp_only, add_deleted, add_history];
+
+/// Version 1 only adds the version stamp itself
+fn stamp_only(_: &Env, _: &mut RwTxn<'_>) -> Result<(), DatabaseError> {
+    Ok(())
+}
+
+/// Version 1 of a store written before parallel edges: its edge keys end
+/// at the destination, so each gets discriminator 0 appended
+fn add_discriminators(
+    env: &Env,
+    wtxn: &mut RwTxn<'_>,
+) -> Result<(), DatabaseError> {
+    let edges: Option<Database<Bytes, Bytes>> = env
+        .open_database(wtxn, Some("edges"))
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let Some(edges) = edges else {
+        return Ok(());
+    };
+    let mut old = Vec::new();
+    for result in edges.iter(wtxn).map_err(|e| DatabaseError::Other {
+        source: Box::new(e),
+    })? {
+        let (key, value) = result.map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        old.push((key.to_vec(), value.to_vec()));
+    }
+    edges.clear(wtxn).map_err(write_error)?;
+    for (mut key, value) in old {
+        key.extend_from_slice(&0u64.to_be_bytes());
+        edges.put(wtxn, &key, &value).map_err(write_error)?;
+    }
+    Ok(())
+}
+
+/// Version 2 adds the `deleted` database of soft-deleted entities
+fn add_deleted(env: &Env, wtxn: &mut RwTxn<'_>) -> Result<(), DatabaseError> {
+    env.create_database::<Bytes, Bytes>(wtxn, Some("deleted"))
+        .map_err(write_error)?;
+    Ok(())
+}
+
+/// Version 3 adds the `history` database of prior entity versions
+fn add_history(env: &Env, wtxn: &mut RwTxn<'_>) -> Result<(), DatabaseError> {
+    env.create_database::<Bytes, Bytes>(wtxn, Some("history"))
+        .map_err(write_error)?;
+    Ok(())
+}
+
+/// The version recorded in `meta`, 0 if none is
+pub(crate) fn stored_version(
+    meta: &Database<Str, Bytes>,
+    txn: &RoTxn<'_>,
+) -> Result<u32, DatabaseError> {
+    let value =
+        meta.get(txn, VERSION_KEY)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+    Ok(value.map_or(0, BigEndian::read_u32))
+}
+
+pub(crate) fn write_version(
+    meta: &Database<Str, Bytes>,
+    txn: &mut RwTxn<'_>,
+    version: u32,
+) -> Result<(), DatabaseError> {
+    let mut buf = [0; 4];
+    BigEndian::write_u32(&mut buf, version);
+    meta.put(txn, VERSION_KEY, &buf).map_err(write_error)
+}
+
+/// Fail unless the store is of [`FORMAT_VERSION`]
+pub(crate) fn check_version(
+    meta: &Database<Str, Bytes>,
+    txn: &RoTxn<'_>,
+) -> Result<(), DatabaseError> {
+    let found = stored_version(meta, txn)?;
+    if found != FORMAT_VERSION {
+        return Err(DatabaseError::UnsupportedFormat {
+            found,
+            supported: FORMAT_VERSION,
+        });
+    }
+    Ok(())
+}
+
+impl HeedEnv {
+    /// Upgrade the store at `path` to [`FORMAT_VERSION`], returning the
+    /// version it had before. Each step commits on its own, so an
+    /// interrupted upgrade resumes where it stopped. Call it while no other
+    /// process has the store open; it fails with
+    /// [`DatabaseError::Overloaded`] while one holds the writer role.
+    ///
+    /// An unversioned store is taken to have edge discriminators; see
+    /// [`HeedEnv::upgrade_pre_discriminator_store`] for older ones.
+    pub fn upgrade_store<P: AsRef<Path>>(
+        path: P,
+        map_size: Option<usize>,
+    ) -> Result<u32, DatabaseError> {
+        upgrade_path(path.as_ref(), map_size, UPGRADES[0])
+    }
+
+    /// Like [`HeedEnv::upgrade_store`], for an unversioned store written
+    /// before parallel edges: its edge keys are rewritten to carry
+    /// discriminator 0. A versioned store already has discriminators and is
+    /// upgraded as by [`HeedEnv::upgrade_store`], which lets an interrupted
+    /// upgrade resume.
+    pub fn upgrade_pre_discriminator_store<P: AsRef<Path>>(
+        path: P,
+        map_size: Option<usize>,
+    ) -> Result<u32, DatabaseError> {
+        upgrade_path(path.as_ref(), map_size, add_discriminators)
+    }
+}
+
+/// Upgrade the store at `path`, stamping an unversioned one with `first`
+fn upgrade_path(
+    path: &Path,
+    map_size: Option<usize>,
+    first: Upgrade,
+) -> Result<u32, DatabaseError> {
+    let mut options = HeedEnvOptions::new();
+    if let Some(map_size) = map_size {
+        options = options.map_size(map_size);
+    }
+    let _writer = lock_writer(path)?;
+    let env = unsafe {
+        let mut env_options = EnvOpenOptions::new();
+        options.apply(&mut env_options);
+        env_options.max_dbs(9).open(path)
+    }
+    .map_err(|e| DatabaseError::Other {
+        source: Box::new(e),
+    })?;
+    let result = upgrade_env(&env, first);
+    env.prepare_for_closing().wait();
+    result
+}
+
+fn upgrade_env(env: &Env, first: Upgrade) -> Result<u32, DatabaseError> {
+    let rtxn = env.read_txn().map_err(|e| DatabaseError::Other {
+        source: Box::new(e),
+    })?;
+    let meta: Option<Database<Str, Bytes>> = env
+        .open_database(&rtxn, Some("meta"))
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let Some(meta) = meta else {
+        return Err(DatabaseError::Other {
+            source: "no store to upgrade".into(),
+        });
+    };
+    let previous = stored_version(&meta, &rtxn)?;
+    // Committing keeps the `meta` handle open for the write transactions
+    rtxn.commit().map_err(|e| DatabaseError::Other {
+        source: Box::new(e),
+    })?;
+    if previous > FORMAT_VERSION {
+        return Err(DatabaseError::UnsupportedFormat {
+            found: previous,
+            supported: FORMAT_VERSION,
+        });
+    }
+
+    for version in previous..FORMAT_VERSION {
+        let mut wtxn = env.write_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let step = if version == 0 {
+            first
+        } else {
+            UPGRADES[version as usize]
+        };
+        step(env, &mut wtxn)?;
+        write_version(&meta, &mut wtxn, version + 1)?;
+        wtxn.commit().map_err(write_error)?;
+    }
+    Ok(previous)
+}
@@ -0,0 +1,119 @@
+//! Detecting reused entity ids.
+//!
+//! Every insert raises a watermark in `meta` to the largest id handed out.
+//! Stored entities above the watermark mean the entities were written
+//! behind the environment's back, e.g. a `data.mdb` spliced together from
+//! several snapshots, and the id provider may hand out ids that clients
+//! already hold. [`HeedEnv::audit_ids`] compares the two,
+//! [`HeedEnv::repair_id_watermark`] raises the watermark to the largest
+//! stored id, and [`HeedEnvOptions::verify_ids`] refuses to open a store
+//! that fails the audit.
+//!
+//! Stores created before the watermark existed have none. Repair them once
+//! before enabling the check.
+//!
+//! ```ignore
+//! let audit = env.audit_ids()?;
+//! if !audit.is_consistent() {
+//!     env.repair_id_watermark()?;
+//! }
+//! ```
+//!
+//! [`HeedEnvOptions::verify_ids`]: crate::HeedEnvOptions::verify_ids
+
+use byteorder::{BigEndian, ByteOrder};
+use ents::{DatabaseError, Id};
+use heed::{RoTxn, RwTxn};
+
+use crate::resize::write_error;
+use crate::HeedEnv;
+
+/// Meta key holding the largest id handed out, big endian
+const WATERMARK_KEY: &str = "id_watermark";
+
+/// Result of comparing the id watermark against the stored entities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdAudit {
+    /// Largest id handed out, None if never recorded
+    pub watermark: Option<Id>,
+    /// Largest id of a stored entity, None if the store is empty
+    pub max_stored: Option<Id>,
+}
+
+impl IdAudit {
+    /// Whether no stored entity lies above the watermark
+    pub fn is_consistent(&self) -> bool {
+        match (self.max_stored, self.watermark) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(max), Some(watermark)) => max <= watermark,
+        }
+    }
+}
+
+impl HeedEnv {
+    /// Compare the id watermark against the largest stored id
+    pub fn audit_ids(&self) -> Result<IdAudit, DatabaseError> {
+        let txn = self.env.read_txn().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.audit_ids_in(&txn)
+    }
+
+    /// Raise the watermark to the largest stored id, returning the audit
+    /// from before the repair
+    pub fn repair_id_watermark(&self) -> Result<IdAudit, DatabaseError> {
+        let mut wtxn = self.fenced_write_txn()?;
+        let audit = self.audit_ids_in(&wtxn)?;
+        if let Some(max) = audit.max_stored {
+            self.raise_watermark(&mut wtxn, max)?;
+        }
+        wtxn.commit().map_err(write_error)?;
+        Ok(audit)
+    }
+
+    pub(crate) fn audit_ids_in(
+        &self,
+        txn: &RoTxn<'_>,
+    ) -> Result<IdAudit, DatabaseError> {
+        let max_stored = self
+            .entities
+            .last(txn)
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .map(|(id, _)| id);
+        Ok(IdAudit {
+            watermark: self.watermark_in(txn)?,
+            max_stored,
+        })
+    }
+
+    /// Record that `id` was handed out
+    pub(crate) fn raise_watermark(
+        &self,
+        wtxn: &mut RwTxn<'_>,
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        if self.watermark_in(wtxn)?.is_some_and(|w| w >= id) {
+            return Ok(());
+        }
+        let mut value = [0u8; 8];
+        BigEndian::write_u64(&mut value, id);
+        self.meta
+            .put(wtxn, WATERMARK_KEY, &value)
+            .map_err(write_error)
+    }
+
+    fn watermark_in(
+        &self,
+        txn: &RoTxn<'_>,
+    ) -> Result<Option<Id>, DatabaseError> {
+        let value = self.meta.get(txn, WATERMARK_KEY).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        Ok(value.filter(|v| v.len() == 8).map(BigEndian::read_u64))
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use ents::clock::{with_clock, MockClock};
+use ents::history::HistoryRetention;
+use ents::{DatabaseError, EdgeQuery, EdgeValue, EntExt, Id, Transactional};
+use ents_heed::{HeedEnv, StoreFeature};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+fn value(ent: Option<Box<dyn ents::Ent>>) -> Option<i32> {
+    ent.map(|ent| ent.into_ent::<TestEntity>().unwrap().value)
+}
+
+#[test]
+fn test_reads_as_of() -> Result<(), DatabaseError> {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)?
+        .with_history(HistoryRetention::unbounded());
+    env.enable_feature(StoreFeature::ChangeCapture)?;
+    let clock = Arc::new(MockClock::new(100));
+    let (alice, bob, carol) = with_clock(clock.clone(), || {
+        let txn = env.write_txn()?;
+        let alice = txn.create(TestEntity::new("alice".to_string(), 1))?;
+        let bob = txn.create(TestEntity::new("bob".to_string(), 1))?;
+        txn.commit()?;
+
+        clock.set(200);
+        let txn = env.write_txn()?;
+        txn.retry_update(alice, 1, |e: &mut TestEntity| e.value = 2)?;
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))?;
+        txn.commit()?;
+
+        clock.set(300);
+        let txn = env.write_txn()?;
+        let carol = txn.create(TestEntity::new("carol".to_string(), 1))?;
+        txn.hide_edge(&EdgeValue::new(alice, b"follows".to_vec(), bob))?;
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), carol))?;
+        txn.commit()?;
+        Ok::<_, DatabaseError>((alice, bob, carol))
+    })?;
+
+    let txn = env.read_txn()?;
+    assert_eq!(value(txn.get_as_of(alice, 150)?), Some(1));
+    assert_eq!(value(txn.get_as_of(alice, 250)?), Some(2));
+    assert_eq!(value(txn.get_as_of(carol, 250)?), None);
+    assert_eq!(value(txn.get_as_of(carol, 300)?), Some(1));
+
+    let follows = |at| -> Result<Vec<Id>, DatabaseError> {
+        let edges =
+            txn.find_edges_as_of(alice, EdgeQuery::asc(&[b"follows"]), at)?;
+        Ok(edges.into_iter().map(|edge| edge.dest).collect())
+    };
+    assert_eq!(follows(150)?, Vec::<Id>::new());
+    assert_eq!(follows(250)?, vec![bob]);
+    assert_eq!(follows(350)?, vec![carol]);
+    drop(txn);
+
+    // Write transactions answer too
+    let txn = env.write_txn()?;
+    assert_eq!(value(txn.get_as_of(alice, 150)?), Some(1));
+    assert_eq!(
+        txn.find_edges_as_of(alice, EdgeQuery::asc(&[b"follows"]), 250)?
+            .len(),
+        1
+    );
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::sync::mpsc;
+use std::thread;
+
+use ents::{EdgeValue, EntExt as _, ReadTransactional, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::User;
+use tempfile::tempdir;
+
+fn user(name: &str) -> User {
+    User::new(name.to_string(), format!("{}@example.com", name))
+}
+
+#[test]
+fn test_verify_backup() {
+    let live_dir = tempdir().unwrap();
+    let snapshot_dir = tempdir().unwrap();
+    let env = HeedEnv::open(live_dir.path(), None).unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(user("alice")).unwrap();
+    let bob = txn.create(user("bob")).unwrap();
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+        .unwrap();
+    // Points at an entity that does not exist
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), 424242))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent());
+    assert_eq!(report.entities, 2);
+    assert_eq!(report.edges, 2);
+    assert_eq!(report.dangling_edges, 1);
+
+    // No write transaction is open, so the data file is a valid snapshot
+    fs::copy(
+        live_dir.path().join("data.mdb"),
+        snapshot_dir.path().join("data.mdb"),
+    )
+    .unwrap();
+
+    let report = env.verify_backup(snapshot_dir.path()).unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+    assert_eq!(report.sampled, 2);
+
+    // Diverge the live store from the snapshot
+    let txn = env.write_txn().unwrap();
+    let ent = txn.get(bob).unwrap().unwrap();
+    let ent = ent.downcast_ent::<User>().unwrap();
+    assert!(txn
+        .update(ent, |u: &mut User| u.username = "robert".to_string())
+        .unwrap());
+    txn.delete::<User>(alice).unwrap();
+    txn.create(user("carol")).unwrap();
+    txn.commit().unwrap();
+
+    let report = env.verify_backup(snapshot_dir.path()).unwrap();
+    assert!(!report.is_ok());
+    assert!(report.snapshot.is_consistent());
+    assert_eq!(report.mismatched, vec![bob]);
+    assert_eq!(report.missing_in_live, vec![alice]);
+}
+
+#[test]
+fn test_verify_backup_rejects_missing_snapshot() {
+    let live_dir = tempdir().unwrap();
+    let empty_dir = tempdir().unwrap();
+    let env = HeedEnv::open(live_dir.path(), None).unwrap();
+
+    assert!(env.verify_backup(empty_dir.path()).is_err());
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let live_dir = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    let env = HeedEnv::open(live_dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(user("alice")).unwrap();
+    txn.commit().unwrap();
+
+    // A compacting backup runs next to a write transaction in progress,
+    // without its writes
+    let (created_tx, created_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+    let bob = thread::scope(|s| {
+        let env = &env;
+        s.spawn(move || {
+            let txn = env.write_txn().unwrap();
+            created_tx.send(txn.create(user("bob")).unwrap()).unwrap();
+            done_rx.recv().unwrap();
+            txn.commit().unwrap();
+        });
+        let bob = created_rx.recv().unwrap();
+        env.backup_to(backups.path().join("compact"), true).unwrap();
+        done_tx.send(()).unwrap();
+        bob
+    });
+    env.backup_to(backups.path().join("full"), false).unwrap();
+    assert!(env.backup_to(backups.path().join("full"), false).is_err());
+
+    let restored_dir = tempdir().unwrap();
+    for (name, has_bob) in [("compact", false), ("full", true)] {
+        let path = restored_dir.path().join(name);
+        HeedEnv::restore(backups.path().join(name), &path).unwrap();
+        assert!(HeedEnv::restore(backups.path().join(name), &path).is_err());
+
+        let restored = HeedEnv::open(&path, None).unwrap();
+        assert!(restored.check_consistency().unwrap().is_consistent());
+        let txn = restored.write_txn().unwrap();
+        assert!(txn.get(alice).unwrap().is_some());
+        assert_eq!(txn.get(bob).unwrap().is_some(), has_bob);
+    }
+}
@@ -0,0 +1,57 @@
+use ents::{
+    EdgeQuery, EdgeValue, Ent, EntExt, Id, QueryEdge, ReadTransactional,
+    Transactional,
+};
+use ents_heed::{HeedEnv, StoreFeature};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+fn entity(i: Id) -> Box<dyn Ent> {
+    let mut ent = TestEntity::new(format!("e{}", i), i as i32);
+    ent.set_id(i);
+    Box::new(ent)
+}
+
+#[test]
+fn test_bulk_load() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    env.enable_feature(StoreFeature::ReverseIndex).unwrap();
+    env.enable_feature(StoreFeature::TypeIndex).unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let edges = (1..10).map(|i| EdgeValue::new(i, b"next".to_vec(), i + 1));
+    let report = txn.bulk_load((1..=10).map(entity), edges).unwrap();
+    assert_eq!((report.entities, report.edges), (10, 9));
+
+    // Out of order input lands in the right place too
+    let report = txn
+        .bulk_load(
+            [entity(20), entity(15), entity(5)],
+            [
+                EdgeValue::new(20, b"next".to_vec(), 15),
+                EdgeValue::new(1, b"after".to_vec(), 20),
+            ],
+        )
+        .unwrap();
+    assert_eq!((report.entities, report.edges), (3, 2));
+    txn.commit().unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let fifth = txn.get(5).unwrap().unwrap().into_ent::<TestEntity>();
+    assert_eq!(fifth.unwrap().value, 5);
+    assert_eq!(
+        txn.list_ids_by_type("TestEntity", None, 100).unwrap(),
+        (1..=10).chain([15, 20]).collect::<Vec<_>>()
+    );
+    let from_one = txn.find_edges(1, EdgeQuery::asc(&[])).unwrap();
+    let dests: Vec<Id> = from_one.iter().map(|e| e.dest).collect();
+    assert_eq!(dests, vec![20, 2]);
+    let into_fifteen = txn.find_edges_to(15, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(into_fifteen[0].source, 20);
+    drop(txn);
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.edges, 11);
+}
@@ -0,0 +1,75 @@
+use ents::cdc::CapturedChange;
+use ents::{EdgeValue, Transactional};
+use ents_heed::{HeedEnv, StoreFeature};
+use ents_test_suite::{Tag, User};
+use tempfile::tempdir;
+
+fn user(name: &str) -> User {
+    User::new(name.to_string(), format!("{}@example.com", name))
+}
+
+#[test]
+fn test_change_capture() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+
+    // Writes before the feature is enabled are not captured
+    let txn = env.write_txn().unwrap();
+    let ada = txn.create(user("ada")).unwrap();
+    txn.commit().unwrap();
+    env.enable_feature(StoreFeature::ChangeCapture).unwrap();
+    assert!(env.changes_since(0, 10).unwrap().is_empty());
+
+    let txn = env.write_txn().unwrap();
+    let rust = txn
+        .create(Tag::new("rust".into(), "orange".into()))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(ada, b"likes".to_vec(), rust))
+        .unwrap();
+    txn.commit().unwrap();
+
+    // Rolled back writes are not captured either
+    let txn = env.write_txn().unwrap();
+    txn.create(user("bob")).unwrap();
+    drop(txn);
+
+    let txn = env.write_txn().unwrap();
+    txn.delete::<Tag>(rust).unwrap();
+    txn.commit().unwrap();
+
+    let records = env.changes_since(0, 10).unwrap();
+    let seqs: Vec<u64> = records.iter().map(|r| r.seq).collect();
+    assert_eq!(seqs, vec![1, 2, 3, 4]);
+    assert!(
+        matches!(&records[0].change, CapturedChange::Created(e) if e.id == rust)
+    );
+    assert!(matches!(
+        &records[1].change,
+        CapturedChange::EdgeAdded(e)
+            if (e.source, e.name.as_slice(), e.dest) == (ada, b"likes", rust)
+    ));
+    assert!(matches!(
+        &records[2].change,
+        CapturedChange::EdgeRemoved(e) if e.dest == rust
+    ));
+    assert!(
+        matches!(&records[3].change, CapturedChange::Deleted(e) if e.id == rust)
+    );
+
+    // Reading resumes after a sequence number
+    let rest = env.changes_since(2, 10).unwrap();
+    assert_eq!(rest, records[2..]);
+    assert_eq!(env.changes_since(0, 1).unwrap(), records[..1]);
+
+    // Trimmed sequence numbers are not reused, even across reopening
+    assert_eq!(env.trim_changes(4).unwrap(), 4);
+    assert!(env.changes_since(0, 10).unwrap().is_empty());
+    drop(env);
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    txn.delete::<User>(ada).unwrap();
+    txn.commit().unwrap();
+    let records = env.changes_since(0, 10).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].seq, 5);
+}
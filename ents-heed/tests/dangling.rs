@@ -0,0 +1,127 @@
+use ents::dangling::IncomingEdgePolicy;
+use ents::{
+    DatabaseError, DraftError, EdgeQuery, EdgeValue, QueryEdge,
+    ReadTransactional, Transactional,
+};
+use ents_heed::HeedEnv;
+use ents_test_suite::{Folder, Tag, User};
+use tempfile::tempdir;
+
+#[test]
+fn test_incoming_edge_policies() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let ada = txn
+        .create(User::new("ada".into(), "ada@example.com".into()))
+        .unwrap();
+    let rust = txn
+        .create(Tag::new("rust".into(), "orange".into()))
+        .unwrap();
+    let follows = EdgeValue::new(ada, b"follows".to_vec(), rust);
+    txn.create_edge(follows.clone()).unwrap();
+    txn.commit().unwrap();
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_incoming_edges(IncomingEdgePolicy::Restrict);
+    let txn = env.write_txn().unwrap();
+    assert!(txn.delete::<Tag>(rust).is_err());
+    drop(txn);
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_incoming_edges(IncomingEdgePolicy::Orphan);
+    let txn = env.write_txn().unwrap();
+    assert!(txn.find_dangling_edges(10).unwrap().is_empty());
+    txn.delete::<Tag>(rust).unwrap();
+    assert!(txn.get(rust).unwrap().is_none());
+    let edges = txn.find_edges(ada, EdgeQuery::asc(&[b"follows"])).unwrap();
+    assert_eq!(edges.len(), 1);
+
+    let dangling = txn.find_dangling_edges(10).unwrap();
+    assert_eq!(dangling.len(), 1);
+    assert_eq!((dangling[0].source, dangling[0].dest), (ada, rust));
+    assert!(txn.find_dangling_edges(0).unwrap().is_empty());
+
+    assert_eq!(txn.remove_dangling_edges().unwrap(), 1);
+    assert!(txn.find_dangling_edges(10).unwrap().is_empty());
+    assert!(txn
+        .find_edges(ada, EdgeQuery::asc(&[b"follows"]))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_restricted_delete_writes_nothing() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_incoming_edges(IncomingEdgePolicy::Restrict);
+    let txn = env.write_txn().unwrap();
+    let root = txn.create(Folder::new("root".into())).unwrap();
+    let child = txn.create(Folder::new("child".into())).unwrap();
+    let ada = txn
+        .create(User::new("ada".into(), "ada@example.com".into()))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(root, b"subfolder".to_vec(), child))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(root, b"shared_with".to_vec(), ada))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(ada, b"owns".to_vec(), root))
+        .unwrap();
+    txn.commit().unwrap();
+
+    // The incoming edge refuses the delete before the cascade and the
+    // tombstone run, so committing afterwards keeps everything
+    let txn = env.write_txn().unwrap();
+    assert!(txn.delete::<Folder>(root).is_err());
+    txn.commit().unwrap();
+
+    let txn = env.write_txn().unwrap();
+    assert!(txn.get(root).unwrap().is_some());
+    assert!(txn.get(child).unwrap().is_some());
+    let edges = txn.find_edges(root, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(edges.len(), 2);
+}
+
+#[test]
+fn test_edge_integrity() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_edge_integrity(true);
+    let txn = env.write_txn().unwrap();
+    let ada = txn
+        .create(User::new("ada".into(), "ada@example.com".into()))
+        .unwrap();
+    let rust = txn
+        .create(Tag::new("rust".into(), "orange".into()))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(ada, b"follows".to_vec(), rust))
+        .unwrap();
+
+    let missing = |edge: EdgeValue| match txn.create_edge(edge) {
+        Err(DatabaseError::Other { source }) => {
+            source.downcast::<DraftError>().ok().map(|e| *e)
+        }
+        _ => None,
+    };
+    let gone = rust + 1000;
+    assert!(matches!(
+        missing(EdgeValue::new(ada, b"follows".to_vec(), gone)),
+        Some(DraftError::DestNotFound(id)) if id == gone
+    ));
+    assert!(matches!(
+        missing(EdgeValue::new(gone, b"follows".to_vec(), ada)),
+        Some(DraftError::SourceNotFound(id)) if id == gone
+    ));
+    assert_eq!(
+        txn.find_edges(ada, EdgeQuery::asc(&[b"follows"]))
+            .unwrap()
+            .len(),
+        1
+    );
+}
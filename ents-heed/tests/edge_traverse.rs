@@ -22,6 +22,8 @@ fn insert_edges(
             source: *source,
             sort_key: sort_key.to_vec(),
             dest: *dest,
+            discriminator: 0,
+            payload: Vec::new(),
         })?;
     }
     Ok(())
@@ -410,3 +412,78 @@ fn test_find_edges_binary_edge_types() {
     assert_eq!(result[0].sort_key, vec![0x00, 0x01, 0x02]);
     assert_eq!(result[0].dest, 10);
 }
+
+#[test]
+fn test_find_edges_parallel_edges() {
+    let (_dir, env) = setup_env();
+    let txn = env.write_txn().unwrap();
+
+    // Three payments between the same users, told apart by discriminator
+    for discriminator in [3, 1, 2] {
+        txn.create_edge(
+            EdgeValue::new(1, b"payment".to_vec(), 2)
+                .with_discriminator(discriminator),
+        )
+        .unwrap();
+    }
+    insert_edges(&txn, &[(1, b"payment", 3)]).unwrap();
+
+    let query = EdgeQuery::asc(&[b"payment"]);
+    let result = txn.find_edges(1, query).unwrap();
+
+    assert_eq!(result.len(), 4);
+    assert_eq!(
+        result[0],
+        Edge::new(1, b"payment".to_vec(), 2).with_discriminator(1)
+    );
+    assert_eq!(result[1].discriminator, 2);
+    assert_eq!(result[2].discriminator, 3);
+    assert_eq!(result[3], Edge::new(1, b"payment".to_vec(), 3));
+
+    // Paging resumes between parallel edges
+    let cursor = EdgeCursor::from_edge(&result[0]);
+    let query = EdgeQuery::asc(&[b"payment"]).with_cursor(cursor);
+    let page = txn.find_edges(1, query).unwrap();
+    assert_eq!(page, result[1..].to_vec());
+
+    let cursor = EdgeCursor::from_edge(&result[2]);
+    let query = EdgeQuery::desc(&[b"payment"]).with_cursor(cursor);
+    let page = txn.find_edges(1, query).unwrap();
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].discriminator, 2);
+    assert_eq!(page[1].discriminator, 1);
+}
+
+#[test]
+fn test_hide_and_restore_edge() {
+    let (_dir, env) = setup_env();
+    let txn = env.write_txn().unwrap();
+
+    insert_edges(&txn, &[(1, b"follows", 10), (1, b"follows", 20)]).unwrap();
+
+    let muted = EdgeValue::new(1, b"follows".to_vec(), 10);
+    assert!(txn.hide_edge(&muted).unwrap());
+
+    // Hidden edges are filtered by default
+    let result = txn.find_edges(1, EdgeQuery::asc(&[b"follows"])).unwrap();
+    assert_eq!(result, vec![Edge::new(1, b"follows".to_vec(), 20)]);
+
+    let query = EdgeQuery::asc(&[b"follows"]).include_hidden();
+    let result = txn.find_edges(1, query).unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(
+        result[0],
+        Edge::new(1, b"follows".to_vec(), 10).with_hidden(true)
+    );
+    assert!(!result[1].hidden);
+
+    assert!(txn.restore_edge(&muted).unwrap());
+    let result = txn.find_edges(1, EdgeQuery::asc(&[b"follows"])).unwrap();
+    assert_eq!(result.len(), 2);
+    assert!(!result[0].hidden);
+
+    // Missing edges are reported
+    let missing = EdgeValue::new(1, b"follows".to_vec(), 30);
+    assert!(!txn.hide_edge(&missing).unwrap());
+    assert!(!txn.restore_edge(&missing).unwrap());
+}
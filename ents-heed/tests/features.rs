@@ -0,0 +1,253 @@
+use ents::{EdgeQuery, EdgeValue, QueryEdge, ReadTransactional, Transactional};
+use ents_heed::{FeatureState, HeedEnv, HeedEnvOptions, StoreFeature};
+use ents_test_suite::{Tag, User};
+use tempfile::tempdir;
+
+fn user(name: &str) -> User {
+    User::new(name.to_string(), format!("{}@example.com", name))
+}
+
+/// A store created without the reverse index, like those from before it
+/// was on by default
+fn open_without_reverse_index(path: &std::path::Path) -> HeedEnv {
+    HeedEnvOptions::new()
+        .reverse_index(false)
+        .open(path)
+        .unwrap()
+}
+
+#[test]
+fn test_new_store_has_reverse_index() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    assert_eq!(
+        env.enabled_features().unwrap(),
+        vec![StoreFeature::ReverseIndex]
+    );
+
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(user("alice")).unwrap();
+    let bob = txn.create(user("bob")).unwrap();
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = env.read_txn().unwrap();
+    let incoming = txn.find_edges_to(bob, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(incoming.len(), 1);
+    drop(txn);
+    // The index holds the edge, which the check only compares while the
+    // index is active
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.reverse_index_mismatches, 0);
+
+    // Existing stores keep what they were created with
+    drop(env);
+    let other = tempdir().unwrap();
+    let env = open_without_reverse_index(other.path());
+    assert!(env.enabled_features().unwrap().is_empty());
+    drop(env);
+    let env = HeedEnv::open(other.path(), None).unwrap();
+    assert!(env.enabled_features().unwrap().is_empty());
+}
+
+#[test]
+fn test_enable_reverse_index() {
+    let dir = tempdir().unwrap();
+    let env = open_without_reverse_index(dir.path());
+    assert!(env.enabled_features().unwrap().is_empty());
+
+    // Edges written before the feature is enabled get backfilled
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(user("alice")).unwrap();
+    let bob = txn.create(user("bob")).unwrap();
+    let carol = txn.create(user("carol")).unwrap();
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(carol, b"follows".to_vec(), bob))
+        .unwrap();
+    txn.commit().unwrap();
+
+    env.enable_feature(StoreFeature::ReverseIndex).unwrap();
+    assert!(env.is_feature_enabled(StoreFeature::ReverseIndex).unwrap());
+    // Enabling twice is a no-op
+    env.enable_feature(StoreFeature::ReverseIndex).unwrap();
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.edges, 2);
+
+    // Later writes keep the index in sync
+    let txn = env.write_txn().unwrap();
+    txn.create_edge(EdgeValue::new(bob, b"follows".to_vec(), alice))
+        .unwrap();
+    txn.delete::<User>(bob).unwrap();
+    txn.commit().unwrap();
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.edges, 1);
+
+    // The feature survives reopening the store
+    drop(env);
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    assert_eq!(
+        env.enabled_features().unwrap(),
+        vec![StoreFeature::ReverseIndex]
+    );
+}
+
+#[test]
+fn test_delete_with_reverse_index() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    env.enable_feature(StoreFeature::ReverseIndex).unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let ids: Vec<_> = (0..20)
+        .map(|i| txn.create(user(&format!("u{}", i))).unwrap())
+        .collect();
+    // Everyone follows the first two users
+    for &source in &ids[2..] {
+        for &dest in &ids[..2] {
+            txn.create_edge(EdgeValue::new(source, b"follows".to_vec(), dest))
+                .unwrap();
+        }
+    }
+    txn.delete::<User>(ids[0]).unwrap();
+    txn.commit().unwrap();
+
+    let txn = env.read_txn().unwrap();
+    let query = EdgeQuery::asc(&[]);
+    assert!(txn.find_edges_to(ids[0], query.clone()).unwrap().is_empty());
+    assert_eq!(txn.find_edges_to(ids[1], query.clone()).unwrap().len(), 18);
+    assert_eq!(txn.find_edges(ids[5], query).unwrap().len(), 1);
+    drop(txn);
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.edges, 18);
+}
+
+#[test]
+fn test_online_backfill() {
+    let dir = tempdir().unwrap();
+    let env = open_without_reverse_index(dir.path());
+
+    let txn = env.write_txn().unwrap();
+    let mut users = Vec::new();
+    for i in 0..10 {
+        users.push(txn.create(user(&format!("user{}", i))).unwrap());
+    }
+    for pair in users.windows(2) {
+        txn.create_edge(EdgeValue::new(pair[0], b"follows".to_vec(), pair[1]))
+            .unwrap();
+    }
+    txn.commit().unwrap();
+
+    assert!(env.backfill_step(StoreFeature::ReverseIndex, 3).is_err());
+    env.start_backfill(StoreFeature::ReverseIndex).unwrap();
+    assert_eq!(
+        env.feature_state(StoreFeature::ReverseIndex).unwrap(),
+        FeatureState::Backfilling
+    );
+    assert!(env.enabled_features().unwrap().is_empty());
+
+    let progress = env.backfill_step(StoreFeature::ReverseIndex, 3).unwrap();
+    assert_eq!(progress.processed, 3);
+    assert!(!progress.done);
+
+    // Writes during the backfill are dual-written, on both sides of the
+    // cursor
+    let txn = env.write_txn().unwrap();
+    txn.create_edge(EdgeValue::new(users[9], b"follows".to_vec(), users[0]))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(users[0], b"likes".to_vec(), users[5]))
+        .unwrap();
+    txn.delete::<User>(users[1]).unwrap();
+    txn.commit().unwrap();
+
+    // The backfill resumes from the persisted cursor after reopening
+    drop(env);
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let mut steps = 0;
+    while !env
+        .backfill_step(StoreFeature::ReverseIndex, 3)
+        .unwrap()
+        .done
+    {
+        steps += 1;
+    }
+    assert!(steps > 0);
+    assert!(env.is_feature_enabled(StoreFeature::ReverseIndex).unwrap());
+
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.edges, 10);
+}
+
+#[test]
+fn test_enable_feature_online() {
+    let dir = tempdir().unwrap();
+    let env = open_without_reverse_index(dir.path());
+
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(user("alice")).unwrap();
+    let bob = txn.create(user("bob")).unwrap();
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+        .unwrap();
+    txn.commit().unwrap();
+
+    env.enable_feature_online(StoreFeature::ReverseIndex)
+        .unwrap();
+    assert_eq!(
+        env.feature_state(StoreFeature::ReverseIndex).unwrap(),
+        FeatureState::Active
+    );
+    let report = env.check_consistency().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+}
+
+#[test]
+fn test_type_index() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let mut users = Vec::new();
+    for i in 0..6 {
+        users.push(txn.create(user(&format!("user{}", i))).unwrap());
+        txn.create(Tag::new(format!("tag{}", i), "red".to_string()))
+            .unwrap();
+    }
+    users.sort();
+    // Without the index, listing scans the entities
+    assert_eq!(txn.list_ids_by_type("User", None, 100).unwrap(), users);
+    txn.commit().unwrap();
+
+    env.start_backfill(StoreFeature::TypeIndex).unwrap();
+    let progress = env.backfill_step(StoreFeature::TypeIndex, 5).unwrap();
+    assert_eq!(progress.processed, 5);
+
+    // Writes during the backfill are indexed on both sides of the cursor
+    let (first, last) = (users[0], users[5]);
+    let txn = env.write_txn().unwrap();
+    users.push(txn.create(user("late")).unwrap());
+    txn.delete::<User>(first).unwrap();
+    txn.delete::<User>(last).unwrap();
+    txn.commit().unwrap();
+    users.retain(|&id| id != first && id != last);
+
+    while !env.backfill_step(StoreFeature::TypeIndex, 5).unwrap().done {}
+    assert!(env.is_feature_enabled(StoreFeature::TypeIndex).unwrap());
+
+    let txn = env.write_txn().unwrap();
+    assert_eq!(txn.list_ids_by_type("User", None, 100).unwrap(), users);
+    assert_eq!(
+        txn.list_ids_by_type("User", Some(users[0]), 2).unwrap(),
+        users[1..3]
+    );
+    assert_eq!(txn.list_ids_by_type("Tag", None, 100).unwrap().len(), 6);
+    assert!(txn.list_ids_by_type("Use", None, 100).unwrap().is_empty());
+}
@@ -0,0 +1,80 @@
+use ents::feed::{self, FanoutProgress, FeedEntry};
+use ents::Transactional;
+use ents_heed::HeedEnv;
+use tempfile::tempdir;
+
+#[test]
+fn test_fanout_and_read_feed() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+
+    let followers: Vec<u64> = (100..110).collect();
+    let mut checkpoints = Vec::new();
+
+    let progress = feed::fanout_write(
+        || env.write_txn(),
+        1,
+        followers.iter().copied(),
+        500,
+        1000,
+        4,
+        |p| checkpoints.push(*p),
+    )
+    .unwrap();
+
+    assert_eq!(progress.processed, 10);
+    assert_eq!(progress.last_follower, Some(109));
+    assert_eq!(
+        checkpoints.iter().map(|p| p.processed).collect::<Vec<_>>(),
+        vec![4, 8, 10]
+    );
+
+    // A second actor shares another item later
+    feed::fanout_write(|| env.write_txn(), 2, [100], 501, 2000, 4, |_| {})
+        .unwrap();
+
+    // Resuming from a checkpoint only writes the remaining followers
+    let resumed = feed::fanout_write(
+        || env.write_txn(),
+        3,
+        followers.iter().copied().skip(checkpoints[1].processed),
+        502,
+        3000,
+        4,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(
+        resumed,
+        FanoutProgress {
+            processed: 2,
+            last_follower: Some(109),
+        }
+    );
+
+    let txn = env.write_txn().unwrap();
+    let page = feed::read_feed(&txn, 100, None, 10).unwrap();
+    assert_eq!(
+        page,
+        vec![
+            FeedEntry {
+                item: 501,
+                actor: 2,
+                timestamp: 2000,
+            },
+            FeedEntry {
+                item: 500,
+                actor: 1,
+                timestamp: 1000,
+            },
+        ]
+    );
+
+    let page = feed::read_feed(&txn, 109, None, 1).unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].item, 502);
+    let next = feed::read_feed(&txn, 109, Some(&page[0]), 10).unwrap();
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].item, 500);
+    txn.commit().unwrap();
+}
@@ -0,0 +1,37 @@
+use ents::{DatabaseError, ReadTransactional, Transactional};
+use ents_heed::{HeedEnv, StoreFeature};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+#[test]
+fn test_freeze() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+
+    env.freeze().unwrap();
+    assert!(env.is_frozen().unwrap());
+    assert!(matches!(env.write_txn(), Err(DatabaseError::Frozen)));
+    assert!(matches!(
+        env.enable_feature(StoreFeature::ReverseIndex),
+        Err(DatabaseError::Frozen)
+    ));
+    // Reads keep working
+    let rtxn = env.read_txn().unwrap();
+    assert!(rtxn.get(id).unwrap().is_some());
+    drop(rtxn);
+
+    // The flag is stored with the data
+    drop(env);
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    assert!(env.is_frozen().unwrap());
+
+    env.thaw().unwrap();
+    assert!(!env.is_frozen().unwrap());
+    let txn = env.write_txn().unwrap();
+    txn.delete::<TestEntity>(id).unwrap();
+    txn.commit().unwrap();
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use ents::ids::SequentialIds;
+use ents_heed::{HeedEnv, StoreFeature};
+use ents_test_suite::golden;
+use heed::types::Bytes;
+use heed::{Database, EnvOpenOptions};
+use tempfile::tempdir;
+
+/// Every database of the store, in order
+const DATABASES: [&str; 9] = [
+    "entities",
+    "edges",
+    "edges_by_dest",
+    "entities_by_type",
+    "meta",
+    "uniques",
+    "changes",
+    "deleted",
+    "history",
+];
+
+/// Every key and value of the store at `path`, one line each
+fn dump(path: &Path) -> String {
+    let env = unsafe { EnvOpenOptions::new().max_dbs(9).open(path) }.unwrap();
+    let rtxn = env.read_txn().unwrap();
+    let mut out = String::new();
+    for name in DATABASES {
+        let db: Option<Database<Bytes, Bytes>> =
+            env.open_database(&rtxn, Some(name)).unwrap();
+        let Some(db) = db else {
+            out.push_str(&format!("[{}] missing\n", name));
+            continue;
+        };
+        out.push_str(&format!("[{}]\n", name));
+        for result in db.iter(&rtxn).unwrap() {
+            let (key, value) = result.unwrap();
+            out.push_str(&format!(
+                "{} = {}\n",
+                golden::display_bytes(key),
+                golden::display_bytes(value)
+            ));
+        }
+    }
+    out
+}
+
+#[test]
+fn test_storage_format() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_provider(SequentialIds::new(1));
+    for &feature in StoreFeature::ALL {
+        env.enable_feature(feature).unwrap();
+    }
+    let txn = env.write_txn().unwrap();
+    golden::write_fixture(&txn).unwrap();
+    golden::commit_fixture(txn).unwrap();
+    drop(env);
+
+    let golden_file =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/store.txt");
+    golden::check_golden(golden_file, &dump(dir.path())).unwrap();
+}
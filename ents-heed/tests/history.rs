@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use ents::history::HistoryRetention;
+use ents::{EntExt, ReadTransactional, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+fn values(txn: &ents_heed::Txn<'_>, id: u64) -> Vec<(u64, i32)> {
+    txn.get_history(id)
+        .unwrap()
+        .into_iter()
+        .map(|version| {
+            let ent = version.ent.into_ent::<TestEntity>().unwrap();
+            (version.version, ent.value)
+        })
+        .collect()
+}
+
+#[test]
+fn test_updates_keep_prior_versions() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_history(HistoryRetention::unbounded());
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    assert!(txn.get_history(id).unwrap().is_empty());
+
+    let mut ent = txn.get_required_as::<TestEntity>(id).unwrap();
+    let stale = ent.clone();
+    txn.try_update(&mut ent, |e: &mut TestEntity| e.value = 2)
+        .unwrap();
+    txn.try_update(&mut ent, |e: &mut TestEntity| e.value = 3)
+        .unwrap();
+    // A lost race archives nothing
+    assert!(!txn
+        .update(stale.clone(), |e: &mut TestEntity| e.value = 4)
+        .unwrap());
+    txn.commit().unwrap();
+
+    let txn = env.write_txn().unwrap();
+    assert_eq!(values(&txn, id), vec![(2, 2), (1, 1)]);
+    let first = txn.get_version(id, 1).unwrap().unwrap();
+    assert_eq!(first.ent.last_updated(), stale.last_updated);
+    assert!(txn.get_version(id, 3).unwrap().is_none());
+
+    let read = env.read_txn().unwrap();
+    assert_eq!(read.get_history(id).unwrap().len(), 2);
+    drop(read);
+
+    txn.delete::<TestEntity>(id).unwrap();
+    assert!(txn.get_history(id).unwrap().is_empty());
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_retention_prunes_versions() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_history(HistoryRetention::versions(2));
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 0)).unwrap();
+    let other = txn.create(TestEntity::new("b".to_string(), 0)).unwrap();
+    for value in 1..=4 {
+        txn.retry_update(id, 1, |e: &mut TestEntity| e.value = value)
+            .unwrap();
+    }
+    assert!(txn.touch::<TestEntity>(other).unwrap());
+
+    assert_eq!(values(&txn, id), vec![(4, 3), (3, 2)]);
+    assert_eq!(values(&txn, other), vec![(1, 0)]);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_retention_prunes_old_versions() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap().with_history(
+        HistoryRetention::unbounded().with_max_age(Duration::from_millis(20)),
+    );
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 0)).unwrap();
+    txn.retry_update(id, 1, |e: &mut TestEntity| e.value = 1)
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(40));
+    txn.retry_update(id, 1, |e: &mut TestEntity| e.value = 2)
+        .unwrap();
+
+    assert_eq!(values(&txn, id), vec![(2, 1)]);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_history_is_off_by_default() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 0)).unwrap();
+    txn.retry_update(id, 1, |e: &mut TestEntity| e.value = 1)
+        .unwrap();
+    assert!(txn.get_history(id).unwrap().is_empty());
+    txn.commit().unwrap();
+}
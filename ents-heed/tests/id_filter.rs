@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use ents::bloom::IdFilter;
+use ents::{Ent, Id, ReadTransactional, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+fn entity(i: Id) -> Box<dyn Ent> {
+    let mut ent = TestEntity::new(format!("e{}", i), i as i32);
+    ent.set_id(i);
+    Box::new(ent)
+}
+
+#[test]
+fn test_filter_tracks_stored_ids() {
+    let dir = tempdir().unwrap();
+    let filter = Arc::new(IdFilter::new(0.01));
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_filter(filter.clone());
+
+    let txn = env.write_txn().unwrap();
+    let first = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    // Unbuilt, the filter passes everything
+    assert!(!filter.is_built());
+    let read = env.read_txn().unwrap();
+    assert!(read.get(first).unwrap().is_some());
+    drop(read);
+
+    assert_eq!(env.rebuild_id_filter().unwrap(), 1);
+    assert!(filter.is_built());
+    assert!(filter.may_contain(first));
+
+    let txn = env.write_txn().unwrap();
+    let second = txn.create(TestEntity::new("b".to_string(), 2)).unwrap();
+    txn.bulk_load((100..110).map(entity), []).unwrap();
+    txn.commit().unwrap();
+
+    let read = env.read_txn().unwrap();
+    for id in [first, second].into_iter().chain(100..110) {
+        assert!(filter.may_contain(id));
+        assert!(read.get(id).unwrap().is_some());
+    }
+    let missing = read.get(second + 1).unwrap();
+    assert!(missing.is_none());
+}
+
+#[test]
+fn test_restore_after_rebuild() {
+    let dir = tempdir().unwrap();
+    let filter = Arc::new(IdFilter::new(0.01));
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_filter(filter.clone());
+    env.rebuild_id_filter().unwrap();
+
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.soft_delete::<TestEntity>(id).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(env.rebuild_id_filter().unwrap(), 0);
+
+    let txn = env.write_txn().unwrap();
+    assert!(txn.get(id).unwrap().is_none());
+    assert!(txn.restore::<TestEntity>(id).unwrap());
+    assert!(txn.get(id).unwrap().is_some());
+    txn.commit().unwrap();
+}
@@ -0,0 +1,31 @@
+use ents::ids::{ScriptedIds, SequentialIds};
+use ents::{DatabaseError, ReadTransactional, Transactional};
+use ents_heed::HeedEnv;
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+#[test]
+fn test_id_providers() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_provider(SequentialIds::new(1));
+    let txn = env.write_txn().unwrap();
+    let a = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    let b = txn.create(TestEntity::new("b".to_string(), 2)).unwrap();
+    assert_eq!((a, b), (1, 2));
+    txn.commit().unwrap();
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_provider(ScriptedIds::new([42]));
+    let txn = env.write_txn().unwrap();
+    let c = txn.create(TestEntity::new("c".to_string(), 3)).unwrap();
+    assert_eq!(c, 42);
+    assert_eq!(txn.get_required_as::<TestEntity>(c).unwrap().id, 42);
+    assert!(matches!(
+        txn.create(TestEntity::new("d".to_string(), 4)),
+        Err(DatabaseError::EntCapacityReached)
+    ));
+}
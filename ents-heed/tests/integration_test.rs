@@ -52,3 +52,16 @@ fn test_all_heed() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_all_heed_append_inserts() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let db_path = temp_dir.path().join("test_db");
+
+    let env = Arc::new(HeedEnv::open(db_path, None)?.with_append_inserts(true));
+    let runner = HeedTestRunner { env };
+
+    run_all_tests(runner)?;
+
+    Ok(())
+}
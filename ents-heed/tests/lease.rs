@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+
+use ents::{DatabaseError, ReadTransactional, Transactional};
+use ents_heed::{HeedEnv, HeedEnvOptions};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+#[test]
+fn test_lease_skips_held_node_ids() {
+    let dir = tempdir().unwrap();
+    // Another process holding node 0
+    let held = File::create(dir.path().join("node-0.lock")).unwrap();
+    held.try_lock().unwrap();
+
+    let env = HeedEnvOptions::new()
+        .node_id(0)
+        .lease_node_id(true)
+        .open(dir.path())
+        .unwrap();
+    assert_eq!(env.leased_node_id(), Some(1));
+    assert!(!env.is_single_writer());
+
+    // Snowflake ids carry the node id in their low bits
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    assert_eq!((id >> 12) & 0x3ff, 1);
+    drop(env);
+
+    // Dropping the environment releases the lease
+    drop(held);
+    let env = HeedEnvOptions::new()
+        .lease_node_id(true)
+        .open(dir.path())
+        .unwrap();
+    assert_eq!(env.leased_node_id(), Some(0));
+}
+
+#[test]
+fn test_single_writer() {
+    let dir = tempdir().unwrap();
+    let options = HeedEnvOptions::new().single_writer(true);
+    let writer = options.open(dir.path()).unwrap();
+    assert!(writer.is_single_writer());
+    assert_eq!(writer.leased_node_id(), None);
+
+    let err = options.open(dir.path()).err().unwrap();
+    assert!(matches!(err, DatabaseError::Overloaded { .. }));
+
+    drop(writer);
+    assert!(options.open(dir.path()).unwrap().is_single_writer());
+}
+
+#[test]
+fn test_plain_open_takes_no_locks() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    assert_eq!(env.leased_node_id(), None);
+    assert!(!env.is_single_writer());
+    assert!(!dir.path().join("writer.lock").exists());
+}
+
+/// Variable pointing `standby_process` at the store it takes over
+const STANDBY_PATH: &str = "ENTS_HEED_STANDBY_PATH";
+
+fn standby_options() -> HeedEnvOptions {
+    HeedEnvOptions::new()
+        .lease_node_id(true)
+        .single_writer(true)
+        .writer_lease_expiry(Duration::from_millis(20))
+}
+
+/// Take the writer role over and write one entity. A no-op unless run by
+/// `run_standby` in a separate process.
+#[test]
+fn standby_process() {
+    let Ok(path) = std::env::var(STANDBY_PATH) else {
+        return;
+    };
+    let env = standby_options().open(path).unwrap();
+    assert_eq!(env.fencing_token(), Some(2));
+    let txn = env.write_txn().unwrap();
+    txn.create(TestEntity::new("standby".to_string(), 2))
+        .unwrap();
+    txn.commit().unwrap();
+}
+
+fn run_standby(path: &Path) {
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["standby_process", "--exact", "--quiet"])
+        .env(STANDBY_PATH, path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_crashed_writer_hands_over_role() {
+    let dir = tempdir().unwrap();
+    let options = HeedEnvOptions::new().single_writer(true);
+    let writer = options.open(dir.path()).unwrap();
+    assert_eq!(writer.fencing_token(), Some(1));
+    // The process exiting releases the lock like dropping the environment
+    drop(writer);
+
+    let standby = options.open(dir.path()).unwrap();
+    assert_eq!(standby.fencing_token(), Some(2));
+    let txn = standby.write_txn().unwrap();
+    txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_writer_lease_expiry() {
+    let dir = tempdir().unwrap();
+    let writer = HeedEnvOptions::new()
+        .single_writer(true)
+        .open(dir.path())
+        .unwrap();
+    drop(writer);
+    // A hung writer keeps holding the lock
+    let hung = File::open(dir.path().join("writer.lock")).unwrap();
+    hung.try_lock().unwrap();
+
+    let err = HeedEnvOptions::new()
+        .single_writer(true)
+        .open(dir.path())
+        .err()
+        .unwrap();
+    assert!(matches!(err, DatabaseError::Overloaded { .. }));
+    let err = HeedEnvOptions::new()
+        .single_writer(true)
+        .writer_lease_expiry(Duration::from_secs(3600))
+        .open(dir.path())
+        .err()
+        .unwrap();
+    assert!(matches!(err, DatabaseError::Overloaded { .. }));
+
+    sleep(Duration::from_millis(40));
+    let standby = standby_options().open(dir.path()).unwrap();
+    assert!(standby.is_single_writer());
+    assert_eq!(standby.fencing_token(), Some(2));
+    standby.renew_writer_lease().unwrap();
+}
+
+#[test]
+fn test_fencing_rejects_superseded_writer() {
+    let dir = tempdir().unwrap();
+    let writer = standby_options().open(dir.path()).unwrap();
+    assert_eq!(writer.fencing_token(), Some(1));
+    let txn = writer.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+
+    // The writer hangs past the expiry while a standby takes over
+    sleep(Duration::from_millis(40));
+    run_standby(dir.path());
+
+    let txn = writer.write_txn().unwrap();
+    let lost = txn.create(TestEntity::new("lost".to_string(), 3)).unwrap();
+    let err = txn.commit().err().unwrap();
+    assert!(matches!(
+        err,
+        DatabaseError::Fenced {
+            token: 1,
+            current: 2
+        }
+    ));
+    assert!(matches!(
+        writer.renew_writer_lease(),
+        Err(DatabaseError::Fenced { .. })
+    ));
+
+    let txn = writer.read_txn().unwrap();
+    assert!(txn.get(id).unwrap().is_some());
+    assert!(txn.get(lost).unwrap().is_none());
+}
+
+#[test]
+fn test_fencing_rejects_maintenance_writes() {
+    let dir = tempdir().unwrap();
+    let writer = standby_options().open(dir.path()).unwrap();
+    writer.freeze().unwrap();
+    writer.thaw().unwrap();
+
+    sleep(Duration::from_millis(40));
+    run_standby(dir.path());
+
+    let fenced = |result: Result<(), DatabaseError>| {
+        matches!(result, Err(DatabaseError::Fenced { token: 1, .. }))
+    };
+    assert!(fenced(writer.freeze()));
+    assert!(fenced(writer.trim_changes(u64::MAX).map(|_| ())));
+    assert!(fenced(writer.repair_id_watermark().map(|_| ())));
+    assert!(!writer.is_frozen().unwrap());
+}
+
+#[test]
+fn test_upgrade_refused_while_writer_holds_role() {
+    let dir = tempdir().unwrap();
+    let writer = HeedEnvOptions::new()
+        .single_writer(true)
+        .open(dir.path())
+        .unwrap();
+    let err = HeedEnv::upgrade_store(dir.path(), None).err().unwrap();
+    assert!(matches!(err, DatabaseError::Overloaded { .. }));
+    drop(writer);
+}
@@ -0,0 +1,67 @@
+use ents::ent_types::EntTypes;
+use ents::migrate::{migrate_batch, MigrationCheckpoint};
+use ents::{EdgeQuery, EdgeValue, QueryEdge, ReadTransactional, Transactional};
+use ents_heed::{HeedEnv, Txn};
+use ents_test_suite::{Tag, User};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use tempfile::tempdir;
+
+#[test]
+fn test_migrate_sqlite_to_heed() {
+    let pool = Pool::new(SqliteConnectionManager::memory()).unwrap();
+    let mut conn = pool.get().unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
+    let src = ents_sqlite::Txn::new(conn.transaction().unwrap());
+    let mut users = Vec::new();
+    for name in ["ada", "bob", "cy"] {
+        let email = format!("{}@example.com", name);
+        users.push(src.create(User::new(name.into(), email)).unwrap());
+    }
+    let rust = src
+        .create(Tag::new("rust".into(), "orange".into()))
+        .unwrap();
+    let follows = EdgeValue::new(users[0], b"follows".to_vec(), users[1]);
+    src.create_edge(follows).unwrap();
+    let hidden = EdgeValue::new(users[2], b"likes".to_vec(), rust)
+        .with_payload(b"2024".as_slice());
+    src.create_edge(hidden.clone()).unwrap();
+    src.hide_edge(&hidden).unwrap();
+    src.create_edge(EdgeValue::new(0, b"featured".to_vec(), rust))
+        .unwrap();
+
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let types = EntTypes::<Txn>::new()
+        .with_type::<User>()
+        .with_type::<Tag>();
+    let mut checkpoint = MigrationCheckpoint::default();
+    let mut batches = 0;
+    while !checkpoint.done {
+        let dst = env.write_txn().unwrap();
+        let next = migrate_batch(&src, &dst, &types, &checkpoint, 2).unwrap();
+        // Repeating a batch skips the entities it copied
+        let again = migrate_batch(&src, &dst, &types, &checkpoint, 2).unwrap();
+        assert_eq!(again.entities, checkpoint.entities);
+        assert_eq!(again.after, next.after);
+        dst.commit().unwrap();
+        checkpoint = next;
+        batches += 1;
+    }
+    // The last batch finds no more entities and copies the system edges
+    assert_eq!(batches, 3);
+    assert_eq!(checkpoint.entities, 4);
+
+    let dst = env.write_txn().unwrap();
+    for &id in users.iter().chain([&rust]) {
+        assert!(dst.get(id).unwrap().is_some());
+    }
+    let query = || EdgeQuery::asc(&[]).include_hidden();
+    let follows = dst.find_edges(users[0], query()).unwrap();
+    assert_eq!(follows.len(), 1);
+    assert_eq!(follows[0].dest, users[1]);
+    let likes = dst.find_edges(users[2], query()).unwrap();
+    assert!(likes[0].hidden);
+    assert_eq!(likes[0].payload, b"2024");
+    assert_eq!(dst.find_edges(0, query()).unwrap().len(), 1);
+}
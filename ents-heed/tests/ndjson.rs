@@ -0,0 +1,67 @@
+use ents::ent_types::EntTypes;
+use ents::{EdgeQuery, EdgeValue, QueryEdge, ReadTransactional, Transactional};
+use ents_heed::{HeedEnv, Txn};
+use ents_test_suite::{Post, Tag, User};
+use tempfile::tempdir;
+
+#[test]
+fn test_dump_and_restore() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let ada = txn
+        .create(User::new("ada".into(), "ada@example.com".into()))
+        .unwrap();
+    let rust = txn
+        .create(Tag::new("rust".into(), "orange".into()))
+        .unwrap();
+    let post = txn
+        .create(Post::new("Intro".into(), "hi".into(), ada, vec![rust]))
+        .unwrap();
+    let liked = EdgeValue::new(ada, b"liked".to_vec(), post)
+        .with_discriminator(3)
+        .with_payload(b"2024".as_slice());
+    txn.create_edge(liked.clone()).unwrap();
+    let hidden = EdgeValue::new(ada, vec![0, 255], rust);
+    txn.create_edge(hidden.clone()).unwrap();
+    txn.hide_edge(&hidden).unwrap();
+    txn.create_edge(EdgeValue::new(0, b"featured".to_vec(), post))
+        .unwrap();
+
+    let mut out = Vec::new();
+    let mut reports = 0;
+    let dumped = ents::dump(&txn, &mut out, |_| reports += 1).unwrap();
+    assert_eq!((dumped.entities, dumped.edges), (3, 5));
+    assert_eq!(reports, 8);
+    let text = String::from_utf8(out.clone()).unwrap();
+    assert_eq!(text.lines().count(), 8);
+    assert!(text.contains(r#""name":"liked""#));
+    drop(txn);
+
+    let dir2 = tempdir().unwrap();
+    let env2 = HeedEnv::open(dir2.path(), None).unwrap();
+    let txn = env2.write_txn().unwrap();
+    let types = EntTypes::<Txn>::new()
+        .with_type::<User>()
+        .with_type::<Tag>()
+        .with_type::<Post>();
+    let restored = ents::restore(&txn, out.as_slice(), &types, |_| {}).unwrap();
+    assert_eq!(restored, dumped);
+
+    assert_eq!(txn.get_required_as::<Post>(post).unwrap().author_id, ada);
+    let edges = txn
+        .find_edges(ada, EdgeQuery::asc(&[]).include_hidden())
+        .unwrap();
+    assert_eq!(edges.len(), 2);
+    assert!(edges.iter().any(|e| e.sort_key == b"liked"
+        && e.discriminator == 3
+        && e.payload == b"2024"));
+    assert!(edges.iter().any(|e| e.sort_key == [0, 255] && e.hidden));
+    let featured = txn.find_edges(0, EdgeQuery::asc(&[b"featured"])).unwrap();
+    assert_eq!(featured.len(), 1);
+
+    // The restored store dumps to the same stream
+    let mut again = Vec::new();
+    ents::dump(&txn, &mut again, |_| {}).unwrap();
+    assert_eq!(again, out);
+}
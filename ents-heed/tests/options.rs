@@ -0,0 +1,54 @@
+use ents::{ReadTransactional, Transactional};
+use ents_heed::{Durability, HeedEnvOptions};
+use ents_test_suite::{Tag, TestEntity};
+use tempfile::tempdir;
+
+#[test]
+fn test_options_open() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnvOptions::new()
+        .node_id(7)
+        .map_size(64 << 20)
+        .max_readers(16)
+        .durability(Durability::NoSync)
+        .open(dir.path())
+        .unwrap();
+    assert_eq!(env.map_size(), 64 << 20);
+
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    // Snowflake ids keep the instance in bits 12..22
+    assert_eq!((id >> 12) & 0x3ff, 7);
+}
+
+#[test]
+fn test_options_reject_node_id() {
+    let dir = tempdir().unwrap();
+    assert!(HeedEnvOptions::new()
+        .node_id(1024)
+        .open(dir.path())
+        .is_err());
+    assert!(HeedEnvOptions::new().node_id(1023).open(dir.path()).is_ok());
+}
+
+#[test]
+fn test_options_allow_types() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnvOptions::new().open(dir.path()).unwrap();
+    let txn = env.write_txn().unwrap();
+    let entity = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    let tag = txn
+        .create(Tag::new("b".to_string(), "red".to_string()))
+        .unwrap();
+    txn.commit().unwrap();
+    drop(env);
+
+    let env = HeedEnvOptions::new()
+        .allow_types(["TestEntity"])
+        .open(dir.path())
+        .unwrap();
+    let txn = env.read_txn().unwrap();
+    assert!(txn.get(entity).unwrap().is_some());
+    assert!(txn.get(tag).unwrap().is_none());
+}
@@ -0,0 +1,60 @@
+use ents::{DatabaseError, Id, ReadTransactional, Transactional};
+use ents_heed::{AutoResize, HeedEnv};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+const SMALL_MAP: usize = 1 << 20;
+
+/// Writes enough entities to overflow `SMALL_MAP`
+fn fill(txn: &ents_heed::Txn<'_>) -> Result<Vec<Id>, DatabaseError> {
+    (0..2000)
+        .map(|i| txn.create(TestEntity::new("x".repeat(1000), i)))
+        .collect()
+}
+
+#[test]
+fn test_map_full() {
+    let dir = tempdir().unwrap();
+    let mut env = HeedEnv::open(dir.path(), Some(SMALL_MAP)).unwrap();
+    assert_eq!(env.map_size(), SMALL_MAP);
+
+    let txn = env.write_txn().unwrap();
+    assert!(matches!(fill(&txn), Err(DatabaseError::EntCapacityReached)));
+    drop(txn);
+
+    // Without auto resize, write reports the full map too
+    assert!(matches!(
+        env.write(fill),
+        Err(DatabaseError::EntCapacityReached)
+    ));
+
+    // Growing the map by hand makes room
+    env.set_map_size(SMALL_MAP * 8).unwrap();
+    assert_eq!(env.write(fill).unwrap().len(), 2000);
+}
+
+#[test]
+fn test_auto_resize() {
+    let dir = tempdir().unwrap();
+    let mut env = HeedEnv::open(dir.path(), Some(SMALL_MAP))
+        .unwrap()
+        .with_auto_resize(AutoResize::new());
+
+    let ids = env.write(fill).unwrap();
+    assert!(env.map_size() > SMALL_MAP);
+    assert_eq!(env.map_size() % SMALL_MAP, 0);
+
+    // Only the successful attempt was committed
+    let txn = env.write_txn().unwrap();
+    assert_eq!(txn.list_ids_by_type("TestEntity", None, 5000).unwrap(), ids);
+    drop(txn);
+
+    // Growth stops at the configured maximum
+    let max = env.map_size();
+    let mut env =
+        env.with_auto_resize(AutoResize::new().with_max_map_size(max));
+    assert!(matches!(
+        env.write(fill),
+        Err(DatabaseError::EntCapacityReached)
+    ));
+}
@@ -0,0 +1,161 @@
+use std::cell::Cell;
+
+use ents::saga::{Saga, SagaOutcome};
+use ents::{DatabaseError, Id, ReadTransactional, Transactional};
+use ents_heed::{HeedEnv, Txn};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+fn fail(msg: &str) -> DatabaseError {
+    DatabaseError::Other {
+        source: msg.to_string().into(),
+    }
+}
+
+#[test]
+fn test_saga_completes() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let created = Cell::new(0);
+
+    let outcome = Saga::<Txn>::new("ok")
+        .step(
+            "create",
+            |txn| {
+                created.set(txn.create(TestEntity::new("a".to_string(), 1))?);
+                Ok(())
+            },
+            |txn| txn.delete::<TestEntity>(created.get()),
+        )
+        .run(|| env.write_txn())
+        .unwrap();
+    assert!(matches!(outcome, SagaOutcome::Completed));
+
+    let txn = env.write_txn().unwrap();
+    assert!(txn.get(created.get()).unwrap().is_some());
+}
+
+#[test]
+fn test_saga_compensates_in_reverse() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let first: Cell<Id> = Cell::new(0);
+    let second: Cell<Id> = Cell::new(0);
+    let undo_order = std::cell::RefCell::new(Vec::new());
+
+    let outcome = Saga::<Txn>::new("transfer")
+        .step(
+            "first",
+            |txn| {
+                first.set(txn.create(TestEntity::new("a".to_string(), 1))?);
+                Ok(())
+            },
+            |txn| {
+                undo_order.borrow_mut().push("first");
+                txn.delete::<TestEntity>(first.get())
+            },
+        )
+        .step(
+            "second",
+            |txn| {
+                second.set(txn.create(TestEntity::new("b".to_string(), 2))?);
+                Ok(())
+            },
+            |txn| {
+                undo_order.borrow_mut().push("second");
+                txn.delete::<TestEntity>(second.get())
+            },
+        )
+        .step(
+            "third",
+            |txn| {
+                // Writes of the failing step are rolled back
+                txn.create(TestEntity::new("c".to_string(), 3))?;
+                Err(fail("out of stock"))
+            },
+            |_| panic!("a failed step is never compensated"),
+        )
+        .run(|| env.write_txn())
+        .unwrap();
+
+    match outcome {
+        SagaOutcome::Compensated { failed_step, error } => {
+            assert_eq!(failed_step, "third");
+            assert!(error.unwrap().to_string().contains("out of stock"));
+        }
+        SagaOutcome::Completed => panic!("saga should have failed"),
+    }
+    assert_eq!(*undo_order.borrow(), vec!["second", "first"]);
+
+    let txn = env.write_txn().unwrap();
+    assert!(txn.get(first.get()).unwrap().is_none());
+    assert!(txn.get(second.get()).unwrap().is_none());
+    drop(txn);
+
+    // Re-running a finished saga reports the recorded failure and does
+    // nothing else
+    let outcome = Saga::<Txn>::new("transfer")
+        .step(
+            "first",
+            |_| panic!("already ran"),
+            |_| panic!("already undone"),
+        )
+        .run(|| env.write_txn())
+        .unwrap();
+    assert!(matches!(
+        outcome,
+        SagaOutcome::Compensated { ref failed_step, error: None }
+            if failed_step == "third"
+    ));
+}
+
+#[test]
+fn test_saga_resumes_after_interruption() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let runs = Cell::new(0);
+
+    // The second step's compensation fails, leaving the saga half undone
+    let result = Saga::<Txn>::new("resume")
+        .step(
+            "first",
+            |_| {
+                runs.set(runs.get() + 1);
+                Ok(())
+            },
+            |_| Ok(()),
+        )
+        .step("second", |_| Ok(()), |_| Err(fail("undo crashed")))
+        .step("third", |_| Err(fail("boom")), |_| Ok(()))
+        .run(|| env.write_txn());
+    assert!(result.is_err());
+
+    let undone = Cell::new(0);
+    let outcome = Saga::<Txn>::new("resume")
+        .step(
+            "first",
+            |_| panic!("must not re-run"),
+            |_| {
+                undone.set(undone.get() + 1);
+                Ok(())
+            },
+        )
+        .step(
+            "second",
+            |_| panic!("must not re-run"),
+            |_| {
+                undone.set(undone.get() + 1);
+                Ok(())
+            },
+        )
+        .step("third", |_| panic!("must not re-run"), |_| Ok(()))
+        .run(|| env.write_txn())
+        .unwrap();
+
+    assert!(matches!(
+        outcome,
+        SagaOutcome::Compensated { error: None, .. }
+    ));
+    assert_eq!(runs.get(), 1);
+    assert_eq!(undone.get(), 2);
+}
@@ -0,0 +1,80 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ents::{DatabaseError, Transactional};
+use ents_heed::{HeedEnv, WriteThrottle};
+use ents_test_suite::TestEntity;
+use tempfile::tempdir;
+
+#[test]
+fn test_map_usage_throttle() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    let usage = env.map_usage().unwrap();
+    assert!(usage > 0.0 && usage < 0.01, "{}", usage);
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_write_throttle(WriteThrottle::new().with_max_map_usage(0.0));
+    assert!(matches!(
+        env.write_txn(),
+        Err(DatabaseError::Overloaded { .. })
+    ));
+    // Reads are never throttled
+    env.read_txn().unwrap();
+    drop(env);
+
+    // Throttled writers wait up to `max_delay` before giving up
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_write_throttle(
+            WriteThrottle::new()
+                .with_max_map_usage(0.0)
+                .with_max_delay(Duration::from_millis(30)),
+        );
+    let start = Instant::now();
+    assert!(env.write_txn().is_err());
+    assert!(start.elapsed() >= Duration::from_millis(30));
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_write_throttle(WriteThrottle::new().with_max_map_usage(0.5));
+    env.write_txn().unwrap().commit().unwrap();
+}
+
+#[test]
+fn test_queued_writers_throttle() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_write_throttle(WriteThrottle::new().with_max_queued_writers(1));
+
+    let txn = env.write_txn().unwrap();
+    assert_eq!(env.queued_writers(), 0);
+    thread::scope(|s| {
+        let waiting = s.spawn(|| {
+            let txn = env.write_txn().unwrap();
+            txn.create(TestEntity::new("b".to_string(), 2)).unwrap();
+            txn.commit().unwrap();
+        });
+        while env.queued_writers() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // One writer is already waiting for the lock
+        let err = thread::scope(|s| s.spawn(|| env.write_txn().err()).join())
+            .unwrap()
+            .unwrap();
+        assert!(matches!(err, DatabaseError::Overloaded { .. }), "{}", err);
+
+        txn.commit().unwrap();
+        waiting.join().unwrap();
+    });
+    assert_eq!(env.queued_writers(), 0);
+    env.write_txn().unwrap().commit().unwrap();
+}
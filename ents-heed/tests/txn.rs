@@ -1,7 +1,8 @@
+use ents::watch::{ChangeKind, EdgeChange};
 use ents::{
     DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
     EntExt as _, EntMutationError, EntWithEdges, Id, NullEdgeProvider,
-    QueryEdge, Transactional,
+    QueryEdge, ReadTransactional, Transactional,
 };
 use ents_heed::HeedEnv;
 use serde::{Deserialize, Serialize};
@@ -336,6 +337,39 @@ fn test_transaction_rollback() {
     assert!(retrieved.is_none());
 }
 
+#[test]
+fn test_on_commit() {
+    let (_dir, env) = setup_test_env();
+    let fired = std::cell::RefCell::new(Vec::new());
+    let ent = || {
+        TestEntity::build()
+            .name("notify".to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    // Rolled back transactions drop their callbacks
+    let txn = env.write_txn().unwrap();
+    txn.create(ent()).unwrap();
+    txn.on_commit(|| fired.borrow_mut().push("rolled back".to_string()));
+    drop(txn);
+    assert!(fired.borrow().is_empty());
+
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(ent()).unwrap();
+    txn.on_commit(|| {
+        // The write is visible by the time the callback runs
+        let read = env.read_txn().unwrap();
+        assert!(read.get(id).unwrap().is_some());
+        fired.borrow_mut().push("first".to_string());
+    });
+    txn.on_commit(|| fired.borrow_mut().push("second".to_string()));
+    assert!(fired.borrow().is_empty());
+    txn.commit().unwrap();
+    assert_eq!(*fired.borrow(), vec!["first", "second"]);
+}
+
 #[test]
 fn test_update_without_cas() {
     let (_dir, env) = setup_test_env();
@@ -431,3 +465,246 @@ fn test_update_edge_change() {
     assert_eq!(edges.len(), 1);
     assert_eq!(edges[0].dest, city2_id);
 }
+
+#[test]
+fn test_graph_stats() {
+    let (_dir, env) = setup_test_env();
+    let txn = env.write_txn().unwrap();
+
+    let city_id = txn
+        .create(
+            TestCity::build()
+                .name("Seoul".to_string())
+                .finish()
+                .unwrap(),
+        )
+        .unwrap();
+    for name in ["Alice", "Bob"] {
+        let person = TestPerson::build()
+            .name(name.to_string())
+            .lives_in_link(city_id)
+            .finish()
+            .unwrap();
+        txn.create(person).unwrap();
+    }
+
+    let stats = txn.graph_stats(1).unwrap();
+    assert_eq!(stats.entity_count, 3);
+    assert_eq!(stats.edge_count, 2);
+    assert_eq!(stats.edges_by_name["lives_in"], 2);
+    assert_eq!(stats.out_degree_histogram[&1], 2);
+    assert_eq!(stats.in_degree_histogram[&2], 1);
+    assert_eq!(stats.top_hubs[0].id, city_id);
+
+    let report = serde_json::to_value(&stats).unwrap();
+    assert_eq!(report["edge_count"], 2);
+}
+
+#[test]
+fn test_sampling() {
+    let (_dir, env) = setup_test_env();
+    let txn = env.write_txn().unwrap();
+
+    let city_id = txn
+        .create(
+            TestCity::build()
+                .name("Seoul".to_string())
+                .finish()
+                .unwrap(),
+        )
+        .unwrap();
+    for i in 0..20 {
+        let person = TestPerson::build()
+            .name(format!("person_{}", i))
+            .lives_in_link(city_id)
+            .finish()
+            .unwrap();
+        txn.create(person).unwrap();
+    }
+
+    let people = txn.sample_entities("TestPerson", 5, 7).unwrap();
+    assert_eq!(people.len(), 5);
+    assert!(people.iter().all(|p| p.is::<TestPerson>()));
+    let ids: Vec<Id> = people.iter().map(|p| p.id()).collect();
+    let again: Vec<Id> = txn
+        .sample_entities("TestPerson", 5, 7)
+        .unwrap()
+        .iter()
+        .map(|p| p.id())
+        .collect();
+    assert_eq!(ids, again);
+
+    let cities = txn.sample_entities("TestCity", 5, 7).unwrap();
+    assert_eq!(cities.len(), 1);
+    assert_eq!(cities[0].id(), city_id);
+
+    let edges = txn.sample_edges(b"lives", 3, 7).unwrap();
+    assert_eq!(edges.len(), 3);
+    assert!(edges.iter().all(|e| e.dest == city_id));
+    assert!(txn.sample_edges(b"works_at", 3, 7).unwrap().is_empty());
+}
+
+#[test]
+fn test_watch() {
+    let (_dir, env) = setup_test_env();
+    let entities = env.watch_type::<TestEntity>();
+
+    let txn = env.write_txn().unwrap();
+    let id = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    txn.create(
+        TestCity::build()
+            .name("Seoul".to_string())
+            .finish()
+            .unwrap(),
+    )
+    .unwrap();
+    // Nothing is delivered before commit
+    assert!(entities.try_recv().is_err());
+    txn.commit().unwrap();
+
+    let changes: Vec<_> = entities.try_iter().collect();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].id, id);
+    assert_eq!(changes[0].kind, ChangeKind::Created);
+
+    let one = env.watch(id);
+    let txn = env.write_txn().unwrap();
+    let mut ent = txn.get(id).unwrap().unwrap().into_ent::<TestEntity>();
+    assert!(txn
+        .update(ent.as_mut().unwrap(), |e: &mut TestEntity| e.value = 7)
+        .unwrap());
+    txn.delete::<TestEntity>(id).unwrap();
+    txn.commit().unwrap();
+
+    let kinds: Vec<_> = one.try_iter().map(|c| c.kind).collect();
+    assert_eq!(kinds, vec![ChangeKind::Updated, ChangeKind::Deleted]);
+    assert_eq!(entities.try_iter().count(), 2);
+
+    // Rolled back writes are never delivered
+    let txn = env.write_txn().unwrap();
+    txn.create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    drop(txn);
+    assert!(entities.try_recv().is_err());
+}
+
+#[test]
+fn test_watch_edges() {
+    let (_dir, env) = setup_test_env();
+    let txn = env.write_txn().unwrap();
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    let b = txn
+        .create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let follows = env.watch_edges(a, b"follows");
+    let edge = EdgeValue::new(a, b"follows".to_vec(), b);
+    let txn = env.write_txn().unwrap();
+    txn.create_edge(edge.clone()).unwrap();
+    txn.create_edge(EdgeValue::new(a, b"likes".to_vec(), b))
+        .unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![EdgeChange::Added(edge.clone())]
+    );
+
+    let txn = env.write_txn().unwrap();
+    assert!(txn.hide_edge(&edge).unwrap());
+    // Hiding a hidden edge or restoring a visible one reports nothing
+    assert!(txn.hide_edge(&edge).unwrap());
+    assert!(txn.restore_edge(&edge).unwrap());
+    assert!(txn.restore_edge(&edge).unwrap());
+    // Deleting the destination removes the edge
+    txn.delete::<TestEntity>(b).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![
+            EdgeChange::Removed(edge.clone()),
+            EdgeChange::Added(edge.clone()),
+            EdgeChange::Removed(edge),
+        ]
+    );
+}
+
+#[test]
+fn test_recreate_edge() {
+    let (_dir, env) = setup_test_env();
+    let txn = env.write_txn().unwrap();
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    let b = txn
+        .create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    let edge = EdgeValue::new(a, b"follows".to_vec(), b);
+    txn.create_edge(edge.clone()).unwrap();
+    txn.commit().unwrap();
+
+    let follows = env.watch_edges(a, b"follows");
+    let txn = env.write_txn().unwrap();
+    // Re-creating a visible edge reports nothing
+    txn.create_edge(edge.clone()).unwrap();
+    assert!(txn.hide_edge(&edge).unwrap());
+    // Re-creating a hidden edge replaces its payload but keeps it hidden
+    txn.create_edge(edge.clone().with_payload(b"again".as_slice()))
+        .unwrap();
+    let names: &[&[u8]] = &[b"follows"];
+    assert!(txn.find_edges(a, EdgeQuery::asc(names)).unwrap().is_empty());
+    let hidden = txn
+        .find_edges(a, EdgeQuery::asc(names).include_hidden())
+        .unwrap();
+    assert!(hidden[0].hidden);
+    assert_eq!(hidden[0].payload, b"again");
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![EdgeChange::Removed(edge)]
+    );
+}
+
+fn count_follows<R: ReadTransactional>(txn: &R, id: Id) -> usize {
+    assert!(txn.get(id).unwrap().is_some());
+    txn.find_edges(id, EdgeQuery::asc(&[b"follows"]))
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn test_read_txn() {
+    let (_dir, env) = setup_test_env();
+    let txn = env.write_txn().unwrap();
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    txn.create_edge(EdgeValue::new(a, b"follows".to_vec(), a))
+        .unwrap();
+    // Read helpers accept write transactions too
+    assert_eq!(count_follows(&txn, a), 1);
+    txn.commit().unwrap();
+
+    let writer = env.write_txn().unwrap();
+    writer
+        .create_edge(EdgeValue::new(a, b"follows".to_vec(), 42))
+        .unwrap();
+
+    // Readers proceed while the write transaction is open and do not see
+    // its uncommitted edge
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let reader = env.read_txn().unwrap();
+            assert_eq!(count_follows(&reader, a), 1);
+        });
+    });
+
+    writer.commit().unwrap();
+    let reader = env.read_txn().unwrap();
+    assert_eq!(count_follows(&reader, a), 2);
+    assert!(reader.get(a + 1).unwrap().is_none());
+}
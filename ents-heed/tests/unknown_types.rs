@@ -0,0 +1,51 @@
+use ents::decode::{DynamicEnt, UnknownTypePolicy};
+use ents::{DatabaseError, EntExt, ReadTransactional};
+use ents_heed::HeedEnv;
+use heed::byteorder::BigEndian;
+use heed::types::{Str, U64};
+use heed::{Database, EnvOpenOptions};
+use tempfile::tempdir;
+
+#[test]
+fn test_unknown_types() {
+    let dir = tempdir().unwrap();
+    drop(HeedEnv::open(dir.path(), None).unwrap());
+
+    // Store an entity of a type this binary does not register
+    {
+        let env = unsafe { EnvOpenOptions::new().max_dbs(6).open(dir.path()) }
+            .unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let entities: Database<U64<BigEndian>, Str> =
+            env.open_database(&wtxn, Some("entities")).unwrap().unwrap();
+        entities
+            .put(&mut wtxn, &5, r#"{"type":"Martian","id":5,"legs":7}"#)
+            .unwrap();
+        wtxn.commit().unwrap();
+        env.prepare_for_closing().wait();
+    }
+
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    assert!(matches!(
+        env.read_txn().unwrap().get(5),
+        Err(DatabaseError::UnknownType { name }) if name == "Martian"
+    ));
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_unknown_types(UnknownTypePolicy::Skip);
+    assert!(env.read_txn().unwrap().get(5).unwrap().is_none());
+    let txn = env.write_txn().unwrap();
+    assert!(txn.sample_entities("Martian", 10, 0).unwrap().is_empty());
+    drop(txn);
+    drop(env);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_unknown_types(UnknownTypePolicy::Lenient);
+    let ent = env.read_txn().unwrap().get(5).unwrap().unwrap();
+    let dynamic = ent.as_ent::<DynamicEnt>().unwrap();
+    assert_eq!((dynamic.id, dynamic.type_name.as_str()), (5, "Martian"));
+    assert_eq!(dynamic.fields["legs"], 7);
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use ents::history::HistoryRetention;
+use ents::{
+    DatabaseError, EdgeQuery, EdgeValue, QueryEdge, ReadTransactional,
+    Transactional,
+};
+use ents_heed::{HeedEnv, FORMAT_VERSION};
+use ents_test_suite::TestEntity;
+use heed::types::{Bytes, Str};
+use heed::{Database, EnvOpenOptions};
+use tempfile::tempdir;
+
+/// Overwrite the stored format version, or remove it for `None`
+fn set_version(path: &Path, version: Option<u32>) {
+    let env = unsafe { EnvOpenOptions::new().max_dbs(7).open(path) }.unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    let meta: Database<Str, Bytes> =
+        env.open_database(&wtxn, Some("meta")).unwrap().unwrap();
+    match version {
+        Some(version) => meta
+            .put(&mut wtxn, "format_version", &version.to_be_bytes())
+            .unwrap(),
+        None => {
+            meta.delete(&mut wtxn, "format_version").unwrap();
+        }
+    }
+    wtxn.commit().unwrap();
+    env.prepare_for_closing().wait();
+}
+
+fn create_store(path: &Path) -> u64 {
+    let env = HeedEnv::open(path, None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    id
+}
+
+#[test]
+fn test_newer_format_is_refused() {
+    let dir = tempdir().unwrap();
+    create_store(dir.path());
+    set_version(dir.path(), Some(FORMAT_VERSION + 1));
+
+    let err = HeedEnv::open(dir.path(), None).err().unwrap();
+    assert!(matches!(
+        err,
+        DatabaseError::UnsupportedFormat { found, supported }
+            if found == FORMAT_VERSION + 1 && supported == FORMAT_VERSION
+    ));
+    assert!(err.to_string().contains("newer version"));
+    assert!(HeedEnv::open_read_only(dir.path()).is_err());
+    assert!(HeedEnv::upgrade_store(dir.path(), None).is_err());
+}
+
+#[test]
+fn test_upgrade_unversioned_store() {
+    let dir = tempdir().unwrap();
+    let id = create_store(dir.path());
+    // A store written before the version stamp existed
+    set_version(dir.path(), None);
+
+    let err = HeedEnv::open(dir.path(), None).err().unwrap();
+    assert!(matches!(
+        err,
+        DatabaseError::UnsupportedFormat { found: 0, .. }
+    ));
+    assert!(err.to_string().contains("upgrade_store"));
+
+    assert_eq!(HeedEnv::upgrade_store(dir.path(), None).unwrap(), 0);
+    assert_eq!(
+        HeedEnv::upgrade_store(dir.path(), None).unwrap(),
+        FORMAT_VERSION
+    );
+
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.read_txn().unwrap();
+    assert!(txn.get(id).unwrap().is_some());
+}
+
+/// Strip the discriminator off every edge key, as stores written before
+/// parallel edges had them
+fn strip_discriminators(path: &Path) {
+    let env = unsafe { EnvOpenOptions::new().max_dbs(7).open(path) }.unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    let edges: Database<Bytes, Bytes> =
+        env.open_database(&wtxn, Some("edges")).unwrap().unwrap();
+    let keys: Vec<Vec<u8>> = edges
+        .iter(&wtxn)
+        .unwrap()
+        .map(|result| result.unwrap().0.to_vec())
+        .collect();
+    edges.clear(&mut wtxn).unwrap();
+    for key in keys {
+        edges.put(&mut wtxn, &key[..key.len() - 8], &[]).unwrap();
+    }
+    wtxn.commit().unwrap();
+    env.prepare_for_closing().wait();
+}
+
+#[test]
+fn test_upgrade_pre_discriminator_store() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    let alice = txn.create(TestEntity::new("alice".to_string(), 1)).unwrap();
+    let bob = txn.create(TestEntity::new("bob".to_string(), 2)).unwrap();
+    txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))
+        .unwrap();
+    txn.commit().unwrap();
+    drop(env);
+    set_version(dir.path(), None);
+    strip_discriminators(dir.path());
+
+    assert_eq!(
+        HeedEnv::upgrade_pre_discriminator_store(dir.path(), None).unwrap(),
+        0
+    );
+
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.read_txn().unwrap();
+    let edges = txn.find_edges(alice, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].sort_key, b"follows");
+    assert_eq!(edges[0].dest, bob);
+    assert_eq!(edges[0].discriminator, 0);
+}
+
+#[test]
+fn test_upgrade_missing_store() {
+    let dir = tempdir().unwrap();
+    assert!(HeedEnv::upgrade_store(dir.path(), None).is_err());
+}
+
+#[test]
+fn test_upgrade_adds_deleted_database() {
+    let dir = tempdir().unwrap();
+    let id = create_store(dir.path());
+    // Version 1 stores had no database of soft-deleted entities
+    set_version(dir.path(), Some(1));
+
+    assert!(HeedEnv::open(dir.path(), None).is_err());
+    assert_eq!(HeedEnv::upgrade_store(dir.path(), None).unwrap(), 1);
+
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let txn = env.write_txn().unwrap();
+    assert!(txn.soft_delete::<TestEntity>(id).unwrap());
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_upgrade_adds_history_database() {
+    let dir = tempdir().unwrap();
+    let id = create_store(dir.path());
+    // Version 2 stores had no database of prior versions
+    set_version(dir.path(), Some(2));
+
+    assert!(HeedEnv::open(dir.path(), None).is_err());
+    assert_eq!(HeedEnv::upgrade_store(dir.path(), None).unwrap(), 2);
+
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_history(HistoryRetention::unbounded());
+    let txn = env.write_txn().unwrap();
+    assert!(txn.touch::<TestEntity>(id).unwrap());
+    assert_eq!(txn.get_history(id).unwrap().len(), 1);
+    txn.commit().unwrap();
+}
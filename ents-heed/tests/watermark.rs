@@ -0,0 +1,64 @@
+use ents::ids::ScriptedIds;
+use ents::Transactional;
+use ents_heed::{HeedEnv, HeedEnvOptions, IdAudit};
+use ents_test_suite::TestEntity;
+use heed::types::{Bytes, Str};
+use heed::{Database, EnvOpenOptions};
+use tempfile::tempdir;
+
+#[test]
+fn test_watermark_follows_inserts() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_provider(ScriptedIds::new([20, 10]));
+    assert_eq!(env.audit_ids().unwrap(), IdAudit::default());
+
+    let txn = env.write_txn().unwrap();
+    txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.create(TestEntity::new("b".to_string(), 2)).unwrap();
+    txn.delete::<TestEntity>(20).unwrap();
+    txn.commit().unwrap();
+
+    // Deleting the largest entity keeps the watermark
+    let audit = env.audit_ids().unwrap();
+    assert_eq!(audit.watermark, Some(20));
+    assert_eq!(audit.max_stored, Some(10));
+    assert!(audit.is_consistent());
+}
+
+#[test]
+fn test_missing_watermark() {
+    let dir = tempdir().unwrap();
+    let env = HeedEnv::open(dir.path(), None)
+        .unwrap()
+        .with_id_provider(ScriptedIds::new([7]));
+    let txn = env.write_txn().unwrap();
+    txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.commit().unwrap();
+    drop(env);
+
+    // Drop the watermark, as in a store written before it existed
+    {
+        let env = unsafe { EnvOpenOptions::new().max_dbs(6).open(dir.path()) }
+            .unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let meta: Database<Str, Bytes> =
+            env.open_database(&wtxn, Some("meta")).unwrap().unwrap();
+        meta.delete(&mut wtxn, "id_watermark").unwrap();
+        wtxn.commit().unwrap();
+        env.prepare_for_closing().wait();
+    }
+
+    let options = HeedEnvOptions::new().verify_ids(true);
+    assert!(options.open(dir.path()).is_err());
+
+    let env = HeedEnv::open(dir.path(), None).unwrap();
+    let audit = env.repair_id_watermark().unwrap();
+    assert_eq!(audit.watermark, None);
+    assert!(!audit.is_consistent());
+    assert!(env.audit_ids().unwrap().is_consistent());
+    drop(env);
+
+    options.open(dir.path()).unwrap();
+}
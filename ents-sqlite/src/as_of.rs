@@ -0,0 +1,122 @@
+//! As-of reads, see [`ents::as_of`].
+//!
+//! The `changes` table is read backwards from its last row until one
+//! committed at or before the requested time, so a read costs the changes
+//! committed since then, whichever entities they touched.
+
+use ents::as_of::{edges_as_of, entity_as_of};
+use ents::cdc::ChangeRecord;
+use ents::decode::UnknownTypePolicy;
+use ents::{DatabaseError, Edge, EdgeQuery, Ent, Id};
+use r2d2_sqlite::rusqlite::{self, Connection};
+
+use crate::history::history_in;
+use crate::{find_edges_in, get_in, Direction, ReadTxn, Txn};
+
+impl Txn<'_> {
+    /// Entity `id` as it was at `at`, in microseconds, as far as its
+    /// history and the change log reach back
+    pub fn get_as_of(
+        &self,
+        id: Id,
+        at: u64,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        get_as_of_in(&self.tx, id, at, self.unknown_types)
+    }
+
+    /// The edges from `source` matching `query` as they were at `at`
+    pub fn find_edges_as_of(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+        at: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_as_of_in(&self.tx, source, query, at)
+    }
+}
+
+impl ReadTxn<'_> {
+    /// Entity `id` as it was at `at`, in microseconds
+    pub fn get_as_of(
+        &self,
+        id: Id,
+        at: u64,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        get_as_of_in(&self.tx, id, at, self.unknown_types)
+    }
+
+    /// The edges from `source` matching `query` as they were at `at`
+    pub fn find_edges_as_of(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+        at: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_as_of_in(&self.tx, source, query, at)
+    }
+}
+
+fn get_as_of_in(
+    conn: &Connection,
+    id: Id,
+    at: u64,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+    Ok(entity_as_of(
+        id,
+        get_in(conn, id, unknown_types)?,
+        history_in(conn, id, unknown_types)?,
+        &changes_after(conn, at)?,
+        at,
+    ))
+}
+
+fn find_edges_as_of_in(
+    conn: &Connection,
+    source: Id,
+    query: EdgeQuery,
+    at: u64,
+) -> Result<Vec<Edge>, DatabaseError> {
+    let current = find_edges_in(
+        conn,
+        Direction::Outgoing,
+        source,
+        EdgeQuery::asc(query.edge_names)
+            .include_hidden()
+            .with_limit(usize::MAX),
+    )?;
+    Ok(edges_as_of(
+        source,
+        current,
+        &changes_after(conn, at)?,
+        &query,
+    ))
+}
+
+/// The captured changes committed after `at`, newest first
+fn changes_after(
+    conn: &Connection,
+    at: u64,
+) -> Result<Vec<ChangeRecord>, DatabaseError> {
+    let mut stmt = conn
+        .prepare_cached("SELECT seq, data FROM changes ORDER BY seq DESC")
+        .map_err(other)?;
+    let mut rows = stmt.query([]).map_err(other)?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next().map_err(other)? {
+        let seq: i64 = row.get(0).map_err(other)?;
+        let data: String = row.get(1).map_err(other)?;
+        let record = ChangeRecord::decode(seq as u64, &data)?;
+        if record.committed_at.is_none_or(|committed| committed <= at) {
+            break;
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn other(e: rusqlite::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
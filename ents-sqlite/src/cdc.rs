@@ -0,0 +1,92 @@
+//! The change capture log.
+//!
+//! Transactions created with [`Txn::with_change_capture`] append their
+//! changes to the `changes` table as part of their commit. SQLite assigns
+//! the sequence numbers, which only ever increase.
+//!
+//! ```ignore
+//! let txn = Txn::new(conn.transaction()?).with_change_capture(true);
+//! txn.create(user)?;
+//! txn.commit()?;
+//! for record in ents_sqlite::changes_since(&conn, last_seen, 100)? {
+//!     index.apply(&record.change)?;
+//! }
+//! ```
+//!
+//! [`Txn::with_change_capture`]: crate::Txn::with_change_capture
+
+use ents::cdc::{CapturedChange, ChangeRecord};
+use ents::clock::now_micros;
+use ents::DatabaseError;
+use r2d2_sqlite::rusqlite::{params, Connection};
+
+/// Up to `limit` captured changes with a sequence number above `after`,
+/// oldest first. Pass 0 to read from the start of the log.
+pub fn changes_since(
+    conn: &Connection,
+    after: u64,
+    limit: usize,
+) -> Result<Vec<ChangeRecord>, DatabaseError> {
+    let mut stmt = conn
+        .prepare("SELECT seq, data FROM changes WHERE seq > ?1 ORDER BY seq LIMIT ?2")
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let rows = stmt
+        .query_map(
+            params![after as i64, limit.min(i64::MAX as usize) as i64],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let mut records = Vec::new();
+    for row in rows {
+        let (seq, data) = row.map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        records.push(ChangeRecord::decode(seq as u64, &data)?);
+    }
+    Ok(records)
+}
+
+/// Drop the captured changes up to and including `through`, e.g. once every
+/// consumer has processed them. Returns how many were dropped.
+pub fn trim_changes(
+    conn: &Connection,
+    through: u64,
+) -> Result<u64, DatabaseError> {
+    let removed = conn
+        .execute(
+            "DELETE FROM changes WHERE seq <= ?1",
+            params![through as i64],
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    Ok(removed as u64)
+}
+
+/// Append `changes` to the log under the next sequence numbers
+pub(crate) fn append_changes(
+    conn: &Connection,
+    changes: &[CapturedChange],
+) -> Result<(), DatabaseError> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let mut stmt = conn
+        .prepare_cached("INSERT INTO changes (data) VALUES (?1)")
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let committed_at = now_micros();
+    for change in changes {
+        let data = ChangeRecord::encode(change, committed_at)?;
+        stmt.execute(params![data])
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+    }
+    Ok(())
+}
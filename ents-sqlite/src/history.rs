@@ -0,0 +1,206 @@
+//! Prior versions of entities, see [`ents::history`].
+//!
+//! An update copies the row it replaces into `history` under the next
+//! version number of its id, in the same statement guard as the update's
+//! `last_updated` check, then prunes the versions the retention no longer
+//! keeps.
+
+use ents::clock::now_micros;
+use ents::decode::UnknownTypePolicy;
+use ents::history::{EntityVersion, HistoryRetention};
+use ents::{DatabaseError, Id};
+use r2d2_sqlite::rusqlite::{self, params, Connection, OptionalExtension};
+
+use crate::{decode_in, ReadTxn, Txn};
+
+impl Txn<'_> {
+    /// Keep the versions of an entity that updates in this transaction
+    /// replace, bounded by `retention`. Every transaction writing to the
+    /// database should set the same retention.
+    pub fn with_history(mut self, retention: HistoryRetention) -> Self {
+        self.history = Some(retention);
+        self
+    }
+
+    /// Prior versions of entity `id`, newest first
+    pub fn get_history(
+        &self,
+        id: Id,
+    ) -> Result<Vec<EntityVersion>, DatabaseError> {
+        history_in(&self.tx, id, self.unknown_types)
+    }
+
+    /// Version `version` of entity `id`, counting from 1 for the entity as
+    /// created, if it is still kept
+    pub fn get_version(
+        &self,
+        id: Id,
+        version: u64,
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        version_in(&self.tx, id, version, self.unknown_types)
+    }
+
+    /// Store the current state of `id` as its next version if its
+    /// `last_updated` is `expected_last_updated`, or unconditionally for
+    /// None, and prune the versions the retention no longer keeps
+    pub(crate) fn archive_version(
+        &self,
+        id: Id,
+        expected_last_updated: Option<u64>,
+    ) -> Result<(), DatabaseError> {
+        let Some(retention) = self.history else {
+            return Ok(());
+        };
+        let now = now_micros();
+        let archived = self
+            .tx
+            .prepare_cached(
+                r#"
+                INSERT INTO history (id, version, recorded_at, data)
+                SELECT id, COALESCE(
+                    (SELECT MAX(version) FROM history WHERE id = ?1), 0
+                ) + 1, ?3, data
+                FROM entities
+                WHERE
+                    id = ?1 AND
+                    (
+                        JSON_EXTRACT(data, '$.last_updated') = ?2 OR
+                        ?2 IS NULL
+                    )
+                "#,
+            )
+            .and_then(|mut stmt| {
+                stmt.execute(params![
+                    id as i64,
+                    expected_last_updated.map(|v| v as i64),
+                    now as i64
+                ])
+            })
+            .map_err(other)?;
+        if archived == 0 {
+            return Ok(());
+        }
+
+        let mut stmt = self
+            .tx
+            .prepare_cached(
+                "SELECT version, recorded_at FROM history
+                 WHERE id = ?1 ORDER BY version DESC",
+            )
+            .map_err(other)?;
+        let versions = stmt
+            .query_map(params![id as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })
+            .map_err(other)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(other)?;
+        for (index, (version, recorded_at)) in versions.into_iter().enumerate()
+        {
+            if !retention.keeps(index, recorded_at as u64, now) {
+                self.tx
+                    .execute(
+                        "DELETE FROM history WHERE id = ?1 AND version = ?2",
+                        params![id as i64, version],
+                    )
+                    .map_err(other)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop every version of `id`
+    pub(crate) fn delete_history(&self, id: Id) -> Result<(), DatabaseError> {
+        self.tx
+            .prepare_cached("DELETE FROM history WHERE id = ?1")
+            .and_then(|mut stmt| stmt.execute(params![id as i64]))
+            .map_err(other)?;
+        Ok(())
+    }
+}
+
+impl ReadTxn<'_> {
+    /// Prior versions of entity `id`, newest first
+    pub fn get_history(
+        &self,
+        id: Id,
+    ) -> Result<Vec<EntityVersion>, DatabaseError> {
+        history_in(&self.tx, id, self.unknown_types)
+    }
+
+    /// Version `version` of entity `id`, if it is still kept
+    pub fn get_version(
+        &self,
+        id: Id,
+        version: u64,
+    ) -> Result<Option<EntityVersion>, DatabaseError> {
+        version_in(&self.tx, id, version, self.unknown_types)
+    }
+}
+
+pub(crate) fn history_in(
+    conn: &Connection,
+    id: Id,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Vec<EntityVersion>, DatabaseError> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT version, recorded_at, data FROM history
+             WHERE id = ?1 ORDER BY version DESC",
+        )
+        .map_err(other)?;
+    let rows = stmt
+        .query_map(params![id as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(other)?;
+    let mut versions = Vec::new();
+    for row in rows {
+        let (version, recorded_at, data_json) = row.map_err(other)?;
+        if let Some(ent) = decode_in(id, &data_json, unknown_types)? {
+            versions.push(EntityVersion {
+                version: version as u64,
+                recorded_at: recorded_at as u64,
+                ent,
+            });
+        }
+    }
+    Ok(versions)
+}
+
+fn version_in(
+    conn: &Connection,
+    id: Id,
+    version: u64,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Option<EntityVersion>, DatabaseError> {
+    let row: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT recorded_at, data FROM history
+             WHERE id = ?1 AND version = ?2",
+            params![id as i64, version as i64],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(other)?;
+    let Some((recorded_at, data_json)) = row else {
+        return Ok(None);
+    };
+    Ok(
+        decode_in(id, &data_json, unknown_types)?.map(|ent| EntityVersion {
+            version,
+            recorded_at: recorded_at as u64,
+            ent,
+        }),
+    )
+}
+
+fn other(e: rusqlite::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
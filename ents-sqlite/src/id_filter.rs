@@ -0,0 +1,82 @@
+//! Short-circuiting lookups of missing ids, see [`ents::bloom`].
+//!
+//! Transactions given the same [`IdFilter`] add the ids they create to it
+//! and answer `get` for ids it has not seen without a query. A rebuild
+//! scans `entities` in an immediate transaction, so no create can commit
+//! between the scan and the swap.
+
+use std::sync::Arc;
+
+use ents::bloom::IdFilter;
+use ents::{DatabaseError, Id};
+use r2d2_sqlite::rusqlite::{
+    self, Connection, Transaction, TransactionBehavior,
+};
+
+use crate::{ReadTxn, Txn};
+
+impl Txn<'_> {
+    /// Skip the lookup of ids `filter` has not seen and add created ids to
+    /// it. Every transaction writing to the database should share the
+    /// filter, or it misses their creates until the next rebuild.
+    pub fn with_id_filter(mut self, filter: Arc<IdFilter>) -> Self {
+        self.id_filter = Some(filter);
+        self
+    }
+
+    /// Whether `id` may be stored, per the id filter if there is one
+    pub(crate) fn may_contain(&self, id: Id) -> bool {
+        self.id_filter
+            .as_ref()
+            .is_none_or(|filter| filter.may_contain(id))
+    }
+
+    /// Add a stored `id` to the id filter
+    pub(crate) fn note_stored(&self, id: Id) {
+        if let Some(filter) = &self.id_filter {
+            filter.insert(id);
+        }
+    }
+}
+
+impl ReadTxn<'_> {
+    /// Skip the lookup of ids `filter` has not seen
+    pub fn with_id_filter(mut self, filter: Arc<IdFilter>) -> Self {
+        self.id_filter = Some(filter);
+        self
+    }
+
+    /// Whether `id` may be stored, per the id filter if there is one
+    pub(crate) fn may_contain(&self, id: Id) -> bool {
+        self.id_filter
+            .as_ref()
+            .is_none_or(|filter| filter.may_contain(id))
+    }
+}
+
+/// Rebuild `filter` from the ids stored in `conn`, returning how many it
+/// holds. Run it periodically: deleted ids stay in the filter until then.
+/// Waits for the write lock like any writer.
+pub fn rebuild_id_filter(
+    conn: &Connection,
+    filter: &IdFilter,
+) -> Result<usize, DatabaseError> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)
+        .map_err(other)?;
+    let ids = tx
+        .prepare("SELECT id FROM entities")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, i64>(0))?
+                .map(|id| id.map(|id| id as Id))
+                .collect::<rusqlite::Result<Vec<Id>>>()
+        })
+        .map_err(other)?;
+    filter.rebuild(&ids);
+    Ok(ids.len())
+}
+
+fn other(e: rusqlite::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
@@ -1,186 +1,211 @@
 use std::borrow::BorrowMut;
+use std::cell::RefCell;
+use std::sync::Arc;
 
+use ents::acyclic;
+use ents::bloom::IdFilter;
+use ents::bulk::BulkLoadReport;
+use ents::cascade;
+use ents::dangling::{self, IncomingEdgePolicy};
+use ents::decode::{decode_ent, UnknownTypePolicy};
+use ents::format::JsonFormat;
+use ents::history::HistoryRetention;
+use ents::ids::IdProvider;
+use ents::sample::Reservoir;
+use ents::stats::{GraphStats, GraphStatsCollector};
+use ents::unique::{self, UniqueKey};
+use ents::watch::{ChangeKind, ChangeLog, EdgeChange, EntityChange, WatchHub};
 use ents::Edge;
 use ents::{
     DatabaseError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
-    EntWithEdges, Id, QueryEdge, SortOrder, Transactional,
+    EntExt as _, EntWithEdges, EntityCursor, EntityPage, Id, QueryEdge,
+    ReadTransactional, SortOrder, Transactional,
+};
+use r2d2_sqlite::rusqlite::{
+    params, Connection, OptionalExtension, Transaction,
 };
-use r2d2_sqlite::rusqlite::{params, OptionalExtension, Transaction};
-
-pub struct Txn<'conn>(Transaction<'conn>);
-
-impl<'conn> Txn<'conn> {
-    pub fn new(tx: Transaction<'conn>) -> Self {
-        Self(tx)
-    }
 
-    fn update(
-        &self,
-        id: Id,
-        ent: Box<dyn Ent>,
-        expected_last_updated: Option<u64>,
-    ) -> Result<bool, DatabaseError> {
-        // Serialize the entity to JSON
-        let entity_type = ent.typetag_name().to_string();
-        let data_json =
-            serde_json::to_string(&ent).map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+mod as_of;
+mod cdc;
+mod history;
+mod id_filter;
+mod schema;
+mod trash;
 
-        // Build the UPDATE query with optional CAS check
-        let rows_affected = self
-            .0
-            .execute(
-                r#"
-                UPDATE entities SET data = ?1, type = ?2
-                WHERE
-                    id = ?3 AND
-                    (
-                        JSON_EXTRACT(data, '$.last_updated') = ?4 OR
-                        ?4 IS NULL
-                    )
-                "#,
-                params![
-                    data_json,
-                    entity_type,
-                    id as i64,
-                    expected_last_updated.map(|v| v as i64)
-                ],
-            )
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+use cdc::append_changes;
+pub use cdc::{changes_since, trim_changes};
+pub use id_filter::rebuild_id_filter;
+pub use schema::{init_schema, schema_version, upgrade_store, SCHEMA_VERSION};
 
-        Ok(rows_affected > 0)
-    }
+pub struct Txn<'conn> {
+    tx: Transaction<'conn>,
+    changes: ChangeLog,
+    watchers: Option<Arc<WatchHub>>,
+    json_format: JsonFormat,
+    ids: Option<Arc<dyn IdProvider>>,
+    unknown_types: UnknownTypePolicy,
+    incoming_edges: IncomingEdgePolicy,
+    edge_integrity: bool,
+    capture_changes: bool,
+    history: Option<HistoryRetention>,
+    id_filter: Option<Arc<IdFilter>>,
+    on_commit: RefCell<Vec<Box<dyn FnOnce() + 'conn>>>,
 }
 
 impl<'conn> Txn<'conn> {
-    fn insert<E: Ent>(&self, ent: &E) -> Result<Id, DatabaseError> {
-        // Serialize the entity to JSON
-        let entity_type = ent.typetag_name().to_string();
-
-        // Had to cast to &dyn Ent to make sure `type` to be serialized as well.
-        let data_json =
-            serde_json::to_string(&(ent as &dyn Ent)).map_err(|e| {
-                DatabaseError::Other {
-                    source: Box::new(e),
-                }
-            })?;
+    pub fn new(tx: Transaction<'conn>) -> Self {
+        Self {
+            tx,
+            changes: ChangeLog::default(),
+            watchers: None,
+            json_format: JsonFormat::COMPACT,
+            ids: None,
+            unknown_types: UnknownTypePolicy::Strict,
+            incoming_edges: IncomingEdgePolicy::Remove,
+            edge_integrity: false,
+            capture_changes: false,
+            history: None,
+            id_filter: None,
+            on_commit: RefCell::new(Vec::new()),
+        }
+    }
 
-        self.0
-            .execute(
-                "INSERT INTO entities (type, data) VALUES (?1, ?2)",
-                params![entity_type, data_json],
-            )
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+    /// Run `f` once this transaction commits, after watchers were notified.
+    /// Callbacks run in the order they were added and are dropped without
+    /// running if the transaction is rolled back or its commit fails.
+    pub fn on_commit(&self, f: impl FnOnce() + 'conn) {
+        self.on_commit.borrow_mut().push(Box::new(f));
+    }
 
-        let inserted_id = self.0.last_insert_rowid() as Id;
+    /// Assign ids of new entities from `ids` instead of one past the largest
+    /// stored id. Every transaction writing to the database should share
+    /// the provider.
+    pub fn with_id_provider(mut self, ids: Arc<dyn IdProvider>) -> Self {
+        self.ids = Some(ids);
+        self
+    }
 
-        Ok(inserted_id)
+    /// Serialize entities written in this transaction with `format`
+    pub fn with_json_format(mut self, format: JsonFormat) -> Self {
+        self.json_format = format;
+        self
     }
-}
 
-impl<'conn> Transactional for Txn<'conn> {
-    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
-        let mut stmt = self
-            .0
-            .prepare("SELECT id, data FROM entities WHERE id = ?1")
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+    /// Handle stored entities of unregistered types per `policy`
+    pub fn with_unknown_types(mut self, policy: UnknownTypePolicy) -> Self {
+        self.unknown_types = policy;
+        self
+    }
 
-        stmt.query_row(params![id as i64], |row| {
-            let id: Id = row.get::<_, i64>(0)? as Id;
-            let data_json: &str = row.get_ref(1)?.as_str()?;
-            let mut ret = serde_json::from_str::<Box<dyn Ent>>(data_json)
-                .expect("failed to parse JSON");
-            ret.set_id(id);
-            Ok(ret)
-        })
-        .optional()
-        .map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })
+    /// Handle the edges pointing at deleted entities per `policy`
+    pub fn with_incoming_edges(mut self, policy: IncomingEdgePolicy) -> Self {
+        self.incoming_edges = policy;
+        self
     }
 
-    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
-        let source = edge.source;
-        let sort_key = edge.sort_key;
-        let dest = edge.dest;
+    /// Refuse to create edges whose source or destination does not exist,
+    /// see [`ents::dangling::check_endpoints`]
+    pub fn with_edge_integrity(mut self, enabled: bool) -> Self {
+        self.edge_integrity = enabled;
+        self
+    }
 
-        self.0
-            .execute(
-                "INSERT INTO edges (source, type, dest) VALUES (?1, ?2, ?3)",
-                params![source as i64, sort_key, dest as i64],
-            )
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+    /// Append the changes of this transaction to the `changes` table when
+    /// it commits, see [`changes_since`]. Every transaction writing to the
+    /// database should enable it for the log to be complete.
+    pub fn with_change_capture(mut self, enabled: bool) -> Self {
+        self.capture_changes = enabled;
+        self
+    }
 
-        Ok(())
+    /// Whether anyone observes the recorded changes
+    fn tracks_changes(&self) -> bool {
+        self.watchers.is_some() || self.capture_changes
     }
 
-    fn delete<E: Ent + EntWithEdges>(
-        &self,
-        id: Id,
-    ) -> Result<(), DatabaseError> {
-        self.0
-            .prepare_cached(
-                r#"
-        DELETE FROM edges WHERE dest = ?1;
-        "#,
-            )
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?
-            .execute(params![id as i64])
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?;
+    /// Wrap `tx`, publishing its changes to `watchers` once it commits.
+    ///
+    /// Every transaction writing to the database must share the same hub
+    /// for watchers to see all changes.
+    pub fn with_watchers(
+        tx: Transaction<'conn>,
+        watchers: Arc<WatchHub>,
+    ) -> Self {
+        Self {
+            watchers: Some(watchers),
+            ..Self::new(tx)
+        }
+    }
 
-        self.0
-            .prepare_cached(
-                r#"
-        DELETE FROM entities WHERE id = ?1;
-        "#,
+    /// Whether `ent` serializes to exactly the stored bytes of its id
+    fn is_stored(&self, ent: &dyn Ent) -> Result<bool, DatabaseError> {
+        let data_json = self.json_format.to_string(ent)?;
+        let stored: Option<String> = self
+            .tx
+            .query_row(
+                "SELECT data FROM entities WHERE id = ?1",
+                params![ent.id() as i64],
+                |row| row.get(0),
             )
-            .map_err(|e| DatabaseError::Other {
-                source: Box::new(e),
-            })?
-            .execute(params![id as i64])
+            .optional()
             .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
             })?;
-
-        Ok(())
+        Ok(stored.as_deref() == Some(data_json.as_str()))
     }
 
-    fn update<T: EntWithEdges, F: FnOnce(&mut T), B: BorrowMut<T>>(
+    /// Apply `mutator` to `ent0` and write it back, unless
+    /// `skip_unchanged` is set and the result matches the stored entity.
+    fn write_update<T, F, B>(
         &self,
         mut ent0: B,
         mutator: F,
-    ) -> Result<bool, DatabaseError> {
+        skip_unchanged: bool,
+    ) -> Result<bool, DatabaseError>
+    where
+        T: EntWithEdges,
+        F: FnOnce(&mut T),
+        B: BorrowMut<T>,
+    {
         let ent = ent0.borrow_mut();
         let draft0 = T::EdgeProvider::draft(ent);
+        let keys0 = ent.unique_keys();
         let expected_last_updated = ent.last_updated();
 
         mutator(ent);
+        // Nothing to write if the entity still serializes to the stored
+        // bytes, which also means nobody updated it since it was loaded
+        if skip_unchanged
+            && T::EdgeProvider::draft(ent) == draft0
+            && self.is_stored(ent)?
+        {
+            return Ok(true);
+        }
         ent.mark_updated().map_err(|e| DatabaseError::Other {
             source: Box::new(e),
         })?;
 
         let draft1 = T::EdgeProvider::draft(ent);
+        let keys1 = ent.unique_keys();
+        if keys0 != keys1 {
+            unique::check_available(self, &keys1, Some(ent.id()))?;
+        }
 
         // Optimization: if drafts are equal, no edge changes needed
         if draft0 == draft1 {
-            return self.update(
+            let updated = self.update(
                 ent.id(),
                 dyn_clone::clone_box(ent),
                 Some(expected_last_updated),
-            );
+            )?;
+            if updated {
+                self.move_unique_keys(&keys0, &keys1, ent.id())?;
+                self.changes.record(EntityChange::new::<T>(
+                    ent.id(),
+                    ChangeKind::Updated,
+                ));
+            }
+            return Ok(updated);
         }
 
         let edge0 = draft0.check(self).map_err(|e| DatabaseError::Other {
@@ -198,133 +223,1213 @@ impl<'conn> Transactional for Txn<'conn> {
 
         if updated {
             // Remove old edges if they existed
-            for edge in edge0 {
-                self.0
-                    .execute(
-                        "DELETE FROM edges WHERE source = ?1 AND type = ?2 AND dest = ?3",
-                        params![edge.source as i64, edge.sort_key, edge.dest as i64],
-                    )
-                    .map_err(|e| DatabaseError::Other {
-                        source: Box::new(e),
-                    })?;
+            for edge in &edge0 {
+                self.delete_edge(edge)?;
             }
 
             // Create new edges if they exist
             for edge in edge1 {
                 self.create_edge(edge)?;
             }
+
+            self.move_unique_keys(&keys0, &keys1, ent.id())?;
+
+            self.changes
+                .record(EntityChange::new::<T>(ent.id(), ChangeKind::Updated));
         }
 
         Ok(updated)
     }
 
-    fn create<E: Ent + EntWithEdges>(
+    fn update(
         &self,
-        mut ent: E,
-    ) -> Result<Id, DatabaseError> {
-        let id = self.insert(&ent)?;
-        ent.set_id(id);
-        ent.setup_edges(self).map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })?;
-        Ok(id)
-    }
+        id: Id,
+        ent: Box<dyn Ent>,
+        expected_last_updated: Option<u64>,
+    ) -> Result<bool, DatabaseError> {
+        // Serialize the entity to JSON
+        let entity_type = ent.typetag_name().to_string();
+        let data_json = self.json_format.to_string(&*ent)?;
 
-    fn commit(self) -> Result<(), DatabaseError> {
-        self.0.commit().map_err(|e| DatabaseError::Other {
-            source: Box::new(e),
-        })
+        self.archive_version(id, expected_last_updated)?;
+        // Build the UPDATE query with optional CAS check
+        let rows_affected = self
+            .tx
+            .execute(
+                r#"
+                UPDATE entities SET data = ?1, type = ?2
+                WHERE
+                    id = ?3 AND
+                    (
+                        JSON_EXTRACT(data, '$.last_updated') = ?4 OR
+                        ?4 IS NULL
+                    )
+                "#,
+                params![
+                    data_json,
+                    entity_type,
+                    id as i64,
+                    expected_last_updated.map(|v| v as i64)
+                ],
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        Ok(rows_affected > 0)
     }
 }
 
-impl<'conn> QueryEdge for Txn<'conn> {
-    fn find_edges(
+impl<'conn> Txn<'conn> {
+    /// Write `entities` under their own ids and `edges` as given, reusing one
+    /// prepared statement for each. See [`ents::bulk`] for what is skipped
+    /// compared to `create`.
+    pub fn bulk_load<I, J>(
         &self,
-        source: Id,
-        query: EdgeQuery,
-    ) -> Result<Vec<Edge>, DatabaseError> {
-        // Build WHERE clause for edge names filter
-        let name_filter = if query.edge_names.is_empty() {
-            String::new()
-        } else {
-            let placeholders = query
-                .edge_names
-                .iter()
-                .map(|_| "?")
-                .collect::<Vec<_>>()
-                .join(", ");
-            format!(" AND type IN ({})", placeholders)
-        };
+        entities: I,
+        edges: J,
+    ) -> Result<BulkLoadReport, DatabaseError>
+    where
+        I: IntoIterator<Item = Box<dyn Ent>>,
+        J: IntoIterator<Item = EdgeValue>,
+    {
+        let mut report = BulkLoadReport::default();
 
-        // Build cursor filter based on sort order
-        let cursor_filter = match (&query.cursor, query.order) {
-            (Some(_), SortOrder::Asc) => " AND (type, dest) > (?, ?)",
-            (Some(_), SortOrder::Desc) => " AND (type, dest) < (?, ?)",
-            (None, _) => "",
-        };
+        let mut insert_entity = self
+            .tx
+            .prepare_cached(
+                "INSERT INTO entities (id, type, data) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (id) DO UPDATE SET type = excluded.type, data = excluded.data",
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        for ent in entities {
+            let data_json = self.json_format.to_string(&*ent)?;
+            insert_entity
+                .execute(params![
+                    ent.id() as i64,
+                    ent.typetag_name(),
+                    data_json
+                ])
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            self.note_stored(ent.id());
+            report.entities += 1;
+        }
 
-        // Build ORDER BY clause
-        let order_clause = match query.order {
-            SortOrder::Asc => "ORDER BY type ASC, dest ASC",
-            SortOrder::Desc => "ORDER BY type DESC, dest DESC",
-        };
+        let mut insert_edge = self
+            .tx
+            .prepare_cached(
+                "INSERT INTO edges (source, type, dest, discriminator, data) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (source, type, dest, discriminator) DO UPDATE SET hidden = 0, data = excluded.data",
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        for edge in edges {
+            insert_edge
+                .execute(params![
+                    edge.source as i64,
+                    edge.sort_key,
+                    edge.dest as i64,
+                    edge.discriminator as i64,
+                    edge.payload
+                ])
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            report.edges += 1;
+        }
 
-        let sql = format!(
-            "SELECT source, type, dest FROM edges WHERE source = ?{}{} {} LIMIT 100",
-            name_filter, cursor_filter, order_clause
-        );
+        Ok(report)
+    }
 
-        // Build parameters
-        let mut params: Vec<Box<dyn r2d2_sqlite::rusqlite::ToSql>> = Vec::new();
-        params.push(Box::new(source as i64));
+    /// Computes graph-wide statistics over this transaction's snapshot.
+    pub fn graph_stats(
+        &self,
+        top_n: usize,
+    ) -> Result<GraphStats, DatabaseError> {
+        let mut collector = GraphStatsCollector::new();
 
-        for name in query.edge_names {
-            params.push(Box::new(name.to_vec()));
+        let mut stmt =
+            self.tx.prepare("SELECT id FROM entities").map_err(|e| {
+                DatabaseError::Other {
+                    source: Box::new(e),
+                }
+            })?;
+        let ids =
+            stmt.query_map([], |row| row.get::<_, i64>(0))
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        for id in ids {
+            let id = id.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            collector.observe_entity(id as Id);
         }
 
-        if let Some(cursor) = query.cursor {
-            params.push(Box::new(cursor.sort_key.to_vec()));
-            params.push(Box::new(cursor.destination as i64));
+        let mut stmt = self
+            .tx
+            .prepare("SELECT source, CAST(type AS BLOB), dest, discriminator FROM edges")
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let edges = stmt
+            .query_map([], |row| {
+                let source: i64 = row.get(0)?;
+                let sort_key: Vec<u8> = row.get(1)?;
+                let dest: i64 = row.get(2)?;
+                let discriminator: i64 = row.get(3)?;
+                Ok(Edge::new(source as Id, sort_key, dest as Id)
+                    .with_discriminator(discriminator as u64))
+            })
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        for edge in edges {
+            let edge = edge.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+            collector.observe_edge(&edge);
         }
 
-        let params_refs: Vec<&dyn r2d2_sqlite::rusqlite::ToSql> =
-            params.iter().map(|p| p.as_ref()).collect();
+        Ok(collector.finish(top_n))
+    }
+
+    /// Returns a uniform random sample of up to `n` entities of the given
+    /// type, using reservoir sampling over a full scan.
+    pub fn sample_entities(
+        &self,
+        type_name: &str,
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<Box<dyn Ent>>, DatabaseError> {
+        let mut reservoir = Reservoir::new(n, seed);
 
-        let mut stmt =
-            self.0.prepare(&sql).map_err(|e| DatabaseError::Other {
+        let mut stmt = self
+            .tx
+            .prepare("SELECT id, data FROM entities WHERE type = ?1")
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let rows = stmt
+            .query_map(params![type_name], |row| {
+                Ok((row.get::<_, i64>(0)? as Id, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
             })?;
+        for row in rows {
+            reservoir.offer(row.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?);
+        }
 
+        reservoir
+            .into_vec()
+            .into_iter()
+            .filter_map(|(id, data_json)| {
+                decode_in(id, &data_json, self.unknown_types).transpose()
+            })
+            .collect()
+    }
+
+    /// Returns a uniform random sample of up to `n` edges whose sort key
+    /// starts with `name`, using reservoir sampling over a full scan.
+    pub fn sample_edges(
+        &self,
+        name: &[u8],
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let mut reservoir = Reservoir::new(n, seed);
+
+        let mut stmt = self
+            .tx
+            .prepare(
+                r#"
+                SELECT source, CAST(type AS BLOB), dest, discriminator, hidden, data
+                FROM edges
+                WHERE substr(CAST(type AS BLOB), 1, ?2) = ?1
+                "#,
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
         let rows = stmt
-            .query_map(params_refs.as_slice(), |row| {
+            .query_map(params![name, name.len() as i64], |row| {
                 let source: i64 = row.get(0)?;
-                let sort_key: Vec<u8> = match row.get_ref(1)? {
-                    r2d2_sqlite::rusqlite::types::ValueRef::Text(s) => {
-                        s.to_vec()
-                    }
-                    r2d2_sqlite::rusqlite::types::ValueRef::Blob(b) => {
-                        b.to_vec()
-                    }
-                    _ => {
-                        return Err(
-                            r2d2_sqlite::rusqlite::Error::InvalidColumnType(
-                                1,
-                                "type".into(),
-                                row.get_ref(1)?.data_type(),
-                            ),
-                        )
-                    }
-                };
-                let destination: i64 = row.get(2)?;
-                Ok(Edge::new(source as Id, sort_key, destination as Id))
+                let sort_key: Vec<u8> = row.get(1)?;
+                let dest: i64 = row.get(2)?;
+                let discriminator: i64 = row.get(3)?;
+                let hidden: bool = row.get(4)?;
+                let payload: Vec<u8> = row.get(5)?;
+                Ok(Edge::new(source as Id, sort_key, dest as Id)
+                    .with_discriminator(discriminator as u64)
+                    .with_hidden(hidden)
+                    .with_payload(payload))
             })
             .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
             })?;
+        for row in rows {
+            reservoir.offer(row.map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?);
+        }
+
+        Ok(reservoir.into_vec())
+    }
 
+    /// Returns up to `limit` edges, hidden ones included, whose destination
+    /// does not exist, e.g. those left by [`IncomingEdgePolicy::Orphan`]
+    pub fn find_dangling_edges(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let mut stmt = self
+            .tx
+            .prepare_cached(
+                r#"
+                SELECT source, CAST(type AS BLOB), dest, discriminator, hidden, data
+                FROM edges
+                WHERE NOT EXISTS (SELECT 1 FROM entities WHERE id = edges.dest)
+                ORDER BY source, type, dest, discriminator
+                LIMIT ?1
+                "#,
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let rows = stmt
+            .query_map(params![limit.min(i64::MAX as usize) as i64], |row| {
+                let source: i64 = row.get(0)?;
+                let sort_key: Vec<u8> = row.get(1)?;
+                let dest: i64 = row.get(2)?;
+                let discriminator: i64 = row.get(3)?;
+                let hidden: bool = row.get(4)?;
+                let payload: Vec<u8> = row.get(5)?;
+                Ok(Edge::new(source as Id, sort_key, dest as Id)
+                    .with_discriminator(discriminator as u64)
+                    .with_hidden(hidden)
+                    .with_payload(payload))
+            })
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
         rows.collect::<Result<Vec<_>, _>>()
             .map_err(|e| DatabaseError::Other {
                 source: Box::new(e),
             })
     }
+
+    /// Deletes every edge whose destination does not exist and returns how
+    /// many were deleted
+    pub fn remove_dangling_edges(&self) -> Result<u64, DatabaseError> {
+        // Only look up the removed edges when someone can observe them
+        if self.tracks_changes() {
+            for edge in self.find_dangling_edges(usize::MAX)? {
+                self.changes.record_edge(EdgeChange::Removed(
+                    EdgeValue::new(edge.source, edge.sort_key, edge.dest)
+                        .with_discriminator(edge.discriminator),
+                ));
+            }
+        }
+        let deleted = self
+            .tx
+            .execute(
+                r#"
+                DELETE FROM edges
+                WHERE NOT EXISTS (SELECT 1 FROM entities WHERE id = edges.dest)
+                "#,
+                [],
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        Ok(deleted as u64)
+    }
+
+    fn entity_exists(&self, id: Id) -> Result<bool, DatabaseError> {
+        if !self.may_contain(id) {
+            return Ok(false);
+        }
+        self.tx
+            .prepare_cached(
+                "SELECT EXISTS (SELECT 1 FROM entities WHERE id = ?1)",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_row(params![id as i64], |row| row.get(0))
+            })
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })
+    }
+
+    /// Deletes one edge, if it exists
+    fn delete_edge(&self, edge: &EdgeValue) -> Result<(), DatabaseError> {
+        let removed = self
+            .tx
+            .execute(
+                "DELETE FROM edges WHERE source = ?1 AND type = ?2 AND dest = ?3 AND discriminator = ?4",
+                params![
+                    edge.source as i64,
+                    edge.sort_key,
+                    edge.dest as i64,
+                    edge.discriminator as i64
+                ],
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        if removed > 0 {
+            self.changes.record_edge(EdgeChange::Removed(edge.clone()));
+        }
+        Ok(())
+    }
+
+    /// Deletes every edge pointing at `dest`
+    fn delete_edges_to(&self, dest: Id) -> Result<(), DatabaseError> {
+        // Only look up the removed edges when someone can observe them
+        if self.tracks_changes() {
+            for edge in self.edges_to(dest)? {
+                self.changes.record_edge(EdgeChange::Removed(edge));
+            }
+        }
+
+        self.tx
+            .prepare_cached(
+                r#"
+        DELETE FROM edges WHERE dest = ?1;
+        "#,
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .execute(params![dest as i64])
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        Ok(())
+    }
+
+    /// Every edge pointing at `dest`
+    fn edges_to(&self, dest: Id) -> Result<Vec<EdgeValue>, DatabaseError> {
+        let mut stmt = self
+            .tx
+            .prepare_cached(
+                "SELECT source, type, discriminator FROM edges WHERE dest = ?1",
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        let rows = stmt
+            .query_map(params![dest as i64], |row| {
+                Ok(EdgeValue::new(
+                    row.get::<_, i64>(0)? as Id,
+                    row.get(1)?,
+                    dest,
+                )
+                .with_discriminator(row.get::<_, i64>(2)? as u64))
+            })
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })
+    }
+
+    /// Sets or clears the hidden flag of an existing edge.
+    fn set_edge_hidden(
+        &self,
+        edge: &EdgeValue,
+        hidden: bool,
+    ) -> Result<bool, DatabaseError> {
+        let current: Option<bool> = self
+            .tx
+            .query_row(
+                r#"
+                SELECT hidden FROM edges
+                WHERE
+                    source = ?1 AND type = ?2 AND
+                    dest = ?3 AND discriminator = ?4
+                "#,
+                params![
+                    edge.source as i64,
+                    edge.sort_key,
+                    edge.dest as i64,
+                    edge.discriminator as i64
+                ],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        match current {
+            None => return Ok(false),
+            // Nothing changes, so nothing is written or reported
+            Some(current) if current == hidden => return Ok(true),
+            Some(_) => {}
+        }
+
+        self.tx
+            .execute(
+                r#"
+                UPDATE edges SET hidden = ?5
+                WHERE
+                    source = ?1 AND type = ?2 AND
+                    dest = ?3 AND discriminator = ?4
+                "#,
+                params![
+                    edge.source as i64,
+                    edge.sort_key,
+                    edge.dest as i64,
+                    edge.discriminator as i64,
+                    hidden
+                ],
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        self.changes.record_edge(if hidden {
+            EdgeChange::Removed(edge.clone())
+        } else {
+            EdgeChange::Added(edge.clone())
+        });
+        Ok(true)
+    }
+
+    /// Records `id` as the holder of `keys`
+    fn put_unique_keys(
+        &self,
+        keys: &[UniqueKey],
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        for key in keys {
+            self.tx
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO uniques (key, id) VALUES (?1, ?2)",
+                )
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?
+                .execute(params![key.encode(), id as i64])
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Releases the keys of `old` that are not in `new` and claims the rest
+    fn move_unique_keys(
+        &self,
+        old: &[UniqueKey],
+        new: &[UniqueKey],
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        if old == new {
+            return Ok(());
+        }
+        let released: Vec<UniqueKey> =
+            old.iter().filter(|k| !new.contains(k)).cloned().collect();
+        self.delete_unique_keys(&released)?;
+        self.put_unique_keys(new, id)
+    }
+
+    fn delete_unique_keys(
+        &self,
+        keys: &[UniqueKey],
+    ) -> Result<(), DatabaseError> {
+        for key in keys {
+            self.tx
+                .prepare_cached("DELETE FROM uniques WHERE key = ?1")
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?
+                .execute(params![key.encode()])
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Creates `ent` with its edges and unique keys, under `id` or else a
+    /// new ID
+    fn create_as<E: EntWithEdges>(
+        &self,
+        mut ent: E,
+        id: Option<Id>,
+    ) -> Result<Id, DatabaseError> {
+        let keys = ent.unique_keys();
+        unique::check_available(self, &keys, None)?;
+        let id = self.insert(&mut ent, id)?;
+        ent.setup_edges(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.put_unique_keys(&keys, id)?;
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Created));
+        Ok(id)
+    }
+
+    fn insert<E: Ent>(
+        &self,
+        ent: &mut E,
+        id: Option<Id>,
+    ) -> Result<Id, DatabaseError> {
+        let id = match (id, &self.ids) {
+            (Some(id), _) => id as i64,
+            (None, Some(ids)) => ids.next_id()? as i64,
+            // Same choice as an implicit rowid, counting soft-deleted ids so
+            // a restore gets its own back; writers are serialized, so it
+            // stays free until the insert
+            (None, None) => self
+                .tx
+                .query_row(
+                    "SELECT MAX(
+                        (SELECT COALESCE(MAX(id), 0) FROM entities),
+                        (SELECT COALESCE(MAX(id), 0) FROM deleted_entities)
+                    ) + 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?,
+        };
+        ent.set_id(id as Id);
+
+        let entity_type = ent.typetag_name().to_string();
+        let data_json = self.json_format.to_string(ent)?;
+
+        self.tx
+            .execute(
+                "INSERT INTO entities (id, type, data) VALUES (?1, ?2, ?3)",
+                params![id, entity_type, data_json],
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        self.note_stored(id as Id);
+
+        Ok(id as Id)
+    }
+}
+
+impl<'conn> ReadTransactional for Txn<'conn> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if !self.may_contain(id) {
+            return Ok(None);
+        }
+        get_in(&self.tx, id, self.unknown_types)
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        trash::get_deleted_in(&self.tx, id, self.unknown_types)
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        trash::list_deleted_in(&self.tx, deleted_before, after, limit)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        list_ids_by_type_in(&self.tx, type_name, after, limit)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        scan_entities_in(&self.tx, cursor, limit, self.unknown_types)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        find_unique_in(&self.tx, key)
+    }
+}
+
+impl<'conn> Transactional for Txn<'conn> {
+    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        if self.edge_integrity {
+            dangling::check_endpoints(&edge, |id| self.entity_exists(id))?;
+        }
+        acyclic::check_edge(self, &edge)?;
+        let args = params![
+            edge.source as i64,
+            edge.sort_key,
+            edge.dest as i64,
+            edge.discriminator as i64,
+            edge.payload
+        ];
+        let inserted = self
+            .tx
+            .execute(
+                "INSERT INTO edges (source, type, dest, discriminator, data) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (source, type, dest, discriminator) DO NOTHING",
+                args,
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            > 0;
+        if !inserted {
+            // Re-creating an edge replaces its data but keeps it hidden or
+            // visible as it was, so it is not reported as added
+            self.tx
+                .execute(
+                    r#"
+                    UPDATE edges SET data = ?5
+                    WHERE
+                        source = ?1 AND type = ?2 AND
+                        dest = ?3 AND discriminator = ?4
+                    "#,
+                    args,
+                )
+                .map_err(|e| DatabaseError::Other {
+                    source: Box::new(e),
+                })?;
+            return Ok(());
+        }
+
+        self.changes.record_edge(EdgeChange::Added(edge));
+        Ok(())
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.set_edge_hidden(edge, true)
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        acyclic::check_edge(self, edge)?;
+        self.set_edge_hidden(edge, false)
+    }
+
+    fn delete<E: Ent + EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        // Unique keys are released from the stored entity, so it has to be
+        // an `E`
+        let stored = match self.get(id)? {
+            Some(ent) => Some(ent.into_ent::<E>().ok_or_else(|| {
+                DatabaseError::Other {
+                    source: format!(
+                        "entity {} is not a {}",
+                        id,
+                        std::any::type_name::<E>()
+                    )
+                    .into(),
+                }
+            })?),
+            None => None,
+        };
+
+        // Refuse before the cascade writes anything
+        if matches!(self.incoming_edges, IncomingEdgePolicy::Restrict) {
+            let incoming = self.edges_to(id)?;
+            if !incoming.is_empty() {
+                return Err(DatabaseError::Other {
+                    source: format!(
+                        "entity {} has {} incoming edges",
+                        id,
+                        incoming.len()
+                    )
+                    .into(),
+                });
+            }
+        }
+
+        cascade::enforce::<E, _>(self, id, |edge| self.delete_edge(edge))?;
+
+        if matches!(self.incoming_edges, IncomingEdgePolicy::Remove) {
+            self.delete_edges_to(id)?;
+        }
+
+        if let Some(ent) = stored {
+            self.delete_unique_keys(&ent.unique_keys())?;
+        }
+
+        let deleted = self
+            .tx
+            .prepare_cached(
+                r#"
+        DELETE FROM entities WHERE id = ?1;
+        "#,
+            )
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?
+            .execute(params![id as i64])
+            .map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        if deleted > 0 {
+            self.delete_history(id)?;
+            self.changes
+                .record(EntityChange::new::<E>(id, ChangeKind::Deleted));
+        }
+
+        Ok(())
+    }
+
+    fn update<T: EntWithEdges, F: FnOnce(&mut T), B: BorrowMut<T>>(
+        &self,
+        ent: B,
+        mutator: F,
+    ) -> Result<bool, DatabaseError> {
+        self.write_update(ent, mutator, true)
+    }
+
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        match self.get_as::<E>(id)? {
+            Some(ent) => self.write_update(ent, |_: &mut E| {}, false),
+            None => Ok(false),
+        }
+    }
+
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        self.move_to_trash::<E>(id)
+    }
+
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.restore_from_trash::<E>(id)
+    }
+
+    fn create<E: Ent + EntWithEdges>(
+        &self,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        self.create_as(ent, None)
+    }
+
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        if self.entity_exists(id)? {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        self.create_as(ent, Some(id))
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        if self.capture_changes {
+            append_changes(&self.tx, &self.changes.captured())?;
+        }
+        self.tx.commit().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        if let Some(watchers) = &self.watchers {
+            self.changes.publish_to(watchers);
+        }
+        for f in self.on_commit.into_inner() {
+            f();
+        }
+        Ok(())
+    }
+}
+
+impl<'conn> QueryEdge for Txn<'conn> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_in(&self.tx, Direction::Outgoing, source, query)
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_in(&self.tx, Direction::Incoming, dest, query)
+    }
+}
+
+/// A read-only transaction.
+///
+/// Wraps a deferred sqlite transaction, which takes no write lock, so
+/// readers do not serialize behind writers.
+pub struct ReadTxn<'conn> {
+    tx: Transaction<'conn>,
+    unknown_types: UnknownTypePolicy,
+    id_filter: Option<Arc<IdFilter>>,
+}
+
+impl<'conn> ReadTxn<'conn> {
+    pub fn new(tx: Transaction<'conn>) -> Self {
+        Self {
+            tx,
+            unknown_types: UnknownTypePolicy::Strict,
+            id_filter: None,
+        }
+    }
+
+    /// Handle stored entities of unregistered types per `policy`
+    pub fn with_unknown_types(mut self, policy: UnknownTypePolicy) -> Self {
+        self.unknown_types = policy;
+        self
+    }
+}
+
+impl<'conn> ReadTransactional for ReadTxn<'conn> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if !self.may_contain(id) {
+            return Ok(None);
+        }
+        get_in(&self.tx, id, self.unknown_types)
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        trash::get_deleted_in(&self.tx, id, self.unknown_types)
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        trash::list_deleted_in(&self.tx, deleted_before, after, limit)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        list_ids_by_type_in(&self.tx, type_name, after, limit)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        find_unique_in(&self.tx, key)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        scan_entities_in(&self.tx, cursor, limit, self.unknown_types)
+    }
+}
+
+impl<'conn> QueryEdge for ReadTxn<'conn> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_in(&self.tx, Direction::Outgoing, source, query)
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        find_edges_in(&self.tx, Direction::Incoming, dest, query)
+    }
+}
+
+fn list_ids_by_type_in(
+    conn: &Connection,
+    type_name: &str,
+    after: Option<Id>,
+    limit: usize,
+) -> Result<Vec<Id>, DatabaseError> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id FROM entities WHERE type = ?1 AND id > ?2 \
+             ORDER BY id LIMIT ?3",
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let rows = stmt
+        .query_map(
+            params![
+                type_name,
+                after.unwrap_or(0) as i64,
+                limit.min(i64::MAX as usize) as i64
+            ],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    rows.map(|id| id.map(|id| id as Id))
+        .collect::<Result<_, _>>()
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+}
+
+fn scan_entities_in(
+    conn: &Connection,
+    cursor: Option<EntityCursor>,
+    limit: usize,
+    unknown_types: UnknownTypePolicy,
+) -> Result<EntityPage, DatabaseError> {
+    if limit == 0 {
+        return Ok((Vec::new(), cursor));
+    }
+    // One row past the page tells whether the scan goes on
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, data FROM entities WHERE id > ?1 \
+             ORDER BY id LIMIT ?2",
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let mut rows = stmt
+        .query_map(
+            params![
+                cursor.map_or(0, |cursor| cursor.after) as i64,
+                limit.saturating_add(1).min(i64::MAX as usize) as i64
+            ],
+            |row| Ok((row.get::<_, i64>(0)? as Id, row.get::<_, String>(1)?)),
+        )
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    let more = rows.len() > limit;
+    rows.truncate(limit);
+    let next = rows
+        .last()
+        .filter(|_| more)
+        .map(|&(after, _)| EntityCursor { after });
+    let mut ents = Vec::new();
+    for (id, data_json) in rows {
+        ents.extend(decode_in(id, &data_json, unknown_types)?);
+    }
+    Ok((ents, next))
+}
+
+fn find_unique_in(
+    conn: &Connection,
+    key: &UniqueKey,
+) -> Result<Option<Id>, DatabaseError> {
+    conn.prepare_cached("SELECT id FROM uniques WHERE key = ?1")
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?
+        .query_row(params![key.encode()], |row| row.get::<_, i64>(0))
+        .optional()
+        .map(|id| id.map(|id| id as Id))
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+}
+
+fn get_in(
+    conn: &Connection,
+    id: Id,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+    let data_json: Option<String> = conn
+        .query_row(
+            "SELECT data FROM entities WHERE id = ?1",
+            params![id as i64],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    match data_json {
+        Some(data_json) => decode_in(id, &data_json, unknown_types),
+        None => Ok(None),
+    }
+}
+
+/// Deserializes stored entity `id` per the unknown type policy
+fn decode_in(
+    id: Id,
+    data_json: &str,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+    let mut ent = decode_ent(data_json, unknown_types)?;
+    if let Some(ent) = &mut ent {
+        ent.set_id(id);
+    }
+    Ok(ent)
+}
+
+/// Which endpoint of an edge a query is anchored at
+#[derive(Clone, Copy)]
+enum Direction {
+    /// Edges leaving the id, ordered by destination
+    Outgoing,
+    /// Edges pointing at the id, ordered by source
+    Incoming,
+}
+
+impl Direction {
+    /// The column matched against the id, and the column ordering results
+    /// after the edge name
+    fn columns(self) -> (&'static str, &'static str) {
+        match self {
+            Direction::Outgoing => ("source", "dest"),
+            Direction::Incoming => ("dest", "source"),
+        }
+    }
+}
+
+fn find_edges_in(
+    conn: &Connection,
+    direction: Direction,
+    id: Id,
+    query: EdgeQuery,
+) -> Result<Vec<Edge>, DatabaseError> {
+    let (anchor, other) = direction.columns();
+
+    // Build WHERE clause for edge names filter
+    let name_filter = if query.edge_names.is_empty() {
+        String::new()
+    } else {
+        let placeholders = query
+            .edge_names
+            .iter()
+            .map(|_| "?")
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(" AND type IN ({})", placeholders)
+    };
+
+    // Hidden edges are skipped unless explicitly requested
+    let hidden_filter = if query.include_hidden {
+        ""
+    } else {
+        " AND hidden = 0"
+    };
+
+    // Build cursor filter based on sort order
+    let cursor_filter = match (&query.cursor, query.order) {
+        (Some(_), SortOrder::Asc) => {
+            format!(" AND (type, {}, discriminator) > (?, ?, ?)", other)
+        }
+        (Some(_), SortOrder::Desc) => {
+            format!(" AND (type, {}, discriminator) < (?, ?, ?)", other)
+        }
+        (None, _) => String::new(),
+    };
+
+    // Build ORDER BY clause
+    let order_clause = match query.order {
+        SortOrder::Asc => {
+            format!("ORDER BY type ASC, {} ASC, discriminator ASC", other)
+        }
+        SortOrder::Desc => {
+            format!("ORDER BY type DESC, {} DESC, discriminator DESC", other)
+        }
+    };
+
+    let sql = format!(
+        "SELECT source, type, dest, discriminator, hidden, data FROM edges WHERE {} = ?{}{}{} {} LIMIT {}",
+        anchor,
+        name_filter,
+        hidden_filter,
+        cursor_filter,
+        order_clause,
+        // sqlite limits are signed 64-bit integers
+        query.max_edges().min(i64::MAX as usize)
+    );
+
+    // Build parameters
+    let mut params: Vec<Box<dyn r2d2_sqlite::rusqlite::ToSql>> = Vec::new();
+    params.push(Box::new(id as i64));
+
+    for name in query.edge_names {
+        params.push(Box::new(name.to_vec()));
+    }
+
+    if let Some(cursor) = query.cursor {
+        params.push(Box::new(cursor.sort_key.to_vec()));
+        params.push(Box::new(cursor.destination as i64));
+        params.push(Box::new(cursor.discriminator as i64));
+    }
+
+    let params_refs: Vec<&dyn r2d2_sqlite::rusqlite::ToSql> =
+        params.iter().map(|p| p.as_ref()).collect();
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| DatabaseError::Other {
+        source: Box::new(e),
+    })?;
+
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            let source: i64 = row.get(0)?;
+            let sort_key: Vec<u8> = match row.get_ref(1)? {
+                r2d2_sqlite::rusqlite::types::ValueRef::Text(s) => s.to_vec(),
+                r2d2_sqlite::rusqlite::types::ValueRef::Blob(b) => b.to_vec(),
+                _ => {
+                    return Err(
+                        r2d2_sqlite::rusqlite::Error::InvalidColumnType(
+                            1,
+                            "type".into(),
+                            row.get_ref(1)?.data_type(),
+                        ),
+                    )
+                }
+            };
+            let destination: i64 = row.get(2)?;
+            let discriminator: i64 = row.get(3)?;
+            let hidden: bool = row.get(4)?;
+            let payload: Vec<u8> = row.get(5)?;
+            Ok(Edge::new(source as Id, sort_key, destination as Id)
+                .with_discriminator(discriminator as u64)
+                .with_hidden(hidden)
+                .with_payload(payload))
+        })
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
 }
@@ -0,0 +1,216 @@
+//! The tables the backend stores entities and edges in.
+//!
+//! [`init_schema`] creates them in a new database and upgrades older ones;
+//! [`upgrade_store`] does the same and reports the version it started from.
+//! The schema version is kept in SQLite's `user_version` pragma; each
+//! migration moves it up by one inside the same transaction as its changes,
+//! so an interrupted upgrade resumes where it stopped. Databases whose
+//! tables were created by hand start at version 0, and the migrations only
+//! add what is missing from them. An `edges` table from before parallel and
+//! hidden edges is rebuilt with the `discriminator` and `hidden` columns and
+//! the primary key covering the discriminator, its edges keeping
+//! discriminator 0.
+//!
+//! ```ignore
+//! let conn = pool.get()?;
+//! ents_sqlite::init_schema(&conn)?;
+//! ```
+
+use ents::DatabaseError;
+use r2d2_sqlite::rusqlite::{self, Connection};
+
+type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+/// Migrations in order; the schema version is the number applied
+const MIGRATIONS: &[Migration] = &[
+    create_tables,
+    add_indexes,
+    add_edge_data,
+    add_changes,
+    add_deleted_entities,
+    add_history,
+];
+
+/// Schema version written by this version of the crate
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Bring the schema of `conn` up to [`SCHEMA_VERSION`]
+pub fn init_schema(conn: &Connection) -> Result<(), DatabaseError> {
+    upgrade_store(conn).map(|_| ())
+}
+
+/// Run the migrations `conn` is missing, returning the schema version it
+/// had before. A database written by a newer version of the crate fails
+/// with [`DatabaseError::UnsupportedFormat`] and is left untouched.
+pub fn upgrade_store(conn: &Connection) -> Result<u32, DatabaseError> {
+    let previous = schema_version(conn)?;
+    loop {
+        let tx = conn.unchecked_transaction().map_err(other)?;
+        let version = schema_version(&tx)?;
+        if version > SCHEMA_VERSION {
+            return Err(DatabaseError::UnsupportedFormat {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        let Some(migration) = MIGRATIONS.get(version as usize) else {
+            return Ok(previous);
+        };
+        migration(&tx).map_err(other)?;
+        tx.pragma_update(None, "user_version", version + 1)
+            .map_err(other)?;
+        tx.commit().map_err(other)?;
+    }
+}
+
+/// Schema version of `conn`, 0 if it was never initialized
+pub fn schema_version(conn: &Connection) -> Result<u32, DatabaseError> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(other)
+}
+
+/// The current `edges` table
+const EDGES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS edges (
+   source INTEGER NOT NULL,
+   type BLOB NOT NULL,
+   dest INTEGER NOT NULL,
+   discriminator INTEGER NOT NULL DEFAULT 0,
+   hidden INTEGER NOT NULL DEFAULT 0,
+   PRIMARY KEY (source, type, dest, discriminator)
+);
+"#;
+
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+CREATE TABLE IF NOT EXISTS entities (
+   id INTEGER PRIMARY KEY,
+   type TEXT NOT NULL,
+   data TEXT NOT NULL
+);
+"#,
+    )?;
+    conn.execute_batch(EDGES_TABLE)?;
+    add_edge_discriminator(conn)
+}
+
+/// Rebuild an `edges` table keyed by (source, type, dest) into the current
+/// layout. SQLite cannot change a primary key in place.
+fn add_edge_discriminator(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt =
+        conn.prepare("SELECT name, pk FROM pragma_table_info('edges')")?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let keyed = columns
+        .iter()
+        .any(|(name, pk)| name == "discriminator" && *pk > 0);
+    let has = |column: &str| columns.iter().any(|(name, _)| name == column);
+    if keyed && has("hidden") {
+        return Ok(());
+    }
+
+    let mut copied = vec!["source", "type", "dest"];
+    let mut selected = vec![
+        "source".to_string(),
+        "CAST(type AS BLOB)".to_string(),
+        "dest".to_string(),
+    ];
+    for column in ["discriminator", "hidden", "data"] {
+        if has(column) {
+            copied.push(column);
+            selected.push(column.to_string());
+        }
+    }
+    conn.execute_batch("ALTER TABLE edges RENAME TO edges_old")?;
+    conn.execute_batch(EDGES_TABLE)?;
+    if has("data") {
+        conn.execute_batch(
+            "ALTER TABLE edges ADD COLUMN data BLOB NOT NULL DEFAULT x''",
+        )?;
+    }
+    conn.execute_batch(&format!(
+        "INSERT INTO edges ({}) SELECT {} FROM edges_old;
+         DROP TABLE edges_old;",
+        copied.join(", "),
+        selected.join(", ")
+    ))
+}
+
+fn add_indexes(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+CREATE INDEX IF NOT EXISTS entities_by_type ON entities (type, id);
+CREATE INDEX IF NOT EXISTS edges_by_dest
+   ON edges (dest, type, source, discriminator);
+CREATE TABLE IF NOT EXISTS uniques (
+   key BLOB PRIMARY KEY,
+   id INTEGER NOT NULL
+);
+"#,
+    )
+}
+
+fn add_edge_data(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM pragma_table_info('edges') WHERE name = 'data'
+        )",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(
+            "ALTER TABLE edges ADD COLUMN data BLOB NOT NULL DEFAULT x''",
+        )?;
+    }
+    Ok(())
+}
+
+fn add_changes(conn: &Connection) -> rusqlite::Result<()> {
+    // AUTOINCREMENT keeps trimmed sequence numbers from being reused
+    conn.execute_batch(
+        r#"
+CREATE TABLE IF NOT EXISTS changes (
+   seq INTEGER PRIMARY KEY AUTOINCREMENT,
+   data TEXT NOT NULL
+);
+"#,
+    )
+}
+
+fn add_deleted_entities(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+CREATE TABLE IF NOT EXISTS deleted_entities (
+   id INTEGER PRIMARY KEY,
+   type TEXT NOT NULL,
+   data TEXT NOT NULL,
+   deleted_at INTEGER NOT NULL
+);
+"#,
+    )
+}
+
+fn add_history(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+CREATE TABLE IF NOT EXISTS history (
+   id INTEGER NOT NULL,
+   version INTEGER NOT NULL,
+   recorded_at INTEGER NOT NULL,
+   data TEXT NOT NULL,
+   PRIMARY KEY (id, version)
+);
+"#,
+    )
+}
+
+fn other(e: rusqlite::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
@@ -0,0 +1,126 @@
+//! Soft-deleted entities.
+//!
+//! `soft_delete` moves the row of an entity from `entities` to
+//! `deleted_entities`, which adds the time of deletion in microseconds.
+//! Edges and unique keys are left alone, so `restore` only has to move the
+//! row back. Purging restores the entity and deletes it like any other.
+
+use ents::clock::now_micros;
+use ents::decode::UnknownTypePolicy;
+use ents::watch::{ChangeKind, EntityChange};
+use ents::{DatabaseError, Ent, EntWithEdges, Id, ReadTransactional};
+use r2d2_sqlite::rusqlite::{self, params, Connection, OptionalExtension};
+
+use crate::{decode_in, Txn};
+
+impl Txn<'_> {
+    pub(crate) fn move_to_trash<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        if self.get_as::<E>(id)?.is_none() {
+            return Ok(false);
+        }
+        self.tx
+            .execute(
+                "INSERT INTO deleted_entities (id, type, data, deleted_at)
+                 SELECT id, type, data, ?2 FROM entities WHERE id = ?1",
+                params![id as i64, now_micros() as i64],
+            )
+            .and_then(|_| {
+                self.tx.execute(
+                    "DELETE FROM entities WHERE id = ?1",
+                    params![id as i64],
+                )
+            })
+            .map_err(other)?;
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Deleted));
+        Ok(true)
+    }
+
+    pub(crate) fn restore_from_trash<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        let Some(ent) = get_deleted_in(&self.tx, id, self.unknown_types)?
+        else {
+            return Ok(false);
+        };
+        if ent.downcast_ref::<E>().is_none() {
+            return Ok(false);
+        }
+        if self.entity_exists(id)? {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        self.tx
+            .execute(
+                "INSERT INTO entities (id, type, data)
+                 SELECT id, type, data FROM deleted_entities WHERE id = ?1",
+                params![id as i64],
+            )
+            .and_then(|_| {
+                self.tx.execute(
+                    "DELETE FROM deleted_entities WHERE id = ?1",
+                    params![id as i64],
+                )
+            })
+            .map_err(other)?;
+        self.note_stored(id);
+        self.changes
+            .record(EntityChange::new::<E>(id, ChangeKind::Created));
+        Ok(true)
+    }
+}
+
+/// The soft-deleted entity `id`
+pub(crate) fn get_deleted_in(
+    conn: &Connection,
+    id: Id,
+    unknown_types: UnknownTypePolicy,
+) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+    let data_json: Option<String> = conn
+        .query_row(
+            "SELECT data FROM deleted_entities WHERE id = ?1",
+            params![id as i64],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(other)?;
+    match data_json {
+        Some(data_json) => decode_in(id, &data_json, unknown_types),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn list_deleted_in(
+    conn: &Connection,
+    deleted_before: u64,
+    after: Option<Id>,
+    limit: usize,
+) -> Result<Vec<Id>, DatabaseError> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id FROM deleted_entities
+             WHERE id > ?1 AND deleted_at < ?2 ORDER BY id LIMIT ?3",
+        )
+        .map_err(other)?;
+    let rows = stmt
+        .query_map(
+            params![
+                after.unwrap_or(0) as i64,
+                deleted_before.min(i64::MAX as u64) as i64,
+                limit.min(i64::MAX as usize) as i64
+            ],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(other)?;
+    rows.map(|id| id.map(|id| id as Id).map_err(other))
+        .collect()
+}
+
+fn other(e: rusqlite::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
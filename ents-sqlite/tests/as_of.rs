@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use ents::clock::{with_clock, MockClock};
+use ents::history::HistoryRetention;
+use ents::{DatabaseError, EdgeQuery, EdgeValue, EntExt, Id, Transactional};
+use ents_sqlite::{ReadTxn, Txn};
+use ents_test_suite::TestEntity;
+use r2d2_sqlite::rusqlite::Connection;
+
+fn write_txn(conn: &mut Connection) -> Txn<'_> {
+    Txn::new(conn.transaction().unwrap())
+        .with_change_capture(true)
+        .with_history(HistoryRetention::unbounded())
+}
+
+fn value(ent: Option<Box<dyn ents::Ent>>) -> Option<i32> {
+    ent.map(|ent| ent.into_ent::<TestEntity>().unwrap().value)
+}
+
+#[test]
+fn test_reads_as_of() -> Result<(), DatabaseError> {
+    let mut conn = Connection::open_in_memory().unwrap();
+    ents_sqlite::init_schema(&conn)?;
+    let clock = Arc::new(MockClock::new(100));
+    let (alice, bob, carol) = with_clock(clock.clone(), || {
+        let txn = write_txn(&mut conn);
+        let alice = txn.create(TestEntity::new("alice".to_string(), 1))?;
+        let bob = txn.create(TestEntity::new("bob".to_string(), 1))?;
+        txn.commit()?;
+
+        clock.set(200);
+        let txn = write_txn(&mut conn);
+        txn.retry_update(alice, 1, |e: &mut TestEntity| e.value = 2)?;
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))?;
+        txn.commit()?;
+
+        clock.set(300);
+        let txn = write_txn(&mut conn);
+        let carol = txn.create(TestEntity::new("carol".to_string(), 1))?;
+        txn.hide_edge(&EdgeValue::new(alice, b"follows".to_vec(), bob))?;
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), carol))?;
+        txn.commit()?;
+        Ok::<_, DatabaseError>((alice, bob, carol))
+    })?;
+
+    let txn = ReadTxn::new(conn.transaction().unwrap());
+    assert_eq!(value(txn.get_as_of(alice, 150)?), Some(1));
+    assert_eq!(value(txn.get_as_of(alice, 250)?), Some(2));
+    assert_eq!(value(txn.get_as_of(carol, 250)?), None);
+    assert_eq!(value(txn.get_as_of(carol, 300)?), Some(1));
+
+    let follows = |at| -> Result<Vec<Id>, DatabaseError> {
+        let edges =
+            txn.find_edges_as_of(alice, EdgeQuery::asc(&[b"follows"]), at)?;
+        Ok(edges.into_iter().map(|edge| edge.dest).collect())
+    };
+    assert_eq!(follows(150)?, Vec::<Id>::new());
+    assert_eq!(follows(250)?, vec![bob]);
+    assert_eq!(follows(350)?, vec![carol]);
+    drop(txn);
+
+    // Write transactions answer too
+    let txn = Txn::new(conn.transaction().unwrap());
+    assert_eq!(value(txn.get_as_of(alice, 150)?), Some(1));
+    assert_eq!(
+        txn.find_edges_as_of(alice, EdgeQuery::asc(&[b"follows"]), 250)?
+            .len(),
+        1
+    );
+    Ok(())
+}
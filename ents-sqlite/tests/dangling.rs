@@ -0,0 +1,38 @@
+use ents::dangling::IncomingEdgePolicy;
+use ents::{EdgeQuery, EdgeValue, QueryEdge, ReadTransactional, Transactional};
+use ents_sqlite::Txn;
+use ents_test_suite::{Folder, User};
+use r2d2_sqlite::rusqlite::Connection;
+
+#[test]
+fn test_restricted_delete_writes_nothing() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    let root = txn.create(Folder::new("root".into())).unwrap();
+    let child = txn.create(Folder::new("child".into())).unwrap();
+    let ada = txn
+        .create(User::new("ada".into(), "ada@example.com".into()))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(root, b"subfolder".to_vec(), child))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(root, b"shared_with".to_vec(), ada))
+        .unwrap();
+    txn.create_edge(EdgeValue::new(ada, b"owns".to_vec(), root))
+        .unwrap();
+    txn.commit().unwrap();
+
+    // The incoming edge refuses the delete before the cascade and the
+    // tombstone run, so committing afterwards keeps everything
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_incoming_edges(IncomingEdgePolicy::Restrict);
+    assert!(txn.delete::<Folder>(root).is_err());
+    txn.commit().unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    assert!(txn.get(root).unwrap().is_some());
+    assert!(txn.get(child).unwrap().is_some());
+    let edges = txn.find_edges(root, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(edges.len(), 2);
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ents::ids::SequentialIds;
+use ents_sqlite::Txn;
+use ents_test_suite::golden;
+use r2d2_sqlite::rusqlite::types::ValueRef;
+use r2d2_sqlite::rusqlite::Connection;
+
+/// The schema and every row of every table, one line each
+fn dump(conn: &Connection) -> String {
+    let version: u32 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .unwrap();
+    let mut out = format!("user_version = {}\n", version);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT type, name, sql FROM sqlite_master \
+             WHERE sql IS NOT NULL ORDER BY type, name",
+        )
+        .unwrap();
+    let schema: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut tables = Vec::new();
+    for (kind, name, sql) in schema {
+        out.push_str(&format!("{} {}: {}\n", kind, name, sql));
+        if kind == "table" && !name.starts_with("sqlite_") {
+            tables.push(name);
+        }
+    }
+
+    for table in tables {
+        out.push_str(&format!("[{}]\n", table));
+        let columns = conn
+            .prepare(&format!("SELECT * FROM {}", table))
+            .unwrap()
+            .column_count();
+        let order: Vec<String> = (1..=columns).map(|i| i.to_string()).collect();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT * FROM {} ORDER BY {}",
+                table,
+                order.join(", ")
+            ))
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            let cells: Vec<String> = (0..columns)
+                .map(|i| match row.get_ref(i).unwrap() {
+                    ValueRef::Null => "NULL".to_string(),
+                    ValueRef::Integer(n) => n.to_string(),
+                    ValueRef::Real(f) => f.to_string(),
+                    ValueRef::Text(text) => golden::display_bytes(text),
+                    ValueRef::Blob(blob) => {
+                        format!("x{}", golden::display_bytes(blob))
+                    }
+                })
+                .collect();
+            out.push_str(&cells.join(" | "));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[test]
+fn test_storage_format() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_id_provider(Arc::new(SequentialIds::new(1)))
+        .with_change_capture(true);
+    golden::write_fixture(&txn).unwrap();
+    golden::commit_fixture(txn).unwrap();
+
+    let golden_file =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/store.txt");
+    golden::check_golden(golden_file, &dump(&conn)).unwrap();
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use ents::bloom::IdFilter;
+use ents::{Ent, Id, ReadTransactional, Transactional};
+use ents_sqlite::{rebuild_id_filter, ReadTxn, Txn};
+use ents_test_suite::TestEntity;
+use r2d2_sqlite::rusqlite::Connection;
+
+fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
+    conn
+}
+
+fn entity(i: Id) -> Box<dyn Ent> {
+    let mut ent = TestEntity::new(format!("e{}", i), i as i32);
+    ent.set_id(i);
+    Box::new(ent)
+}
+
+#[test]
+fn test_filter_tracks_stored_ids() {
+    let mut conn = setup();
+    let filter = Arc::new(IdFilter::new(0.01));
+    assert_eq!(rebuild_id_filter(&conn, &filter).unwrap(), 0);
+
+    let txn =
+        Txn::new(conn.transaction().unwrap()).with_id_filter(filter.clone());
+    let id = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    txn.bulk_load((100..110).map(entity), []).unwrap();
+    txn.commit().unwrap();
+
+    let read = ReadTxn::new(conn.transaction().unwrap())
+        .with_id_filter(filter.clone());
+    for id in std::iter::once(id).chain(100..110) {
+        assert!(filter.may_contain(id));
+        assert!(read.get(id).unwrap().is_some());
+    }
+    let missing = read.get(1_000).unwrap();
+    assert!(missing.is_none());
+}
+
+#[test]
+fn test_rebuild_picks_up_unshared_creates() {
+    let mut conn = setup();
+    let filter = Arc::new(IdFilter::new(0.01));
+    rebuild_id_filter(&conn, &filter).unwrap();
+
+    // Written without the filter, so it is missed until the next rebuild
+    let txn = Txn::new(conn.transaction().unwrap());
+    txn.bulk_load([entity(7)], []).unwrap();
+    txn.commit().unwrap();
+    let txn =
+        Txn::new(conn.transaction().unwrap()).with_id_filter(filter.clone());
+    assert!(txn.get(7).unwrap().is_none());
+    drop(txn);
+
+    assert_eq!(rebuild_id_filter(&conn, &filter).unwrap(), 1);
+    let txn =
+        Txn::new(conn.transaction().unwrap()).with_id_filter(filter.clone());
+    assert!(txn.get(7).unwrap().is_some());
+    txn.soft_delete::<TestEntity>(7).unwrap();
+    txn.commit().unwrap();
+
+    rebuild_id_filter(&conn, &filter).unwrap();
+    let txn =
+        Txn::new(conn.transaction().unwrap()).with_id_filter(filter.clone());
+    assert!(txn.restore::<TestEntity>(7).unwrap());
+    assert!(txn.get(7).unwrap().is_some());
+}
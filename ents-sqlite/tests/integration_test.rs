@@ -44,22 +44,7 @@ impl TestSuiteRunner for SqliteTestRunner {
 fn setup_test_db() -> Pool<SqliteConnectionManager> {
     let pool = Pool::new(SqliteConnectionManager::memory()).unwrap();
     let conn = pool.get().unwrap();
-    conn.execute_batch(
-        r#"
-CREATE TABLE IF NOT EXISTS entities (
-   id INTEGER PRIMARY KEY,
-   type TEXT NOT NULL,
-   data TEXT NOT NULL
-);
-CREATE TABLE IF NOT EXISTS edges (
-   source INTEGER NOT NULL,
-   type TEXT NOT NULL,
-   dest INTEGER NOT NULL,
-   PRIMARY KEY (source, type, dest)
-);
-"#,
-    )
-    .unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
     pool
 }
 
@@ -0,0 +1,142 @@
+use ents::{DatabaseError, EdgeQuery, EdgeValue, QueryEdge, Transactional};
+use ents_sqlite::{
+    init_schema, schema_version, upgrade_store, Txn, SCHEMA_VERSION,
+};
+use ents_test_suite::TestEntity;
+use r2d2_sqlite::rusqlite::Connection;
+
+#[test]
+fn test_init_schema() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    assert_eq!(schema_version(&conn).unwrap(), 0);
+    init_schema(&conn).unwrap();
+    assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    // Running it again changes nothing
+    init_schema(&conn).unwrap();
+    assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    let a = txn.create(TestEntity::new("a".to_string(), 1)).unwrap();
+    let b = txn.create(TestEntity::new("b".to_string(), 2)).unwrap();
+    txn.create_edge(
+        EdgeValue::new(a, b"likes".to_vec(), b).with_payload(b"x".as_slice()),
+    )
+    .unwrap();
+    let edges = txn.find_edges_to(b, EdgeQuery::asc(&[])).unwrap();
+    assert_eq!(edges[0].payload, b"x");
+}
+
+const HAND_WRITTEN_SCHEMA: &str = r#"
+CREATE TABLE entities (
+   id INTEGER PRIMARY KEY,
+   type TEXT NOT NULL,
+   data TEXT NOT NULL
+);
+CREATE TABLE edges (
+   source INTEGER NOT NULL,
+   type TEXT NOT NULL,
+   dest INTEGER NOT NULL,
+   PRIMARY KEY (source, type, dest)
+);
+INSERT INTO edges (source, type, dest) VALUES (1, X'6c696b6573', 2);
+"#;
+
+#[test]
+fn test_upgrade_hand_written_schema() {
+    // The schema consumers used to create themselves, before parallel and
+    // hidden edges and edge payloads
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(HAND_WRITTEN_SCHEMA).unwrap();
+
+    init_schema(&conn).unwrap();
+    assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    let data: Vec<u8> = conn
+        .query_row("SELECT data FROM edges", [], |row| row.get(0))
+        .unwrap();
+    assert!(data.is_empty());
+    let indexes: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index'
+             AND name IN ('entities_by_type', 'edges_by_dest')",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(indexes, 2);
+}
+
+#[test]
+fn test_upgrade_pre_discriminator_edges() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(HAND_WRITTEN_SCHEMA).unwrap();
+    init_schema(&conn).unwrap();
+
+    // The existing edge gets discriminator 0 and parallel edges fit beside it
+    let txn = Txn::new(conn.transaction().unwrap());
+    let likes = EdgeValue::new(1, b"likes".to_vec(), 2);
+    txn.create_edge(likes.clone().with_discriminator(1))
+        .unwrap();
+    assert!(txn.hide_edge(&likes).unwrap());
+    let edges = txn
+        .find_edges(1, EdgeQuery::asc(&[b"likes"]).include_hidden())
+        .unwrap();
+    let found: Vec<_> = edges
+        .iter()
+        .map(|e| (e.dest, e.discriminator, e.hidden))
+        .collect();
+    assert_eq!(found, vec![(2, 0, true), (2, 1, false)]);
+}
+
+#[test]
+fn test_newer_schema_is_rejected() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+    let err = init_schema(&conn).unwrap_err();
+    assert!(matches!(
+        err,
+        DatabaseError::UnsupportedFormat { found, supported }
+            if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+    ));
+    assert!(err.to_string().contains("newer version"));
+    assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+}
+
+#[test]
+fn test_upgrade_store_reports_previous_version() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", 0).unwrap();
+    assert_eq!(upgrade_store(&conn).unwrap(), 0);
+    assert_eq!(upgrade_store(&conn).unwrap(), SCHEMA_VERSION);
+    assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+}
+
+/// The query plan of `sql`, one detail line per step
+fn query_plan(conn: &Connection, sql: &str) -> String {
+    let mut stmt = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+        .unwrap();
+    let details = stmt
+        .query_map([1], |row| row.get::<_, String>(3))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    details.join("\n")
+}
+
+#[test]
+fn test_inbound_edges_use_dest_index() {
+    let conn = Connection::open_in_memory().unwrap();
+    init_schema(&conn).unwrap();
+
+    // Cleaning up inbound edges on delete, and finding them
+    for sql in [
+        "DELETE FROM edges WHERE dest = ?1",
+        "SELECT source, type FROM edges WHERE dest = ?1 AND hidden = 0
+         ORDER BY type ASC, source ASC, discriminator ASC",
+    ] {
+        let plan = query_plan(&conn, sql);
+        assert!(plan.contains("edges_by_dest"), "{}: {}", sql, plan);
+        assert!(!plan.contains("TEMP B-TREE"), "{}: {}", sql, plan);
+    }
+}
@@ -1,12 +1,19 @@
+use ents::cdc::CapturedChange;
+use ents::dangling::IncomingEdgePolicy;
+use ents::decode::{DynamicEnt, UnknownTypePolicy};
+use ents::format::JsonFormat;
+use ents::ids::ScriptedIds;
+use ents::watch::{ChangeKind, EdgeChange, WatchHub};
 use ents::{
-    DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
-    EntExt as _, EntMutationError, EntWithEdges, Id, NullEdgeProvider,
-    QueryEdge, Transactional,
+    DatabaseError, DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue,
+    Ent, EntExt as _, EntMutationError, EntWithEdges, Id, NullEdgeProvider,
+    QueryEdge, ReadTransactional, Transactional,
 };
-use ents_sqlite::Txn;
+use ents_sqlite::{changes_since, trim_changes, ReadTxn, Txn};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Clone, Serialize, Deserialize)]
 struct TestEntity {
@@ -250,22 +257,7 @@ impl TestCityBuilder {
 fn setup_test_db() -> Pool<SqliteConnectionManager> {
     let pool = Pool::new(SqliteConnectionManager::memory()).unwrap();
     let conn = pool.get().unwrap();
-    conn.execute_batch(
-        r#"
-CREATE TABLE IF NOT EXISTS entities (
-   id INTEGER PRIMARY KEY,
-   type TEXT NOT NULL,
-   data TEXT NOT NULL
-);
-CREATE TABLE IF NOT EXISTS edges (
-   source INTEGER NOT NULL,
-   type TEXT NOT NULL,
-   dest INTEGER NOT NULL,
-   PRIMARY KEY (source, type, dest)
-);
-"#,
-    )
-    .unwrap();
+    ents_sqlite::init_schema(&conn).unwrap();
     pool
 }
 
@@ -567,3 +559,556 @@ fn test_update4_edge_change() {
     assert_eq!(edges.len(), 1);
     assert_eq!(edges[0].dest, city2_id);
 }
+
+#[test]
+fn test_graph_stats() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let tx = conn.transaction().unwrap();
+    let txn = Txn::new(tx);
+
+    let city_id = txn
+        .create(
+            TestCity::build()
+                .name("Seoul".to_string())
+                .finish()
+                .unwrap(),
+        )
+        .unwrap();
+    for name in ["Alice", "Bob"] {
+        let person = TestPerson::build()
+            .name(name.to_string())
+            .lives_in_link(city_id)
+            .finish()
+            .unwrap();
+        txn.create(person).unwrap();
+    }
+
+    let stats = txn.graph_stats(1).unwrap();
+    assert_eq!(stats.entity_count, 3);
+    assert_eq!(stats.edge_count, 2);
+    assert_eq!(stats.edges_by_name["lives_in"], 2);
+    assert_eq!(stats.out_degree_histogram[&1], 2);
+    assert_eq!(stats.in_degree_histogram[&2], 1);
+    assert_eq!(stats.top_hubs[0].id, city_id);
+
+    let report = serde_json::to_value(&stats).unwrap();
+    assert_eq!(report["edge_count"], 2);
+}
+
+#[test]
+fn test_sampling() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let tx = conn.transaction().unwrap();
+    let txn = Txn::new(tx);
+
+    let city_id = txn
+        .create(
+            TestCity::build()
+                .name("Seoul".to_string())
+                .finish()
+                .unwrap(),
+        )
+        .unwrap();
+    for i in 0..20 {
+        let person = TestPerson::build()
+            .name(format!("person_{}", i))
+            .lives_in_link(city_id)
+            .finish()
+            .unwrap();
+        txn.create(person).unwrap();
+    }
+
+    let people = txn.sample_entities("TestPerson", 5, 7).unwrap();
+    assert_eq!(people.len(), 5);
+    assert!(people.iter().all(|p| p.is::<TestPerson>()));
+    let ids: Vec<Id> = people.iter().map(|p| p.id()).collect();
+    let again: Vec<Id> = txn
+        .sample_entities("TestPerson", 5, 7)
+        .unwrap()
+        .iter()
+        .map(|p| p.id())
+        .collect();
+    assert_eq!(ids, again);
+
+    let cities = txn.sample_entities("TestCity", 5, 7).unwrap();
+    assert_eq!(cities.len(), 1);
+    assert_eq!(cities[0].id(), city_id);
+
+    let edges = txn.sample_edges(b"lives", 3, 7).unwrap();
+    assert_eq!(edges.len(), 3);
+    assert!(edges.iter().all(|e| e.dest == city_id));
+    assert!(txn.sample_edges(b"works_at", 3, 7).unwrap().is_empty());
+}
+
+#[test]
+fn test_watch() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let hub = Arc::new(WatchHub::new());
+    let entities = hub.watch_type::<TestEntity>();
+
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    let id = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    txn.create(
+        TestCity::build()
+            .name("Seoul".to_string())
+            .finish()
+            .unwrap(),
+    )
+    .unwrap();
+    // Nothing is delivered before commit
+    assert!(entities.try_recv().is_err());
+    txn.commit().unwrap();
+
+    let changes: Vec<_> = entities.try_iter().collect();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].id, id);
+    assert_eq!(changes[0].kind, ChangeKind::Created);
+
+    let one = hub.watch(id);
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    let mut ent = txn.get(id).unwrap().unwrap().into_ent::<TestEntity>();
+    assert!(txn
+        .update(ent.as_mut().unwrap(), |e: &mut TestEntity| e.value = 7)
+        .unwrap());
+    txn.delete::<TestEntity>(id).unwrap();
+    txn.commit().unwrap();
+
+    let kinds: Vec<_> = one.try_iter().map(|c| c.kind).collect();
+    assert_eq!(kinds, vec![ChangeKind::Updated, ChangeKind::Deleted]);
+    assert_eq!(entities.try_iter().count(), 2);
+
+    // Rolled back writes are never delivered
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    txn.create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    drop(txn);
+    assert!(entities.try_recv().is_err());
+}
+
+#[test]
+fn test_watch_edges() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let hub = Arc::new(WatchHub::new());
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    let b = txn
+        .create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let follows = hub.watch_edges(a, b"follows");
+    let edge = EdgeValue::new(a, b"follows".to_vec(), b);
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    txn.create_edge(edge.clone()).unwrap();
+    txn.create_edge(EdgeValue::new(a, b"likes".to_vec(), b))
+        .unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![EdgeChange::Added(edge.clone())]
+    );
+
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    assert!(txn.hide_edge(&edge).unwrap());
+    // Hiding a hidden edge or restoring a visible one reports nothing
+    assert!(txn.hide_edge(&edge).unwrap());
+    assert!(txn.restore_edge(&edge).unwrap());
+    assert!(txn.restore_edge(&edge).unwrap());
+    // Deleting the destination removes the edge
+    txn.delete::<TestEntity>(b).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![
+            EdgeChange::Removed(edge.clone()),
+            EdgeChange::Added(edge.clone()),
+            EdgeChange::Removed(edge),
+        ]
+    );
+}
+
+#[test]
+fn test_recreate_edge() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let hub = Arc::new(WatchHub::new());
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    let b = txn
+        .create(TestEntity::build().name("b".to_string()).finish().unwrap())
+        .unwrap();
+    let edge = EdgeValue::new(a, b"follows".to_vec(), b);
+    txn.create_edge(edge.clone()).unwrap();
+    txn.commit().unwrap();
+
+    let follows = hub.watch_edges(a, b"follows");
+    let txn = Txn::with_watchers(conn.transaction().unwrap(), hub.clone());
+    // Re-creating a visible edge reports nothing
+    txn.create_edge(edge.clone()).unwrap();
+    assert!(txn.hide_edge(&edge).unwrap());
+    // Re-creating a hidden edge replaces its payload but keeps it hidden
+    txn.create_edge(edge.clone().with_payload(b"again".as_slice()))
+        .unwrap();
+    let names: &[&[u8]] = &[b"follows"];
+    assert!(txn.find_edges(a, EdgeQuery::asc(names)).unwrap().is_empty());
+    let hidden = txn
+        .find_edges(a, EdgeQuery::asc(names).include_hidden())
+        .unwrap();
+    assert!(hidden[0].hidden);
+    assert_eq!(hidden[0].payload, b"again");
+    txn.commit().unwrap();
+    assert_eq!(
+        follows.try_iter().collect::<Vec<_>>(),
+        vec![EdgeChange::Removed(edge)]
+    );
+}
+
+fn count_follows<R: ReadTransactional>(txn: &R, id: Id) -> usize {
+    assert!(txn.get(id).unwrap().is_some());
+    txn.find_edges(id, EdgeQuery::asc(&[b"follows"]))
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn test_read_txn() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    let a = txn
+        .create(TestEntity::build().name("a".to_string()).finish().unwrap())
+        .unwrap();
+    txn.create_edge(EdgeValue::new(a, b"follows".to_vec(), a))
+        .unwrap();
+    // Read helpers accept write transactions too
+    assert_eq!(count_follows(&txn, a), 1);
+    txn.commit().unwrap();
+
+    let reader = ReadTxn::new(conn.transaction().unwrap());
+    assert_eq!(count_follows(&reader, a), 1);
+    assert!(reader.get(a + 1).unwrap().is_none());
+}
+
+#[test]
+fn test_bulk_load() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let txn = Txn::new(conn.transaction().unwrap());
+
+    let entities = (1..=5).map(|i| {
+        let mut ent = TestEntity::build()
+            .name(format!("e{}", i))
+            .value(i)
+            .finish()
+            .unwrap();
+        ent.set_id(i as Id);
+        Box::new(ent) as Box<dyn Ent>
+    });
+    let edges = (1..5).map(|i| EdgeValue::new(i, b"next".to_vec(), i + 1));
+    let report = txn.bulk_load(entities, edges).unwrap();
+    assert_eq!((report.entities, report.edges), (5, 4));
+
+    let third = txn.get(3).unwrap().unwrap();
+    assert_eq!(third.into_ent::<TestEntity>().unwrap().value, 3);
+    let next = txn.find_edges(3, EdgeQuery::asc(&[b"next"])).unwrap();
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].dest, 4);
+    assert_eq!(
+        txn.list_ids_by_type("TestEntity", None, 10).unwrap().len(),
+        5
+    );
+
+    // Later creates get ids past the loaded ones
+    let id = txn
+        .create(
+            TestEntity::build()
+                .name("new".to_string())
+                .finish()
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(id > 5);
+}
+
+#[test]
+fn test_json_format() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_json_format(JsonFormat::new().with_pretty(true));
+    let ent = TestEntity::build()
+        .name("pretty".to_string())
+        .finish()
+        .unwrap();
+    let id = txn.create(ent).unwrap();
+    txn.commit().unwrap();
+
+    let stored: String = conn
+        .query_row(
+            "SELECT data FROM entities WHERE id = ?1",
+            [id as i64],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(stored.contains("\n  \"name\": \"pretty\""), "{}", stored);
+
+    // JSON functions still see the fields
+    let name: String = conn
+        .query_row(
+            "SELECT JSON_EXTRACT(data, '$.name') FROM entities WHERE id = ?1",
+            [id as i64],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(name, "pretty");
+}
+
+#[test]
+fn test_id_provider() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_id_provider(Arc::new(ScriptedIds::new([7, 3])));
+    let new = || TestEntity::build().name("x".to_string()).finish().unwrap();
+    assert_eq!(txn.create(new()).unwrap(), 7);
+    assert_eq!(txn.create(new()).unwrap(), 3);
+    assert!(txn.create(new()).is_err());
+    txn.commit().unwrap();
+
+    // Without a provider, ids continue past the largest one
+    let txn = Txn::new(conn.transaction().unwrap());
+    assert_eq!(txn.create(new()).unwrap(), 8);
+}
+
+#[test]
+fn test_unknown_types() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    conn.execute(
+        "INSERT INTO entities (id, type, data) VALUES (5, 'Martian', ?1)",
+        [r#"{"type":"Martian","id":5,"legs":7}"#],
+    )
+    .unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    assert!(matches!(
+        txn.get(5),
+        Err(DatabaseError::UnknownType { name }) if name == "Martian"
+    ));
+    assert!(txn.sample_entities("Martian", 10, 0).is_err());
+    txn.commit().unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_unknown_types(UnknownTypePolicy::Skip);
+    assert!(txn.get(5).unwrap().is_none());
+    assert!(txn.sample_entities("Martian", 10, 0).unwrap().is_empty());
+    txn.commit().unwrap();
+
+    let reader = ReadTxn::new(conn.transaction().unwrap())
+        .with_unknown_types(UnknownTypePolicy::Lenient);
+    let ent = reader.get(5).unwrap().unwrap();
+    let dynamic = ent.as_ent::<DynamicEnt>().unwrap();
+    assert_eq!((dynamic.id, dynamic.type_name.as_str()), (5, "Martian"));
+}
+
+#[test]
+fn test_incoming_edge_policies() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let new_entity = |name: &str| {
+        TestEntity::build()
+            .name(name.to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    let source = txn.create(new_entity("source")).unwrap();
+    let dest = txn.create(new_entity("dest")).unwrap();
+    txn.create_edge(EdgeValue::new(source, b"points".to_vec(), dest))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_incoming_edges(IncomingEdgePolicy::Restrict);
+    assert!(txn.delete::<TestEntity>(dest).is_err());
+    assert!(txn.get(dest).unwrap().is_some());
+    drop(txn);
+
+    let txn = Txn::new(conn.transaction().unwrap())
+        .with_incoming_edges(IncomingEdgePolicy::Orphan);
+    assert!(txn.find_dangling_edges(10).unwrap().is_empty());
+    txn.delete::<TestEntity>(dest).unwrap();
+    let dangling = txn.find_dangling_edges(10).unwrap();
+    assert_eq!(dangling.len(), 1);
+    assert_eq!(
+        (
+            dangling[0].source,
+            dangling[0].sort_key.as_slice(),
+            dangling[0].dest
+        ),
+        (source, &b"points"[..], dest)
+    );
+    assert_eq!(txn.remove_dangling_edges().unwrap(), 1);
+    assert!(txn.find_dangling_edges(10).unwrap().is_empty());
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_edge_integrity() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let new_entity = |name: &str| {
+        TestEntity::build()
+            .name(name.to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    let txn = Txn::new(conn.transaction().unwrap()).with_edge_integrity(true);
+    let source = txn.create(new_entity("source")).unwrap();
+    let dest = txn.create(new_entity("dest")).unwrap();
+    txn.create_edge(EdgeValue::new(source, b"points".to_vec(), dest))
+        .unwrap();
+
+    let missing = |edge: EdgeValue| match txn.create_edge(edge) {
+        Err(DatabaseError::Other { source }) => {
+            source.downcast::<DraftError>().ok().map(|e| *e)
+        }
+        _ => None,
+    };
+    let gone = dest + 1000;
+    assert!(matches!(
+        missing(EdgeValue::new(source, b"points".to_vec(), gone)),
+        Some(DraftError::DestNotFound(id)) if id == gone
+    ));
+    assert!(matches!(
+        missing(EdgeValue::new(gone, b"points".to_vec(), dest)),
+        Some(DraftError::SourceNotFound(id)) if id == gone
+    ));
+    assert_eq!(txn.find_dangling_edges(10).unwrap().len(), 0);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_change_capture() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let new_entity = |name: &str| {
+        TestEntity::build()
+            .name(name.to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    let txn = Txn::new(conn.transaction().unwrap()).with_change_capture(true);
+    let source = txn.create(new_entity("source")).unwrap();
+    let dest = txn.create(new_entity("dest")).unwrap();
+    txn.create_edge(EdgeValue::new(source, b"points".to_vec(), dest))
+        .unwrap();
+    txn.commit().unwrap();
+
+    // Transactions without capture leave the log alone
+    let txn = Txn::new(conn.transaction().unwrap());
+    txn.create(new_entity("other")).unwrap();
+    txn.commit().unwrap();
+
+    let txn = Txn::new(conn.transaction().unwrap()).with_change_capture(true);
+    txn.delete::<TestEntity>(dest).unwrap();
+    txn.commit().unwrap();
+
+    let records = changes_since(&conn, 0, 10).unwrap();
+    let seqs: Vec<u64> = records.iter().map(|r| r.seq).collect();
+    assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+    assert!(matches!(
+        &records[2].change,
+        CapturedChange::EdgeAdded(e) if (e.source, e.dest) == (source, dest)
+    ));
+    assert!(matches!(
+        &records[3].change,
+        CapturedChange::EdgeRemoved(e) if (e.source, e.dest) == (source, dest)
+    ));
+    assert!(
+        matches!(&records[4].change, CapturedChange::Deleted(e) if e.id == dest)
+    );
+    assert_eq!(changes_since(&conn, 3, 10).unwrap(), records[3..]);
+
+    // Trimmed sequence numbers are not reused
+    assert_eq!(trim_changes(&conn, 5).unwrap(), 5);
+    let txn = Txn::new(conn.transaction().unwrap()).with_change_capture(true);
+    txn.delete::<TestEntity>(source).unwrap();
+    txn.commit().unwrap();
+    let records = changes_since(&conn, 0, 10).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].seq, 6);
+}
+
+#[test]
+fn test_on_commit() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let fired = std::cell::RefCell::new(Vec::new());
+    let new_entity = || {
+        TestEntity::build()
+            .name("notify".to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    // Rolled back transactions drop their callbacks
+    let txn = Txn::new(conn.transaction().unwrap());
+    txn.create(new_entity()).unwrap();
+    txn.on_commit(|| fired.borrow_mut().push("rolled back"));
+    drop(txn);
+    assert!(fired.borrow().is_empty());
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    txn.create(new_entity()).unwrap();
+    txn.on_commit(|| fired.borrow_mut().push("first"));
+    txn.on_commit(|| fired.borrow_mut().push("second"));
+    assert!(fired.borrow().is_empty());
+    txn.commit().unwrap();
+    assert_eq!(*fired.borrow(), vec!["first", "second"]);
+}
+
+#[test]
+fn test_soft_deleted_ids_are_not_reused() {
+    let pool = setup_test_db();
+    let mut conn = pool.get().unwrap();
+    let new_entity = |name: &str| {
+        TestEntity::build()
+            .name(name.to_string())
+            .value(1)
+            .finish()
+            .unwrap()
+    };
+
+    let txn = Txn::new(conn.transaction().unwrap());
+    txn.create(new_entity("first")).unwrap();
+    let last = txn.create(new_entity("last")).unwrap();
+    assert!(txn.soft_delete::<TestEntity>(last).unwrap());
+    let created = txn.create(new_entity("created")).unwrap();
+    assert!(created > last);
+
+    assert!(txn.restore::<TestEntity>(last).unwrap());
+    let restored = txn.get_as::<TestEntity>(last).unwrap().unwrap();
+    assert_eq!(restored.name, "last");
+    let created = txn.get_as::<TestEntity>(created).unwrap().unwrap();
+    assert_eq!(created.name, "created");
+    txn.commit().unwrap();
+}
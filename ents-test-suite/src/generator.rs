@@ -0,0 +1,234 @@
+//! Synthetic graph generator for benchmarks and capacity planning.
+//!
+//! Populates a store with users, tags and posts using the test entities of
+//! this crate, plus `follows` edges between users whose fan-out follows a
+//! configurable distribution. Generation is deterministic for a given seed,
+//! and writes are committed in batches through a [`TestCaseRunner`].
+
+use std::collections::HashSet;
+
+use ents::{EdgeValue, Id, Transactional};
+use rand::distr::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{Post, Tag, TestCaseRunner, User};
+
+/// Sort key of the edges between users
+pub const FOLLOWS_EDGE: &[u8] = b"follows";
+
+/// Distribution of the number of users each user follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanOut {
+    /// Every user follows exactly this many others
+    Fixed(usize),
+    /// Each user follows a uniformly random number of others in `min..=max`
+    Uniform { min: usize, max: usize },
+    /// Each user follows this many earlier users, chosen proportionally to
+    /// their degree so far (Barabási–Albert). Produces a power-law degree
+    /// distribution with a few large hubs.
+    PreferentialAttachment(usize),
+}
+
+/// Shape of the graph to generate
+#[derive(Debug, Clone)]
+pub struct GraphSpec {
+    pub users: usize,
+    pub tags: usize,
+    pub posts: usize,
+    pub follows: FanOut,
+    /// Maximum number of tags per post; each post gets `0..=tags_per_post`
+    pub tags_per_post: usize,
+    /// Size of the random content of each post, in bytes
+    pub payload_bytes: usize,
+    /// Number of writes per committed transaction
+    pub batch_size: usize,
+    pub seed: u64,
+}
+
+impl Default for GraphSpec {
+    fn default() -> Self {
+        Self {
+            users: 100,
+            tags: 10,
+            posts: 200,
+            follows: FanOut::PreferentialAttachment(3),
+            tags_per_post: 3,
+            payload_bytes: 256,
+            batch_size: 1000,
+            seed: 0,
+        }
+    }
+}
+
+/// Ids of everything written by [`generate`]
+#[derive(Debug, Clone, Default)]
+pub struct GeneratedGraph {
+    pub users: Vec<Id>,
+    pub tags: Vec<Id>,
+    pub posts: Vec<Id>,
+    pub follow_edges: usize,
+}
+
+/// Populate the store behind `runner` according to `spec`
+pub fn generate<C: TestCaseRunner>(
+    runner: &mut C,
+    spec: &GraphSpec,
+) -> anyhow::Result<GeneratedGraph> {
+    let mut rng = StdRng::seed_from_u64(spec.seed);
+    let batch_size = spec.batch_size.max(1);
+
+    let users = (0..spec.users)
+        .map(|i| {
+            User::new(format!("user_{}", i), format!("user_{}@example.com", i))
+        })
+        .collect();
+    let users = write_batches(runner, users, batch_size, |txn, user| {
+        Ok(txn.create(user)?)
+    })?;
+
+    let tags = (0..spec.tags)
+        .map(|i| Tag::new(format!("tag_{}", i), "gray".to_string()))
+        .collect();
+    let tags = write_batches(runner, tags, batch_size, |txn, tag| {
+        Ok(txn.create(tag)?)
+    })?;
+
+    let follows = follow_pairs(&mut rng, spec.follows, &users);
+    let follow_edges = follows.len();
+    write_batches(runner, follows, batch_size, |txn, (source, dest)| {
+        Ok(txn.create_edge(EdgeValue::new(
+            source,
+            FOLLOWS_EDGE.to_vec(),
+            dest,
+        ))?)
+    })?;
+
+    let mut posts = Vec::with_capacity(spec.posts);
+    if !users.is_empty() {
+        for i in 0..spec.posts {
+            let author_id = users[rng.random_range(0..users.len())];
+            let tag_count =
+                rng.random_range(0..=spec.tags_per_post.min(tags.len()));
+            let tag_ids =
+                rand::seq::index::sample(&mut rng, tags.len(), tag_count)
+                    .into_iter()
+                    .map(|idx| tags[idx])
+                    .collect();
+            let content = (&mut rng)
+                .sample_iter(Alphanumeric)
+                .take(spec.payload_bytes)
+                .map(char::from)
+                .collect();
+            posts.push(Post::new(
+                format!("post_{}", i),
+                content,
+                author_id,
+                tag_ids,
+            ));
+        }
+    }
+    let posts = write_batches(runner, posts, batch_size, |txn, post| {
+        Ok(txn.create(post)?)
+    })?;
+
+    Ok(GeneratedGraph {
+        users,
+        tags,
+        posts,
+        follow_edges,
+    })
+}
+
+/// Pick the `(follower, followee)` pairs of the follow graph
+fn follow_pairs(
+    rng: &mut StdRng,
+    fan_out: FanOut,
+    users: &[Id],
+) -> Vec<(Id, Id)> {
+    let mut pairs = Vec::new();
+    if users.len() < 2 {
+        return pairs;
+    }
+
+    match fan_out {
+        FanOut::Fixed(_) | FanOut::Uniform { .. } => {
+            for (i, &source) in users.iter().enumerate() {
+                let count = match fan_out {
+                    FanOut::Fixed(n) => n,
+                    FanOut::Uniform { min, max } => {
+                        rng.random_range(min..=max.max(min))
+                    }
+                    FanOut::PreferentialAttachment(_) => unreachable!(),
+                };
+                let count = count.min(users.len() - 1);
+                // Sample among the other users by skipping our own slot
+                for idx in rand::seq::index::sample(rng, users.len() - 1, count)
+                {
+                    let idx = if idx >= i { idx + 1 } else { idx };
+                    pairs.push((source, users[idx]));
+                }
+            }
+        }
+        FanOut::PreferentialAttachment(m) => {
+            // Every edge endpoint is listed once, so a uniform pick from this
+            // list is proportional to degree
+            let mut endpoints: Vec<usize> = Vec::new();
+            for i in 1..users.len() {
+                let count = m.min(i);
+                let mut chosen = HashSet::with_capacity(count);
+                while chosen.len() < count {
+                    // Mix in a uniform pick so nodes without edges can be
+                    // reached too
+                    let target = if endpoints.is_empty() || rng.random_bool(0.1)
+                    {
+                        rng.random_range(0..i)
+                    } else {
+                        endpoints[rng.random_range(0..endpoints.len())]
+                    };
+                    chosen.insert(target);
+                }
+                let mut chosen: Vec<usize> = chosen.into_iter().collect();
+                chosen.sort_unstable();
+                for target in chosen {
+                    pairs.push((users[i], users[target]));
+                    endpoints.push(i);
+                    endpoints.push(target);
+                }
+            }
+        }
+    }
+
+    pairs
+}
+
+/// Apply `write` to every item, committing a transaction every `batch_size`
+/// items
+fn write_batches<C, I, O, F>(
+    runner: &mut C,
+    items: Vec<I>,
+    batch_size: usize,
+    mut write: F,
+) -> anyhow::Result<Vec<O>>
+where
+    C: TestCaseRunner,
+    F: FnMut(&C::Tx, I) -> anyhow::Result<O>,
+{
+    let mut results = Vec::with_capacity(items.len());
+    let mut items = items.into_iter().peekable();
+
+    while items.peek().is_some() {
+        let batch: Vec<I> = items.by_ref().take(batch_size).collect();
+        let written = runner.execute(|txn| {
+            let written = batch
+                .into_iter()
+                .map(|item| write(&txn, item))
+                .collect::<anyhow::Result<Vec<O>>>()?;
+            txn.commit()?;
+            Ok(written)
+        })?;
+        results.extend(written);
+    }
+
+    Ok(results)
+}
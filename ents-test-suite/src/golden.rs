@@ -0,0 +1,148 @@
+//! Golden-file snapshots of the storage format.
+//!
+//! Each backend writes the same [`write_fixture`] store with fixed ids and a
+//! fixed clock, dumps what it stored (keys and values, rows and schema) as
+//! text, and compares the dump with a file checked into its `tests/golden`
+//! directory using [`check_golden`]. Any change to key encoding, value
+//! layout or schema then fails the test instead of silently producing
+//! stores older versions cannot read.
+//!
+//! An intended format change comes with a format version bump; regenerate
+//! the golden files with `UPDATE_GOLDEN=1 cargo test` and commit them with
+//! the change.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(dir.path(), None)?
+//!     .with_id_provider(SequentialIds::new(1));
+//! let txn = env.write_txn()?;
+//! golden::write_fixture(&txn)?;
+//! golden::commit_fixture(txn)?;
+//! golden::check_golden("tests/golden/store.txt", &dump(dir.path()))?;
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{ensure, Context};
+use ents::clock::{with_clock, MockClock};
+use ents::{EdgeValue, Transactional};
+
+use crate::{Folder, Tag, TestEntity, User, UserWithUniqueEmail};
+
+/// The time every fixture entity is written at, in microseconds
+pub const FIXTURE_MICROS: u64 = 1_700_000_000_000_000;
+
+/// Environment variable that rewrites golden files instead of comparing
+pub const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+/// Write the fixture store: a few entities of each kind, one of them
+/// updated and one soft-deleted, a unique key, and a plain, a parallel, a
+/// payload-carrying and a hidden edge. Needs a backend handing out sequential ids so the dump does
+/// not depend on the time.
+pub fn write_fixture<T: Transactional>(txn: &T) -> anyhow::Result<()> {
+    let clock = Arc::new(MockClock::new(FIXTURE_MICROS));
+    with_clock(clock, || {
+        let plain = txn.create(TestEntity::new("plain".to_string(), 42))?;
+        let mut ent = txn.get_required_as::<TestEntity>(plain)?;
+        txn.update(&mut ent, |e: &mut TestEntity| e.value = 43)?;
+        let alice = txn.create(User::new(
+            "alice".to_string(),
+            "alice@example.com".to_string(),
+        ))?;
+        let bob = txn.create(User::new(
+            "bob".to_string(),
+            "bob@example.com".to_string(),
+        ))?;
+        txn.create(UserWithUniqueEmail::new(
+            "carol".to_string(),
+            "carol@example.com".to_string(),
+        ))?;
+        let tag = txn.create(Tag::new("rust".to_string(), "#dea584".into()))?;
+        let folder = txn.create(Folder::new("docs".to_string()))?;
+
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), bob))?;
+        txn.create_edge(
+            EdgeValue::new(bob, b"follows".to_vec(), alice)
+                .with_payload(b"since 2023".to_vec()),
+        )?;
+        for discriminator in [1, 2] {
+            txn.create_edge(
+                EdgeValue::new(alice, b"liked".to_vec(), plain)
+                    .with_discriminator(discriminator),
+            )?;
+        }
+        let shared = EdgeValue::new(folder, b"shared_with".to_vec(), bob);
+        txn.create_edge(shared.clone())?;
+        txn.hide_edge(&shared)?;
+        txn.create_edge(EdgeValue::new(folder, b"tagged".to_vec(), tag))?;
+
+        let trashed = txn.create(TestEntity::new("trashed".to_string(), 7))?;
+        txn.soft_delete::<TestEntity>(trashed)?;
+        anyhow::Ok(())
+    })
+}
+
+/// Commit `txn` at [`FIXTURE_MICROS`] too, so the commit times in the
+/// change log do not depend on the time either
+pub fn commit_fixture<T: Transactional>(txn: T) -> anyhow::Result<()> {
+    let clock = Arc::new(MockClock::new(FIXTURE_MICROS));
+    with_clock(clock, || txn.commit())?;
+    Ok(())
+}
+
+/// Compare `actual` with the golden file at `path`, or overwrite the file
+/// when [`UPDATE_VAR`] is set
+pub fn check_golden(
+    path: impl AsRef<Path>,
+    actual: &str,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        return std::fs::write(path, actual)
+            .with_context(|| format!("writing {}", path.display()));
+    }
+    let expected = std::fs::read_to_string(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    if let Some((line, (want, got))) = expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .find(|(_, (want, got))| want != got)
+    {
+        anyhow::bail!(
+            "storage format differs from {} at line {}:\n  \
+             expected: {}\n  actual:   {}\n\
+             If the change is intended, bump the format version and rerun \
+             with {}=1",
+            path.display(),
+            line + 1,
+            want,
+            got,
+            UPDATE_VAR
+        );
+    }
+    ensure!(
+        expected.lines().count() == actual.lines().count(),
+        "storage format differs from {}: {} lines expected, {} written. \
+         If the change is intended, bump the format version and rerun with \
+         {}=1",
+        path.display(),
+        expected.lines().count(),
+        actual.lines().count(),
+        UPDATE_VAR
+    );
+    Ok(())
+}
+
+/// `bytes` as text if it is printable UTF-8, as hex otherwise
+pub fn display_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => {
+            format!("{:?}", text)
+        }
+        _ => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
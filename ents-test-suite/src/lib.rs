@@ -1,8 +1,34 @@
+pub mod generator;
+pub mod golden;
+pub mod scenario;
+pub mod sim;
 mod test_entity;
 
-pub use test_entity::{Post, Tag, TestEntity, User, UserWithUniqueEmail};
-
-use ents::{EdgeQuery, EntExt, Id, QueryEdge, Transactional};
+pub use test_entity::{
+    Folder, Place, Post, Tag, TestEntity, User, UserWithUniqueEmail,
+};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ents::branch::Branch;
+use ents::clock::{with_clock, MockClock};
+use ents::closure::ClosureTable;
+use ents::decorate::{ReadOnlyTxn, TxnMetrics, TxnOp, TxnStack};
+use ents::fixtures::FixtureLoader;
+use ents::geo::{self, BoundingBox, GeoPoint};
+use ents::hash::ent_hash;
+use ents::namespace::Namespace;
+use ents::observe::{EntObserver, Observers};
+use ents::tree::Tree;
+use ents::unique::UniqueKey;
+use ents::{
+    idempotency, metrics, rate_limit, timeline, workflow, DatabaseError,
+    EdgeQuery, EdgeValue, Ent, EntExt, EntityCursor, Id, QueryEdge,
+    ReadTransactional, Transactional,
+};
+use rand::Rng;
 
 pub trait TestCaseRunner {
     type Tx: Transactional;
@@ -34,9 +60,10 @@ pub fn test_basic_create<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
         let retrieved = txn.get(id)?;
         match retrieved {
             Some(ent) => {
-                let test_ent = ent.as_ent::<TestEntity>().ok_or_else(|| {
-                    anyhow::anyhow!("Entity is not TestEntity")
-                })?;
+                let test_ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
                 assert_eq!(test_ent.name, "test_create");
                 assert_eq!(test_ent.value, 42);
                 assert_eq!(test_ent.id, id);
@@ -154,45 +181,1779 @@ pub fn test_unique_constraints<R: TestSuiteRunner>(
 ) -> anyhow::Result<()> {
     println!("  Testing UNIQUE constraints...");
 
+    let email = |e: &str| UniqueKey::new("user_email", e.as_bytes());
+
     let mut runner1 = r.create()?;
-    let result = runner1.execute(|txn| {
-        // Create first user with unique email
-        let user1 = UserWithUniqueEmail::new("user1".to_string(), "unique@example.com".to_string());
-        let _user1_id = txn.create(user1)?;
-
-        // Try to create second user with same email - this should fail with UNIQUE constraint
-        let user2 = UserWithUniqueEmail::new("user2".to_string(), "unique@example.com".to_string());
-
-        // Currently UNIQUE constraints are not implemented, so this will succeed
-        // When UNIQUE constraints are implemented, this should return an error
-        match txn.create(user2) {
-            Ok(_) => {
-                // UNIQUE constraint not enforced - this is expected in current implementation
-                println!("    Note: UNIQUE constraints not yet implemented - duplicate email allowed");
+    let (user1, user3) = runner1.execute(|txn| {
+        let user1 = txn.create(UserWithUniqueEmail::new(
+            "user1".to_string(),
+            "unique@example.com".to_string(),
+        ))?;
+
+        // A second user with the same email is rejected
+        let err = txn
+            .create(UserWithUniqueEmail::new(
+                "user2".to_string(),
+                "unique@example.com".to_string(),
+            ))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ents::DatabaseError::UniqueViolation { existing, .. }
+                if existing == user1
+        ));
+
+        let user3 = txn.create(UserWithUniqueEmail::new(
+            "user3".to_string(),
+            "other@example.com".to_string(),
+        ))?;
+        txn.commit()?;
+        Ok((user1, user3))
+    })?;
+
+    let mut runner2 = r.create()?;
+    runner2.execute(|txn| {
+        assert_eq!(txn.find_unique(&email("unique@example.com"))?, Some(user1));
+
+        // Updating onto a held key fails and leaves the entity unchanged
+        let user = txn.get_required_as::<UserWithUniqueEmail>(user3)?;
+        let err = txn
+            .update(user, |u: &mut UserWithUniqueEmail| {
+                u.email = "unique@example.com".to_string()
+            })
+            .unwrap_err();
+        assert!(matches!(err, ents::DatabaseError::UniqueViolation { .. }));
+        assert_eq!(
+            txn.get_required_as::<UserWithUniqueEmail>(user3)?.email,
+            "other@example.com"
+        );
+
+        // Changing the email moves the key and releases the old one
+        let user = txn.get_required_as::<UserWithUniqueEmail>(user1)?;
+        assert!(txn.update(user, |u: &mut UserWithUniqueEmail| {
+            u.email = "new@example.com".to_string()
+        })?);
+        assert_eq!(txn.find_unique(&email("unique@example.com"))?, None);
+        assert_eq!(txn.find_unique(&email("new@example.com"))?, Some(user1));
+
+        // A delete naming another type is refused and keeps the key
+        assert!(txn.delete::<User>(user3).is_err());
+        assert_eq!(txn.find_unique(&email("other@example.com"))?, Some(user3));
+
+        // Deleting releases the key
+        txn.delete::<UserWithUniqueEmail>(user3)?;
+        assert_eq!(txn.find_unique(&email("other@example.com"))?, None);
+        txn.create(UserWithUniqueEmail::new(
+            "user4".to_string(),
+            "other@example.com".to_string(),
+        ))?;
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_timeline_edges<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing timeline edges...");
+
+    let mut runner = r.create()?;
+    let (user_id, item_ids) = runner.execute(|txn| {
+        let user_id = txn.create(User::new(
+            "feeder".to_string(),
+            "feeder@example.com".to_string(),
+        ))?;
+        let mut item_ids = Vec::new();
+        for i in 0..5 {
+            let item_id =
+                txn.create(TestEntity::new(format!("item_{}", i), i))?;
+            txn.create_edge(timeline::timeline_edge(
+                user_id,
+                b"posted",
+                1000 + i as u64 * 10,
+                item_id,
+            ))?;
+            item_ids.push(item_id);
+        }
+        // Unrelated edges must not leak into the timeline
+        txn.create_edge(ents::EdgeValue::new(
+            user_id,
+            b"posted".to_vec(),
+            item_ids[0],
+        ))?;
+        txn.create_edge(ents::EdgeValue::new(
+            user_id,
+            b"postedx".to_vec(),
+            item_ids[0],
+        ))?;
+        txn.commit()?;
+        Ok((user_id, item_ids))
+    })?;
+
+    let mut runner2 = r.create()?;
+    runner2.execute(|txn| {
+        let latest = timeline::latest(&txn, user_id, b"posted", 2)?;
+        let dests: Vec<Id> = latest.iter().map(|e| e.dest).collect();
+        assert_eq!(dests, vec![item_ids[4], item_ids[3]]);
+        assert_eq!(
+            timeline::parse_timeline_key(b"posted", &latest[0].sort_key),
+            Some(1040)
+        );
+
+        let range = timeline::between(&txn, user_id, b"posted", 1010, 1030)?;
+        let dests: Vec<Id> = range.iter().map(|e| e.dest).collect();
+        assert_eq!(dests, vec![item_ids[3], item_ids[2], item_ids[1]]);
+
+        let all = timeline::latest(&txn, user_id, b"posted", 100)?;
+        assert_eq!(all.len(), 5);
+
+        let next =
+            timeline::page(&txn, user_id, b"posted", Some(&latest[1]), 10)?;
+        let dests: Vec<Id> = next.iter().map(|e| e.dest).collect();
+        assert_eq!(dests, vec![item_ids[2], item_ids[1], item_ids[0]]);
+
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_generated_graph<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing synthetic graph generation...");
+
+    let spec = generator::GraphSpec {
+        users: 30,
+        tags: 5,
+        posts: 20,
+        follows: generator::FanOut::PreferentialAttachment(2),
+        tags_per_post: 2,
+        payload_bytes: 64,
+        batch_size: 7,
+        seed: 42,
+    };
+    let mut runner = r.create()?;
+    let graph = generator::generate(&mut runner, &spec)?;
+
+    assert_eq!(graph.users.len(), 30);
+    assert_eq!(graph.tags.len(), 5);
+    assert_eq!(graph.posts.len(), 20);
+    // The first user has nobody to follow, the second only the first
+    assert_eq!(graph.follow_edges, 1 + 28 * 2);
+
+    let mut runner2 = r.create()?;
+    runner2.execute(|txn| {
+        let mut follows = 0;
+        for &user in &graph.users {
+            let edges = txn
+                .find_edges(user, EdgeQuery::asc(&[generator::FOLLOWS_EDGE]))?;
+            assert!(edges.iter().all(|e| e.dest != user));
+            follows += edges.len();
+        }
+        assert_eq!(follows, graph.follow_edges);
+
+        for &post_id in &graph.posts {
+            let post = txn.get(post_id)?.expect("post should exist");
+            let post = post.downcast_ent::<Post>().expect("should be a Post");
+            assert_eq!(post.content.len(), 64);
+            assert!(graph.users.contains(&post.author_id));
+            assert!(post.tag_ids.len() <= 2);
+        }
+
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+/// Model-based test: random creates, updates and deletes spread over virtual
+/// time must leave the store agreeing with an in-memory model, for every seed.
+pub fn test_simulated_workload<R: TestSuiteRunner>(
+    r: &R,
+) -> anyhow::Result<()> {
+    println!("  Testing simulated workload...");
+
+    #[derive(Clone, Copy)]
+    enum Op {
+        Create,
+        Update,
+        Delete,
+    }
+
+    sim::run_seeds(0..5, |seed| {
+        let mut sim = sim::Simulation::new(seed);
+        sim.schedule_at(0, Op::Create);
+        for _ in 0..50 {
+            let delay =
+                Duration::from_millis(sim.rng().random_range(0..10_000));
+            let op = match sim.rng().random_range(0..3) {
+                0 => Op::Create,
+                1 => Op::Update,
+                _ => Op::Delete,
+            };
+            sim.schedule_in(delay, op);
+        }
+
+        let mut model: BTreeMap<Id, i32> = BTreeMap::new();
+        let mut deleted = Vec::new();
+        let mut runner = r.create()?;
+        sim.run(|sim, op| {
+            let value = (sim.clock().now_micros() / 1000) as i32;
+            let target = if model.is_empty() {
+                None
+            } else {
+                let idx = sim.rng().random_range(0..model.len());
+                model.keys().nth(idx).copied()
+            };
+
+            match (op, target) {
+                (Op::Update, Some(id)) => {
+                    runner.execute(|txn| {
+                        let ent = txn
+                            .get(id)?
+                            .and_then(|e| e.downcast_ent::<TestEntity>())
+                            .ok_or_else(|| anyhow::anyhow!("{} missing", id))?;
+                        let updated =
+                            txn.update(ent, |e: &mut TestEntity| {
+                                e.value = value;
+                            })?;
+                        assert!(updated, "Update should succeed");
+                        txn.commit()?;
+                        Ok(())
+                    })?;
+                    model.insert(id, value);
+                }
+                (Op::Delete, Some(id)) => {
+                    runner.execute(|txn| {
+                        txn.delete::<TestEntity>(id)?;
+                        txn.commit()?;
+                        Ok(())
+                    })?;
+                    model.remove(&id);
+                    deleted.push(id);
+                }
+                // Creates, and anything scheduled while the model is empty
+                _ => {
+                    let id = runner.execute(|txn| {
+                        let id = txn.create(TestEntity::new(
+                            format!("sim_{}", seed),
+                            value,
+                        ))?;
+                        txn.commit()?;
+                        Ok(id)
+                    })?;
+                    // Backends may reuse the ids of deleted entities
+                    deleted.retain(|&d| d != id);
+                    model.insert(id, value);
+                }
             }
-            Err(e) => {
-                // UNIQUE constraint is enforced - this would be the desired behavior
-                println!("    UNIQUE constraint enforced - duplicate email rejected: {}", e);
-                return Err(anyhow::anyhow!("UNIQUE constraint test should pass when implemented"));
+            Ok(())
+        })?;
+
+        runner.execute(|txn| {
+            for (&id, &value) in &model {
+                let ent = txn.get(id)?.ok_or_else(|| {
+                    anyhow::anyhow!("entity {} should exist", id)
+                })?;
+                let ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
+                assert_eq!(ent.value, value);
             }
-        }
+            for &id in &deleted {
+                assert!(txn.get(id)?.is_none(), "{} should be deleted", id);
+            }
+            txn.commit()?;
+            Ok(())
+        })
+    })
+}
+
+pub fn test_workflow_log<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing workflow log...");
+
+    let steps = ["step_0", "step_1", "step_2"];
 
+    // First attempt commits one step, then "crashes" before the second
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let mut wf = workflow::begin_workflow(&txn, "import")?;
+        assert!(wf.completed_steps.is_empty());
+        txn.create(TestEntity::new(steps[0].to_string(), 0))?;
+        workflow::checkpoint(&txn, &mut wf, steps[0])?;
         txn.commit()?;
         Ok(())
-    });
+    })?;
+    runner.execute(|txn| {
+        let mut wf = workflow::begin_workflow(&txn, "import")?;
+        txn.create(TestEntity::new(steps[1].to_string(), 1))?;
+        workflow::checkpoint(&txn, &mut wf, steps[1])?;
+        // Dropped without commit
+        Ok(())
+    })?;
 
-    // For now, we expect this test to "pass" since UNIQUE constraints aren't implemented
-    // When UNIQUE constraints are implemented, this test should be updated to expect failure
-    match result {
-        Ok(_) => {
-            println!("    UNIQUE constraint test completed (constraints not yet implemented)");
+    // Resuming skips the committed step and finishes the rest
+    let mut created = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        runner.execute(|txn| {
+            let mut wf = workflow::begin_workflow(&txn, "import")?;
+            if wf.is_done(step) {
+                return Ok(());
+            }
+            created
+                .push(txn.create(TestEntity::new(step.to_string(), i as i32))?);
+            workflow::checkpoint(&txn, &mut wf, step)?;
+            // Checkpointing twice is a no-op
+            workflow::checkpoint(&txn, &mut wf, step)?;
+            txn.commit()?;
             Ok(())
+        })?;
+    }
+    assert_eq!(created.len(), 2);
+
+    runner.execute(|txn| {
+        let mut wf = workflow::find_workflow(&txn, "import")?
+            .ok_or_else(|| anyhow::anyhow!("workflow should exist"))?;
+        assert_eq!(wf.completed_steps, steps);
+        assert_eq!(wf.last_step(), Some("step_2"));
+        assert!(!wf.completed);
+        workflow::complete(&txn, &mut wf)?;
+        txn.commit()?;
+        Ok(())
+    })?;
+
+    runner.execute(|txn| {
+        let wf = workflow::begin_workflow(&txn, "import")?;
+        assert!(wf.completed);
+        assert!(workflow::find_workflow(&txn, "other")?.is_none());
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_idempotency_keys<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing idempotency keys...");
+
+    fn create<T: Transactional>(txn: &T) -> Result<Id, ents::DatabaseError> {
+        idempotency::with_idempotency_key(txn, "req-1", |txn| {
+            txn.create(TestEntity::new("idempotent".to_string(), 1))
+        })
+    }
+
+    let mut runner = r.create()?;
+    let first = runner.execute(|txn| {
+        let id = create(&txn)?;
+        txn.commit()?;
+        Ok(id)
+    })?;
+
+    // A retried request gets the original result without writing again
+    runner.execute(|txn| {
+        assert_eq!(create(&txn)?, first);
+        let calls = std::cell::Cell::new(0);
+        let other: Vec<String> =
+            idempotency::with_idempotency_key(&txn, "req-2", |_| {
+                calls.set(calls.get() + 1);
+                Ok(vec!["a".to_string()])
+            })?;
+        assert_eq!(other, vec!["a".to_string()]);
+        let other: Vec<String> =
+            idempotency::with_idempotency_key(&txn, "req-2", |_| {
+                calls.set(calls.get() + 1);
+                Ok(vec![])
+            })?;
+        assert_eq!(other, vec!["a".to_string()]);
+        assert_eq!(calls.get(), 1);
+        txn.commit()?;
+        Ok(())
+    })?;
+
+    // Expired keys are forgotten, so the operation runs again
+    runner.execute(|txn| {
+        assert_eq!(idempotency::expire_before(&txn, u64::MAX)?, 2);
+        assert!(idempotency::find_idempotency_key(&txn, "req-1")?.is_none());
+        let second = create(&txn)?;
+        assert_ne!(second, first);
+        assert_eq!(idempotency::expire_before(&txn, 1)?, 0);
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_edge_namespaces<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing edge namespaces...");
+
+    const BILLING: Namespace = Namespace::new("billing");
+    const CHAT: Namespace = Namespace::new("chat");
+
+    let mut runner = r.create()?;
+    let (group, alice, bob) = runner.execute(|txn| {
+        let group = txn.create(TestEntity::new("group".to_string(), 0))?;
+        let alice = txn.create(TestEntity::new("alice".to_string(), 1))?;
+        let bob = txn.create(TestEntity::new("bob".to_string(), 2))?;
+        txn.commit()?;
+        Ok((group, alice, bob))
+    })?;
+
+    // The same edge name in two modules does not collide
+    runner.execute(|txn| {
+        let txn = BILLING.wrap(txn);
+        txn.create_edge(EdgeValue::new(group, b"member".to_vec(), alice))?;
+        txn.commit()?;
+        Ok(())
+    })?;
+    runner.execute(|txn| {
+        let txn = CHAT.wrap(txn);
+        txn.create_edge(EdgeValue::new(group, b"member".to_vec(), bob))?;
+        txn.create_edge(EdgeValue::new(group, b"muted".to_vec(), alice))?;
+        txn.commit()?;
+        Ok(())
+    })?;
+
+    runner.execute(|txn| {
+        let billing = BILLING.wrap(txn);
+        let members =
+            billing.find_edges(group, EdgeQuery::asc(&[b"member"]))?;
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].dest, alice);
+        assert_eq!(members[0].sort_key, b"member");
+
+        // Unfiltered queries only see the namespace's own edges
+        assert_eq!(billing.find_edges(group, EdgeQuery::asc(&[]))?.len(), 1);
+
+        let txn = billing.into_inner();
+        let chat = CHAT.wrap(txn);
+        let edges = chat.find_edges(group, EdgeQuery::asc(&[]))?;
+        let names: Vec<&[u8]> =
+            edges.iter().map(|e| e.sort_key.as_slice()).collect();
+        assert_eq!(names, vec![&b"member"[..], &b"muted"[..]]);
+        assert!(chat.hide_edge(&EdgeValue::new(
+            group,
+            b"muted".to_vec(),
+            alice
+        ))?);
+        assert_eq!(chat.find_edges(group, EdgeQuery::asc(&[]))?.len(), 1);
+
+        // The raw transaction sees the stored, prefixed names
+        let txn = chat.into_inner();
+        let raw =
+            txn.find_edges(group, EdgeQuery::asc(&[b"billing/member"]))?;
+        assert_eq!(raw.len(), 1);
+        assert!(txn
+            .find_edges(group, EdgeQuery::asc(&[b"member"]))?
+            .is_empty());
+        Ok(())
+    })
+}
+
+pub fn test_geo_queries<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing geo queries...");
+
+    let city_hall = GeoPoint::new(37.5663, 126.9779);
+    let mut runner = r.create()?;
+    let (gwanghwamun, gangnam) = runner.execute(|txn| {
+        // ~800m north of city hall
+        let gwanghwamun = txn.create(Place::new(
+            "gwanghwamun".to_string(),
+            37.5759,
+            126.9768,
+        ))?;
+        // ~9km south-east
+        let gangnam =
+            txn.create(Place::new("gangnam".to_string(), 37.4979, 127.0276))?;
+        txn.create(Place::new("busan".to_string(), 35.1796, 129.0756))?;
+        txn.commit()?;
+        Ok((gwanghwamun, gangnam))
+    })?;
+
+    runner.execute(|txn| {
+        let near = geo::within_radius::<Place, _>(&txn, city_hall, 2_000.0)?;
+        assert_eq!(near.len(), 1);
+        assert_eq!(near[0].0.id, gwanghwamun);
+        assert!((1_000.0..1_200.0).contains(&near[0].1), "{}", near[0].1);
+
+        let seoul = geo::within_radius::<Place, _>(&txn, city_hall, 20_000.0)?;
+        let ids: Vec<Id> = seoul.iter().map(|(p, _)| p.id).collect();
+        assert_eq!(ids, vec![gwanghwamun, gangnam]);
+
+        let bbox = BoundingBox::new(34.0, 128.0, 36.0, 130.0);
+        let south = geo::within_bbox::<Place, _>(&txn, bbox)?;
+        assert_eq!(south.len(), 1);
+        assert_eq!(south[0].name, "busan");
+
+        let world = BoundingBox::new(-90.0, -180.0, 90.0, 180.0);
+        assert!(geo::within_bbox::<Place, _>(&txn, world).is_err());
+        Ok(())
+    })?;
+
+    // Moving or deleting an entity updates the index
+    runner.execute(|txn| {
+        let mut place = txn
+            .get(gangnam)?
+            .and_then(|e| e.into_ent::<Place>())
+            .ok_or_else(|| anyhow::anyhow!("place should exist"))?;
+        assert!(txn.update(&mut place, |p: &mut Place| {
+            p.lat = 37.5700;
+            p.lng = 126.9800;
+        })?);
+        txn.delete::<Place>(gwanghwamun)?;
+        txn.commit()?;
+        Ok(())
+    })?;
+    runner.execute(|txn| {
+        let near = geo::within_radius::<Place, _>(&txn, city_hall, 2_000.0)?;
+        let ids: Vec<Id> = near.iter().map(|(p, _)| p.id).collect();
+        assert_eq!(ids, vec![gangnam]);
+        Ok(())
+    })
+}
+
+pub fn test_incoming_edges<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing incoming edges...");
+
+    let mut runner = r.create()?;
+    let (tag, posts) = runner.execute(|txn| {
+        let tag = txn.create(TestEntity::new("tag".to_string(), 0))?;
+        let mut posts = Vec::new();
+        for i in 0..120 {
+            let post = txn.create(TestEntity::new(format!("post{}", i), i))?;
+            txn.create_edge(EdgeValue::new(post, b"tagged".to_vec(), tag))?;
+            posts.push(post);
         }
-        Err(e) => {
-            println!("    UNIQUE constraint test failed unexpectedly: {}", e);
-            Err(e)
+        txn.create_edge(EdgeValue::new(posts[0], b"pinned".to_vec(), tag))?;
+        txn.create_edge(EdgeValue::new(tag, b"tagged".to_vec(), posts[0]))?;
+        txn.commit()?;
+        Ok((tag, posts))
+    })?;
+
+    runner.execute(|txn| {
+        // Pages through the incoming edges in (name, source) order
+        let mut seen = Vec::new();
+        let mut page = txn.find_edges_to(tag, EdgeQuery::asc(&[b"tagged"]))?;
+        assert_eq!(page.len(), 100);
+        while !page.is_empty() {
+            seen.extend(page.iter().map(|e| e.source));
+            let last = page.last().cloned().unwrap();
+            page = txn.find_edges_to(
+                tag,
+                EdgeQuery::asc(&[b"tagged"])
+                    .with_cursor(ents::EdgeCursor::from_incoming_edge(&last)),
+            )?;
+        }
+        let mut expected = posts.clone();
+        expected.sort();
+        assert_eq!(seen, expected);
+
+        let all = txn.find_edges_to(tag, EdgeQuery::desc(&[]))?;
+        assert_eq!(all[0].sort_key, b"tagged");
+        assert_eq!(all[0].source, expected[expected.len() - 1]);
+        assert!(all.iter().all(|e| e.dest == tag));
+
+        let pinned = txn.find_edges_to(tag, EdgeQuery::asc(&[b"pinned"]))?;
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].source, posts[0]);
+
+        assert!(txn.hide_edge(&EdgeValue::new(
+            posts[0],
+            b"pinned".to_vec(),
+            tag
+        ))?);
+        assert!(txn
+            .find_edges_to(tag, EdgeQuery::asc(&[b"pinned"]))?
+            .is_empty());
+        let hidden = txn.find_edges_to(
+            tag,
+            EdgeQuery::asc(&[b"pinned"]).include_hidden(),
+        )?;
+        assert!(hidden[0].hidden);
+
+        let to_post = txn.find_edges_to(posts[0], EdgeQuery::asc(&[]))?;
+        assert_eq!(to_post.len(), 1);
+        assert_eq!(to_post[0].source, tag);
+        Ok(())
+    })
+}
+
+pub fn test_metric_series<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing metric series...");
+
+    use metrics::{MetricSeries, Rollup};
+    const VIEWS: MetricSeries = MetricSeries::new(
+        "views",
+        &[Rollup::new(60, 600), Rollup::new(3600, 86_400)],
+    );
+
+    let mut runner = r.create()?;
+    let post = runner.execute(|txn| {
+        let post = txn.create(TestEntity::new("post".to_string(), 0))?;
+        for at in [0, 30, 61, 3599, 3600] {
+            VIEWS.record(&txn, post, 1.0, at)?;
+        }
+        VIEWS.record(&txn, post, 5.0, 3610)?;
+        txn.commit()?;
+        Ok(post)
+    })?;
+
+    runner.execute(|txn| {
+        let minutes = VIEWS.range(&txn, post, 60, 0, u64::MAX)?;
+        let starts: Vec<u64> = minutes.iter().map(|b| b.start).collect();
+        // Buckets older than ten minutes before the newest are dropped
+        assert_eq!(starts, vec![3540, 3600]);
+        assert_eq!(minutes[1].count, 2);
+        assert_eq!(minutes[1].mean(), Some(3.0));
+
+        let hours = VIEWS.range(&txn, post, 3600, 0, u64::MAX)?;
+        let counts: Vec<(u64, u64)> =
+            hours.iter().map(|b| (b.start, b.count)).collect();
+        assert_eq!(counts, vec![(0, 4), (3600, 2)]);
+        assert_eq!(hours[1].max, 5.0);
+
+        assert_eq!(VIEWS.range(&txn, post, 3600, 1, 3600)?.len(), 1);
+        assert!(VIEWS.range(&txn, post, 120, 0, u64::MAX)?.is_empty());
+
+        assert_eq!(VIEWS.clear(&txn, post)?, 4);
+        assert!(VIEWS.range(&txn, post, 60, 0, u64::MAX)?.is_empty());
+        Ok(())
+    })
+}
+
+pub fn test_typed_get<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing typed get...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let id = txn.create(TestEntity::new("typed".to_string(), 7))?;
+
+        let entity = txn.get_as::<TestEntity>(id)?.expect("entity exists");
+        assert_eq!(entity.value, 7);
+        assert!(txn.get_as::<User>(id)?.is_none());
+        assert!(txn.get_as::<TestEntity>(id + 1000)?.is_none());
+
+        assert_eq!(txn.get_required_as::<TestEntity>(id)?.name, "typed");
+        assert!(txn.get_required_as::<User>(id).is_err());
+        assert!(txn.get_required_as::<TestEntity>(id + 1000).is_err());
+        Ok(())
+    })
+}
+
+pub fn test_tree<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing tree helpers...");
+
+    const CATEGORIES: Tree = Tree::new("category");
+
+    let mut runner = r.create()?;
+    let ids = runner.execute(|txn| {
+        let mut ids = Vec::new();
+        for name in ["root", "a", "b", "a1", "a2", "a1x"] {
+            ids.push(txn.create(TestEntity::new(name.to_string(), 0))?);
+        }
+        let [root, a, b, a1, a2, a1x] = ids[..] else {
+            unreachable!()
+        };
+        // Attaching a subtree carries its descendants along
+        CATEGORIES.attach(&txn, a1x, a1)?;
+        CATEGORIES.attach(&txn, a1, a)?;
+        CATEGORIES.attach(&txn, a2, a)?;
+        CATEGORIES.attach(&txn, a, root)?;
+        CATEGORIES.attach(&txn, b, root)?;
+        assert!(CATEGORIES.attach(&txn, b, a).is_err());
+        txn.commit()?;
+        Ok(ids)
+    })?;
+    let [root, a, b, a1, a2, a1x] = ids[..] else {
+        unreachable!()
+    };
+
+    runner.execute(|txn| {
+        assert_eq!(CATEGORIES.parent(&txn, a1x)?, Some(a1));
+        assert_eq!(CATEGORIES.parent(&txn, root)?, None);
+        assert_eq!(CATEGORIES.ancestors(&txn, a1x)?, vec![a1, a, root]);
+        assert_eq!(CATEGORIES.path(&txn, a1x)?, vec![root, a, a1, a1x]);
+        assert_eq!(CATEGORIES.children(&txn, a)?, vec![a1, a2]);
+        assert_eq!(
+            CATEGORIES.descendants(&txn, root, usize::MAX)?,
+            vec![a, a1, a1x, a2, b]
+        );
+        assert_eq!(CATEGORIES.descendants(&txn, root, 1)?, vec![a, b]);
+        assert_eq!(CATEGORIES.descendants(&txn, a, 1)?, vec![a1, a2]);
+
+        // Moving a node under itself or its subtree is rejected
+        assert!(CATEGORIES.move_subtree(&txn, a, Some(a)).is_err());
+        assert!(CATEGORIES.move_subtree(&txn, a, Some(a1x)).is_err());
+
+        CATEGORIES.move_subtree(&txn, a1, Some(b))?;
+        assert_eq!(CATEGORIES.children(&txn, a)?, vec![a2]);
+        assert_eq!(CATEGORIES.path(&txn, a1x)?, vec![root, b, a1, a1x]);
+        assert_eq!(CATEGORIES.descendants(&txn, a, usize::MAX)?, vec![a2]);
+        assert_eq!(CATEGORIES.descendants(&txn, b, usize::MAX)?, vec![a1, a1x]);
+
+        CATEGORIES.detach(&txn, b)?;
+        assert_eq!(
+            CATEGORIES.descendants(&txn, root, usize::MAX)?,
+            vec![a, a2]
+        );
+        assert_eq!(CATEGORIES.descendants(&txn, b, 2)?, vec![a1, a1x]);
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_acyclic_relations<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing acyclic relations...");
+
+    let depends =
+        |from: Id, to: Id| EdgeValue::new(from, b"depends_on".to_vec(), to);
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let mut tasks = Vec::new();
+        for i in 0..4 {
+            tasks.push(txn.create(TestEntity::new(format!("task{}", i), i))?);
+        }
+        let [a, b, c, d] = tasks[..] else {
+            unreachable!()
+        };
+        txn.create_edge(depends(a, b))?;
+        txn.create_edge(depends(b, c))?;
+        txn.create_edge(depends(a, c))?;
+
+        // Closing a -> b -> c -> a is rejected, as is a self loop
+        assert!(matches!(
+            txn.create_edge(depends(c, a)),
+            Err(ents::DatabaseError::CycleDetected { from, to, .. })
+                if from == c && to == a
+        ));
+        assert!(txn.create_edge(depends(d, d)).is_err());
+        txn.create_edge(depends(c, d))?;
+
+        // Other relations may form cycles
+        txn.create_edge(EdgeValue::new(c, b"follows".to_vec(), a))?;
+
+        // A hidden edge does not count, but restoring it is checked
+        assert!(txn.hide_edge(&depends(b, c))?);
+        assert!(txn.hide_edge(&depends(a, c))?);
+        txn.create_edge(depends(c, a))?;
+        assert!(txn.restore_edge(&depends(b, c)).is_err());
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_closure_table<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing closure table...");
+
+    const REPORTS_TO: ClosureTable = ClosureTable::new(b"reports_to");
+
+    let mut runner = r.create()?;
+    let ids = runner.execute(|txn| {
+        let mut ids = Vec::new();
+        for name in ["alice", "bob", "carol", "dave", "erin"] {
+            ids.push(txn.create(TestEntity::new(name.to_string(), 0))?);
+        }
+        let [alice, bob, carol, dave, _] = ids[..] else {
+            unreachable!()
+        };
+        REPORTS_TO.link(&txn, alice, bob)?;
+        REPORTS_TO.link(&txn, carol, dave)?;
+        // Linking the two chains extends every ancestor's closure
+        REPORTS_TO.link(&txn, bob, carol)?;
+        txn.commit()?;
+        Ok(ids)
+    })?;
+    let [alice, bob, carol, dave, erin] = ids[..] else {
+        unreachable!()
+    };
+
+    runner.execute(|txn| {
+        assert!(REPORTS_TO.is_reachable(&txn, alice, dave)?);
+        assert!(REPORTS_TO.is_reachable(&txn, bob, carol)?);
+        assert!(!REPORTS_TO.is_reachable(&txn, dave, alice)?);
+        assert!(!REPORTS_TO.is_reachable(&txn, alice, erin)?);
+        assert_eq!(
+            REPORTS_TO.reachable_from(&txn, alice)?,
+            vec![bob, carol, dave]
+        );
+        assert_eq!(REPORTS_TO.reaching(&txn, dave)?, vec![alice, bob, carol]);
+
+        // A second path keeps carol reachable after the first is removed
+        REPORTS_TO.link(&txn, alice, carol)?;
+        assert!(REPORTS_TO.unlink(&txn, bob, carol)?);
+        assert!(!REPORTS_TO.unlink(&txn, bob, carol)?);
+        assert_eq!(
+            REPORTS_TO.reachable_from(&txn, alice)?,
+            vec![bob, carol, dave]
+        );
+        assert!(REPORTS_TO.reachable_from(&txn, bob)?.is_empty());
+        assert_eq!(REPORTS_TO.reaching(&txn, dave)?, vec![alice, carol]);
+
+        // Relinking restores the hidden closure edges
+        REPORTS_TO.link(&txn, bob, carol)?;
+        assert!(REPORTS_TO.is_reachable(&txn, bob, dave)?);
+
+        // Base edges written directly are picked up by a rebuild
+        txn.create_edge(EdgeValue::new(dave, b"reports_to".to_vec(), erin))?;
+        assert!(!REPORTS_TO.is_reachable(&txn, alice, erin)?);
+        assert_eq!(REPORTS_TO.rebuild(&txn, ids.clone())?, 4);
+        assert!(REPORTS_TO.is_reachable(&txn, alice, erin)?);
+        assert_eq!(REPORTS_TO.rebuild(&txn, ids.clone())?, 0);
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_edge_query_limit<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing edge query limits...");
+
+    const CHAT: Namespace = Namespace::new("chat");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let source = txn.create(TestEntity::new("source".to_string(), 0))?;
+        let mut dests = Vec::new();
+        for i in 0..150 {
+            let dest = txn.create(TestEntity::new(format!("dest{}", i), i))?;
+            txn.create_edge(EdgeValue::new(source, b"member".to_vec(), dest))?;
+            dests.push(dest);
+        }
+        dests.sort();
+
+        let names: &[&[u8]] = &[b"member"];
+        assert_eq!(txn.find_edges(source, EdgeQuery::asc(names))?.len(), 100);
+        let all =
+            txn.find_edges(source, EdgeQuery::asc(names).with_limit(500))?;
+        assert_eq!(all.len(), 150);
+        assert!(txn
+            .find_edges(source, EdgeQuery::asc(names).with_limit(0))?
+            .is_empty());
+
+        // Paging in small chunks visits every edge once
+        let mut seen = Vec::new();
+        let mut last: Option<ents::Edge> = None;
+        loop {
+            let query = EdgeQuery::asc(names).with_limit(7).with_cursor_opt(
+                last.as_ref().map(ents::EdgeCursor::from_edge),
+            );
+            let page = txn.find_edges(source, query)?;
+            assert!(page.len() <= 7);
+            if page.is_empty() {
+                break;
+            }
+            seen.extend(page.iter().map(|e| e.dest));
+            last = page.last().cloned();
+        }
+        assert_eq!(seen, dests);
+
+        let newest =
+            txn.find_edges(source, EdgeQuery::desc(names).with_limit(3))?;
+        let newest: Vec<Id> = newest.iter().map(|e| e.dest).collect();
+        assert_eq!(
+            newest,
+            dests[147..].iter().rev().copied().collect::<Vec<_>>()
+        );
+
+        let incoming =
+            txn.find_edges_to(dests[0], EdgeQuery::asc(&[]).with_limit(1))?;
+        assert_eq!(incoming.len(), 1);
+
+        // Namespaced queries honor the limit too
+        let chat = CHAT.wrap(txn);
+        for &dest in &dests[..5] {
+            chat.create_edge(EdgeValue::new(source, b"member".to_vec(), dest))?;
+        }
+        assert_eq!(
+            chat.find_edges(source, EdgeQuery::asc(&[]).with_limit(2))?
+                .len(),
+            2
+        );
+        assert_eq!(chat.find_edges(source, EdgeQuery::asc(&[]))?.len(), 5);
+        Ok(())
+    })
+}
+
+pub fn test_list_by_type<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing listing entities by type...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let mut tags = Vec::new();
+        for i in 0..12 {
+            tags.push(txn.create(Tag::new(
+                format!("listed{}", i),
+                "blue".to_string(),
+            ))?);
+            txn.create(TestEntity::new(format!("unlisted{}", i), i))?;
+        }
+        tags.sort();
+
+        // Other tests share the store, so collect every tag by paging
+        let mut listed = Vec::new();
+        let mut after = None;
+        loop {
+            let page = txn.list_ids_by_type("Tag", after, 5)?;
+            assert!(page.len() <= 5);
+            if page.is_empty() {
+                break;
+            }
+            after = page.last().copied();
+            listed.extend(page);
+        }
+        assert!(listed.windows(2).all(|w| w[0] < w[1]));
+        for &id in &listed {
+            assert!(txn.get_as::<Tag>(id)?.is_some());
+        }
+        assert!(tags.iter().all(|id| listed.contains(id)));
+
+        // Paging resumes after the given id
+        let rest = txn.list_ids_by_type("Tag", Some(tags[5]), 100)?;
+        assert!(rest.iter().all(|&id| id > tags[5]));
+        assert!(tags[6..].iter().all(|id| rest.contains(id)));
+        assert!(txn.list_ids_by_type("Tag", None, 0)?.is_empty());
+        assert!(txn.list_ids_by_type("NoSuchType", None, 10)?.is_empty());
+
+        txn.delete::<Tag>(tags[0])?;
+        let all = txn.list_ids_by_type("Tag", None, usize::MAX)?;
+        assert!(!all.contains(&tags[0]));
+        assert!(all.contains(&tags[1]));
+        Ok(())
+    })
+}
+
+/// Ids of every entity `txn` scans, `limit` at a time
+fn scan_ids<T: Transactional>(
+    txn: &T,
+    limit: usize,
+) -> anyhow::Result<Vec<Id>> {
+    let mut ids = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = txn.scan_entities(cursor, limit)?;
+        assert!(page.len() <= limit);
+        ids.extend(page.iter().map(|ent| ent.id()));
+        match next {
+            Some(next) => {
+                assert_eq!(Some(next.after), ids.last().copied());
+                cursor = Some(next);
+            }
+            None => break,
         }
     }
+    Ok(ids)
+}
+
+pub fn test_scan_entities<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing entity scans...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let mut created = Vec::new();
+        for i in 0..12 {
+            created
+                .push(txn.create(TestEntity::new(format!("scanned{}", i), i))?);
+        }
+        created.sort();
+
+        // Other tests share the store, so scan everything
+        let scanned = scan_ids(&txn, 5)?;
+        assert!(scanned.windows(2).all(|w| w[0] < w[1]));
+        assert!(created.iter().all(|id| scanned.contains(id)));
+        assert_eq!(scan_ids(&txn, usize::MAX)?, scanned);
+
+        // Scans resume after the cursor
+        let cursor = EntityCursor { after: created[5] };
+        let (rest, _) = txn.scan_entities(Some(cursor), usize::MAX)?;
+        assert!(rest.iter().all(|ent| ent.id() > created[5]));
+        assert_eq!(rest[0].id(), created[6]);
+        let (empty, same) = txn.scan_entities(Some(cursor), 0)?;
+        assert!(empty.is_empty());
+        assert_eq!(same, Some(cursor));
+
+        // A branch scans the base with its own writes applied
+        let branch = Branch::new(&txn);
+        branch.delete::<TestEntity>(created[0])?;
+        let added =
+            branch.create(TestEntity::new("branched".to_string(), 1))?;
+        let branched = scan_ids(&branch, 3)?;
+        assert!(branched.windows(2).all(|w| w[0] < w[1]));
+        assert!(!branched.contains(&created[0]));
+        assert!(branched.contains(&added));
+        assert_eq!(branched.len(), scanned.len());
+        drop(branch);
+
+        for id in created {
+            txn.delete::<TestEntity>(id)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn test_edge_payloads<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing edge payloads...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let alice = txn.create(TestEntity::new("alice".to_string(), 1))?;
+        let bob = txn.create(TestEntity::new("bob".to_string(), 2))?;
+        let carol = txn.create(TestEntity::new("carol".to_string(), 3))?;
+
+        let follows = EdgeValue::new(alice, b"follows".to_vec(), bob);
+        txn.create_edge(
+            follows.clone().with_payload(b"2024-01-01".as_slice()),
+        )?;
+        txn.create_edge(EdgeValue::new(alice, b"follows".to_vec(), carol))?;
+
+        let names: &[&[u8]] = &[b"follows"];
+        let edges = txn.find_edges(alice, EdgeQuery::asc(names))?;
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].payload, b"2024-01-01");
+        assert!(edges[1].payload.is_empty());
+        let incoming = txn.find_edges_to(bob, EdgeQuery::asc(names))?;
+        assert_eq!(incoming[0].payload, b"2024-01-01");
+
+        // Hiding and restoring keeps the payload; re-creating replaces it
+        txn.hide_edge(&follows)?;
+        let hidden =
+            txn.find_edges(alice, EdgeQuery::asc(names).include_hidden())?;
+        assert!(hidden[0].hidden);
+        assert_eq!(hidden[0].payload, b"2024-01-01");
+        txn.restore_edge(&follows)?;
+        assert_eq!(
+            txn.find_edges(alice, EdgeQuery::asc(names))?[0].payload,
+            b"2024-01-01"
+        );
+        txn.create_edge(follows.with_payload(b"2025-06-30".as_slice()))?;
+        assert_eq!(
+            txn.find_edges(alice, EdgeQuery::asc(names))?[0].payload,
+            b"2025-06-30"
+        );
+        Ok(())
+    })
+}
+
+pub fn test_unchanged_update<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing updates without effective changes...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let id = txn.create(TestEntity::new("steady".to_string(), 7))?;
+        let stored = txn.get_required_as::<TestEntity>(id)?;
+
+        // Setting a field to its current value writes nothing
+        let mut ent = stored.clone();
+        assert!(txn.update(&mut ent, |e: &mut TestEntity| e.value = 7)?);
+        assert_eq!(ent.last_updated, stored.last_updated);
+        let after = txn.get_required_as::<TestEntity>(id)?;
+        assert_eq!(ent_hash(&after)?, ent_hash(&stored)?);
+
+        // Changes made before calling update still count
+        let mut ent = stored.clone();
+        ent.value = 8;
+        assert!(txn.update(&mut ent, |_: &mut TestEntity| ())?);
+        let after = txn.get_required_as::<TestEntity>(id)?;
+        assert_eq!(after.value, 8);
+        assert_ne!(ent_hash(&after)?, ent_hash(&stored)?);
+
+        // A stale copy still fails its check
+        let mut stale = stored.clone();
+        assert!(!txn.update(&mut stale, |e: &mut TestEntity| e.value = 7)?);
+        Ok(())
+    })
+}
+
+pub fn test_touch<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing touch...");
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let id = txn.create(TestEntity::new("touched".to_string(), 3))?;
+        let before = txn.get_required_as::<TestEntity>(id)?;
+
+        assert!(txn.touch::<TestEntity>(id)?);
+        let after = txn.get_required_as::<TestEntity>(id)?;
+        assert!(after.last_updated > before.last_updated);
+        assert_eq!((after.name.as_str(), after.value), ("touched", 3));
+
+        // Copies loaded before the touch are stale now
+        let mut stale = before.clone();
+        assert!(!txn.update(&mut stale, |e: &mut TestEntity| e.value = 4)?);
+
+        assert!(!txn.touch::<Tag>(id)?);
+        txn.delete::<TestEntity>(id)?;
+        assert!(!txn.touch::<TestEntity>(id)?);
+        Ok(())
+    })
+}
+
+pub fn test_mock_clock<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing mock clock...");
+
+    let clock = Arc::new(MockClock::new(1_000));
+    let mut runner = r.create()?;
+    with_clock(clock.clone(), || {
+        runner.execute(|txn| {
+            let id = txn.create(TestEntity::new("clocked".to_string(), 1))?;
+            let mut ent = txn.get_required_as::<TestEntity>(id)?;
+            assert!(txn.update(&mut ent, |e: &mut TestEntity| e.value = 2)?);
+            assert_eq!(ent.last_updated, 1_000);
+
+            clock.advance(500);
+            assert!(txn.touch::<TestEntity>(id)?);
+            let touched = txn.get_required_as::<TestEntity>(id)?;
+            assert_eq!(touched.last_updated, 1_500);
+            Ok(())
+        })
+    })
+}
+
+pub fn test_update_conflict<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing update conflicts...");
+
+    let clock = Arc::new(MockClock::new(2_000));
+    let mut runner = r.create()?;
+    with_clock(clock.clone(), || {
+        runner.execute(|txn| {
+            let id = txn.create(TestEntity::new("contended".to_string(), 1))?;
+            let mut first = txn.get_required_as::<TestEntity>(id)?;
+            let mut second = first.clone();
+            let expected = second.last_updated;
+
+            txn.try_update(&mut first, |e: &mut TestEntity| e.value = 2)?;
+            assert_eq!(first.last_updated, 2_000);
+
+            let err = txn
+                .try_update(&mut second, |e: &mut TestEntity| e.value = 3)
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                DatabaseError::Conflict { id: i, expected: e, actual: 2_000 }
+                    if i == id && e == expected
+            ));
+
+            txn.delete::<TestEntity>(id)?;
+            let err = txn
+                .try_update(&mut first, |e: &mut TestEntity| e.value = 4)
+                .unwrap_err();
+            assert!(matches!(err, DatabaseError::Other { .. }));
+            Ok(())
+        })
+    })
+}
+
+pub fn test_retry_update<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing retry_update...");
+
+    let clock = Arc::new(MockClock::new(3_000));
+    let mut runner = r.create()?;
+    with_clock(clock.clone(), || {
+        runner.execute(|txn| {
+            let id = txn.create(TestEntity::new("retried".to_string(), 1))?;
+
+            // The first two attempts race with a concurrent touch
+            let mut attempts = 0;
+            let updated = txn.retry_update(id, 3, |e: &mut TestEntity| {
+                attempts += 1;
+                if attempts < 3 {
+                    clock.advance(1);
+                    txn.touch::<TestEntity>(id).unwrap();
+                }
+                e.value += 10;
+            })?;
+            assert_eq!(attempts, 3);
+            assert_eq!(updated.value, 11);
+            assert_eq!(txn.get_required_as::<TestEntity>(id)?.value, 11);
+
+            let result = txn.retry_update(id, 1, |e: &mut TestEntity| {
+                clock.advance(1);
+                txn.touch::<TestEntity>(id).unwrap();
+                e.value += 10;
+            });
+            assert!(matches!(result, Err(DatabaseError::Conflict { .. })));
+            assert_eq!(txn.get_required_as::<TestEntity>(id)?.value, 11);
+
+            assert!(txn
+                .retry_update(id, 0, |e: &mut TestEntity| e.value = 0)
+                .is_err());
+            txn.delete::<TestEntity>(id)?;
+            assert!(txn
+                .retry_update(id, 3, |e: &mut TestEntity| e.value = 0)
+                .is_err());
+            Ok(())
+        })
+    })
+}
+
+pub fn test_fixtures<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing fixtures...");
+
+    const FIXTURES: &str = r#"{
+        "entities": [
+            {"$name": "ada", "type": "User", "username": "ada",
+             "email": "ada@example.com", "id": 0, "last_updated": 0},
+            {"$name": "rust", "type": "Tag", "name": "rust",
+             "color": "orange", "id": 0, "last_updated": 0},
+            {"$name": "intro", "type": "Post", "title": "Intro",
+             "content": "hi", "author_id": {"$ref": "ada"},
+             "tag_ids": [{"$ref": "rust"}], "id": 0, "last_updated": 0}
+        ],
+        "edges": [
+            {"source": "ada", "name": "pinned", "dest": "intro"}
+        ]
+    }"#;
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let loader = FixtureLoader::new()
+            .with_type::<User>()
+            .with_type::<Tag>()
+            .with_type::<Post>();
+        let ids = loader.load(&txn, FIXTURES)?;
+        let (ada, rust, intro) = (ids["ada"], ids["rust"], ids["intro"]);
+
+        let post = txn.get_required_as::<Post>(intro)?;
+        assert_eq!(post.author_id, ada);
+        assert_eq!(post.tag_ids, vec![rust]);
+        // Edge providers ran on create
+        let authors = txn.find_edges(intro, EdgeQuery::asc(&[b"author"]))?;
+        assert_eq!(authors.len(), 1);
+        assert_eq!(authors[0].dest, ada);
+        let pinned = txn.find_edges(ada, EdgeQuery::asc(&[b"pinned"]))?;
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].dest, intro);
+
+        // Types must be registered with the loader
+        let err = FixtureLoader::new().with_type::<User>().load(
+            &txn,
+            r#"{"entities": [{"type": "Tag", "name": "x", "color": "red",
+                "id": 0, "last_updated": 0}]}"#,
+        );
+        assert!(matches!(err, Err(DatabaseError::UnknownType { .. })));
+
+        // References must name an earlier entity
+        let err = loader.load(
+            &txn,
+            r#"{"edges": [{"source": "nobody", "name": "x", "dest": 1}]}"#,
+        );
+        assert!(err.is_err());
+        Ok(())
+    })
+}
+
+pub fn test_get_or_create<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing get_or_create...");
+
+    let key = UniqueKey::new("user_email", b"once@example.com".as_slice());
+    let user = |name: &str| {
+        UserWithUniqueEmail::new(name.to_string(), "once@example.com".into())
+    };
+
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let (first, created) = txn.get_or_create(&key, || user("first"))?;
+        assert!(created);
+        assert_eq!(txn.find_unique(&key)?, Some(first.id));
+
+        let (again, created) = txn.get_or_create(&key, || user("second"))?;
+        assert!(!created);
+        assert_eq!((again.id, again.username.as_str()), (first.id, "first"));
+
+        // The factory must produce an entity holding the key
+        let other =
+            UniqueKey::new("user_email", b"never@example.com".as_slice());
+        assert!(txn.get_or_create(&other, || user("third")).is_err());
+
+        // Held by another type
+        assert!(txn
+            .get_or_create(&key, || Tag::new("t".into(), "red".into()))
+            .is_err());
+        Ok(())
+    })
+}
+
+pub fn test_create_with_id<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing create with explicit id...");
+
+    // Far above generated ids of either backend in this suite
+    let id: Id = 1 << 62;
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let post = Post::new("imported".into(), "x".into(), 7, vec![]);
+        assert_eq!(txn.create_with_id(id, post)?, id);
+        let stored = txn.get_required_as::<Post>(id)?;
+        assert_eq!((stored.id, stored.title.as_str()), (id, "imported"));
+        // Edge providers ran with the explicit id
+        let authors = txn.find_edges(id, EdgeQuery::asc(&[b"author"]))?;
+        assert_eq!(authors.len(), 1);
+
+        let again = TestEntity::new("again".to_string(), 1);
+        assert!(matches!(
+            txn.create_with_id(id, again),
+            Err(DatabaseError::AlreadyExists { id: taken }) if taken == id
+        ));
+        assert!(txn.get_required_as::<Post>(id).is_ok());
+        txn.delete::<Post>(id)?;
+        Ok(())
+    })
+}
+
+pub fn test_rate_limit<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing rate limits...");
+
+    let second = Duration::from_secs(1);
+    let clock = Arc::new(MockClock::new(10_000_000));
+    let mut runner = r.create()?;
+    with_clock(clock.clone(), || {
+        runner.execute(|txn| {
+            let key = "suite:rate";
+            assert!(rate_limit::check_and_increment(&txn, key, 2, second)?);
+            assert!(rate_limit::check_and_increment(&txn, key, 2, second)?);
+            assert!(!rate_limit::check_and_increment(&txn, key, 2, second)?);
+
+            // Half of the previous window still counts
+            clock.advance(1_500_000);
+            assert!(!rate_limit::check_and_increment(&txn, key, 1, second)?);
+            assert!(rate_limit::check_and_increment(&txn, key, 2, second)?);
+            assert!(!rate_limit::check_and_increment(&txn, key, 2, second)?);
+
+            clock.advance(2_000_000);
+            assert!(rate_limit::check_and_increment(&txn, key, 1, second)?);
+
+            let unique = UniqueKey::new(rate_limit::RATE_LIMIT_KEY, key);
+            let id = txn.find_unique(&unique)?.expect("counter stored");
+            txn.delete::<rate_limit::RateCounter>(id)?;
+            Ok(())
+        })
+    })
+}
+
+pub fn test_txn_decorators<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing transaction decorators...");
+
+    let metrics = Arc::new(TxnMetrics::new());
+    let logged = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut runner = r.create()?;
+    let id = runner.execute(|txn| {
+        let log = logged.clone();
+        let txn = TxnStack::new(txn)
+            .with_metrics(metrics.clone())
+            .with_logging(move |event| {
+                log.lock().unwrap().push((event.op, event.error.is_some()))
+            })
+            .build();
+        let id = txn.create(TestEntity::new("decorated".to_string(), 1))?;
+        assert!(txn.get(id)?.is_some());
+        assert!(txn.get(0)?.is_none());
+        txn.commit()?;
+        Ok(id)
+    })?;
+    assert_eq!(metrics.get(TxnOp::Get).calls, 2);
+    assert_eq!(metrics.get(TxnOp::Create).calls, 1);
+    assert_eq!(metrics.get(TxnOp::Commit).calls, 1);
+    assert_eq!(
+        *logged.lock().unwrap(),
+        vec![
+            (TxnOp::Create, false),
+            (TxnOp::Get, false),
+            (TxnOp::Get, false),
+            (TxnOp::Commit, false),
+        ]
+    );
+
+    // The guard refuses writes before they reach the backend, and the
+    // metrics layer outside it still counts the failures
+    runner.execute(|txn| {
+        let txn = TxnStack::new(txn)
+            .read_only()
+            .with_metrics(metrics.clone())
+            .build();
+        assert!(txn.get(id)?.is_some());
+        assert!(txn.delete::<TestEntity>(id).is_err());
+        assert!(txn
+            .create_edge(EdgeValue::new(id, b"self".to_vec(), id))
+            .is_err());
+        assert!(txn.find_edges(id, EdgeQuery::asc(&[b"self"]))?.is_empty());
+        let txn = txn.into_inner().into_inner();
+        txn.delete::<TestEntity>(id)?;
+        txn.commit()?;
+        Ok(())
+    })?;
+    assert_eq!(metrics.get(TxnOp::Delete).errors, 1);
+    assert_eq!(metrics.get(TxnOp::CreateEdge).errors, 1);
+    assert_eq!(metrics.get(TxnOp::FindEdges).calls, 1);
+    Ok(())
+}
+
+/// Rejects negative values and logs every mutation it sees
+struct AuditObserver {
+    log: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl AuditObserver {
+    fn push(&self, entry: String) {
+        self.log.lock().unwrap().push(entry);
+    }
+}
+
+impl<T: Transactional> EntObserver<T> for AuditObserver {
+    fn before_create(
+        &self,
+        _txn: &T,
+        ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        match ent.downcast_ref::<TestEntity>() {
+            Some(e) if e.value < 0 => Err(DatabaseError::Other {
+                source: "negative value".into(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn after_create(
+        &self,
+        _txn: &T,
+        ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        self.push(format!("created {}", ent.id()));
+        Ok(())
+    }
+
+    fn before_update(
+        &self,
+        _txn: &T,
+        old: &dyn Ent,
+        new: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        let value =
+            |e: &dyn Ent| e.downcast_ref::<TestEntity>().map(|e| e.value);
+        self.push(format!("update {:?} -> {:?}", value(old), value(new)));
+        Ok(())
+    }
+
+    fn after_delete(
+        &self,
+        txn: &T,
+        ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        assert!(txn.get(ent.id())?.is_none());
+        self.push(format!("deleted {}", ent.id()));
+        Ok(())
+    }
+}
+
+pub fn test_ent_observers<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing entity observers...");
+
+    let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let observers = Observers::new().with(AuditObserver { log: log.clone() });
+    let mut runner = r.create()?;
+    runner.execute(|txn| {
+        let txn = observers.wrap(txn);
+        let rejected = txn.create(TestEntity::new("neg".to_string(), -1));
+        assert!(rejected.is_err());
+        let id = txn.create(TestEntity::new("observed".to_string(), 1))?;
+        let mut ent = txn.get_required_as::<TestEntity>(id)?;
+        assert!(txn.update(&mut ent, |e: &mut TestEntity| e.value = 2)?);
+        assert_eq!(ent.value, 2);
+        txn.delete::<TestEntity>(id)?;
+        // Deleting a missing id calls no observer
+        txn.delete::<TestEntity>(id)?;
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                format!("created {}", id),
+                "update Some(1) -> Some(2)".to_string(),
+                format!("deleted {}", id),
+            ]
+        );
+        Ok(())
+    })
+}
+
+pub fn test_read_only_txn<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing read-only transactions...");
+
+    let mut runner = r.create()?;
+    let id = runner.execute(|txn| {
+        let id = txn.create(TestEntity::new("report".to_string(), 1))?;
+        txn.commit()?;
+        Ok(id)
+    })?;
+
+    runner.execute(|txn| {
+        let txn = ReadOnlyTxn::read_only(txn);
+        let mut ent = txn.get_required_as::<TestEntity>(id)?;
+        let violation = |result: Result<_, DatabaseError>, op: &str| {
+            matches!(
+                result,
+                Err(DatabaseError::ReadOnlyViolation { operation })
+                    if operation == op
+            )
+        };
+        assert!(violation(
+            txn.create(TestEntity::new("nope".to_string(), 2))
+                .map(|_| ()),
+            "create"
+        ));
+        assert!(violation(
+            txn.update(&mut ent, |e: &mut TestEntity| e.value = 2)
+                .map(|_| ()),
+            "update"
+        ));
+        assert!(violation(txn.delete::<TestEntity>(id), "delete"));
+        assert!(violation(
+            txn.create_edge(EdgeValue::new(id, b"self".to_vec(), id)),
+            "create_edge"
+        ));
+        txn.commit()?;
+        Ok(())
+    })?;
+
+    runner.execute(|txn| {
+        assert_eq!(txn.get_required_as::<TestEntity>(id)?.value, 1);
+        txn.delete::<TestEntity>(id)?;
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_branch<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing branch transactions...");
+
+    let mut runner = r.create()?;
+    let base_id = runner.execute(|txn| {
+        let id = txn.create(TestEntity::new("base".to_string(), 1))?;
+        txn.commit()?;
+        Ok(id)
+    })?;
+
+    let created = runner.execute(|txn| {
+        let branch = Branch::new(&txn);
+        let mut base = branch.get_required_as::<TestEntity>(base_id)?;
+        assert!(branch.update(&mut base, |e: &mut TestEntity| e.value = 2)?);
+        let created =
+            branch.create(TestEntity::new("branched".to_string(), 3))?;
+        branch.create_edge(EdgeValue::new(
+            base_id,
+            b"link".to_vec(),
+            created,
+        ))?;
+
+        // The branch sees its own writes over the base
+        assert_eq!(branch.get_required_as::<TestEntity>(base_id)?.value, 2);
+        assert!(branch.get(created)?.is_some());
+        let edges = branch.find_edges(base_id, EdgeQuery::asc(&[b"link"]))?;
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].dest, created);
+        let after = Some(base_id.min(created) - 1);
+        let ids = branch.list_ids_by_type("TestEntity", after, 100)?;
+        assert!(ids.contains(&base_id) && ids.contains(&created));
+        assert_eq!(branch.pending_writes(), 3);
+
+        // ...while the base does not
+        assert_eq!(txn.get_required_as::<TestEntity>(base_id)?.value, 1);
+        assert!(txn.get(created)?.is_none());
+        assert!(txn
+            .find_edges(base_id, EdgeQuery::asc(&[b"link"]))?
+            .is_empty());
+
+        branch.replay(&txn)?;
+        txn.commit()?;
+        Ok(created)
+    })?;
+
+    runner.execute(|txn| {
+        assert_eq!(txn.get_required_as::<TestEntity>(base_id)?.value, 2);
+        assert_eq!(txn.get_required_as::<TestEntity>(created)?.value, 3);
+        assert_eq!(
+            txn.find_edges(base_id, EdgeQuery::asc(&[b"link"]))?.len(),
+            1
+        );
+
+        // A dropped branch leaves nothing behind
+        let branch = Branch::new(&txn);
+        branch.delete::<TestEntity>(created)?;
+        assert!(branch.get(created)?.is_none());
+        assert!(branch
+            .find_edges(base_id, EdgeQuery::asc(&[b"link"]))?
+            .is_empty());
+        drop(branch);
+        assert!(txn.get(created)?.is_some());
+
+        txn.delete::<TestEntity>(created)?;
+        txn.delete::<TestEntity>(base_id)?;
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_delete_policies<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing delete policies...");
+
+    let mut runner = r.create()?;
+    let (root, child, grandchild, user) = runner.execute(|txn| {
+        let root = txn.create(Folder::new("root".to_string()))?;
+        let child = txn.create(Folder::new("child".to_string()))?;
+        let grandchild = txn.create(Folder::new("grandchild".to_string()))?;
+        let user = txn.create(User::new(
+            "folder_owner".to_string(),
+            "owner@example.com".to_string(),
+        ))?;
+        txn.create_edge(EdgeValue::new(root, b"subfolder".to_vec(), child))?;
+        txn.create_edge(EdgeValue::new(
+            child,
+            b"subfolder".to_vec(),
+            grandchild,
+        ))?;
+        // A cycle of cascades still ends
+        txn.create_edge(EdgeValue::new(
+            grandchild,
+            b"subfolder".to_vec(),
+            root,
+        ))?;
+        txn.create_edge(EdgeValue::new(root, b"shared_with".to_vec(), user))?;
+        txn.create_edge(EdgeValue::new(root, b"locked_by".to_vec(), user))?;
+        txn.commit()?;
+        Ok((root, child, grandchild, user))
+    })?;
+
+    runner.execute(|txn| {
+        // Restrictions are checked before anything is written
+        assert!(txn.delete::<Folder>(root).is_err());
+        assert!(txn.get(child)?.is_some());
+        assert_eq!(
+            txn.find_edges(root, EdgeQuery::asc(&[b"subfolder"]))?.len(),
+            1
+        );
+        Ok(())
+    })?;
+
+    runner.execute(|txn| {
+        let lock = EdgeValue::new(root, b"locked_by".to_vec(), user);
+        assert!(txn.hide_edge(&lock)?);
+        txn.delete::<Folder>(root)?;
+        txn.commit()?;
+        Ok(())
+    })?;
+
+    runner.execute(|txn| {
+        for id in [root, child, grandchild] {
+            assert!(txn.get(id)?.is_none(), "folder {} survived", id);
+        }
+        assert!(txn.get(user)?.is_some());
+        let shares = txn.find_edges(
+            root,
+            EdgeQuery::asc(&[b"shared_with"]).include_hidden(),
+        )?;
+        assert_eq!(shares.len(), 1);
+        assert!(shares[0].hidden);
+
+        txn.delete::<User>(user)?;
+        txn.commit()?;
+        Ok(())
+    })
+}
+
+pub fn test_soft_delete<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing soft delete...");
+
+    let clock = Arc::new(MockClock::new(5_000));
+    let mut runner = r.create()?;
+    with_clock(clock.clone(), || {
+        let (user, purged, fan) = runner.execute(|txn| {
+            let user = txn.create(UserWithUniqueEmail::new(
+                "trashed".to_string(),
+                "trashed@example.com".to_string(),
+            ))?;
+            let purged =
+                txn.create(TestEntity::new("purged".to_string(), 1))?;
+            let fan = txn.create(TestEntity::new("fan".to_string(), 2))?;
+            txn.create_edge(EdgeValue::new(fan, b"likes".to_vec(), purged))?;
+
+            assert!(!txn.soft_delete::<Tag>(user)?);
+            assert!(txn.soft_delete::<UserWithUniqueEmail>(user)?);
+            clock.advance(1_000);
+            assert!(txn.soft_delete::<TestEntity>(purged)?);
+            assert!(!txn.soft_delete::<TestEntity>(purged)?);
+            txn.commit()?;
+            Ok((user, purged, fan))
+        })?;
+
+        runner.execute(|txn| {
+            assert!(txn.get(user)?.is_none());
+            assert!(txn.get(purged)?.is_none());
+            let page =
+                txn.list_ids_by_type("TestEntity", Some(purged - 1), 1)?;
+            assert_ne!(page, vec![purged]);
+            let trashed = txn
+                .get_deleted(user)?
+                .ok_or_else(|| anyhow::anyhow!("User is not in the trash"))?;
+            let trashed = trashed
+                .downcast_ref::<UserWithUniqueEmail>()
+                .ok_or_else(|| anyhow::anyhow!("Entity is not a user"))?;
+            assert_eq!(trashed.username, "trashed");
+            assert!(txn.get_deleted(fan)?.is_none());
+
+            // Edges and unique keys are kept
+            let likes = txn.find_edges(fan, EdgeQuery::asc(&[b"likes"]))?;
+            assert_eq!(likes.len(), 1);
+            let key = UniqueKey::new("user_email", b"trashed@example.com");
+            assert_eq!(txn.find_unique(&key)?, Some(user));
+
+            let ours = |ids: Vec<Id>| -> Vec<Id> {
+                ids.into_iter()
+                    .filter(|id| [user, purged].contains(id))
+                    .collect()
+            };
+            assert_eq!(
+                ours(txn.list_deleted(5_001, None, usize::MAX)?),
+                [user]
+            );
+            let mut both = vec![user, purged];
+            both.sort_unstable();
+            assert_eq!(ours(txn.list_deleted(6_001, None, usize::MAX)?), both);
+            Ok(())
+        })?;
+
+        // A branch keeps its soft deletes to itself until replayed; the
+        // replayed writes are rolled back
+        runner.execute(|txn| {
+            let branch = Branch::new(&txn);
+            assert!(branch.restore::<TestEntity>(purged)?);
+            assert!(branch.get(purged)?.is_some());
+            assert!(branch.soft_delete::<TestEntity>(fan)?);
+            assert!(branch.get_deleted(fan)?.is_some());
+            assert!(txn.get(purged)?.is_none());
+            assert!(txn.get(fan)?.is_some());
+            branch.replay(&txn)?;
+            assert!(txn.get(purged)?.is_some());
+            assert!(txn.get_deleted(fan)?.is_some());
+            Ok(())
+        })?;
+
+        runner.execute(|txn| {
+            assert!(!txn.restore::<TestEntity>(user)?);
+            assert!(txn.restore::<UserWithUniqueEmail>(user)?);
+            assert!(txn.get_deleted(user)?.is_none());
+            let restored = txn.get_required_as::<UserWithUniqueEmail>(user)?;
+            assert_eq!(restored.email, "trashed@example.com");
+            assert!(!txn.restore::<UserWithUniqueEmail>(user)?);
+
+            // Purging deletes for good, edges and all
+            assert!(txn.purge::<TestEntity>(purged)?);
+            assert!(!txn.purge::<TestEntity>(purged)?);
+            assert!(txn.get(purged)?.is_none());
+            assert!(txn.get_deleted(purged)?.is_none());
+            assert!(txn
+                .find_edges(fan, EdgeQuery::asc(&[b"likes"]))?
+                .is_empty());
+
+            txn.delete::<UserWithUniqueEmail>(user)?;
+            txn.delete::<TestEntity>(fan)?;
+            txn.commit()?;
+            Ok(())
+        })
+    })
+}
+
+pub fn test_scenarios<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
+    println!("  Testing bundled scenarios...");
+
+    scenario::run_scenarios(r, scenario::bundled_scenarios_dir())
 }
 
 pub fn run_all_tests<R: TestSuiteRunner + Clone>(
@@ -209,6 +1970,39 @@ pub fn run_all_tests<R: TestSuiteRunner + Clone>(
     test_relationships(&runner)?;
     test_unique_constraints(&runner)?;
     test_concurrent_updates(&runner)?;
+    test_timeline_edges(&runner)?;
+    test_generated_graph(&runner)?;
+    test_simulated_workload(&runner)?;
+    test_workflow_log(&runner)?;
+    test_idempotency_keys(&runner)?;
+    test_edge_namespaces(&runner)?;
+    test_geo_queries(&runner)?;
+    test_incoming_edges(&runner)?;
+    test_metric_series(&runner)?;
+    test_typed_get(&runner)?;
+    test_tree(&runner)?;
+    test_acyclic_relations(&runner)?;
+    test_closure_table(&runner)?;
+    test_edge_query_limit(&runner)?;
+    test_list_by_type(&runner)?;
+    test_scan_entities(&runner)?;
+    test_edge_payloads(&runner)?;
+    test_unchanged_update(&runner)?;
+    test_touch(&runner)?;
+    test_mock_clock(&runner)?;
+    test_update_conflict(&runner)?;
+    test_retry_update(&runner)?;
+    test_fixtures(&runner)?;
+    test_get_or_create(&runner)?;
+    test_create_with_id(&runner)?;
+    test_rate_limit(&runner)?;
+    test_txn_decorators(&runner)?;
+    test_ent_observers(&runner)?;
+    test_read_only_txn(&runner)?;
+    test_branch(&runner)?;
+    test_delete_policies(&runner)?;
+    test_soft_delete(&runner)?;
+    test_scenarios(&runner)?;
 
     println!("All tests passed!");
     Ok(())
@@ -230,9 +2024,10 @@ pub fn test_basic_read<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
         let retrieved = txn.get(id)?;
         match retrieved {
             Some(ent) => {
-                let test_ent = ent.as_ent::<TestEntity>().ok_or_else(|| {
-                    anyhow::anyhow!("Entity is not TestEntity")
-                })?;
+                let test_ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
                 assert_eq!(test_ent.name, "test_read");
                 assert_eq!(test_ent.value, 100);
             }
@@ -292,9 +2087,10 @@ pub fn test_basic_update<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
         let retrieved = txn.get(id)?;
         match retrieved {
             Some(ent) => {
-                let test_ent = ent.as_ent::<TestEntity>().ok_or_else(|| {
-                    anyhow::anyhow!("Entity is not TestEntity")
-                })?;
+                let test_ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
                 assert_eq!(test_ent.name, "updated_name");
                 assert_eq!(test_ent.value, 75);
             }
@@ -390,7 +2186,7 @@ pub fn test_multiple_entities<R: TestSuiteRunner>(r: &R) -> anyhow::Result<()> {
             match retrieved {
                 Some(ent) => {
                     let test_ent =
-                        ent.as_ent::<TestEntity>().ok_or_else(|| {
+                        ent.downcast_ref::<TestEntity>().ok_or_else(|| {
                             anyhow::anyhow!("Entity is not TestEntity")
                         })?;
                     assert_eq!(test_ent.name, format!("test_multi_{}", i));
@@ -428,9 +2224,10 @@ pub fn test_concurrent_updates<R: TestSuiteRunner>(
         let retrieved = txn.get(entity_id)?;
         match retrieved {
             Some(ent) => {
-                let test_ent = ent.as_ent::<TestEntity>().ok_or_else(|| {
-                    anyhow::anyhow!("Entity is not TestEntity")
-                })?;
+                let test_ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
                 Ok((test_ent.clone(), test_ent.last_updated))
             }
             None => Err(anyhow::anyhow!("Entity not found")),
@@ -559,9 +2356,10 @@ pub fn test_concurrent_updates<R: TestSuiteRunner>(
         let retrieved = txn.get(entity_id)?;
         match retrieved {
             Some(ent) => {
-                let test_ent = ent.as_ent::<TestEntity>().ok_or_else(|| {
-                    anyhow::anyhow!("Entity is not TestEntity")
-                })?;
+                let test_ent =
+                    ent.downcast_ref::<TestEntity>().ok_or_else(|| {
+                        anyhow::anyhow!("Entity is not TestEntity")
+                    })?;
                 assert_eq!(test_ent.name, "sequential_update_2");
                 assert_eq!(test_ent.value, 202);
                 println!("      Sequential updates completed successfully");
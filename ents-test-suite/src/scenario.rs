@@ -0,0 +1,360 @@
+//! Data-driven conformance scenarios.
+//!
+//! A [`Scenario`] is a JSON file listing operations and the observations
+//! expected after them, so a new conformance case is a new file under
+//! `scenarios/` rather than new Rust. Every scenario there runs against each
+//! backend as part of [`run_all_tests`](crate::run_all_tests).
+//!
+//! ```json
+//! {
+//!   "name": "rename a tag",
+//!   "steps": [
+//!     { "op": "create", "as": "red", "type": "Tag",
+//!       "fields": { "name": "red", "color": "#f00" } },
+//!     { "op": "commit" },
+//!     { "op": "update", "ref": "red", "fields": { "name": "crimson" } },
+//!     { "op": "expect_entity", "ref": "red", "fields": { "name": "crimson" } },
+//!     { "op": "commit" }
+//!   ]
+//! }
+//! ```
+//!
+//! Steps run in one transaction until a `commit` or `rollback` step, then
+//! the next steps start a new one; steps after the last commit are rolled
+//! back. Entities are named with `as` when created and referred to by that
+//! name afterwards. A step with `"fails": true` must return an error. Once
+//! the steps are done, every entity the scenario created is deleted again.
+//!
+//! Entity types are the ones of this crate, by name: `TestEntity`, `User`,
+//! `UserWithUniqueEmail`, `Tag` and `Folder`. `id` and `last_updated` can be
+//! left out of `fields`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use ents::{EdgeQuery, EdgeValue, EntWithEdges, Id, Transactional};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    Folder, Tag, TestCaseRunner, TestEntity, TestSuiteRunner, User,
+    UserWithUniqueEmail,
+};
+
+/// Run `$body` with `$E` bound to the entity type named `$name`
+macro_rules! with_type {
+    ($name:expr, $E:ident => $body:expr) => {
+        match $name {
+            "TestEntity" => {
+                type $E = TestEntity;
+                $body
+            }
+            "User" => {
+                type $E = User;
+                $body
+            }
+            "UserWithUniqueEmail" => {
+                type $E = UserWithUniqueEmail;
+                $body
+            }
+            "Tag" => {
+                type $E = Tag;
+                $body
+            }
+            "Folder" => {
+                type $E = Folder;
+                $body
+            }
+            other => bail!("unknown entity type {}", other),
+        }
+    };
+}
+
+/// A named list of steps
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub op: Op,
+    /// The step must fail
+    #[serde(default)]
+    pub fails: bool,
+}
+
+/// One operation or observation. Entities are referred to by the name
+/// they were created `as`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    Create {
+        #[serde(rename = "as")]
+        name: String,
+        #[serde(rename = "type")]
+        type_name: String,
+        #[serde(default)]
+        fields: Map<String, Value>,
+    },
+    /// Overwrite the given fields
+    Update {
+        #[serde(rename = "ref")]
+        name: String,
+        fields: Map<String, Value>,
+    },
+    Delete {
+        #[serde(rename = "ref")]
+        name: String,
+    },
+    CreateEdge {
+        source: String,
+        edge: String,
+        dest: String,
+    },
+    HideEdge {
+        source: String,
+        edge: String,
+        dest: String,
+    },
+    Commit,
+    Rollback,
+    /// The entity exists and its fields include `fields`
+    ExpectEntity {
+        #[serde(rename = "ref")]
+        name: String,
+        #[serde(default)]
+        fields: Map<String, Value>,
+    },
+    ExpectMissing {
+        #[serde(rename = "ref")]
+        name: String,
+    },
+    /// The visible `edge` edges of `source` lead to exactly `dests`
+    ExpectEdges {
+        source: String,
+        edge: String,
+        dests: Vec<String>,
+    },
+}
+
+/// Entities created by a scenario: id and type name by name
+type Refs = HashMap<String, (Id, String)>;
+
+impl Scenario {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))?;
+        Self::from_json(&json)
+            .with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn run<R: TestSuiteRunner>(&self, r: &R) -> anyhow::Result<()> {
+        let mut runner = r.create()?;
+        let mut refs = Refs::new();
+        let mut order = Vec::new();
+        let mut steps = self.steps.iter().enumerate().peekable();
+        let result = loop {
+            if steps.peek().is_none() {
+                break Ok(());
+            }
+            let ran = runner.execute(|txn| {
+                for (i, step) in steps.by_ref() {
+                    match step.op {
+                        Op::Commit => return Ok(txn.commit()?),
+                        Op::Rollback => return Ok(()),
+                        _ => {}
+                    }
+                    let outcome = run_op(&txn, &step.op, &mut refs, &mut order);
+                    match (outcome, step.fails) {
+                        (Ok(()), false) => {}
+                        (Err(_), true) => {}
+                        (Ok(()), true) => {
+                            bail!("step {} ({:?}) did not fail", i, step.op)
+                        }
+                        (Err(e), false) => {
+                            return Err(e.context(format!("step {}", i)))
+                        }
+                    }
+                }
+                Ok(())
+            });
+            if let Err(e) = ran {
+                break Err(e);
+            }
+        };
+
+        let cleanup = runner.execute(|txn| {
+            for name in order.iter().rev() {
+                let (id, type_name) = &refs[name];
+                with_type!(type_name.as_str(), E => txn.delete::<E>(*id)?);
+            }
+            Ok(txn.commit()?)
+        });
+        result
+            .and(cleanup)
+            .with_context(|| format!("scenario {:?}", self.name))
+    }
+}
+
+/// Run every `*.json` scenario in `dir`, in file name order
+pub fn run_scenarios<R: TestSuiteRunner>(
+    r: &R,
+    dir: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let dir = dir.as_ref();
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    for path in paths {
+        Scenario::from_file(&path)?.run(r)?;
+    }
+    Ok(())
+}
+
+/// The scenarios shipped with this crate
+pub fn bundled_scenarios_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios"))
+}
+
+fn run_op<T: Transactional>(
+    txn: &T,
+    op: &Op,
+    refs: &mut Refs,
+    order: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let lookup = |name: &str| {
+        refs.get(name)
+            .cloned()
+            .with_context(|| format!("unknown entity {:?}", name))
+    };
+    match op {
+        Op::Create {
+            name,
+            type_name,
+            fields,
+        } => {
+            ensure!(!refs.contains_key(name), "{:?} already exists", name);
+            let id = with_type!(type_name.as_str(), E => {
+                txn.create(from_fields::<E>(fields.clone())?)?
+            });
+            refs.insert(name.clone(), (id, type_name.clone()));
+            order.push(name.clone());
+        }
+        Op::Update { name, fields } => {
+            let (id, type_name) = lookup(name)?;
+            with_type!(type_name.as_str(), E => {
+                let ent = txn.get_required_as::<E>(id)?;
+                let mut value = serde_json::to_value(&ent)?;
+                merge(&mut value, fields);
+                let updated: E = serde_json::from_value(value)?;
+                txn.update(ent, |e: &mut E| *e = updated)?;
+            });
+        }
+        Op::Delete { name } => {
+            let (id, type_name) = lookup(name)?;
+            with_type!(type_name.as_str(), E => txn.delete::<E>(id)?);
+        }
+        Op::CreateEdge { source, edge, dest } => {
+            let edge = EdgeValue::new(
+                lookup(source)?.0,
+                edge.as_bytes().to_vec(),
+                lookup(dest)?.0,
+            );
+            txn.create_edge(edge)?;
+        }
+        Op::HideEdge { source, edge, dest } => {
+            let edge = EdgeValue::new(
+                lookup(source)?.0,
+                edge.as_bytes().to_vec(),
+                lookup(dest)?.0,
+            );
+            ensure!(txn.hide_edge(&edge)?, "no edge to hide");
+        }
+        Op::Commit | Op::Rollback => {}
+        Op::ExpectEntity { name, fields } => {
+            let (id, type_name) = lookup(name)?;
+            let value = with_type!(type_name.as_str(), E => {
+                let ent = txn
+                    .get_as::<E>(id)?
+                    .with_context(|| format!("{:?} does not exist", name))?;
+                serde_json::to_value(&ent)?
+            });
+            for (field, expected) in fields {
+                let actual = value.get(field).unwrap_or(&Value::Null);
+                ensure!(
+                    actual == expected,
+                    "{}.{} is {}, expected {}",
+                    name,
+                    field,
+                    actual,
+                    expected
+                );
+            }
+        }
+        Op::ExpectMissing { name } => {
+            let (id, _) = lookup(name)?;
+            ensure!(txn.get(id)?.is_none(), "{:?} still exists", name);
+        }
+        Op::ExpectEdges {
+            source,
+            edge,
+            dests,
+        } => {
+            let names = [edge.as_bytes()];
+            let query = EdgeQuery::asc(&names).with_limit(usize::MAX);
+            let mut actual: Vec<Id> = txn
+                .find_edges(lookup(source)?.0, query)?
+                .into_iter()
+                .map(|e| e.dest)
+                .collect();
+            let mut expected = dests
+                .iter()
+                .map(|d| lookup(d).map(|(id, _)| id))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            actual.sort_unstable();
+            expected.sort_unstable();
+            ensure!(
+                actual == expected,
+                "{} edges of {:?} lead to {:?}, expected {:?}",
+                edge,
+                source,
+                actual,
+                dests
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Build an entity from its fields, defaulting the id and timestamp
+fn from_fields<E: EntWithEdges + serde::de::DeserializeOwned>(
+    mut fields: Map<String, Value>,
+) -> anyhow::Result<E> {
+    fields.entry("id").or_insert(Value::from(0));
+    fields.entry("last_updated").or_insert(Value::from(0));
+    Ok(serde_json::from_value(Value::Object(fields))?)
+}
+
+fn merge(value: &mut Value, fields: &Map<String, Value>) {
+    if let Value::Object(object) = value {
+        for (field, v) in fields {
+            object.insert(field.clone(), v.clone());
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use ents::cascade::DeletePolicy;
+use ents::geo::{GeoIndex, GeoPoint, Located};
+use ents::unique::UniqueKey;
 use ents::{
-    DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
-    EntMutationError, EntWithEdges, Id, NullEdgeProvider, Transactional,
+    DraftError, EdgeDraft, EdgeProvider, EdgeValue, Ent, EntMutationError,
+    EntWithEdges, Id, NullEdgeProvider, Transactional,
 };
 use serde::{Deserialize, Serialize};
 
@@ -28,10 +31,7 @@ impl Ent for TestEntity {
     }
 
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -40,6 +40,8 @@ impl EntWithEdges for TestEntity {
     type EdgeProvider = NullEdgeProvider;
 }
 
+ents::register_ent!(TestEntity);
+
 impl TestEntity {
     pub fn new(name: String, value: i32) -> Self {
         Self {
@@ -75,10 +77,7 @@ impl Ent for User {
     }
 
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -87,6 +86,10 @@ impl EntWithEdges for User {
     type EdgeProvider = NullEdgeProvider;
 }
 
+ents::register_ent!(User);
+ents::register_edge!(User => User, b"follows");
+ents::register_acyclic!(b"depends_on");
+
 impl User {
     pub fn new(username: String, email: String) -> Self {
         Self {
@@ -122,70 +125,20 @@ impl Ent for UserWithUniqueEmail {
     }
 
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
 
-/// Edge draft for enforcing unique email constraint
-#[derive(PartialEq)]
-pub struct UniqueEmailDraft {
-    pub user_id: Id,
-    pub email: String,
-}
-
-impl EdgeDraft for UniqueEmailDraft {
-    fn check<T: Transactional>(
-        self,
-        txn: &T,
-    ) -> Result<Vec<EdgeValue>, DraftError> {
-        // Check if any existing user has this email
-        let existing_edges = txn
-            .find_edges(0, EdgeQuery::asc(&[b"unique_email"]))?
-            .into_iter()
-            .filter(|_edge| {
-                // In a real implementation, we'd need to check the email value
-                // For now, this is a placeholder - UNIQUE constraints aren't fully implemented
-                false // This would be replaced with actual uniqueness checking
-            })
-            .collect::<Vec<_>>();
-
-        if !existing_edges.is_empty() {
-            return Err(DraftError::ValidationFailed(format!(
-                "Email '{}' is already taken",
-                self.email
-            )));
-        }
-
-        // Create the unique email edge
-        Ok(vec![EdgeValue::new(
-            0, // Use a special source ID for global constraints
-            b"unique_email".to_vec(),
-            self.user_id,
-        )])
-    }
-}
-
-/// Edge provider for UserWithUniqueEmail that enforces unique email
-pub struct UserWithUniqueEmailEdgeProvider;
-
-impl EdgeProvider<UserWithUniqueEmail> for UserWithUniqueEmailEdgeProvider {
-    type Draft = UniqueEmailDraft;
+impl EntWithEdges for UserWithUniqueEmail {
+    type EdgeProvider = NullEdgeProvider;
 
-    fn draft(ent: &UserWithUniqueEmail) -> Self::Draft {
-        UniqueEmailDraft {
-            user_id: ent.id,
-            email: ent.email.clone(),
-        }
+    fn unique_keys(&self) -> Vec<UniqueKey> {
+        vec![UniqueKey::new("user_email", self.email.as_bytes())]
     }
 }
 
-impl EntWithEdges for UserWithUniqueEmail {
-    type EdgeProvider = UserWithUniqueEmailEdgeProvider;
-}
+ents::register_ent!(UserWithUniqueEmail);
 
 impl UserWithUniqueEmail {
     pub fn new(username: String, email: String) -> Self {
@@ -222,10 +175,7 @@ impl Ent for Tag {
     }
 
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -234,6 +184,8 @@ impl EntWithEdges for Tag {
     type EdgeProvider = NullEdgeProvider;
 }
 
+ents::register_ent!(Tag);
+
 impl Tag {
     pub fn new(name: String, color: String) -> Self {
         Self {
@@ -331,10 +283,7 @@ impl Ent for Post {
     }
 
     fn mark_updated(&mut self) -> Result<(), EntMutationError> {
-        self.last_updated = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        self.last_updated = ents::clock::now_micros();
         Ok(())
     }
 }
@@ -343,6 +292,10 @@ impl EntWithEdges for Post {
     type EdgeProvider = PostEdgeProvider;
 }
 
+ents::register_ent!(Post);
+ents::register_edge!(Post => User, b"author");
+ents::register_edge!(Post => Tag, b"tag");
+
 impl Post {
     pub fn new(
         title: String,
@@ -360,3 +313,114 @@ impl Post {
         }
     }
 }
+
+/// Entity with a location for testing geo queries
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Place {
+    pub name: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub id: Id,
+    pub last_updated: u64,
+}
+
+#[typetag::serde]
+impl Ent for Place {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.last_updated
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        self.last_updated = ents::clock::now_micros();
+        Ok(())
+    }
+}
+
+impl Located for Place {
+    const GEO_INDEX: &'static str = "place";
+
+    fn location(&self) -> Option<GeoPoint> {
+        Some(GeoPoint::new(self.lat, self.lng))
+    }
+}
+
+impl EntWithEdges for Place {
+    type EdgeProvider = GeoIndex;
+}
+
+ents::register_ent!(Place);
+
+impl Place {
+    pub fn new(name: String, lat: f64, lng: f64) -> Self {
+        Self {
+            name,
+            lat,
+            lng,
+            id: 0,
+            last_updated: 0,
+        }
+    }
+}
+
+/// Folder entity for testing delete policies: deleting a folder deletes
+/// its subfolders, is refused while it is locked and hides its shares
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Folder {
+    pub name: String,
+    pub id: Id,
+    pub last_updated: u64,
+}
+
+#[typetag::serde]
+impl Ent for Folder {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.last_updated
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        self.last_updated = ents::clock::now_micros();
+        Ok(())
+    }
+}
+
+impl EntWithEdges for Folder {
+    type EdgeProvider = NullEdgeProvider;
+
+    fn delete_policy<T: Transactional>() -> DeletePolicy<T> {
+        DeletePolicy::new()
+            .cascade::<Folder>(b"subfolder")
+            .restrict(b"locked_by")
+            .tombstone(b"shared_with")
+    }
+}
+
+ents::register_ent!(Folder);
+ents::register_edge!(Folder => Folder, b"subfolder");
+ents::register_edge!(Folder => User, b"locked_by");
+ents::register_edge!(Folder => User, b"shared_with");
+
+impl Folder {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            id: 0,
+            last_updated: 0,
+        }
+    }
+}
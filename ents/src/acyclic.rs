@@ -0,0 +1,128 @@
+//! Relations declared acyclic, such as task dependencies.
+//!
+//! Declaring an edge name with [`register_acyclic!`](crate::register_acyclic)
+//! makes the backends check every new or restored edge of that name: the
+//! edge is rejected with [`DatabaseError::CycleDetected`] when its source is
+//! reachable from its destination through edges of the same name. The search
+//! visits at most [`MAX_CYCLE_SEARCH`] entities and rejects the edge when the
+//! limit is hit, so acyclic relations suit graphs of bounded depth.
+//!
+//! ```ignore
+//! ents::register_acyclic!(b"depends_on");
+//!
+//! txn.create_edge(EdgeValue::new(build, b"depends_on".to_vec(), fetch))?;
+//! // Fails: fetch -> build -> fetch
+//! txn.create_edge(EdgeValue::new(fetch, b"depends_on".to_vec(), build))?;
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, QueryEdge,
+    DEFAULT_EDGE_LIMIT,
+};
+
+/// Most entities visited while checking one edge for a cycle
+pub const MAX_CYCLE_SEARCH: usize = 10_000;
+
+/// An edge name whose edges must not form a cycle
+#[derive(Debug)]
+pub struct AcyclicRelation {
+    pub name: &'static [u8],
+}
+
+impl AcyclicRelation {
+    pub const fn new(name: &'static [u8]) -> Self {
+        Self { name }
+    }
+}
+
+inventory::collect!(AcyclicRelation);
+
+/// Declare an edge name acyclic.
+///
+/// ```ignore
+/// ents::register_acyclic!(b"depends_on");
+/// ```
+#[macro_export]
+macro_rules! register_acyclic {
+    ($name:expr) => {
+        $crate::inventory::submit! {
+            $crate::acyclic::AcyclicRelation::new($name)
+        }
+    };
+}
+
+/// Whether edges named `name` are declared acyclic
+pub fn is_acyclic(name: &[u8]) -> bool {
+    inventory::iter::<AcyclicRelation>
+        .into_iter()
+        .any(|r| r.name == name)
+}
+
+/// Fail with [`DatabaseError::CycleDetected`] if adding `edge` would close
+/// a cycle in its acyclic relation. Edges of other names always pass.
+pub fn check_edge<Q: QueryEdge>(
+    txn: &Q,
+    edge: &EdgeValue,
+) -> Result<(), DatabaseError> {
+    if !is_acyclic(&edge.sort_key) {
+        return Ok(());
+    }
+    let cycle = || DatabaseError::CycleDetected {
+        name: String::from_utf8_lossy(&edge.sort_key).into_owned(),
+        from: edge.source,
+        to: edge.dest,
+    };
+
+    let mut seen = HashSet::from([edge.dest]);
+    let mut queue = VecDeque::from([edge.dest]);
+    while let Some(node) = queue.pop_front() {
+        if node == edge.source {
+            return Err(cycle());
+        }
+        let names = [edge.sort_key.as_slice()];
+        let mut last: Option<Edge> = None;
+        loop {
+            let query = EdgeQuery::asc(&names)
+                .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+            let page = txn.find_edges(node, query)?;
+            let done = page.len() < DEFAULT_EDGE_LIMIT;
+            for next in &page {
+                if seen.insert(next.dest) {
+                    if seen.len() > MAX_CYCLE_SEARCH {
+                        return Err(DatabaseError::Other {
+                            source: format!(
+                                "cycle check for '{}' visited more than {} \
+                                 entities",
+                                String::from_utf8_lossy(&edge.sort_key),
+                                MAX_CYCLE_SEARCH
+                            )
+                            .into(),
+                        });
+                    }
+                    queue.push_back(next.dest);
+                }
+            }
+            last = page.last().cloned();
+            if done {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::register_acyclic!(b"blocks");
+
+    #[test]
+    fn test_is_acyclic() {
+        assert!(is_acyclic(b"blocks"));
+        assert!(!is_acyclic(b"blocks_"));
+        assert!(!is_acyclic(b"follows"));
+    }
+}
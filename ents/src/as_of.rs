@@ -0,0 +1,268 @@
+//! Reading entities and edges as they were at an earlier time.
+//!
+//! Backends answer `get_as_of` and `find_edges_as_of` from what they
+//! already keep: the versions updates replaced (see
+//! [`history`](crate::history)) and the change log (see
+//! [`cdc`](crate::cdc)), read from its newest record back to the first one
+//! committed at or before the requested time. The functions here rewind
+//! the current state with those records.
+//!
+//! ```ignore
+//! let yesterday = now_micros() - 86_400_000_000;
+//! let user = txn.get_as_of(id, yesterday)?;
+//! let query = EdgeQuery::asc(&[b"follows"]);
+//! let follows = txn.find_edges_as_of(id, query, yesterday)?;
+//! ```
+//!
+//! Answers reach back as far as the store remembers:
+//! - Without history, an entity reads as its current state
+//! - Without change capture, entities created since read as if they existed
+//!   and edges read as they are now
+//! - Versions pruned by the retention and records trimmed from the log are
+//!   gone, as is the history of a deleted entity
+//! - The log does not hold edge payloads, so an edge removed since reads
+//!   back without one. Hidden edges are never returned.
+
+use std::collections::BTreeMap;
+
+use crate::cdc::{CapturedChange, ChangeRecord};
+use crate::history::EntityVersion;
+use crate::{Edge, EdgeQuery, Ent, Id, SortOrder};
+
+/// The state of entity `id` at `at`, given its `current` state, its prior
+/// `versions` and the `changes` committed after `at`, both newest first
+pub fn entity_as_of(
+    id: Id,
+    current: Option<Box<dyn Ent>>,
+    versions: Vec<EntityVersion>,
+    changes: &[ChangeRecord],
+    at: u64,
+) -> Option<Box<dyn Ent>> {
+    // The first change after `at` tells whether the entity existed then
+    let first = changes
+        .iter()
+        .rev()
+        .find_map(|record| match &record.change {
+            CapturedChange::Created(e) if e.id == id => Some(false),
+            CapturedChange::Updated(e) | CapturedChange::Deleted(e)
+                if e.id == id =>
+            {
+                Some(true)
+            }
+            _ => None,
+        });
+    if !first.unwrap_or(current.is_some()) {
+        return None;
+    }
+    // The oldest version replaced after `at` was the current one at `at`
+    versions
+        .into_iter()
+        .rev()
+        .find(|version| version.recorded_at > at)
+        .map(|version| version.ent)
+        .or(current)
+}
+
+/// The edges from `source` matching `query` at `at`, given the `current`
+/// edges from it matching the query's names, hidden ones included, and
+/// the `changes` committed after `at`, newest first
+pub fn edges_as_of(
+    source: Id,
+    current: Vec<Edge>,
+    changes: &[ChangeRecord],
+    query: &EdgeQuery,
+) -> Vec<Edge> {
+    let mut edges: BTreeMap<(Vec<u8>, Id, u64), Edge> = current
+        .into_iter()
+        .map(|edge| {
+            ((edge.sort_key.clone(), edge.dest, edge.discriminator), edge)
+        })
+        .collect();
+    for record in changes {
+        let (edge, added) = match &record.change {
+            CapturedChange::EdgeAdded(edge) => (edge, true),
+            CapturedChange::EdgeRemoved(edge) => (edge, false),
+            _ => continue,
+        };
+        if edge.source != source {
+            continue;
+        }
+        let key = (edge.name.clone(), edge.dest, edge.discriminator);
+        // Undo the change: an added edge was absent or hidden before it,
+        // a removed one was visible
+        edges
+            .entry(key)
+            .or_insert_with(|| {
+                Edge::new(source, edge.name.clone(), edge.dest)
+                    .with_discriminator(edge.discriminator)
+            })
+            .hidden = added;
+    }
+
+    let mut visible: Vec<Edge> = edges
+        .into_values()
+        .filter(|edge| {
+            !edge.hidden
+                && (query.edge_names.is_empty()
+                    || query.edge_names.contains(&edge.sort_key.as_slice()))
+        })
+        .collect();
+    if query.order == SortOrder::Desc {
+        visible.reverse();
+    }
+    visible
+        .into_iter()
+        .filter(|edge| {
+            let Some(cursor) = &query.cursor else {
+                return true;
+            };
+            let key = (edge.sort_key.as_slice(), edge.dest, edge.discriminator);
+            let cursor_key =
+                (cursor.sort_key, cursor.destination, cursor.discriminator);
+            match query.order {
+                SortOrder::Asc => key > cursor_key,
+                SortOrder::Desc => key < cursor_key,
+            }
+        })
+        .take(query.max_edges())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdc::{CapturedEdge, CapturedEntity};
+    use crate::{EdgeCursor, EntMutationError};
+
+    #[derive(Clone, serde::Serialize, serde::Deserialize)]
+    struct Note {
+        id: Id,
+        text: String,
+    }
+
+    #[typetag::serde]
+    impl Ent for Note {
+        fn id(&self) -> Id {
+            self.id
+        }
+        fn set_id(&mut self, id: Id) {
+            self.id = id;
+        }
+        fn last_updated(&self) -> u64 {
+            0
+        }
+        fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+            Ok(())
+        }
+    }
+
+    fn note(text: &str) -> Box<dyn Ent> {
+        Box::new(Note {
+            id: 1,
+            text: text.to_string(),
+        })
+    }
+
+    fn text(ent: Option<Box<dyn Ent>>) -> Option<String> {
+        ent.map(|ent| ent.downcast_ref::<Note>().unwrap().text.clone())
+    }
+
+    fn version(version: u64, recorded_at: u64, text: &str) -> EntityVersion {
+        EntityVersion {
+            version,
+            recorded_at,
+            ent: note(text),
+        }
+    }
+
+    fn record(change: CapturedChange) -> ChangeRecord {
+        ChangeRecord {
+            seq: 0,
+            change,
+            committed_at: None,
+        }
+    }
+
+    fn entity(id: Id) -> CapturedEntity {
+        CapturedEntity {
+            id,
+            type_name: "Note".to_string(),
+        }
+    }
+
+    fn edge(dest: Id) -> CapturedEdge {
+        CapturedEdge {
+            source: 1,
+            name: b"follows".to_vec(),
+            dest,
+            discriminator: 0,
+        }
+    }
+
+    #[test]
+    fn test_entity_as_of_picks_version() {
+        let versions = || vec![version(2, 200, "b"), version(1, 100, "a")];
+        let current = || Some(note("c"));
+        assert_eq!(
+            text(entity_as_of(1, current(), versions(), &[], 50)),
+            Some("a".to_string())
+        );
+        assert_eq!(
+            text(entity_as_of(1, current(), versions(), &[], 100)),
+            Some("b".to_string())
+        );
+        assert_eq!(
+            text(entity_as_of(1, current(), versions(), &[], 250)),
+            Some("c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_entity_as_of_follows_log() {
+        // Created after `at`
+        let created = [record(CapturedChange::Created(entity(1)))];
+        let ent = entity_as_of(1, Some(note("a")), vec![], &created, 0);
+        assert!(ent.is_none());
+
+        // Soft-deleted, then restored after `at`
+        let restored = [
+            record(CapturedChange::Created(entity(1))),
+            record(CapturedChange::Deleted(entity(1))),
+        ];
+        assert_eq!(
+            text(entity_as_of(1, Some(note("a")), vec![], &restored, 0)),
+            Some("a".to_string())
+        );
+        // Changes to other entities do not count
+        let other = [record(CapturedChange::Created(entity(2)))];
+        assert!(entity_as_of(1, Some(note("a")), vec![], &other, 0).is_some());
+        assert!(entity_as_of(1, None, vec![], &other, 0).is_none());
+    }
+
+    #[test]
+    fn test_edges_as_of_rewinds_changes() {
+        let current = vec![
+            Edge::new(1, b"follows".to_vec(), 2),
+            Edge::new(1, b"follows".to_vec(), 3).with_hidden(true),
+            Edge::new(1, b"follows".to_vec(), 5).with_payload(b"x".to_vec()),
+        ];
+        let changes = [
+            record(CapturedChange::EdgeRemoved(edge(3))),
+            record(CapturedChange::EdgeRemoved(edge(4))),
+            record(CapturedChange::EdgeAdded(edge(2))),
+        ];
+        let names: &[&[u8]] = &[b"follows"];
+        let dests = |edges: Vec<Edge>| {
+            edges.iter().map(|edge| edge.dest).collect::<Vec<_>>()
+        };
+
+        let edges = edges_as_of(1, current, &changes, &EdgeQuery::asc(names));
+        assert_eq!(dests(edges.clone()), vec![3, 4, 5]);
+        assert_eq!(edges[2].payload, b"x".to_vec());
+
+        let desc = EdgeQuery::desc(names)
+            .with_cursor(EdgeCursor::new(b"follows", 5))
+            .with_limit(1);
+        assert_eq!(dests(edges_as_of(1, edges, &[], &desc)), vec![4]);
+    }
+}
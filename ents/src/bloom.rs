@@ -0,0 +1,196 @@
+//! A bloom filter over entity ids for cheap negative lookups.
+//!
+//! Probing for ids that mostly do not exist, as deduplication checks do,
+//! pays a B-tree lookup per miss. Backends given an [`IdFilter`] answer
+//! `get` for an id the filter has never seen without touching the store.
+//! Creates add their id to the filter, so it only errs towards looking the
+//! id up; deleted ids stay in it until the next rebuild.
+//!
+//! A new filter passes every id until it is first built from the stored
+//! ids with the backend's `rebuild_id_filter`. Rebuild it again
+//! periodically, e.g. once [`IdFilter::needs_rebuild`] says inserts have
+//! outgrown its size, and after other processes wrote to the store, whose
+//! creates it has not seen.
+//!
+//! ```ignore
+//! let filter = Arc::new(IdFilter::new(0.01));
+//! let env = HeedEnv::open(path, None)?.with_id_filter(filter.clone());
+//! env.rebuild_id_filter()?;
+//! ```
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use crate::Id;
+
+/// Ids a filter is sized for at least
+const MIN_CAPACITY: usize = 1024;
+
+/// A bloom filter of the ids stored in a backend
+#[derive(Debug)]
+pub struct IdFilter {
+    false_positive_rate: f64,
+    /// None until first built
+    bits: RwLock<Option<Bits>>,
+}
+
+#[derive(Debug)]
+struct Bits {
+    words: Vec<AtomicU64>,
+    hashes: u32,
+    /// Ids the filter holds at its false positive rate
+    capacity: usize,
+    inserted: AtomicUsize,
+}
+
+impl IdFilter {
+    /// An unbuilt filter that keeps about `false_positive_rate` of the
+    /// missing ids it is asked about once built
+    pub fn new(false_positive_rate: f64) -> Self {
+        Self {
+            false_positive_rate: false_positive_rate.clamp(1e-9, 0.5),
+            bits: RwLock::new(None),
+        }
+    }
+
+    /// Whether `id` may be stored; false only if it is certainly not
+    pub fn may_contain(&self, id: Id) -> bool {
+        let bits = self.bits.read().unwrap_or_else(|e| e.into_inner());
+        bits.as_ref().is_none_or(|bits| bits.contains(id))
+    }
+
+    /// Record that `id` is stored
+    pub fn insert(&self, id: Id) {
+        let bits = self.bits.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(bits) = bits.as_ref() {
+            bits.insert(id);
+        }
+    }
+
+    /// Replace the contents with `ids`, sized for twice as many. Callers
+    /// keep inserts from racing the rebuild, as the backends do by holding
+    /// the write lock.
+    pub fn rebuild(&self, ids: &[Id]) {
+        let new = Bits::new(
+            (ids.len() * 2).max(MIN_CAPACITY),
+            self.false_positive_rate,
+        );
+        for &id in ids {
+            new.insert(id);
+        }
+        *self.bits.write().unwrap_or_else(|e| e.into_inner()) = Some(new);
+    }
+
+    /// Whether the filter was built
+    pub fn is_built(&self) -> bool {
+        self.bits
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Whether the filter holds more ids than it was sized for, so misses
+    /// pass it more often than its false positive rate
+    pub fn needs_rebuild(&self) -> bool {
+        let bits = self.bits.read().unwrap_or_else(|e| e.into_inner());
+        bits.as_ref().is_some_and(|bits| {
+            bits.inserted.load(Ordering::Relaxed) > bits.capacity
+        })
+    }
+}
+
+impl Bits {
+    fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * false_positive_rate.ln() / (ln2 * ln2))
+            .ceil() as usize;
+        let words = bits.div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity as f64 * ln2)
+            .round()
+            .clamp(1.0, 32.0) as u32;
+        Self {
+            words: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            hashes,
+            capacity,
+            inserted: AtomicUsize::new(0),
+        }
+    }
+
+    /// Bit positions of `id`, by double hashing
+    fn positions(&self, id: Id) -> impl Iterator<Item = usize> + '_ {
+        let len = (self.words.len() * 64) as u64;
+        let h1 = mix(id);
+        let h2 = mix(id ^ 0x9e37_79b9_7f4a_7c15) | 1;
+        (0..self.hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+
+    fn insert(&self, id: Id) {
+        for bit in self.positions(id) {
+            self.words[bit / 64].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+        self.inserted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn contains(&self, id: Id) -> bool {
+        self.positions(id).all(|bit| {
+            self.words[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64))
+                != 0
+        })
+    }
+}
+
+/// The splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unbuilt_filter_passes_everything() {
+        let filter = IdFilter::new(0.01);
+        filter.insert(1);
+        assert!(!filter.is_built());
+        assert!(filter.may_contain(2));
+        assert!(!filter.needs_rebuild());
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let filter = IdFilter::new(0.01);
+        let ids: Vec<Id> = (0..5_000).map(|i| i * 7).collect();
+        filter.rebuild(&ids);
+        filter.insert(1);
+        assert!(ids.iter().all(|&id| filter.may_contain(id)));
+        assert!(filter.may_contain(1));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let filter = IdFilter::new(0.01);
+        let ids: Vec<Id> = (0..10_000).collect();
+        filter.rebuild(&ids);
+        let passed = (1_000_000..1_100_000)
+            .filter(|&id| filter.may_contain(id))
+            .count();
+        // Half full, so well under the configured rate
+        assert!(passed < 1_000, "{} of 100000 misses passed", passed);
+    }
+
+    #[test]
+    fn test_needs_rebuild_once_full() {
+        let filter = IdFilter::new(0.01);
+        filter.rebuild(&[]);
+        for id in 0..MIN_CAPACITY as Id {
+            filter.insert(id);
+        }
+        assert!(!filter.needs_rebuild());
+        filter.insert(u64::MAX);
+        assert!(filter.needs_rebuild());
+    }
+}
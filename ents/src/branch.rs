@@ -0,0 +1,658 @@
+//! What-if transactions layered over a base transaction.
+//!
+//! A [`Branch`] keeps its writes in memory instead of the store: reads see
+//! the base transaction with the branch's writes applied on top, and the
+//! base is never written. Once done, the branch is either dropped, which
+//! discards its writes, or [replayed](Branch::replay) onto a real write
+//! transaction, which repeats them in order. This serves previews ("what
+//! would this import change?"), validation runs and tests that must not
+//! touch shared data.
+//!
+//! ```ignore
+//! let txn = env.write_txn()?;
+//! let branch = Branch::new(&txn);
+//! import(&branch, rows)?;
+//! show_preview(&branch)?;
+//! if confirmed {
+//!     branch.replay(&txn)?;
+//!     txn.commit()?;
+//! }
+//! ```
+//!
+//! Entities created in the branch get ids from its [`IdProvider`],
+//! snowflake ids by default, and keep them on replay. Replaying an update
+//! fails with [`DatabaseError::Conflict`] if the entity changed in the
+//! target since the branch read it. `commit` on a branch does nothing, so
+//! code that commits at the end runs unchanged against a branch.
+//!
+//! Deletes apply the entity's [`DeletePolicy`] and assume the default
+//! [`IncomingEdgePolicy`]: the edges pointing at the deleted entity are
+//! removed. Queries merge every matching edge of the base in memory, and
+//! `list_deleted` every soft-deleted id, so a branch suits previews and
+//! tests rather than bulk work.
+//!
+//! [`DeletePolicy`]: crate::cascade::DeletePolicy
+//! [`IncomingEdgePolicy`]: crate::dangling::IncomingEdgePolicy
+
+use std::borrow::BorrowMut;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+use crate::cascade;
+use crate::clock::now_micros;
+use crate::ids::{IdProvider, SnowflakeIds};
+use crate::unique::{self, UniqueKey};
+use crate::{
+    DatabaseError, Edge, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue, Ent,
+    EntWithEdges, EntityCursor, EntityPage, Id, QueryEdge, ReadTransactional,
+    SortOrder, Transactional,
+};
+
+/// Source, name, destination and discriminator of an edge
+type EdgeKey = (Id, Vec<u8>, Id, u64);
+
+/// A write to repeat on replay
+type ReplayOp<T> = Box<dyn FnOnce(&T) -> Result<(), DatabaseError>>;
+
+/// A soft-deleted entity and the time it was deleted at
+type Trashed = (Box<dyn Ent>, u64);
+
+/// Writes of a branch; `None` marks something the branch removed
+#[derive(Default)]
+struct Overlay {
+    entities: BTreeMap<Id, Option<Box<dyn Ent>>>,
+    edges: BTreeMap<EdgeKey, Option<Edge>>,
+    uniques: HashMap<UniqueKey, Option<Id>>,
+    /// Soft-deleted entities
+    deleted: BTreeMap<Id, Option<Trashed>>,
+}
+
+/// A transaction keeping its writes in memory over a base transaction
+pub struct Branch<'b, T> {
+    base: &'b T,
+    overlay: RefCell<Overlay>,
+    ops: RefCell<Vec<ReplayOp<T>>>,
+    /// Whether writes are recorded for replay; off while an edge provider
+    /// runs, since replaying the entity runs the provider again
+    recording: Cell<bool>,
+    ids: Box<dyn IdProvider>,
+}
+
+impl<'b, T: Transactional> Branch<'b, T> {
+    pub fn new(base: &'b T) -> Self {
+        Self {
+            base,
+            overlay: RefCell::new(Overlay::default()),
+            ops: RefCell::new(Vec::new()),
+            recording: Cell::new(true),
+            ids: Box::new(SnowflakeIds::default()),
+        }
+    }
+
+    /// Assign ids of entities created in the branch from `ids`
+    pub fn with_id_provider(mut self, ids: impl IdProvider + 'static) -> Self {
+        self.ids = Box::new(ids);
+        self
+    }
+
+    pub fn base(&self) -> &'b T {
+        self.base
+    }
+
+    /// Number of writes a replay repeats
+    pub fn pending_writes(&self) -> usize {
+        self.ops.borrow().len()
+    }
+
+    /// Repeat the writes of this branch on `target`, in order
+    pub fn replay(self, target: &T) -> Result<(), DatabaseError> {
+        for op in self.ops.into_inner() {
+            op(target)?;
+        }
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        op: impl FnOnce(&T) -> Result<(), DatabaseError> + 'static,
+    ) {
+        if self.recording.get() {
+            self.ops.borrow_mut().push(Box::new(op));
+        }
+    }
+
+    /// Run an edge provider without recording the edges it creates
+    fn setup_edges<E: EntWithEdges>(
+        &self,
+        ent: &E,
+    ) -> Result<(), DatabaseError> {
+        let recording = self.recording.replace(false);
+        let result = ent.setup_edges(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        });
+        self.recording.set(recording);
+        result
+    }
+
+    fn create_as<E: EntWithEdges>(
+        &self,
+        mut ent: E,
+        id: Id,
+    ) -> Result<Id, DatabaseError> {
+        let keys = ent.unique_keys();
+        unique::check_available(self, &keys, None)?;
+        ent.set_id(id);
+        self.overlay
+            .borrow_mut()
+            .entities
+            .insert(id, Some(Box::new(dyn_clone::clone(&ent))));
+        self.setup_edges(&ent)?;
+        self.move_unique_keys(&[], &keys, id);
+        self.record(move |txn| txn.create_with_id(id, ent).map(|_| ()));
+        Ok(id)
+    }
+
+    fn move_unique_keys(&self, old: &[UniqueKey], new: &[UniqueKey], id: Id) {
+        let mut overlay = self.overlay.borrow_mut();
+        for key in old.iter().filter(|k| !new.contains(k)) {
+            overlay.uniques.insert(key.clone(), None);
+        }
+        for key in new {
+            overlay.uniques.insert(key.clone(), Some(id));
+        }
+    }
+
+    /// Current state of one edge, None if it does not exist
+    fn find_edge(
+        &self,
+        edge: &EdgeValue,
+    ) -> Result<Option<Edge>, DatabaseError> {
+        let key = edge_key(edge);
+        if let Some(state) = self.overlay.borrow().edges.get(&key) {
+            return Ok(state.clone());
+        }
+        let names: [&[u8]; 1] = [&edge.sort_key];
+        Ok(self
+            .base
+            .find_edges(edge.source, unbounded(&names))?
+            .into_iter()
+            .find(|e| {
+                e.dest == edge.dest && e.discriminator == edge.discriminator
+            }))
+    }
+
+    fn set_hidden(
+        &self,
+        edge: &EdgeValue,
+        hidden: bool,
+    ) -> Result<bool, DatabaseError> {
+        let Some(mut current) = self.find_edge(edge)? else {
+            return Ok(false);
+        };
+        current.hidden = hidden;
+        self.overlay
+            .borrow_mut()
+            .edges
+            .insert(edge_key(edge), Some(current));
+        Ok(true)
+    }
+
+    /// Edges of the base merged with the branch's edges that `matches`
+    /// accepts, filtered by the names and hidden flag of `query`
+    fn merged_edges(
+        &self,
+        base: Vec<Edge>,
+        query: &EdgeQuery,
+        matches: impl Fn(&EdgeKey) -> bool,
+    ) -> Vec<Edge> {
+        let overlay = self.overlay.borrow();
+        let mut edges: BTreeMap<EdgeKey, Edge> = base
+            .into_iter()
+            .map(|e| {
+                ((e.source, e.sort_key.clone(), e.dest, e.discriminator), e)
+            })
+            .collect();
+        for (key, state) in &overlay.edges {
+            let named = query.edge_names.is_empty()
+                || query.edge_names.contains(&key.1.as_slice());
+            if !named || !matches(key) {
+                continue;
+            }
+            match state {
+                Some(edge) => edges.insert(key.clone(), edge.clone()),
+                None => edges.remove(key),
+            };
+        }
+        edges
+            .into_values()
+            .filter(|e| query.include_hidden || !e.hidden)
+            .collect()
+    }
+}
+
+impl<T: Transactional> QueryEdge for Branch<'_, T> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let base = self.base.find_edges(source, unbounded(query.edge_names))?;
+        let edges = self.merged_edges(base, &query, |k| k.0 == source);
+        Ok(paginate(edges, &query, |e| e.dest))
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        let base =
+            self.base.find_edges_to(dest, unbounded(query.edge_names))?;
+        let edges = self.merged_edges(base, &query, |k| k.2 == dest);
+        Ok(paginate(edges, &query, |e| e.source))
+    }
+}
+
+impl<T: Transactional> ReadTransactional for Branch<'_, T> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if let Some(state) = self.overlay.borrow().entities.get(&id) {
+            return Ok(state.clone());
+        }
+        self.base.get(id)
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        if let Some(state) = self.overlay.borrow().deleted.get(&id) {
+            return Ok(state.as_ref().map(|(ent, _)| ent.clone()));
+        }
+        self.base.get_deleted(id)
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let overlay = self.overlay.borrow();
+        let mut ids: Vec<Id> = self
+            .base
+            .list_deleted(deleted_before, after, usize::MAX)?
+            .into_iter()
+            .filter(|id| !overlay.deleted.contains_key(id))
+            .collect();
+        ids.extend(
+            overlay
+                .deleted
+                .iter()
+                .filter(|(&id, _)| after.is_none_or(|after| id > after))
+                .filter_map(|(&id, state)| match state {
+                    Some((_, at)) if *at < deleted_before => Some(id),
+                    _ => None,
+                }),
+        );
+        ids.sort_unstable();
+        ids.truncate(limit);
+        Ok(ids)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let overlay = self.overlay.borrow();
+        let mut ids: Vec<Id> = overlay
+            .entities
+            .iter()
+            .filter(|(&id, _)| after.is_none_or(|after| id > after))
+            .filter_map(|(&id, ent)| match ent {
+                Some(ent) if ent.typetag_name() == type_name => Some(id),
+                _ => None,
+            })
+            .collect();
+        let mut cursor = after;
+        loop {
+            let page = self.base.list_ids_by_type(type_name, cursor, limit)?;
+            cursor = page.last().copied();
+            let exhausted = page.len() < limit;
+            // Ids the branch deleted or wrote itself are already decided
+            ids.extend(
+                page.into_iter()
+                    .filter(|id| !overlay.entities.contains_key(id)),
+            );
+            // Only ids up to the cursor are known to be the smallest ones
+            let settled = ids.iter().filter(|&&id| Some(id) <= cursor).count();
+            if exhausted || settled >= limit || cursor.is_none() {
+                break;
+            }
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        ids.truncate(limit);
+        Ok(ids)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        if let Some(&owner) = self.overlay.borrow().uniques.get(key) {
+            return Ok(owner);
+        }
+        self.base.find_unique(key)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        if limit == 0 {
+            return Ok((Vec::new(), cursor));
+        }
+        let overlay = self.overlay.borrow();
+        let lower = match cursor {
+            Some(cursor) => Bound::Excluded(cursor.after),
+            None => Bound::Unbounded,
+        };
+        // What the branch wrote or deleted itself is already decided
+        let mut merged: BTreeMap<Id, Option<Box<dyn Ent>>> = overlay
+            .entities
+            .range((lower, Bound::Unbounded))
+            .map(|(&id, state)| (id, state.clone()))
+            .collect();
+        let mut base_cursor = cursor;
+        loop {
+            let (page, next) = self
+                .base
+                .scan_entities(base_cursor, limit.saturating_add(1))?;
+            for ent in page {
+                merged.entry(ent.id()).or_insert(Some(ent));
+            }
+            base_cursor = next;
+            // Only ids up to the base cursor are known to be the smallest
+            // ones; one past the page tells whether the scan goes on
+            let Some(settled) = base_cursor else {
+                break;
+            };
+            let live = merged
+                .range(..=settled.after)
+                .filter(|(_, state)| state.is_some())
+                .count();
+            if live > limit {
+                break;
+            }
+        }
+        let mut ents: Vec<Box<dyn Ent>> = merged
+            .into_values()
+            .flatten()
+            .take(limit.saturating_add(1))
+            .collect();
+        let more = ents.len() > limit;
+        ents.truncate(limit);
+        let next = ents
+            .last()
+            .filter(|_| more)
+            .map(|ent| EntityCursor { after: ent.id() });
+        Ok((ents, next))
+    }
+}
+
+impl<T: Transactional> Transactional for Branch<'_, T> {
+    fn create<E: EntWithEdges>(&self, ent: E) -> Result<Id, DatabaseError> {
+        // The provider may share its sequence with the base's, e.g. two
+        // snowflake generators of the same instance
+        let mut id = self.ids.next_id()?;
+        while self.get(id)?.is_some() {
+            id = self.ids.next_id()?;
+        }
+        self.create_as(ent, id)
+    }
+
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        if self.get(id)?.is_some() {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        self.create_as(ent, id)
+    }
+
+    fn delete<E: EntWithEdges>(&self, id: Id) -> Result<(), DatabaseError> {
+        let Some(ent) = self.get_as::<E>(id)? else {
+            return Ok(());
+        };
+        // Replaying the delete applies the policy again in the target
+        let recording = self.recording.replace(false);
+        let enforced = cascade::enforce::<E, _>(self, id, |edge| {
+            self.overlay.borrow_mut().edges.insert(edge_key(edge), None);
+            Ok(())
+        });
+        self.recording.set(recording);
+        enforced?;
+        self.record(move |txn| txn.delete::<E>(id));
+
+        let all = EdgeQuery::asc(&[]).include_hidden().with_limit(usize::MAX);
+        let edges = self.find_edges_to(id, all)?;
+        self.move_unique_keys(&ent.unique_keys(), &[], id);
+        let mut overlay = self.overlay.borrow_mut();
+        for edge in edges {
+            let key =
+                (edge.source, edge.sort_key, edge.dest, edge.discriminator);
+            overlay.edges.insert(key, None);
+        }
+        overlay.entities.insert(id, None);
+        Ok(())
+    }
+
+    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        // Re-creating an edge keeps it hidden or visible as it was
+        let hidden = self.find_edge(&edge)?.is_some_and(|e| e.hidden);
+        let stored = Edge::new(edge.source, edge.sort_key.clone(), edge.dest)
+            .with_discriminator(edge.discriminator)
+            .with_payload(edge.payload.clone())
+            .with_hidden(hidden);
+        self.overlay
+            .borrow_mut()
+            .edges
+            .insert(edge_key(&edge), Some(stored));
+        self.record(move |txn| txn.create_edge(edge));
+        Ok(())
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        let found = self.set_hidden(edge, true)?;
+        if found {
+            let edge = edge.clone();
+            self.record(move |txn| txn.hide_edge(&edge).map(|_| ()));
+        }
+        Ok(found)
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        let found = self.set_hidden(edge, false)?;
+        if found {
+            let edge = edge.clone();
+            self.record(move |txn| txn.restore_edge(&edge).map(|_| ()));
+        }
+        Ok(found)
+    }
+
+    fn update<E, F, B>(
+        &self,
+        mut ent: B,
+        mutator: F,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: EntWithEdges,
+        F: FnOnce(&mut E),
+        B: BorrowMut<E>,
+    {
+        let ent = ent.borrow_mut();
+        let id = ent.id();
+        let Some(stored) = self.get(id)? else {
+            return Ok(false);
+        };
+        if stored.last_updated() != ent.last_updated() {
+            return Ok(false);
+        }
+        // Replay checks the target still holds what the branch read, unless
+        // the branch wrote the entity itself
+        let from_base = !self.overlay.borrow().entities.contains_key(&id);
+        let read_last_updated = ent.last_updated();
+
+        let draft0 = E::EdgeProvider::draft(ent);
+        let keys0 = ent.unique_keys();
+        mutator(ent);
+        ent.mark_updated().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        let draft1 = E::EdgeProvider::draft(ent);
+        let keys1 = ent.unique_keys();
+        if keys0 != keys1 {
+            unique::check_available(self, &keys1, Some(id))?;
+        }
+
+        if draft0 != draft1 {
+            let draft_error = |e| DatabaseError::Other {
+                source: Box::new(e),
+            };
+            let edges0 = draft0.check(self).map_err(draft_error)?;
+            let edges1 = draft1.check(self).map_err(draft_error)?;
+            let recording = self.recording.replace(false);
+            for edge in &edges0 {
+                self.overlay.borrow_mut().edges.insert(edge_key(edge), None);
+            }
+            let created =
+                edges1.into_iter().try_for_each(|e| self.create_edge(e));
+            self.recording.set(recording);
+            created?;
+        }
+        self.overlay
+            .borrow_mut()
+            .entities
+            .insert(id, Some(Box::new(dyn_clone::clone(&*ent))));
+        self.move_unique_keys(&keys0, &keys1, id);
+
+        let after = dyn_clone::clone(&*ent);
+        self.record(move |txn| {
+            let Some(current) = txn.get_as::<E>(id)? else {
+                return Err(DatabaseError::Other {
+                    source: format!("entity {} not found", id).into(),
+                });
+            };
+            if from_base && current.last_updated() != read_last_updated {
+                return Err(DatabaseError::Conflict {
+                    id,
+                    expected: read_last_updated,
+                    actual: current.last_updated(),
+                });
+            }
+            txn.update(current, |e: &mut E| *e = after).map(|_| ())
+        });
+        Ok(true)
+    }
+
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        let Some(mut ent) = self.get_as::<E>(id)? else {
+            return Ok(false);
+        };
+        ent.mark_updated().map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+        self.overlay
+            .borrow_mut()
+            .entities
+            .insert(id, Some(Box::new(ent)));
+        self.record(move |txn| txn.touch::<E>(id).map(|_| ()));
+        Ok(true)
+    }
+
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        let Some(ent) = self.get_as::<E>(id)? else {
+            return Ok(false);
+        };
+        let mut overlay = self.overlay.borrow_mut();
+        overlay.entities.insert(id, None);
+        overlay
+            .deleted
+            .insert(id, Some((Box::new(ent), now_micros())));
+        self.record(move |txn| txn.soft_delete::<E>(id).map(|_| ()));
+        Ok(true)
+    }
+
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        let Some(ent) = self.get_deleted(id)? else {
+            return Ok(false);
+        };
+        if ent.downcast_ref::<E>().is_none() {
+            return Ok(false);
+        }
+        if self.get(id)?.is_some() {
+            return Err(DatabaseError::AlreadyExists { id });
+        }
+        let mut overlay = self.overlay.borrow_mut();
+        overlay.deleted.insert(id, None);
+        overlay.entities.insert(id, Some(ent));
+        self.record(move |txn| txn.restore::<E>(id).map(|_| ()));
+        Ok(true)
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+}
+
+fn edge_key(edge: &EdgeValue) -> EdgeKey {
+    (
+        edge.source,
+        edge.sort_key.clone(),
+        edge.dest,
+        edge.discriminator,
+    )
+}
+
+/// Every edge named in `names`, hidden ones included
+fn unbounded<'a>(names: &'a [&'a [u8]]) -> EdgeQuery<'a> {
+    EdgeQuery::asc(names)
+        .include_hidden()
+        .with_limit(usize::MAX)
+}
+
+/// Order `edges` by (sort key, `endpoint`, discriminator) and cut the page
+/// `query` asks for
+fn paginate(
+    mut edges: Vec<Edge>,
+    query: &EdgeQuery,
+    endpoint: fn(&Edge) -> Id,
+) -> Vec<Edge> {
+    let key = |e: &Edge| (e.sort_key.clone(), endpoint(e), e.discriminator);
+    edges.sort_by_key(key);
+    if query.order == SortOrder::Desc {
+        edges.reverse();
+    }
+    let cursor = query
+        .cursor
+        .as_ref()
+        .map(|c| (c.sort_key.to_vec(), c.destination, c.discriminator));
+    edges
+        .into_iter()
+        .filter(|e| match (&cursor, query.order) {
+            (None, _) => true,
+            (Some(cursor), SortOrder::Asc) => key(e) > *cursor,
+            (Some(cursor), SortOrder::Desc) => key(e) < *cursor,
+        })
+        .take(query.max_edges())
+        .collect()
+}
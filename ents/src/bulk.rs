@@ -0,0 +1,23 @@
+//! Loading large datasets without per-entity transactions.
+//!
+//! The backends' write transactions provide a `bulk_load` that writes
+//! entities under the ids they already carry and edges as given. It skips
+//! the per-entity work of `create`: edge providers are not run, acyclic
+//! relations and unique keys are neither checked nor claimed, and watchers
+//! are not notified. Store indexes such as the reverse edge index are kept
+//! up to date. Entities sorted by id and edges sorted by source, name,
+//! destination and discriminator load fastest; other orders still load
+//! correctly.
+//!
+//! ```ignore
+//! let txn = env.write_txn()?;
+//! let report = txn.bulk_load(users, follows)?;
+//! txn.commit()?;
+//! ```
+
+/// What a bulk load wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BulkLoadReport {
+    pub entities: usize,
+    pub edges: usize,
+}
@@ -0,0 +1,214 @@
+//! What deleting an entity does along its outgoing edges.
+//!
+//! An entity type declares a [`DeletePolicy`] for the edges it is the source
+//! of, by edge name, in [`EntWithEdges::delete_policy`]:
+//!
+//! - `restrict` refuses to delete the entity while it has such edges
+//! - `cascade` deletes the entity at the other end of each edge as well,
+//!   applying that type's own policy in turn
+//! - `tombstone` hides the edges instead of removing them, so they stay
+//!   behind as a record of the link
+//!
+//! ```ignore
+//! impl EntWithEdges for Post {
+//!     type EdgeProvider = PostEdges;
+//!
+//!     fn delete_policy<T: Transactional>() -> DeletePolicy<T> {
+//!         DeletePolicy::new()
+//!             .cascade::<Comment>(b"comment")
+//!             .restrict(b"pinned_in")
+//!             .tombstone(b"tagged")
+//!     }
+//! }
+//! ```
+//!
+//! Backends [`enforce`] the policy in `delete` once the
+//! [`IncomingEdgePolicy`] has allowed it, and before the edges pointing at
+//! the entity are removed. Edges without a declared policy are left as they
+//! are, and hidden edges are never followed.
+//!
+//! A delete refused by the entity's own restrictions writes nothing, but
+//! one refused further down a cascade, by a restriction on an entity it
+//! reached, comes after the edges and entities above it were already
+//! removed. The transaction is then partly written, so drop it rather than
+//! commit.
+//!
+//! [`IncomingEdgePolicy`]: crate::dangling::IncomingEdgePolicy
+
+use crate::{
+    DatabaseError, EdgeQuery, EdgeValue, EntWithEdges, Id, Transactional,
+};
+
+/// Deletes an entity of the type a cascade was declared with
+type DeleteFn<T> = fn(&T, Id) -> Result<(), DatabaseError>;
+
+/// What happens to one kind of outgoing edge when its source is deleted
+pub enum OnDelete<T> {
+    Restrict,
+    Cascade(DeleteFn<T>),
+    Tombstone,
+}
+
+impl<T> Clone for OnDelete<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for OnDelete<T> {}
+
+/// The rules an entity type applies to its outgoing edges on delete
+pub struct DeletePolicy<T> {
+    rules: Vec<(&'static [u8], OnDelete<T>)>,
+}
+
+impl<T> Default for DeletePolicy<T> {
+    fn default() -> Self {
+        Self { rules: Vec::new() }
+    }
+}
+
+impl<T> DeletePolicy<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the delete while the entity has visible `edge` edges
+    pub fn restrict(self, edge: &'static [u8]) -> Self {
+        self.with_rule(edge, OnDelete::Restrict)
+    }
+
+    /// Hide the `edge` edges, keeping their destinations
+    pub fn tombstone(self, edge: &'static [u8]) -> Self {
+        self.with_rule(edge, OnDelete::Tombstone)
+    }
+
+    /// The declared rules, in order. A later rule for an edge replaces an
+    /// earlier one.
+    pub fn rules(&self) -> &[(&'static [u8], OnDelete<T>)] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    fn with_rule(
+        mut self,
+        edge: &'static [u8],
+        on_delete: OnDelete<T>,
+    ) -> Self {
+        self.rules.retain(|(name, _)| *name != edge);
+        self.rules.push((edge, on_delete));
+        self
+    }
+}
+
+impl<T: Transactional> DeletePolicy<T> {
+    /// Delete the destinations of `edge` edges, which are `D`s
+    pub fn cascade<D: EntWithEdges>(self, edge: &'static [u8]) -> Self {
+        self.with_rule(edge, OnDelete::Cascade(delete_as::<D, T>))
+    }
+}
+
+fn delete_as<D: EntWithEdges, T: Transactional>(
+    txn: &T,
+    id: Id,
+) -> Result<(), DatabaseError> {
+    txn.delete::<D>(id)
+}
+
+/// Apply the delete policy of `E` to the outgoing edges of `id`, for
+/// backends to call before deleting the entity. `remove_edge` deletes an
+/// edge outright; a cascade removes the edge before deleting its
+/// destination, so cycles of cascades end.
+///
+/// The restrictions of `E` are checked before anything is written; those
+/// of the entities a cascade reaches are checked as each is deleted.
+pub fn enforce<E: EntWithEdges, T: Transactional>(
+    txn: &T,
+    id: Id,
+    remove_edge: impl Fn(&EdgeValue) -> Result<(), DatabaseError>,
+) -> Result<(), DatabaseError> {
+    let policy = E::delete_policy::<T>();
+    if policy.is_empty() {
+        return Ok(());
+    }
+
+    let mut found = Vec::with_capacity(policy.rules.len());
+    for &(name, on_delete) in &policy.rules {
+        let edges = outgoing(txn, id, name)?;
+        if matches!(on_delete, OnDelete::Restrict) && !edges.is_empty() {
+            return Err(DatabaseError::Other {
+                source: format!(
+                    "entity {} has {} {} edges",
+                    id,
+                    edges.len(),
+                    String::from_utf8_lossy(name)
+                )
+                .into(),
+            });
+        }
+        found.push((on_delete, edges));
+    }
+
+    for (on_delete, edges) in found {
+        for edge in edges {
+            match on_delete {
+                OnDelete::Restrict => {}
+                OnDelete::Cascade(delete) => {
+                    remove_edge(&edge)?;
+                    delete(txn, edge.dest)?;
+                }
+                OnDelete::Tombstone => {
+                    txn.hide_edge(&edge)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The visible `name` edges leaving `source`
+fn outgoing<T: Transactional>(
+    txn: &T,
+    source: Id,
+    name: &[u8],
+) -> Result<Vec<EdgeValue>, DatabaseError> {
+    let names = [name];
+    let query = EdgeQuery::asc(&names).with_limit(usize::MAX);
+    Ok(txn
+        .find_edges(source, query)?
+        .into_iter()
+        .map(|e| {
+            EdgeValue::new(e.source, e.sort_key, e.dest)
+                .with_discriminator(e.discriminator)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_rule_replaces_earlier() {
+        let policy = DeletePolicy::<()>::new()
+            .restrict(b"owns")
+            .tombstone(b"likes")
+            .tombstone(b"owns");
+        let rules: Vec<_> = policy
+            .rules()
+            .iter()
+            .map(|(name, on_delete)| {
+                (*name, matches!(on_delete, OnDelete::Tombstone))
+            })
+            .collect();
+        assert_eq!(rules, vec![(&b"likes"[..], true), (&b"owns"[..], true)]);
+    }
+
+    #[test]
+    fn test_default_policy_is_empty() {
+        assert!(DeletePolicy::<()>::default().is_empty());
+    }
+}
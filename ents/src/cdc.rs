@@ -0,0 +1,236 @@
+//! Change data capture: a durable log of committed changes.
+//!
+//! Where [`watch`](crate::watch) notifies subscribers in the same process,
+//! change capture appends every committed create, update, delete and edge
+//! change to a log kept in the store itself, written in the transaction
+//! that made the change. Each [`ChangeRecord`] carries a sequence number
+//! that increases with every record, so a consumer such as a search
+//! indexer or a replica remembers the last sequence it processed and asks
+//! for the changes after it.
+//!
+//! Capture is opt-in per backend, and only changes committed while it is
+//! on are logged. The log holds what changed and when its transaction
+//! committed, not the new values: consumers read the entities they care
+//! about from the store.
+//!
+//! ```ignore
+//! env.enable_feature(StoreFeature::ChangeCapture)?;
+//! // ... writes commit ...
+//! let mut after = load_position()?;
+//! for record in env.changes_since(after, 100)? {
+//!     index.apply(&record.change)?;
+//!     after = record.seq;
+//! }
+//! save_position(after)?;
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::ndjson::EdgeName;
+use crate::watch::{ChangeKind, EdgeChange, EntityChange};
+use crate::{DatabaseError, EdgeValue, Id};
+
+/// A committed change as kept in the change log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum CapturedChange {
+    Created(CapturedEntity),
+    Updated(CapturedEntity),
+    Deleted(CapturedEntity),
+    EdgeAdded(CapturedEdge),
+    EdgeRemoved(CapturedEdge),
+}
+
+/// The entity a change applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedEntity {
+    pub id: Id,
+    /// Rust type name of the entity
+    pub type_name: String,
+}
+
+/// The edge a change applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedEdge {
+    pub source: Id,
+    #[serde(serialize_with = "serialize_name")]
+    #[serde(deserialize_with = "deserialize_name")]
+    pub name: Vec<u8>,
+    pub dest: Id,
+    #[serde(default)]
+    pub discriminator: u64,
+}
+
+/// A captured change and its position in the log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeRecord {
+    pub seq: u64,
+    pub change: CapturedChange,
+    /// When the transaction making the change committed, in microseconds.
+    /// None for changes captured before commit times were recorded.
+    pub committed_at: Option<u64>,
+}
+
+/// A change as stored by backends, with the time it committed
+#[derive(Serialize, Deserialize)]
+struct StoredChange<C> {
+    #[serde(flatten)]
+    change: C,
+    #[serde(rename = "at", default, skip_serializing_if = "Option::is_none")]
+    committed_at: Option<u64>,
+}
+
+impl ChangeRecord {
+    /// Encode `change`, committed at `committed_at`, as stored by backends
+    pub fn encode(
+        change: &CapturedChange,
+        committed_at: u64,
+    ) -> Result<String, DatabaseError> {
+        serde_json::to_string(&StoredChange {
+            change,
+            committed_at: Some(committed_at),
+        })
+        .map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+    }
+
+    /// Decode the record `seq` stored by a backend
+    pub fn decode(seq: u64, json: &str) -> Result<Self, DatabaseError> {
+        let stored: StoredChange<CapturedChange> =
+            serde_json::from_str(json).map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+        Ok(Self {
+            seq,
+            change: stored.change,
+            committed_at: stored.committed_at,
+        })
+    }
+}
+
+impl CapturedChange {
+    /// Encode the change as stored by backends
+    pub fn to_json(&self) -> Result<String, DatabaseError> {
+        serde_json::to_string(self).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+    }
+
+    /// Decode a change stored by a backend
+    pub fn from_json(json: &str) -> Result<Self, DatabaseError> {
+        serde_json::from_str(json).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+    }
+}
+
+impl From<&EntityChange> for CapturedChange {
+    fn from(change: &EntityChange) -> Self {
+        let entity = CapturedEntity {
+            id: change.id,
+            type_name: change.type_name.to_string(),
+        };
+        match change.kind {
+            ChangeKind::Created => CapturedChange::Created(entity),
+            ChangeKind::Updated => CapturedChange::Updated(entity),
+            ChangeKind::Deleted => CapturedChange::Deleted(entity),
+        }
+    }
+}
+
+impl From<&EdgeChange> for CapturedChange {
+    fn from(change: &EdgeChange) -> Self {
+        match change {
+            EdgeChange::Added(edge) => CapturedChange::EdgeAdded(edge.into()),
+            EdgeChange::Removed(edge) => {
+                CapturedChange::EdgeRemoved(edge.into())
+            }
+        }
+    }
+}
+
+impl From<&EdgeValue> for CapturedEdge {
+    fn from(edge: &EdgeValue) -> Self {
+        Self {
+            source: edge.source,
+            name: edge.sort_key.clone(),
+            dest: edge.dest,
+            discriminator: edge.discriminator,
+        }
+    }
+}
+
+fn serialize_name<S: Serializer>(
+    name: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    EdgeName::new(name.to_vec()).serialize(serializer)
+}
+
+fn deserialize_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<u8>, D::Error> {
+    Ok(EdgeName::deserialize(deserializer)?.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let edge = CapturedChange::EdgeAdded(CapturedEdge {
+            source: 1,
+            name: b"follows".to_vec(),
+            dest: 2,
+            discriminator: 0,
+        });
+        let json = edge.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"{"op":"edge_added","source":1,"name":"follows","dest":2,"discriminator":0}"#
+        );
+        assert_eq!(CapturedChange::from_json(&json).unwrap(), edge);
+
+        let binary = CapturedChange::EdgeRemoved(CapturedEdge {
+            source: 1,
+            name: vec![0xff, 0],
+            dest: 2,
+            discriminator: 7,
+        });
+        let json = binary.to_json().unwrap();
+        assert_eq!(CapturedChange::from_json(&json).unwrap(), binary);
+
+        let deleted = CapturedChange::Deleted(CapturedEntity {
+            id: 3,
+            type_name: "app::User".to_string(),
+        });
+        let json = deleted.to_json().unwrap();
+        assert_eq!(json, r#"{"op":"deleted","id":3,"type_name":"app::User"}"#);
+        assert_eq!(CapturedChange::from_json(&json).unwrap(), deleted);
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let created = CapturedChange::Created(CapturedEntity {
+            id: 3,
+            type_name: "app::User".to_string(),
+        });
+        let json = ChangeRecord::encode(&created, 1_000).unwrap();
+        assert_eq!(
+            json,
+            r#"{"op":"created","id":3,"type_name":"app::User","at":1000}"#
+        );
+        let record = ChangeRecord::decode(5, &json).unwrap();
+        assert_eq!(record.seq, 5);
+        assert_eq!(record.change, created);
+        assert_eq!(record.committed_at, Some(1_000));
+
+        // Records captured before commit times were recorded
+        let record =
+            ChangeRecord::decode(6, &created.to_json().unwrap()).unwrap();
+        assert_eq!(record.change, created);
+        assert_eq!(record.committed_at, None);
+    }
+}
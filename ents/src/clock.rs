@@ -0,0 +1,127 @@
+//! Where `last_updated` timestamps come from.
+//!
+//! `mark_updated` implementations read the time through [`now_micros`]
+//! instead of calling `SystemTime::now()` themselves. Outside of tests that
+//! is the [`SystemClock`]; [`with_clock`] swaps in another [`Clock`] for the
+//! calls made by a closure on the current thread, such as a [`MockClock`]
+//! that only moves when told to.
+//!
+//! ```ignore
+//! fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+//!     self.last_updated = ents::clock::now_micros();
+//!     Ok(())
+//! }
+//!
+//! let clock = Arc::new(MockClock::new(1_000));
+//! with_clock(clock.clone(), || txn.update(&mut user, |u| u.age += 1))?;
+//! assert_eq!(user.last_updated, 1_000);
+//! ```
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of timestamps
+pub trait Clock: Send + Sync {
+    /// Microseconds since the Unix epoch
+    fn now_micros(&self) -> u64;
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_micros(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64)
+    }
+}
+
+/// A clock for tests that stands still until set or advanced
+#[derive(Debug, Default)]
+pub struct MockClock {
+    micros: AtomicU64,
+}
+
+impl MockClock {
+    pub const fn new(micros: u64) -> Self {
+        Self {
+            micros: AtomicU64::new(micros),
+        }
+    }
+
+    pub fn set(&self, micros: u64) {
+        self.micros.store(micros, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, micros: u64) {
+        self.micros.fetch_add(micros, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_micros(&self) -> u64 {
+        self.micros.load(Ordering::SeqCst)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn Clock>>> =
+        const { RefCell::new(None) };
+}
+
+/// The current time of this thread's clock
+pub fn now_micros() -> u64 {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some(clock) => clock.now_micros(),
+        None => SystemClock.now_micros(),
+    })
+}
+
+/// Run `f` with `clock` as this thread's clock. The previous clock is
+/// restored afterwards, also if `f` panics.
+pub fn with_clock<R>(clock: Arc<dyn Clock>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn Clock>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore =
+        Restore(CURRENT.with(|current| current.replace(Some(clock))));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = Arc::new(MockClock::new(100));
+        let system = now_micros();
+        let (a, b) = with_clock(clock.clone(), || {
+            let a = now_micros();
+            clock.advance(5);
+            (a, now_micros())
+        });
+        assert_eq!((a, b), (100, 105));
+        assert!(now_micros() >= system);
+    }
+
+    #[test]
+    fn test_with_clock_nests() {
+        let outer = Arc::new(MockClock::new(1));
+        let inner = Arc::new(MockClock::new(2));
+        with_clock(outer, || {
+            assert_eq!(with_clock(inner, now_micros), 2);
+            assert_eq!(now_micros(), 1);
+        });
+    }
+}
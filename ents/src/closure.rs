@@ -0,0 +1,254 @@
+//! Transitive closure of a relation, for fast reachability checks such as
+//! "is A an ancestor of B".
+//!
+//! A [`ClosureTable`] keeps, next to the base edges of a relation, an edge
+//! named `closure:<relation>` from every entity to every entity reachable
+//! from it. [`ClosureTable::is_reachable`] is then a single edge lookup, and
+//! everything reachable from or reaching an entity is one edge query.
+//!
+//! The closure is maintained incrementally when the relation is changed
+//! through [`ClosureTable::link`] and [`ClosureTable::unlink`]. Base edges
+//! written directly, or data from before the table was introduced, are
+//! brought up to date with [`ClosureTable::rebuild`]. Closure edges that are
+//! no longer valid are hidden.
+//!
+//! ```ignore
+//! const REPORTS_TO: ClosureTable = ClosureTable::new(b"reports_to");
+//!
+//! REPORTS_TO.link(&txn, alice, bob)?;
+//! REPORTS_TO.link(&txn, bob, carol)?;
+//! assert!(REPORTS_TO.is_reachable(&txn, alice, carol)?);
+//! ```
+
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::ndjson::put_edge;
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, Id, QueryEdge,
+    Transactional, DEFAULT_EDGE_LIMIT,
+};
+
+/// Prefix of the closure edge names
+pub const CLOSURE_EDGE_PREFIX: &[u8] = b"closure:";
+
+/// The transitive closure of the edges named `relation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClosureTable {
+    relation: &'static [u8],
+}
+
+impl ClosureTable {
+    pub const fn new(relation: &'static [u8]) -> Self {
+        Self { relation }
+    }
+
+    pub fn relation(&self) -> &'static [u8] {
+        self.relation
+    }
+
+    fn closure_name(&self) -> Vec<u8> {
+        let mut name = CLOSURE_EDGE_PREFIX.to_vec();
+        name.extend_from_slice(self.relation);
+        name
+    }
+
+    /// Whether `to` is reachable from `from` through at least one edge
+    pub fn is_reachable<Q: QueryEdge>(
+        &self,
+        txn: &Q,
+        from: Id,
+        to: Id,
+    ) -> Result<bool, DatabaseError> {
+        has_edge(txn, from, &self.closure_name(), to)
+    }
+
+    /// Every entity reachable from `from`, in id order
+    pub fn reachable_from<Q: QueryEdge>(
+        &self,
+        txn: &Q,
+        from: Id,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let name = self.closure_name();
+        let names = [name.as_slice()];
+        let mut ids = Vec::new();
+        let mut last: Option<Edge> = None;
+        loop {
+            let query = EdgeQuery::asc(&names)
+                .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+            let page = txn.find_edges(from, query)?;
+            let done = page.len() < DEFAULT_EDGE_LIMIT;
+            ids.extend(page.iter().map(|e| e.dest));
+            last = page.last().cloned();
+            if done {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Every entity `to` is reachable from, in id order
+    pub fn reaching<Q: QueryEdge>(
+        &self,
+        txn: &Q,
+        to: Id,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let name = self.closure_name();
+        let names = [name.as_slice()];
+        let mut ids = Vec::new();
+        let mut last: Option<Edge> = None;
+        loop {
+            let query = EdgeQuery::asc(&names).with_cursor_opt(
+                last.as_ref().map(EdgeCursor::from_incoming_edge),
+            );
+            let page = txn.find_edges_to(to, query)?;
+            let done = page.len() < DEFAULT_EDGE_LIMIT;
+            ids.extend(page.iter().map(|e| e.source));
+            last = page.last().cloned();
+            if done {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Add the edge `from -> to` to the relation and extend the closure
+    pub fn link<T: Transactional>(
+        &self,
+        txn: &T,
+        from: Id,
+        to: Id,
+    ) -> Result<(), DatabaseError> {
+        put_edge(txn, EdgeValue::new(from, self.relation.to_vec(), to), false)?;
+
+        let mut sources = self.reaching(txn, from)?;
+        sources.push(from);
+        let mut targets = self.reachable_from(txn, to)?;
+        targets.push(to);
+
+        let name = self.closure_name();
+        for source in sources {
+            let known: BTreeSet<Id> =
+                self.reachable_from(txn, source)?.into_iter().collect();
+            for &target in &targets {
+                if !known.contains(&target) {
+                    put_edge(
+                        txn,
+                        EdgeValue::new(source, name.clone(), target),
+                        false,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hide the edge `from -> to` of the relation and shrink the closure.
+    /// Returns false if there is no such visible edge.
+    pub fn unlink<T: Transactional>(
+        &self,
+        txn: &T,
+        from: Id,
+        to: Id,
+    ) -> Result<bool, DatabaseError> {
+        if !has_edge(txn, from, self.relation, to)? {
+            return Ok(false);
+        }
+        let mut sources = self.reaching(txn, from)?;
+        sources.push(from);
+        txn.hide_edge(&EdgeValue::new(from, self.relation.to_vec(), to))?;
+        for source in sources {
+            self.sync(txn, source)?;
+        }
+        Ok(true)
+    }
+
+    /// Recompute the closure edges leaving each of `sources` from the base
+    /// edges. Returns the number of closure edges added or hidden.
+    pub fn rebuild<T, I>(
+        &self,
+        txn: &T,
+        sources: I,
+    ) -> Result<usize, DatabaseError>
+    where
+        T: Transactional,
+        I: IntoIterator<Item = Id>,
+    {
+        let mut changed = 0;
+        for source in sources {
+            changed += self.sync(txn, source)?;
+        }
+        Ok(changed)
+    }
+
+    /// Make the closure edges of `source` match its reachable set
+    fn sync<T: Transactional>(
+        &self,
+        txn: &T,
+        source: Id,
+    ) -> Result<usize, DatabaseError> {
+        let actual = self.traverse(txn, source)?;
+        let stored: BTreeSet<Id> =
+            self.reachable_from(txn, source)?.into_iter().collect();
+
+        let name = self.closure_name();
+        let mut changed = 0;
+        for &target in stored.difference(&actual) {
+            txn.hide_edge(&EdgeValue::new(source, name.clone(), target))?;
+            changed += 1;
+        }
+        for &target in actual.difference(&stored) {
+            put_edge(txn, EdgeValue::new(source, name.clone(), target), false)?;
+            changed += 1;
+        }
+        Ok(changed)
+    }
+
+    /// Entities reachable from `source` through the base edges
+    fn traverse<Q: QueryEdge>(
+        &self,
+        txn: &Q,
+        source: Id,
+    ) -> Result<BTreeSet<Id>, DatabaseError> {
+        let names = [self.relation];
+        let mut reached = BTreeSet::new();
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            let mut last: Option<Edge> = None;
+            loop {
+                let query = EdgeQuery::asc(&names)
+                    .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+                let page = txn.find_edges(node, query)?;
+                let done = page.len() < DEFAULT_EDGE_LIMIT;
+                for edge in &page {
+                    if reached.insert(edge.dest) {
+                        queue.push_back(edge.dest);
+                    }
+                }
+                last = page.last().cloned();
+                if done {
+                    break;
+                }
+            }
+        }
+        Ok(reached)
+    }
+}
+
+/// Whether the visible edge `source -> dest` named `name` exists
+fn has_edge<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    dest: Id,
+) -> Result<bool, DatabaseError> {
+    let Some(before) = dest.checked_sub(1) else {
+        return Ok(false);
+    };
+    let names = [name];
+    let query =
+        EdgeQuery::asc(&names).with_cursor(EdgeCursor::new(name, before));
+    let page = txn.find_edges(source, query)?;
+    // Parallel edges to `before` may still precede the first edge to `dest`
+    Ok(page
+        .iter()
+        .take_while(|e| e.dest <= dest)
+        .any(|e| e.dest == dest))
+}
@@ -0,0 +1,89 @@
+//! What deleting an entity does to the edges pointing at it.
+//!
+//! By default `delete` removes the edges whose destination is the deleted
+//! entity. Backends can be configured with an [`IncomingEdgePolicy`] instead:
+//!
+//! - `Remove` deletes the incoming edges with the entity
+//! - `Restrict` refuses to delete an entity that has incoming edges
+//! - `Orphan` leaves the edges in place; they become dangling
+//!
+//! Orphaning keeps deletes cheap, and suits applications that clean up
+//! later rather than on every delete. Backends list the edges whose
+//! destination no longer exists with `find_dangling_edges` and drop them
+//! with `remove_dangling_edges`.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?
+//!     .with_incoming_edges(IncomingEdgePolicy::Orphan);
+//! let txn = env.write_txn()?;
+//! txn.delete::<User>(id)?;
+//! for edge in txn.find_dangling_edges(100)? {
+//!     println!("{} -> {}", edge.source, edge.dest);
+//! }
+//! txn.remove_dangling_edges()?;
+//! ```
+//!
+//! Edges can also dangle from the start, since `create_edge` takes any ids.
+//! Backends opened with `with_edge_integrity(true)` refuse such edges: the
+//! create fails with [`DraftError::SourceNotFound`] or
+//! [`DraftError::DestNotFound`] unless both endpoints exist.
+
+use crate::{DatabaseError, DraftError, EdgeValue, Id};
+
+/// What `delete` does with the edges pointing at the deleted entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IncomingEdgePolicy {
+    /// Delete the edges with the entity
+    #[default]
+    Remove,
+    /// Fail the delete while the entity has incoming edges
+    Restrict,
+    /// Keep the edges, leaving them dangling
+    Orphan,
+}
+
+/// Fail unless both endpoints of `edge` exist, as told by `exists`. The
+/// error's source is a [`DraftError::SourceNotFound`] or
+/// [`DraftError::DestNotFound`].
+pub fn check_endpoints(
+    edge: &EdgeValue,
+    exists: impl Fn(Id) -> Result<bool, DatabaseError>,
+) -> Result<(), DatabaseError> {
+    let missing = if !exists(edge.source)? {
+        DraftError::SourceNotFound(edge.source)
+    } else if !exists(edge.dest)? {
+        DraftError::DestNotFound(edge.dest)
+    } else {
+        return Ok(());
+    };
+    Err(DatabaseError::Other {
+        source: Box::new(missing),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_endpoints() {
+        let exists = |id: Id| Ok(id != 3);
+        let edge = |source, dest| EdgeValue::new(source, b"e".to_vec(), dest);
+        assert!(check_endpoints(&edge(1, 2), exists).is_ok());
+
+        let missing = |result: Result<(), DatabaseError>| match result {
+            Err(DatabaseError::Other { source }) => {
+                source.downcast::<DraftError>().ok().map(|e| *e)
+            }
+            _ => None,
+        };
+        assert!(matches!(
+            missing(check_endpoints(&edge(3, 2), exists)),
+            Some(DraftError::SourceNotFound(3))
+        ));
+        assert!(matches!(
+            missing(check_endpoints(&edge(1, 3), exists)),
+            Some(DraftError::DestNotFound(3))
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+//! Reading stored entities whose type this binary does not know.
+//!
+//! An entity written by a newer or different program may carry a typetag
+//! name that is not compiled into the reader. [`UnknownTypePolicy`] decides
+//! what backends do with it wherever they load entities, on `get` as well as
+//! on scans such as `sample_entities`:
+//!
+//! - `Strict` fails with [`DatabaseError::UnknownType`]
+//! - `Lenient` returns the raw fields as a [`DynamicEnt`]
+//! - `Skip` treats the entity as absent
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?
+//!     .with_unknown_types(UnknownTypePolicy::Lenient);
+//! let ent = env.read_txn()?.get(id)?.unwrap();
+//! if let Some(dynamic) = ent.as_ent::<DynamicEnt>() {
+//!     println!("{} {}", dynamic.type_name, dynamic.to_json());
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{DatabaseError, Ent, EntMutationError, Id};
+
+/// What to do with a stored entity of an unregistered type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownTypePolicy {
+    /// Fail with [`DatabaseError::UnknownType`]
+    #[default]
+    Strict,
+    /// Return the entity as a [`DynamicEnt`]
+    Lenient,
+    /// Treat the entity as absent
+    Skip,
+}
+
+/// An entity of a type unknown to this binary, kept as its JSON fields.
+///
+/// It is read-only: `mark_updated` fails, and as it is not an
+/// [`EntWithEdges`](crate::EntWithEdges) it cannot be created or updated.
+/// It serializes under its own tag; [`DynamicEnt::to_json`] restores the
+/// stored form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DynamicEnt {
+    /// The typetag name the entity was stored with
+    pub type_name: String,
+    pub id: Id,
+    /// The stored fields, without the type tag
+    pub fields: Map<String, Value>,
+}
+
+impl DynamicEnt {
+    /// The entity as it was stored, including the type tag
+    pub fn to_json(&self) -> Value {
+        let mut fields = self.fields.clone();
+        fields.insert("type".to_string(), Value::from(self.type_name.clone()));
+        Value::Object(fields)
+    }
+}
+
+#[typetag::serde]
+impl Ent for DynamicEnt {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.fields
+            .get("last_updated")
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        Err(EntMutationError::Other(format!(
+            "entity of unknown type {} is read-only",
+            self.type_name
+        )))
+    }
+}
+
+/// Deserialize stored entity JSON, handling an unknown type per `policy`.
+/// Returns None if the entity is skipped.
+pub fn decode_ent(
+    data_json: &str,
+    policy: UnknownTypePolicy,
+) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+    let err = match serde_json::from_str::<Box<dyn Ent>>(data_json) {
+        Ok(ent) => return Ok(Some(ent)),
+        Err(err) => err,
+    };
+    let Some((type_name, fields)) = unknown_type(data_json, &err) else {
+        return Err(DatabaseError::Other {
+            source: Box::new(err),
+        });
+    };
+    match policy {
+        UnknownTypePolicy::Strict => {
+            Err(DatabaseError::UnknownType { name: type_name })
+        }
+        UnknownTypePolicy::Lenient => Ok(Some(Box::new(DynamicEnt {
+            type_name,
+            id: 0,
+            fields,
+        }))),
+        UnknownTypePolicy::Skip => Ok(None),
+    }
+}
+
+/// The type tag and remaining fields of `data_json` if `err` is about the
+/// tag being unknown, rather than about a field of a known type
+fn unknown_type(
+    data_json: &str,
+    err: &serde_json::Error,
+) -> Option<(String, Map<String, Value>)> {
+    let Ok(Value::Object(mut fields)) = serde_json::from_str(data_json) else {
+        return None;
+    };
+    let Some(Value::String(type_name)) = fields.remove("type") else {
+        return None;
+    };
+    let unknown = format!("unknown variant `{}`", type_name);
+    err.to_string()
+        .contains(&unknown)
+        .then_some((type_name, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntExt;
+
+    const UNKNOWN: &str =
+        r#"{"type":"Martian","id":3,"last_updated":9,"legs":7}"#;
+
+    #[test]
+    fn test_unknown_type_policies() {
+        assert!(matches!(
+            decode_ent(UNKNOWN, UnknownTypePolicy::Strict),
+            Err(DatabaseError::UnknownType { name }) if name == "Martian"
+        ));
+
+        assert!(decode_ent(UNKNOWN, UnknownTypePolicy::Skip)
+            .unwrap()
+            .is_none());
+
+        let ent = decode_ent(UNKNOWN, UnknownTypePolicy::Lenient)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ent.last_updated(), 9);
+        let dynamic = ent.as_ent::<DynamicEnt>().unwrap();
+        assert_eq!(dynamic.type_name, "Martian");
+        assert_eq!(dynamic.fields["legs"], 7);
+        assert_eq!(
+            dynamic.to_json(),
+            serde_json::from_str::<Value>(UNKNOWN).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_malformed_known_type() {
+        let json = r#"{"type":"DynamicEnt","id":"not a number"}"#;
+        for policy in [UnknownTypePolicy::Lenient, UnknownTypePolicy::Skip] {
+            assert!(matches!(
+                decode_ent(json, policy),
+                Err(DatabaseError::Other { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! Transaction decorators for cross-cutting concerns.
+//!
+//! A [`Decorated`] transaction wraps any backend's transaction and
+//! delegates every call to it, running a [`TxnHook`] around each one. A
+//! hook may refuse an operation before it runs and observes its outcome
+//! afterwards, so concerns like logging, metrics or access checks are
+//! written once instead of per backend. The crate ships three:
+//!
+//! - [`LoggingTxn`] hands a [`TxnEvent`] per operation to a function
+//! - [`MetricsTxn`] counts calls, errors and time per [`TxnOp`] in a shared
+//!   [`TxnMetrics`]
+//! - [`ReadOnlyGuardTxn`] fails every write with
+//!   [`DatabaseError::ReadOnlyViolation`], for code paths that must not
+//!   change the store, such as report generation or GET handlers
+//!
+//! [`TxnStack`] stacks decorators over a transaction. Each layer wraps the
+//! ones added before it, so the last one added sees a call first.
+//!
+//! ```ignore
+//! let metrics = Arc::new(TxnMetrics::new());
+//! let txn = TxnStack::new(env.write_txn()?)
+//!     .with_metrics(metrics.clone())
+//!     .with_logging(|event| eprintln!("{}", event))
+//!     .build();
+//! txn.create(user)?;
+//! txn.commit()?;
+//! println!("{:?}", metrics.get(TxnOp::Create));
+//! ```
+//!
+//! Edges created by an entity's [`EdgeProvider`](crate::EdgeProvider) and
+//! other writes the backend makes on its own are not seen by the hooks.
+
+use std::borrow::BorrowMut;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::unique::UniqueKey;
+use crate::{
+    DatabaseError, Edge, EdgeQuery, EdgeValue, Ent, EntWithEdges, EntityCursor,
+    EntityPage, Id, QueryEdge, ReadTransactional, Transactional,
+};
+
+/// An operation of [`Transactional`] or [`QueryEdge`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TxnOp {
+    Get,
+    /// `create` and `create_with_id`
+    Create,
+    Delete,
+    CreateEdge,
+    HideEdge,
+    RestoreEdge,
+    Update,
+    Touch,
+    SoftDelete,
+    /// `restore`; `purge` runs as a restore and a delete
+    Restore,
+    GetDeleted,
+    ListDeleted,
+    Commit,
+    ListIdsByType,
+    FindUnique,
+    ScanEntities,
+    FindEdges,
+    FindEdgesTo,
+}
+
+impl TxnOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            TxnOp::Get => "get",
+            TxnOp::Create => "create",
+            TxnOp::Delete => "delete",
+            TxnOp::CreateEdge => "create_edge",
+            TxnOp::HideEdge => "hide_edge",
+            TxnOp::RestoreEdge => "restore_edge",
+            TxnOp::Update => "update",
+            TxnOp::Touch => "touch",
+            TxnOp::SoftDelete => "soft_delete",
+            TxnOp::Restore => "restore",
+            TxnOp::GetDeleted => "get_deleted",
+            TxnOp::ListDeleted => "list_deleted",
+            TxnOp::Commit => "commit",
+            TxnOp::ListIdsByType => "list_ids_by_type",
+            TxnOp::FindUnique => "find_unique",
+            TxnOp::ScanEntities => "scan_entities",
+            TxnOp::FindEdges => "find_edges",
+            TxnOp::FindEdgesTo => "find_edges_to",
+        }
+    }
+
+    /// Whether the operation changes the store. Commit does not count: it
+    /// only makes earlier writes durable.
+    pub fn is_write(self) -> bool {
+        matches!(
+            self,
+            TxnOp::Create
+                | TxnOp::Delete
+                | TxnOp::CreateEdge
+                | TxnOp::HideEdge
+                | TxnOp::RestoreEdge
+                | TxnOp::Update
+                | TxnOp::Touch
+                | TxnOp::SoftDelete
+                | TxnOp::Restore
+        )
+    }
+}
+
+impl fmt::Display for TxnOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The outcome of one delegated operation
+#[derive(Debug)]
+pub struct TxnEvent<'a> {
+    pub op: TxnOp,
+    pub elapsed: Duration,
+    /// The error the operation failed with, if any
+    pub error: Option<&'a DatabaseError>,
+}
+
+impl fmt::Display for TxnEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error {
+            None => write!(f, "{} took {:?}", self.op, self.elapsed),
+            Some(e) => {
+                write!(f, "{} failed after {:?}: {}", self.op, self.elapsed, e)
+            }
+        }
+    }
+}
+
+/// Behavior run around every operation of a [`Decorated`] transaction
+pub trait TxnHook {
+    /// Called before `op` runs; an error fails the operation without
+    /// running it
+    fn before(&self, _op: TxnOp) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    /// Called once `op` ran
+    fn after(&self, _event: &TxnEvent<'_>) {}
+}
+
+/// A transaction running a [`TxnHook`] around every call to `T`
+pub struct Decorated<H, T> {
+    hook: H,
+    txn: T,
+}
+
+impl<H: TxnHook, T> Decorated<H, T> {
+    pub fn new(hook: H, txn: T) -> Self {
+        Self { hook, txn }
+    }
+
+    pub fn hook(&self) -> &H {
+        &self.hook
+    }
+
+    /// The underlying transaction, which bypasses the hook
+    pub fn inner(&self) -> &T {
+        &self.txn
+    }
+
+    pub fn into_inner(self) -> T {
+        self.txn
+    }
+
+    fn run<R>(
+        &self,
+        op: TxnOp,
+        f: impl FnOnce(&T) -> Result<R, DatabaseError>,
+    ) -> Result<R, DatabaseError> {
+        run_hooked(&self.hook, op, || f(&self.txn))
+    }
+}
+
+fn run_hooked<H: TxnHook, R>(
+    hook: &H,
+    op: TxnOp,
+    f: impl FnOnce() -> Result<R, DatabaseError>,
+) -> Result<R, DatabaseError> {
+    let started = Instant::now();
+    let result = hook.before(op).and_then(|()| f());
+    hook.after(&TxnEvent {
+        op,
+        elapsed: started.elapsed(),
+        error: result.as_ref().err(),
+    });
+    result
+}
+
+impl<H: TxnHook, T: QueryEdge> QueryEdge for Decorated<H, T> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.run(TxnOp::FindEdges, |txn| txn.find_edges(source, query))
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.run(TxnOp::FindEdgesTo, |txn| txn.find_edges_to(dest, query))
+    }
+}
+
+impl<H: TxnHook, T: Transactional> ReadTransactional for Decorated<H, T> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.run(TxnOp::Get, |txn| txn.get(id))
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.run(TxnOp::GetDeleted, |txn| txn.get_deleted(id))
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.run(TxnOp::ListDeleted, |txn| {
+            txn.list_deleted(deleted_before, after, limit)
+        })
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.run(TxnOp::ListIdsByType, |txn| {
+            txn.list_ids_by_type(type_name, after, limit)
+        })
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        self.run(TxnOp::FindUnique, |txn| txn.find_unique(key))
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        self.run(TxnOp::ScanEntities, |txn| txn.scan_entities(cursor, limit))
+    }
+}
+
+impl<H: TxnHook, T: Transactional> Transactional for Decorated<H, T> {
+    fn create<E: EntWithEdges>(&self, ent: E) -> Result<Id, DatabaseError> {
+        self.run(TxnOp::Create, |txn| txn.create(ent))
+    }
+
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        self.run(TxnOp::Create, |txn| txn.create_with_id(id, ent))
+    }
+
+    fn delete<E: EntWithEdges>(&self, id: Id) -> Result<(), DatabaseError> {
+        self.run(TxnOp::Delete, |txn| txn.delete::<E>(id))
+    }
+
+    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        self.run(TxnOp::CreateEdge, |txn| txn.create_edge(edge))
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.run(TxnOp::HideEdge, |txn| txn.hide_edge(edge))
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.run(TxnOp::RestoreEdge, |txn| txn.restore_edge(edge))
+    }
+
+    fn update<E, F, B>(&self, ent: B, mutator: F) -> Result<bool, DatabaseError>
+    where
+        E: EntWithEdges,
+        F: FnOnce(&mut E),
+        B: BorrowMut<E>,
+    {
+        self.run(TxnOp::Update, |txn| txn.update(ent, mutator))
+    }
+
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.run(TxnOp::Touch, |txn| txn.touch::<E>(id))
+    }
+
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        self.run(TxnOp::SoftDelete, |txn| txn.soft_delete::<E>(id))
+    }
+
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.run(TxnOp::Restore, |txn| txn.restore::<E>(id))
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        let Decorated { hook, txn } = self;
+        run_hooked(&hook, TxnOp::Commit, || txn.commit())
+    }
+}
+
+/// Hook handing every [`TxnEvent`] to a function
+#[derive(Clone)]
+pub struct Logging {
+    logger: Arc<dyn Fn(&TxnEvent<'_>) + Send + Sync>,
+}
+
+impl Logging {
+    pub fn new(logger: impl Fn(&TxnEvent<'_>) + Send + Sync + 'static) -> Self {
+        Self {
+            logger: Arc::new(logger),
+        }
+    }
+}
+
+impl TxnHook for Logging {
+    fn after(&self, event: &TxnEvent<'_>) {
+        (self.logger)(event);
+    }
+}
+
+/// Calls, errors and time spent in one operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub calls: u64,
+    pub errors: u64,
+    pub elapsed: Duration,
+}
+
+/// Per-operation statistics, shared by the transactions recording into it
+#[derive(Debug, Default)]
+pub struct TxnMetrics {
+    ops: Mutex<BTreeMap<TxnOp, OpStats>>,
+}
+
+impl TxnMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Statistics of `op`, zero if it never ran
+    pub fn get(&self, op: TxnOp) -> OpStats {
+        self.lock().get(&op).copied().unwrap_or_default()
+    }
+
+    /// Statistics of every operation that ran, in [`TxnOp`] order
+    pub fn snapshot(&self) -> Vec<(TxnOp, OpStats)> {
+        self.lock()
+            .iter()
+            .map(|(&op, &stats)| (op, stats))
+            .collect()
+    }
+
+    fn record(&self, event: &TxnEvent<'_>) {
+        let mut ops = self.lock();
+        let stats = ops.entry(event.op).or_default();
+        stats.calls += 1;
+        stats.errors += event.error.is_some() as u64;
+        stats.elapsed += event.elapsed;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<TxnOp, OpStats>> {
+        self.ops.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Hook recording into a [`TxnMetrics`]
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    metrics: Arc<TxnMetrics>,
+}
+
+impl TxnHook for Metrics {
+    fn after(&self, event: &TxnEvent<'_>) {
+        self.metrics.record(event);
+    }
+}
+
+/// Hook failing every write
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOnlyGuard;
+
+impl TxnHook for ReadOnlyGuard {
+    fn before(&self, op: TxnOp) -> Result<(), DatabaseError> {
+        if op.is_write() {
+            return Err(DatabaseError::ReadOnlyViolation {
+                operation: op.name().to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub type LoggingTxn<T> = Decorated<Logging, T>;
+pub type MetricsTxn<T> = Decorated<Metrics, T>;
+pub type ReadOnlyGuardTxn<T> = Decorated<ReadOnlyGuard, T>;
+pub type ReadOnlyTxn<T> = ReadOnlyGuardTxn<T>;
+
+impl<T> ReadOnlyTxn<T> {
+    /// Wrap `txn` so that every write fails
+    pub fn read_only(txn: T) -> Self {
+        Decorated::new(ReadOnlyGuard, txn)
+    }
+}
+
+/// Stacks decorators over a transaction
+pub struct TxnStack<T> {
+    txn: T,
+}
+
+impl<T> TxnStack<T> {
+    pub fn new(txn: T) -> Self {
+        Self { txn }
+    }
+
+    /// Wrap the stack with `hook`
+    pub fn with_hook<H: TxnHook>(self, hook: H) -> TxnStack<Decorated<H, T>> {
+        TxnStack::new(Decorated::new(hook, self.txn))
+    }
+
+    /// Hand every operation's outcome to `logger`
+    pub fn with_logging(
+        self,
+        logger: impl Fn(&TxnEvent<'_>) + Send + Sync + 'static,
+    ) -> TxnStack<LoggingTxn<T>> {
+        self.with_hook(Logging::new(logger))
+    }
+
+    /// Record every operation into `metrics`
+    pub fn with_metrics(
+        self,
+        metrics: Arc<TxnMetrics>,
+    ) -> TxnStack<MetricsTxn<T>> {
+        self.with_hook(Metrics { metrics })
+    }
+
+    /// Fail every write
+    pub fn read_only(self) -> TxnStack<ReadOnlyGuardTxn<T>> {
+        self.with_hook(ReadOnlyGuard)
+    }
+
+    pub fn build(self) -> T {
+        self.txn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_record() {
+        let metrics = TxnMetrics::new();
+        let error = DatabaseError::Frozen;
+        for error in [None, Some(&error)] {
+            metrics.record(&TxnEvent {
+                op: TxnOp::Get,
+                elapsed: Duration::from_millis(2),
+                error,
+            });
+        }
+        assert_eq!(
+            metrics.get(TxnOp::Get),
+            OpStats {
+                calls: 2,
+                errors: 1,
+                elapsed: Duration::from_millis(4),
+            }
+        );
+        assert_eq!(metrics.get(TxnOp::Commit), OpStats::default());
+        assert_eq!(metrics.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_read_only_guard() {
+        assert!(ReadOnlyGuard.before(TxnOp::Get).is_ok());
+        assert!(ReadOnlyGuard.before(TxnOp::Commit).is_ok());
+        assert!(matches!(
+            ReadOnlyGuard.before(TxnOp::CreateEdge),
+            Err(DatabaseError::ReadOnlyViolation { operation })
+                if operation == "create_edge"
+        ));
+    }
+}
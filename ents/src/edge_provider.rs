@@ -5,8 +5,12 @@
 
 use std::borrow::BorrowMut;
 
+use serde::{Deserialize, Serialize};
+
+use crate::cascade::DeletePolicy;
 use crate::query_edge::QueryEdge;
-use crate::{DatabaseError, Ent, Id};
+use crate::unique::UniqueKey;
+use crate::{DatabaseError, Ent, EntExt, Id};
 
 /// Represents a validated edge ready to be inserted into the database.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,6 +21,12 @@ pub struct EdgeValue {
     pub sort_key: Vec<u8>,
     /// The destination entity ID
     pub dest: Id,
+    /// Distinguishes parallel edges sharing the same (source, sort_key, dest).
+    /// Plain edges use 0.
+    pub discriminator: u64,
+    /// Data stored with the edge, such as when a follow started. Empty for
+    /// plain edges.
+    pub payload: Vec<u8>,
 }
 
 impl EdgeValue {
@@ -26,8 +36,24 @@ impl EdgeValue {
             source,
             sort_key,
             dest,
+            discriminator: 0,
+            payload: Vec::new(),
         }
     }
+
+    /// Set the discriminator, allowing multiple edges between the same
+    /// endpoints under the same sort key
+    pub fn with_discriminator(mut self, discriminator: u64) -> Self {
+        self.discriminator = discriminator;
+        self
+    }
+
+    /// Attach `payload` to the edge. Creating an edge that already exists
+    /// replaces its payload.
+    pub fn with_payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
 }
 
 /// Errors that can occur when creating an edge draft
@@ -77,6 +103,18 @@ where
 pub trait EntWithEdges: Ent {
     type EdgeProvider: EdgeProvider<Self>;
 
+    /// Keys no other entity may hold at the same time, enforced by the
+    /// backend on create and update
+    fn unique_keys(&self) -> Vec<UniqueKey> {
+        Vec::new()
+    }
+
+    /// What deleting an entity of this type does along its outgoing edges,
+    /// enforced by the backend; see [`crate::cascade`]
+    fn delete_policy<T: Transactional>() -> DeletePolicy<T> {
+        DeletePolicy::new()
+    }
+
     fn setup_edges<T: Transactional>(&self, txn: &T) -> Result<(), DraftError> {
         let draft = Self::EdgeProvider::draft(self);
         for edge in draft.check(txn)? {
@@ -109,6 +147,17 @@ impl<E: Ent> EdgeProvider<E> for NullEdgeProvider {
     }
 }
 
+/// Where a page of [`Transactional::scan_entities`] ended: the next page
+/// starts after entity `after`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityCursor {
+    pub after: Id,
+}
+
+/// A page of [`Transactional::scan_entities`] and the cursor of the next
+/// one, None once the scan is done
+pub type EntityPage = (Vec<Box<dyn Ent>>, Option<EntityCursor>);
+
 /// A trait for abstracting database transactions and operations.
 ///
 /// This trait provides a unified interface for performing CRUD (Create, Read, Update, Delete)
@@ -118,19 +167,45 @@ impl<E: Ent> EdgeProvider<E> for NullEdgeProvider {
 ///
 /// # Key Features
 ///
-/// - **Entity Management**: `insert`, `get`, `remove`, `update` entities.
-/// - **Edge Management**: `add_edge`, `remove_edge`.
+/// - **Entity Management**: `insert`, `get`, `remove`, `update` entities, and
+///   `soft_delete`, `restore` or `purge` them.
+/// - **Edge Management**: `create_edge`, `hide_edge`, `restore_edge`.
 /// - **Querying**: Find edges (`find_edge`, `find_edges_in`), find entities by type (`find_by_type`).
 /// - **Concurrency Control**: `update` supports optimistic concurrency control via CAS (Compare-And-Set).
-pub trait Transactional: QueryEdge {
-    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError>;
-
+pub trait Transactional: ReadTransactional {
     fn create<E: EntWithEdges>(&self, ent: E) -> Result<Id, DatabaseError>;
 
+    /// Create `ent` under `id` instead of a newly assigned id, e.g. to keep
+    /// the ids of imported data. Fails with [`DatabaseError::AlreadyExists`]
+    /// if an entity has the id.
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError>;
+
     fn delete<E: EntWithEdges>(&self, id: Id) -> Result<(), DatabaseError>;
 
+    /// Store `edge`. Re-creating an existing edge replaces its payload and
+    /// keeps it hidden or visible as it was.
     fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError>;
 
+    /// Hide an edge without removing it. Hidden edges are skipped by
+    /// `find_edges` unless the query includes them.
+    ///
+    /// Returns false if the edge does not exist.
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError>;
+
+    /// Restore a previously hidden edge.
+    ///
+    /// Returns false if the edge does not exist.
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError>;
+
+    /// Apply `mutator` to `ent`, mark it updated and write it back. If the
+    /// result serializes to the stored bytes and its edge draft did not
+    /// change, nothing is written and `ent` keeps its `last_updated`.
+    ///
+    /// Returns false if the entity was updated since `ent` was loaded.
     fn update<T, F, B>(
         &self,
         ent: B,
@@ -141,7 +216,204 @@ pub trait Transactional: QueryEdge {
         F: FnOnce(&mut T),
         B: BorrowMut<T>;
 
+    /// Like `update`, but a lost race fails with
+    /// [`DatabaseError::Conflict`] instead of returning false, and a
+    /// missing entity with [`DatabaseError::Other`].
+    fn try_update<T, F, B>(
+        &self,
+        ent: B,
+        mutator: F,
+    ) -> Result<(), DatabaseError>
+    where
+        T: EntWithEdges,
+        F: FnOnce(&mut T),
+        B: BorrowMut<T>,
+    {
+        let (id, expected) = {
+            let ent: &T = ent.borrow();
+            (ent.id(), ent.last_updated())
+        };
+        if self.update(ent, mutator)? {
+            return Ok(());
+        }
+        match self.get(id)? {
+            Some(current) => Err(DatabaseError::Conflict {
+                id,
+                expected,
+                actual: current.last_updated(),
+            }),
+            None => Err(DatabaseError::Other {
+                source: format!("entity {} not found", id).into(),
+            }),
+        }
+    }
+
+    /// Load entity `id` as a `T` and update it with `mutator`, reloading
+    /// and applying `mutator` again while the update loses a race, for at
+    /// most `max_attempts` attempts. Returns the updated entity.
+    ///
+    /// Fails with [`DatabaseError::Conflict`] once the attempts run out.
+    fn retry_update<T, F>(
+        &self,
+        id: Id,
+        max_attempts: usize,
+        mut mutator: F,
+    ) -> Result<T, DatabaseError>
+    where
+        T: EntWithEdges,
+        F: FnMut(&mut T),
+    {
+        let mut conflict = None;
+        for _ in 0..max_attempts {
+            let mut ent = self.get_required_as::<T>(id)?;
+            match self.try_update(&mut ent, &mut mutator) {
+                Ok(()) => return Ok(ent),
+                Err(e @ DatabaseError::Conflict { .. }) => conflict = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(conflict.unwrap_or_else(|| DatabaseError::Other {
+            source: "retry_update needs at least one attempt".into(),
+        }))
+    }
+
+    /// Mark entity `id` updated and write it back without changing its
+    /// fields, e.g. to invalidate caches or move it up a by-recency list.
+    /// Edges derived from `last_updated` are refreshed as in `update`.
+    ///
+    /// Returns false if the entity does not exist or is not an `E`.
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError>;
+
+    /// Hide entity `id` from `get` and scans without removing it. Its
+    /// edges and unique keys stay in place and no delete policy runs, so
+    /// [`restore`](Self::restore) brings it back as it was.
+    ///
+    /// Returns false if the entity does not exist or is not an `E`.
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError>;
+
+    /// Bring back the soft-deleted entity `id`. Fails with
+    /// [`DatabaseError::AlreadyExists`] if an entity was created under its
+    /// id since.
+    ///
+    /// Returns false if no `E` was soft-deleted under `id`.
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError>;
+
+    /// Delete the soft-deleted entity `id` for good, applying its delete
+    /// policy like `delete`. Watchers see it restored and then deleted.
+    ///
+    /// Returns false if no `E` was soft-deleted under `id`.
+    fn purge<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        if !self.restore::<E>(id)? {
+            return Ok(false);
+        }
+        self.delete::<E>(id)?;
+        Ok(true)
+    }
+
     fn commit(self) -> Result<(), DatabaseError>;
+
+    /// The entity holding `key` as an `E`, or else a new entity from
+    /// `factory`, which must hold `key` itself. The second value tells
+    /// whether the entity was created. Fails if `key` is held by an entity
+    /// of another type.
+    fn get_or_create<E, F>(
+        &self,
+        key: &UniqueKey,
+        factory: F,
+    ) -> Result<(E, bool), DatabaseError>
+    where
+        E: EntWithEdges,
+        F: FnOnce() -> E,
+    {
+        if let Some(id) = self.find_unique(key)? {
+            return Ok((self.get_required_as::<E>(id)?, false));
+        }
+        let ent = factory();
+        if !ent.unique_keys().contains(key) {
+            return Err(DatabaseError::Other {
+                source: format!(
+                    "get_or_create factory does not hold key '{}'",
+                    key.name
+                )
+                .into(),
+            });
+        }
+        let id = self.create(ent)?;
+        Ok((self.get_required_as::<E>(id)?, true))
+    }
+}
+
+/// Read access to entities and edges.
+///
+/// Implemented by read-only transactions, and by every [`Transactional`],
+/// of which it is a supertrait, so read helpers accept either.
+pub trait ReadTransactional: QueryEdge {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError>;
+
+    /// The soft-deleted entity `id`
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError>;
+
+    /// Ids of the entities soft-deleted before `deleted_before`, in
+    /// microseconds, in id order, starting after `after` and returning at
+    /// most `limit`; e.g. for a job purging them after a retention period
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError>;
+
+    /// Ids of the entities whose typetag name is `type_name`, in id order,
+    /// starting after `after` and returning at most `limit`
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError>;
+
+    /// The entity holding the unique key `key`
+    fn find_unique(&self, key: &UniqueKey)
+        -> Result<Option<Id>, DatabaseError>;
+
+    /// Every entity in id order, at most `limit` at a time, e.g. to
+    /// reindex a store. Start with a `cursor` of None and pass the returned
+    /// cursor until it is None. A page may hold fewer than `limit`
+    /// entities when stored entities are skipped per the unknown type
+    /// policy. A `limit` of 0 returns an empty page and `cursor` as is.
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError>;
+
+    /// Load entity `id` as a `T`. Returns None if the entity does not exist
+    /// or is of another type.
+    fn get_as<T: Ent>(&self, id: Id) -> Result<Option<T>, DatabaseError> {
+        Ok(self.get(id)?.and_then(|e| e.into_ent::<T>()))
+    }
+
+    /// Load entity `id` as a `T`, failing if it does not exist or is of
+    /// another type.
+    fn get_required_as<T: Ent>(&self, id: Id) -> Result<T, DatabaseError> {
+        let ent = self.get(id)?.ok_or_else(|| DatabaseError::Other {
+            source: format!("entity {} not found", id).into(),
+        })?;
+        ent.into_ent::<T>().ok_or_else(|| DatabaseError::Other {
+            source: format!(
+                "entity {} is not a {}",
+                id,
+                std::any::type_name::<T>()
+            )
+            .into(),
+        })
+    }
 }
 
 impl<T1, T2> EdgeDraft for (T1, T2)
@@ -170,5 +442,22 @@ mod tests {
         assert_eq!(edge.source, 1);
         assert_eq!(edge.sort_key, b"connects_to");
         assert_eq!(edge.dest, 2);
+        assert_eq!(edge.discriminator, 0);
+    }
+
+    #[test]
+    fn test_edge_value_discriminator() {
+        let edge =
+            EdgeValue::new(1, b"payment".to_vec(), 2).with_discriminator(7);
+        assert_eq!(edge.discriminator, 7);
+        assert_ne!(edge, EdgeValue::new(1, b"payment".to_vec(), 2));
+    }
+
+    #[test]
+    fn test_edge_value_payload() {
+        let edge = EdgeValue::new(1, b"follows".to_vec(), 2);
+        assert!(edge.payload.is_empty());
+        let edge = edge.with_payload(b"2024-01-01".as_slice());
+        assert_eq!(edge.payload, b"2024-01-01");
     }
 }
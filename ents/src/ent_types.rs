@@ -0,0 +1,75 @@
+//! Creating entities whose type is only known at runtime.
+//!
+//! `Transactional::create` needs the concrete entity type, while loaders
+//! such as [`fixtures`](crate::fixtures) and [`ndjson`](crate::ndjson)
+//! deserialize entities as `Box<dyn Ent>`. [`EntTypes`] lists the types a
+//! loader may create and dispatches each entity to the `create` of its
+//! type.
+//!
+//! ```ignore
+//! let types = EntTypes::new().with_type::<User>().with_type::<Post>();
+//! let id = types.create(&txn, ent, None)?;
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::{DatabaseError, Ent, EntExt, EntWithEdges, Id, Transactional};
+
+type Creator<T> = fn(&T, Box<dyn Ent>, Option<Id>) -> Result<Id, DatabaseError>;
+
+/// A set of entity types that can be created in transactions of type `T`
+pub struct EntTypes<T> {
+    creators: HashMap<TypeId, Creator<T>>,
+}
+
+impl<T: Transactional> Default for EntTypes<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Transactional> EntTypes<T> {
+    pub fn new() -> Self {
+        Self {
+            creators: HashMap::new(),
+        }
+    }
+
+    /// Add the entity type `E`
+    pub fn with_type<E: EntWithEdges>(mut self) -> Self {
+        self.creators.insert(TypeId::of::<E>(), create_as::<T, E>);
+        self
+    }
+
+    /// Create `ent` as its concrete type, under `id` if given. Fails with
+    /// [`DatabaseError::UnknownType`] if the type is not in the set.
+    pub fn create(
+        &self,
+        txn: &T,
+        ent: Box<dyn Ent>,
+        id: Option<Id>,
+    ) -> Result<Id, DatabaseError> {
+        let creator = self
+            .creators
+            .get(&(&*ent as &dyn Any).type_id())
+            .ok_or_else(|| DatabaseError::UnknownType {
+                name: ent.typetag_name().to_string(),
+            })?;
+        creator(txn, ent, id)
+    }
+}
+
+fn create_as<T: Transactional, E: EntWithEdges>(
+    txn: &T,
+    ent: Box<dyn Ent>,
+    id: Option<Id>,
+) -> Result<Id, DatabaseError> {
+    let ent = ent.into_ent::<E>().ok_or_else(|| DatabaseError::Other {
+        source: "entity does not match its type id".into(),
+    })?;
+    match id {
+        Some(id) => txn.create_with_id(id, ent),
+        None => txn.create(ent),
+    }
+}
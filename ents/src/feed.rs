@@ -0,0 +1,123 @@
+//! Feed fan-out utilities built on timeline edges.
+//!
+//! Publishing an item writes one timeline edge per follower:
+//! `follower --feed||reverse_timestamp--> item`, with the actor stored as the
+//! edge discriminator so the same item shared by different actors does not
+//! collide. Large follower sets are written in batches, one transaction per
+//! batch, reporting a checkpoint after each commit so an interrupted fan-out
+//! can resume where it stopped.
+
+use crate::timeline;
+use crate::{DatabaseError, Edge, EdgeValue, Id, QueryEdge, Transactional};
+
+/// Sort key name used for feed edges
+pub const FEED_EDGE: &[u8] = b"feed";
+
+/// Progress of a fan-out, reported after each committed batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FanoutProgress {
+    /// Number of followers whose feed edge has been committed
+    pub processed: usize,
+    /// The last follower written, if any
+    pub last_follower: Option<Id>,
+}
+
+/// A single entry of a user's feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// The published item
+    pub item: Id,
+    /// The entity that published the item
+    pub actor: Id,
+    /// Publication timestamp
+    pub timestamp: u64,
+}
+
+impl FeedEntry {
+    /// Convert a feed edge back into an entry
+    pub fn from_edge(edge: &Edge) -> Option<Self> {
+        let timestamp =
+            timeline::parse_timeline_key(FEED_EDGE, &edge.sort_key)?;
+        Some(Self {
+            item: edge.dest,
+            actor: edge.discriminator,
+            timestamp,
+        })
+    }
+
+    /// The feed edge of this entry in `user`'s feed, usable as a cursor
+    pub fn to_edge(&self, user: Id) -> Edge {
+        Edge::new(
+            user,
+            timeline::timeline_key(FEED_EDGE, self.timestamp),
+            self.item,
+        )
+        .with_discriminator(self.actor)
+    }
+
+    fn to_edge_value(self, user: Id) -> EdgeValue {
+        timeline::timeline_edge(user, FEED_EDGE, self.timestamp, self.item)
+            .with_discriminator(self.actor)
+    }
+}
+
+/// Write `item` into the feed of every follower.
+///
+/// Followers are written in batches of `batch_size`, each in its own
+/// transaction obtained from `begin` and committed before `checkpoint` is
+/// called with the progress so far. To resume an interrupted fan-out, skip
+/// the first `processed` followers of the last reported checkpoint.
+pub fn fanout_write<T, B, I, C>(
+    mut begin: B,
+    actor: Id,
+    followers: I,
+    item: Id,
+    timestamp: u64,
+    batch_size: usize,
+    mut checkpoint: C,
+) -> Result<FanoutProgress, DatabaseError>
+where
+    T: Transactional,
+    B: FnMut() -> Result<T, DatabaseError>,
+    I: IntoIterator<Item = Id>,
+    C: FnMut(&FanoutProgress),
+{
+    let entry = FeedEntry {
+        item,
+        actor,
+        timestamp,
+    };
+    let batch_size = batch_size.max(1);
+    let mut progress = FanoutProgress::default();
+    let mut followers = followers.into_iter().peekable();
+
+    while followers.peek().is_some() {
+        let txn = begin()?;
+        let mut written = 0;
+        for follower in followers.by_ref().take(batch_size) {
+            txn.create_edge(entry.to_edge_value(follower))?;
+            progress.last_follower = Some(follower);
+            written += 1;
+        }
+        txn.commit()?;
+
+        progress.processed += written;
+        checkpoint(&progress);
+    }
+
+    Ok(progress)
+}
+
+/// Read up to `limit` entries of `user`'s feed, newest first.
+///
+/// Pass the last entry of the previous page as `cursor` to continue.
+pub fn read_feed<Q: QueryEdge>(
+    txn: &Q,
+    user: Id,
+    cursor: Option<&FeedEntry>,
+    limit: usize,
+) -> Result<Vec<FeedEntry>, DatabaseError> {
+    let cursor = cursor.map(|entry| entry.to_edge(user));
+    let edges = timeline::page(txn, user, FEED_EDGE, cursor.as_ref(), limit)?;
+    Ok(edges.iter().filter_map(FeedEntry::from_edge).collect())
+}
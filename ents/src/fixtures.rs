@@ -0,0 +1,209 @@
+//! Seed data for tests, examples and demo environments.
+//!
+//! A fixture file is JSON listing entities, each in its stored form plus a
+//! symbolic `$name`, and edges between those names. Wherever an entity
+//! field holds `{"$ref": "<name>"}`, the id of the named entity is put in
+//! its place, so a post can point at its author before either has an id.
+//! Entities are created in file order through `Transactional::create`, so
+//! edge providers run and unique keys are claimed, and a reference must
+//! come after the entity it names.
+//!
+//! ```json
+//! {
+//!   "entities": [
+//!     {"$name": "alice", "type": "User", "username": "alice",
+//!      "email": "alice@example.com", "id": 0, "last_updated": 0},
+//!     {"$name": "hello", "type": "Post", "title": "Hello",
+//!      "content": "...", "author_id": {"$ref": "alice"}, "tag_ids": [],
+//!      "id": 0, "last_updated": 0}
+//!   ],
+//!   "edges": [
+//!     {"source": "alice", "name": "pinned", "dest": "hello"}
+//!   ]
+//! }
+//! ```
+//!
+//! Edge endpoints are names or numeric ids of existing entities. Creating an
+//! entity needs its concrete type, so the types a file uses are registered
+//! with the loader:
+//!
+//! ```ignore
+//! let loader = FixtureLoader::new().with_type::<User>().with_type::<Post>();
+//! let ids = loader.load_file(&txn, "fixtures/blog.json")?;
+//! let alice = ids["alice"];
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::ent_types::EntTypes;
+use crate::{DatabaseError, EdgeValue, Ent, EntWithEdges, Id, Transactional};
+
+/// Ids of the fixture entities by their `$name`
+pub type FixtureIds = BTreeMap<String, Id>;
+
+/// Creates the entities and edges of fixture files in a transaction
+pub struct FixtureLoader<T> {
+    types: EntTypes<T>,
+}
+
+impl<T: Transactional> Default for FixtureLoader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Transactional> FixtureLoader<T> {
+    pub fn new() -> Self {
+        Self {
+            types: EntTypes::new(),
+        }
+    }
+
+    /// Allow fixture entities of type `E`
+    pub fn with_type<E: EntWithEdges>(mut self) -> Self {
+        self.types = self.types.with_type::<E>();
+        self
+    }
+
+    /// Load the fixture file at `path`
+    pub fn load_file<P: AsRef<Path>>(
+        &self,
+        txn: &T,
+        path: P,
+    ) -> Result<FixtureIds, DatabaseError> {
+        let json = std::fs::read_to_string(path).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        })?;
+        self.load(txn, &json)
+    }
+
+    /// Load fixtures from the JSON text `json`
+    pub fn load(
+        &self,
+        txn: &T,
+        json: &str,
+    ) -> Result<FixtureIds, DatabaseError> {
+        let file: FixtureFile =
+            serde_json::from_str(json).map_err(|e| DatabaseError::Other {
+                source: Box::new(e),
+            })?;
+
+        let mut ids = FixtureIds::new();
+        for mut fields in file.entities {
+            let name = match fields.remove("$name") {
+                Some(Value::String(name)) => Some(name),
+                None => None,
+                Some(_) => return Err(fixture_error("$name must be a string")),
+            };
+            let mut value = Value::Object(fields);
+            resolve_refs(&mut value, &ids)?;
+            let ent: Box<dyn Ent> =
+                serde_json::from_value(value).map_err(|e| {
+                    DatabaseError::Other {
+                        source: Box::new(e),
+                    }
+                })?;
+            let id = self.types.create(txn, ent, None)?;
+            if let Some(name) = name {
+                if ids.insert(name.clone(), id).is_some() {
+                    return Err(fixture_error(format!(
+                        "duplicate fixture name {}",
+                        name
+                    )));
+                }
+            }
+        }
+
+        for edge in file.edges {
+            let source = edge.source.resolve(&ids)?;
+            let dest = edge.dest.resolve(&ids)?;
+            txn.create_edge(
+                EdgeValue::new(source, edge.name.into_bytes(), dest)
+                    .with_discriminator(edge.discriminator),
+            )?;
+        }
+        Ok(ids)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixtureFile {
+    #[serde(default)]
+    entities: Vec<Map<String, Value>>,
+    #[serde(default)]
+    edges: Vec<FixtureEdge>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixtureEdge {
+    source: FixtureRef,
+    name: String,
+    dest: FixtureRef,
+    #[serde(default)]
+    discriminator: u64,
+}
+
+/// An edge endpoint: an entity id, or the `$name` of a fixture entity
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FixtureRef {
+    Id(Id),
+    Name(String),
+}
+
+impl FixtureRef {
+    fn resolve(&self, ids: &FixtureIds) -> Result<Id, DatabaseError> {
+        match self {
+            FixtureRef::Id(id) => Ok(*id),
+            FixtureRef::Name(name) => lookup(ids, name),
+        }
+    }
+}
+
+/// Replace every `{"$ref": name}` in `value` by the id of `name`
+fn resolve_refs(
+    value: &mut Value,
+    ids: &FixtureIds,
+) -> Result<(), DatabaseError> {
+    match value {
+        Value::Object(fields) => {
+            if let (1, Some(target)) = (fields.len(), fields.get("$ref")) {
+                let name = target
+                    .as_str()
+                    .ok_or_else(|| fixture_error("$ref must be a string"))?;
+                *value = Value::from(lookup(ids, name)?);
+                return Ok(());
+            }
+            for field in fields.values_mut() {
+                resolve_refs(field, ids)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                resolve_refs(item, ids)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn lookup(ids: &FixtureIds, name: &str) -> Result<Id, DatabaseError> {
+    ids.get(name).copied().ok_or_else(|| {
+        fixture_error(format!("no fixture entity named {} before here", name))
+    })
+}
+
+fn fixture_error(message: impl Into<String>) -> DatabaseError {
+    DatabaseError::Other {
+        source: message.into().into(),
+    }
+}
@@ -0,0 +1,150 @@
+//! How entities are written as JSON.
+//!
+//! Both backends store an entity as the JSON text produced by a
+//! [`JsonFormat`]. The default is compact JSON with fields in the order serde
+//! emits them, which is what earlier versions wrote. Sorting keys makes the
+//! stored text depend only on the entity's contents, so two stores written
+//! with the same format can be diffed byte for byte; pretty printing trades
+//! space for readable dumps. Reads accept any format, so changing it only
+//! affects entities written afterwards.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?.with_json_format(JsonFormat::CANONICAL);
+//! let txn = ents_sqlite::Txn::new(tx).with_json_format(JsonFormat::CANONICAL);
+//! ```
+
+use crate::{DatabaseError, Ent};
+
+/// Options for serializing entities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct JsonFormat {
+    /// Indent nested values and put each field on its own line
+    pub pretty: bool,
+    /// Write object keys in lexicographic order, at every level
+    pub sort_keys: bool,
+}
+
+impl JsonFormat {
+    /// Compact, in serde's field order
+    pub const COMPACT: Self = Self::new();
+
+    /// Compact with sorted keys, for stores that are compared byte by byte
+    pub const CANONICAL: Self = Self::new().with_sorted_keys(true);
+
+    pub const fn new() -> Self {
+        Self {
+            pretty: false,
+            sort_keys: false,
+        }
+    }
+
+    pub const fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    pub const fn with_sorted_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Serialize `ent`, including its `type` tag
+    pub fn to_string(&self, ent: &dyn Ent) -> Result<String, DatabaseError> {
+        let json = if self.sort_keys {
+            serde_json::to_value(ent).and_then(|mut value| {
+                value.sort_all_objects();
+                self.write(&value)
+            })
+        } else {
+            self.write(&ent)
+        };
+        json.map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })
+    }
+
+    fn write<T: serde::Serialize + ?Sized>(
+        &self,
+        value: &T,
+    ) -> serde_json::Result<String> {
+        if self.pretty {
+            serde_json::to_string_pretty(value)
+        } else {
+            serde_json::to_string(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntMutationError, Id};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Memo {
+        id: Id,
+        last_updated: u64,
+        title: String,
+        attrs: Attrs,
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Attrs {
+        zeta: u32,
+        alpha: u32,
+    }
+
+    #[typetag::serde]
+    impl Ent for Memo {
+        fn id(&self) -> Id {
+            self.id
+        }
+        fn set_id(&mut self, id: Id) {
+            self.id = id;
+        }
+        fn last_updated(&self) -> u64 {
+            self.last_updated
+        }
+        fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+            Ok(())
+        }
+    }
+
+    fn memo() -> Memo {
+        Memo {
+            id: 7,
+            last_updated: 1,
+            title: "hi".into(),
+            attrs: Attrs { zeta: 2, alpha: 3 },
+        }
+    }
+
+    #[test]
+    fn test_default_matches_serde_json() {
+        let ent = memo();
+        let expected = serde_json::to_string(&(&ent as &dyn Ent)).unwrap();
+        assert_eq!(JsonFormat::default(), JsonFormat::COMPACT);
+        assert_eq!(JsonFormat::COMPACT.to_string(&ent).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_sorted_keys() {
+        let json = JsonFormat::CANONICAL.to_string(&memo()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"attrs":{"alpha":3,"zeta":2},"id":7,"last_updated":1,"title":"hi","type":"Memo"}"#
+        );
+    }
+
+    #[test]
+    fn test_pretty_round_trips() {
+        let format = JsonFormat::new().with_pretty(true);
+        let json = format.to_string(&memo()).unwrap();
+        assert!(json.contains("\n  \"title\": \"hi\""));
+
+        let ent: Box<dyn Ent> = serde_json::from_str(&json).unwrap();
+        assert_eq!(ent.id(), 7);
+        assert_eq!(ent.typetag_name(), "Memo");
+    }
+}
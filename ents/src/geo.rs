@@ -0,0 +1,350 @@
+//! Geospatial queries over entities with a location.
+//!
+//! An entity type opts in by implementing [`Located`] and adding [`GeoIndex`]
+//! to its edge provider. Each located entity is then indexed by edges from
+//! the system id 0 named `geo:<index>:<geohash>`, one per indexed precision,
+//! so cell lookups are plain `find_edges` calls on any backend and the index
+//! follows the entity through updates and deletes like any other edge.
+//!
+//! Queries cover the requested area with geohash cells, load the entities
+//! indexed under them and filter by their exact location.
+//!
+//! ```ignore
+//! impl Located for Place {
+//!     const GEO_INDEX: &'static str = "place";
+//!     fn location(&self) -> Option<GeoPoint> {
+//!         Some(GeoPoint::new(self.lat, self.lng))
+//!     }
+//! }
+//!
+//! impl EntWithEdges for Place {
+//!     type EdgeProvider = GeoIndex;
+//! }
+//!
+//! let nearby = geo::within_radius::<Place, _>(&txn, here, 2_000.0)?;
+//! ```
+
+use crate::{
+    DatabaseError, DraftError, Edge, EdgeCursor, EdgeDraft, EdgeProvider,
+    EdgeQuery, EdgeValue, Ent, EntExt, Id, QueryEdge, ReadTransactional,
+    Transactional, DEFAULT_EDGE_LIMIT,
+};
+
+/// Prefix of the geo index edges from the system id 0
+pub const GEO_EDGE_PREFIX: &[u8] = b"geo:";
+
+/// Geohash precisions every located entity is indexed at: ~156km and ~4.9km
+/// cells
+const INDEX_PRECISIONS: [usize; 2] = [3, 5];
+
+/// Most cells a query may look up before falling back to a coarser precision
+const MAX_QUERY_CELLS: usize = 64;
+
+/// Mean Earth radius in meters
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// A position in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Self { lat, lng }
+    }
+
+    /// Great-circle distance in meters (haversine)
+    pub fn distance_meters(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlng = (other.lng - self.lng).to_radians();
+        let a = (dlat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// An area between two latitudes and two longitudes. Boxes do not wrap
+/// around the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_lat: f64, min_lng: f64, max_lat: f64, max_lng: f64) -> Self {
+        Self {
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng,
+        }
+    }
+
+    /// The smallest box containing the circle of `meters` around `center`,
+    /// clamped to valid coordinates
+    pub fn around(center: GeoPoint, meters: f64) -> Self {
+        let dlat = (meters / EARTH_RADIUS_METERS).to_degrees();
+        let cos = center.lat.to_radians().cos();
+        let dlng = if cos > 1e-9 { dlat / cos } else { 180.0 };
+        Self {
+            min_lat: (center.lat - dlat).max(-90.0),
+            min_lng: (center.lng - dlng).max(-180.0),
+            max_lat: (center.lat + dlat).min(90.0),
+            max_lng: (center.lng + dlng).min(180.0),
+        }
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        (self.min_lat..=self.max_lat).contains(&point.lat)
+            && (self.min_lng..=self.max_lng).contains(&point.lng)
+    }
+}
+
+/// An entity type with an optional location
+pub trait Located: Ent {
+    /// Name of this type's geo index, stored in the index edge names
+    const GEO_INDEX: &'static str;
+
+    fn location(&self) -> Option<GeoPoint>;
+}
+
+/// Edge provider maintaining the geo index of a [`Located`] type
+pub struct GeoIndex;
+
+/// Draft of the geo index edges of an entity
+#[derive(PartialEq)]
+pub struct GeoIndexDraft {
+    id: Id,
+    names: Vec<Vec<u8>>,
+}
+
+impl EdgeDraft for GeoIndexDraft {
+    fn check<T: Transactional>(
+        self,
+        _txn: &T,
+    ) -> Result<Vec<EdgeValue>, DraftError> {
+        Ok(self
+            .names
+            .into_iter()
+            .map(|name| EdgeValue::new(0, name, self.id))
+            .collect())
+    }
+}
+
+impl<T: Located> EdgeProvider<T> for GeoIndex {
+    type Draft = GeoIndexDraft;
+
+    fn draft(ent: &T) -> Self::Draft {
+        let names = match ent.location() {
+            Some(point) => INDEX_PRECISIONS
+                .iter()
+                .map(|&p| geo_edge(T::GEO_INDEX, &geohash(point, p)))
+                .collect(),
+            None => Vec::new(),
+        };
+        GeoIndexDraft {
+            id: ent.id(),
+            names,
+        }
+    }
+}
+
+fn geo_edge(index: &str, cell: &str) -> Vec<u8> {
+    let mut name = GEO_EDGE_PREFIX.to_vec();
+    name.extend_from_slice(index.as_bytes());
+    name.push(b':');
+    name.extend_from_slice(cell.as_bytes());
+    name
+}
+
+/// Geohash of `point` with `precision` characters
+pub fn geohash(point: GeoPoint, precision: usize) -> String {
+    let (mut lat_lo, mut lat_hi) = (-90.0, 90.0);
+    let (mut lng_lo, mut lng_hi) = (-180.0, 180.0);
+    let mut hash = String::with_capacity(precision);
+    let mut bits = 0;
+    let mut n = 0;
+    let mut even = true;
+
+    while hash.len() < precision {
+        let (value, lo, hi) = if even {
+            (point.lng, &mut lng_lo, &mut lng_hi)
+        } else {
+            (point.lat, &mut lat_lo, &mut lat_hi)
+        };
+        let mid = (*lo + *hi) / 2.0;
+        if value >= mid {
+            bits = (bits << 1) | 1;
+            *lo = mid;
+        } else {
+            bits <<= 1;
+            *hi = mid;
+        }
+        even = !even;
+        n += 1;
+        if n == 5 {
+            hash.push(BASE32[bits] as char);
+            bits = 0;
+            n = 0;
+        }
+    }
+    hash
+}
+
+/// The geohash cells of `precision` overlapping `bbox`, or None if there are
+/// more than `limit`
+fn cells_covering(
+    bbox: &BoundingBox,
+    precision: usize,
+    limit: usize,
+) -> Option<Vec<String>> {
+    let lat_bits = (5 * precision / 2) as i32;
+    let lng_bits = ((5 * precision).div_ceil(2)) as i32;
+    let height = 180.0 / 2f64.powi(lat_bits);
+    let width = 360.0 / 2f64.powi(lng_bits);
+    let index = |value: f64, origin: f64, size: f64, bits: i32| {
+        (((value - origin) / size).floor() as i64).clamp(0, (1 << bits) - 1)
+    };
+
+    let lat_range = index(bbox.min_lat, -90.0, height, lat_bits)
+        ..=index(bbox.max_lat, -90.0, height, lat_bits);
+    let lng_range = index(bbox.min_lng, -180.0, width, lng_bits)
+        ..=index(bbox.max_lng, -180.0, width, lng_bits);
+    let count = (lat_range.end() - lat_range.start() + 1)
+        * (lng_range.end() - lng_range.start() + 1);
+    if count as usize > limit {
+        return None;
+    }
+
+    let mut cells = Vec::with_capacity(count as usize);
+    for i in lat_range {
+        for j in lng_range.clone() {
+            let center = GeoPoint::new(
+                -90.0 + (i as f64 + 0.5) * height,
+                -180.0 + (j as f64 + 0.5) * width,
+            );
+            cells.push(geohash(center, precision));
+        }
+    }
+    Some(cells)
+}
+
+/// Entities of type `T` located inside `bbox`
+pub fn within_bbox<T, Q>(
+    txn: &Q,
+    bbox: BoundingBox,
+) -> Result<Vec<T>, DatabaseError>
+where
+    T: Located,
+    Q: ReadTransactional,
+{
+    let cells = INDEX_PRECISIONS
+        .iter()
+        .rev()
+        .find_map(|&p| cells_covering(&bbox, p, MAX_QUERY_CELLS))
+        .ok_or_else(|| DatabaseError::Other {
+            source: "geo query area is too large".into(),
+        })?;
+
+    let mut found = Vec::new();
+    for cell in cells {
+        let name = geo_edge(T::GEO_INDEX, &cell);
+        for edge in all_edges(txn, &name)? {
+            let Some(ent) = txn.get(edge.dest)?.and_then(|e| e.into_ent::<T>())
+            else {
+                continue;
+            };
+            if ent.location().is_some_and(|p| bbox.contains(&p)) {
+                found.push(ent);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Entities of type `T` within `meters` of `center` with their distance,
+/// nearest first
+pub fn within_radius<T, Q>(
+    txn: &Q,
+    center: GeoPoint,
+    meters: f64,
+) -> Result<Vec<(T, f64)>, DatabaseError>
+where
+    T: Located,
+    Q: ReadTransactional,
+{
+    let mut found: Vec<(T, f64)> =
+        within_bbox::<T, Q>(txn, BoundingBox::around(center, meters))?
+            .into_iter()
+            .filter_map(|ent| {
+                let distance = center.distance_meters(&ent.location()?);
+                (distance <= meters).then_some((ent, distance))
+            })
+            .collect();
+    found.sort_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(found)
+}
+
+/// Every edge named `name` from the system id 0
+fn all_edges<Q: QueryEdge>(
+    txn: &Q,
+    name: &[u8],
+) -> Result<Vec<Edge>, DatabaseError> {
+    let names = [name];
+    let mut edges = Vec::new();
+    let mut last: Option<Edge> = None;
+    loop {
+        let query = EdgeQuery::asc(&names)
+            .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+        let page = txn.find_edges(0, query)?;
+        let done = page.len() < DEFAULT_EDGE_LIMIT;
+        last = page.last().cloned();
+        edges.extend(page);
+        if done {
+            return Ok(edges);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geohash() {
+        let point = GeoPoint::new(57.64911, 10.40744);
+        assert_eq!(geohash(point, 11), "u4pruydqqvj");
+        assert_eq!(geohash(point, 5), "u4pru");
+    }
+
+    #[test]
+    fn test_distance() {
+        let seoul = GeoPoint::new(37.5665, 126.9780);
+        let busan = GeoPoint::new(35.1796, 129.0756);
+        let d = seoul.distance_meters(&busan);
+        assert!((320_000.0..330_000.0).contains(&d), "{}", d);
+        assert_eq!(seoul.distance_meters(&seoul), 0.0);
+    }
+
+    #[test]
+    fn test_cells_covering() {
+        let point = GeoPoint::new(57.64911, 10.40744);
+        let bbox = BoundingBox::around(point, 1_000.0);
+        assert!(bbox.contains(&point));
+        let cells = cells_covering(&bbox, 5, MAX_QUERY_CELLS).unwrap();
+        assert!(cells.contains(&geohash(point, 5)));
+        assert!(cells.len() <= 4);
+
+        let world = BoundingBox::new(-90.0, -180.0, 90.0, 180.0);
+        assert!(cells_covering(&world, 3, MAX_QUERY_CELLS).is_none());
+        assert_eq!(cells_covering(&world, 1, 32).unwrap().len(), 32);
+    }
+}
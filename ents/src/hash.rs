@@ -0,0 +1,84 @@
+//! Content hashes of entities.
+//!
+//! [`ent_hash`] hashes the canonical JSON of an entity, with keys sorted as
+//! by [`JsonFormat::CANONICAL`], using XXH3 with the default seed. The
+//! result depends only on the entity's type and serialized fields, not on
+//! the store it came from, the store's JSON format or the platform, so it
+//! can be compared across stores and processes by sync and diff tools. Every
+//! serialized field counts, including `last_updated`.
+//!
+//! ```ignore
+//! let primary = primary_txn.get(id)?.map(|e| ent_hash(&*e)).transpose()?;
+//! let replica = replica_txn.get(id)?.map(|e| ent_hash(&*e)).transpose()?;
+//! if primary != replica {
+//!     resync(id);
+//! }
+//! ```
+
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::format::JsonFormat;
+use crate::{DatabaseError, Ent};
+
+/// Hash of the canonical serialization of `ent`
+pub fn ent_hash(ent: &dyn Ent) -> Result<u64, DatabaseError> {
+    let json = JsonFormat::CANONICAL.to_string(ent)?;
+    Ok(xxh3_64(json.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntMutationError, Id};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Counter {
+        id: Id,
+        last_updated: u64,
+        count: u32,
+    }
+
+    #[typetag::serde]
+    impl Ent for Counter {
+        fn id(&self) -> Id {
+            self.id
+        }
+        fn set_id(&mut self, id: Id) {
+            self.id = id;
+        }
+        fn last_updated(&self) -> u64 {
+            self.last_updated
+        }
+        fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+            self.last_updated += 1;
+            Ok(())
+        }
+    }
+
+    fn counter(count: u32) -> Counter {
+        Counter {
+            id: 1,
+            last_updated: 10,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_equal_contents_hash_equal() {
+        let hash = ent_hash(&counter(3)).unwrap();
+        assert_eq!(ent_hash(&counter(3)).unwrap(), hash);
+        assert_ne!(ent_hash(&counter(4)).unwrap(), hash);
+
+        let mut touched = counter(3);
+        touched.mark_updated().unwrap();
+        assert_ne!(ent_hash(&touched).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_hash_is_stable() {
+        // Persisted hashes must survive upgrades; changing the canonical
+        // serialization or the hasher breaks them.
+        assert_eq!(ent_hash(&counter(3)).unwrap(), 8089110559937500654);
+    }
+}
@@ -0,0 +1,100 @@
+//! Prior versions of entities.
+//!
+//! Backends configured with `with_history` keep the stored state of an
+//! entity each time an update or touch overwrites it. Versions are
+//! numbered from 1 in the order they were replaced, so version 1 is the
+//! entity as created. Together with `last_updated` this shows what a stale
+//! copy was loaded from when an update loses a race.
+//!
+//! A [`HistoryRetention`] bounds how many versions are kept per entity and
+//! for how long. Versions past it are pruned when the same entity is
+//! updated again; deleting an entity drops its history.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?
+//!     .with_history(HistoryRetention::versions(10));
+//! let txn = env.write_txn()?;
+//! for version in txn.get_history(id)? {
+//!     println!("{} {}", version.version, version.ent.last_updated());
+//! }
+//! ```
+
+use std::time::Duration;
+
+use crate::Ent;
+
+/// Which prior versions of an entity are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HistoryRetention {
+    /// Versions kept per entity, newest first
+    pub max_versions: Option<usize>,
+    /// How long a version is kept after it was replaced
+    pub max_age: Option<Duration>,
+}
+
+impl HistoryRetention {
+    /// Keep every version
+    pub const fn unbounded() -> Self {
+        Self {
+            max_versions: None,
+            max_age: None,
+        }
+    }
+
+    /// Keep the `max_versions` newest versions of each entity
+    pub const fn versions(max_versions: usize) -> Self {
+        Self {
+            max_versions: Some(max_versions),
+            max_age: None,
+        }
+    }
+
+    pub const fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Whether the version at `index`, counting from the newest at 0, that
+    /// was replaced at `recorded_at` is still kept at `now`, both in
+    /// microseconds
+    pub fn keeps(&self, index: usize, recorded_at: u64, now: u64) -> bool {
+        let young = self.max_age.is_none_or(|age| {
+            now.saturating_sub(recorded_at) <= age.as_micros() as u64
+        });
+        young && self.max_versions.is_none_or(|max| index < max)
+    }
+}
+
+/// One prior version of an entity
+#[derive(Clone)]
+pub struct EntityVersion {
+    /// Number of the version, from 1
+    pub version: u64,
+    /// When the version was replaced, in microseconds
+    pub recorded_at: u64,
+    pub ent: Box<dyn Ent>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_newest_versions() {
+        let retention = HistoryRetention::versions(2);
+        assert!(retention.keeps(0, 0, u64::MAX));
+        assert!(retention.keeps(1, 0, u64::MAX));
+        assert!(!retention.keeps(2, 0, u64::MAX));
+        assert!(HistoryRetention::unbounded().keeps(1_000, 0, u64::MAX));
+    }
+
+    #[test]
+    fn test_keeps_young_versions() {
+        let retention =
+            HistoryRetention::unbounded().with_max_age(Duration::from_secs(1));
+        assert!(retention.keeps(5, 1_000, 1_001_000));
+        assert!(!retention.keeps(0, 1_000, 1_001_001));
+        // A clock going backwards keeps everything
+        assert!(retention.keeps(0, 2_000, 1_000));
+    }
+}
@@ -0,0 +1,187 @@
+//! Idempotency keys for operations triggered by external requests.
+//!
+//! [`with_idempotency_key`] runs an operation once per key and stores its
+//! serialized result in the same transaction, so a retried request gets the
+//! original result back instead of repeating the writes. Keys are stored as
+//! [`IdempotencyRecord`] entities indexed from the system id 0, plus a
+//! timeline edge used to expire old keys.
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::now_micros;
+use crate::timeline;
+use crate::{
+    DatabaseError, DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue,
+    Ent, EntMutationError, EntWithEdges, Id, Transactional,
+};
+
+/// Prefix of the index edges from the system id 0 to idempotency records
+pub const IDEMPOTENCY_EDGE_PREFIX: &[u8] = b"idempotency:";
+
+/// Timeline of idempotency records by creation time, used for expiry
+pub const IDEMPOTENCY_TIMELINE: &[u8] = b"idempotency_ttl";
+
+/// A recorded operation result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub key: String,
+    /// JSON-serialized result of the operation
+    pub result: String,
+    /// Creation time in microseconds since the epoch
+    pub created_at: u64,
+    pub id: Id,
+    pub last_updated: u64,
+}
+
+#[typetag::serde(name = "ents::Idempotency")]
+impl Ent for IdempotencyRecord {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.last_updated
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        self.last_updated = now_micros();
+        Ok(())
+    }
+}
+
+impl EntWithEdges for IdempotencyRecord {
+    type EdgeProvider = IdempotencyEdgeProvider;
+}
+
+crate::register_ent!(IdempotencyRecord, name = "ents::Idempotency");
+
+/// Maintains the key index and expiry timeline edges of a record
+pub struct IdempotencyEdgeProvider;
+
+/// Draft of the edges of an idempotency record
+#[derive(PartialEq)]
+pub struct IdempotencyEdgeDraft {
+    id: Id,
+    key: String,
+    created_at: u64,
+}
+
+impl EdgeDraft for IdempotencyEdgeDraft {
+    fn check<T: Transactional>(
+        self,
+        _txn: &T,
+    ) -> Result<Vec<EdgeValue>, DraftError> {
+        Ok(vec![
+            EdgeValue::new(0, idempotency_edge(&self.key), self.id),
+            timeline::timeline_edge(
+                0,
+                IDEMPOTENCY_TIMELINE,
+                self.created_at,
+                self.id,
+            ),
+        ])
+    }
+}
+
+impl EdgeProvider<IdempotencyRecord> for IdempotencyEdgeProvider {
+    type Draft = IdempotencyEdgeDraft;
+
+    fn draft(ent: &IdempotencyRecord) -> Self::Draft {
+        IdempotencyEdgeDraft {
+            id: ent.id,
+            key: ent.key.clone(),
+            created_at: ent.created_at,
+        }
+    }
+}
+
+fn idempotency_edge(key: &str) -> Vec<u8> {
+    let mut name = IDEMPOTENCY_EDGE_PREFIX.to_vec();
+    name.extend_from_slice(key.as_bytes());
+    name
+}
+
+/// Look up the record of a key
+pub fn find_idempotency_key<T: Transactional>(
+    txn: &T,
+    key: &str,
+) -> Result<Option<IdempotencyRecord>, DatabaseError> {
+    let name = idempotency_edge(key);
+    for edge in txn.find_edges(0, EdgeQuery::asc(&[&name]))? {
+        if let Some(record) = txn.get_as::<IdempotencyRecord>(edge.dest)? {
+            return Ok(Some(record));
+        }
+    }
+    Ok(None)
+}
+
+/// Run `op` unless `key` was seen before, in which case the stored result of
+/// the first run is returned instead.
+///
+/// The key is recorded in `txn`, so it only sticks if the transaction commits
+/// together with the operation's writes. Errors are not recorded; a failed
+/// operation can be retried with the same key.
+pub fn with_idempotency_key<T, R, F>(
+    txn: &T,
+    key: &str,
+    op: F,
+) -> Result<R, DatabaseError>
+where
+    T: Transactional,
+    R: Serialize + DeserializeOwned,
+    F: FnOnce(&T) -> Result<R, DatabaseError>,
+{
+    if let Some(record) = find_idempotency_key(txn, key)? {
+        return serde_json::from_str(&record.result).map_err(|e| {
+            DatabaseError::Other {
+                source: Box::new(e),
+            }
+        });
+    }
+
+    let result = op(txn)?;
+    let serialized =
+        serde_json::to_string(&result).map_err(|e| DatabaseError::Other {
+            source: Box::new(e),
+        })?;
+    txn.create(IdempotencyRecord {
+        key: key.to_string(),
+        result: serialized,
+        created_at: now_micros(),
+        id: 0,
+        last_updated: 0,
+    })?;
+    Ok(result)
+}
+
+/// Forget keys recorded before `cutoff` (microseconds since the epoch).
+/// Returns the number of keys removed.
+pub fn expire_before<T: Transactional>(
+    txn: &T,
+    cutoff: u64,
+) -> Result<usize, DatabaseError> {
+    if cutoff == 0 {
+        return Ok(0);
+    }
+    let expired =
+        timeline::between(txn, 0, IDEMPOTENCY_TIMELINE, 0, cutoff - 1)?;
+    for edge in &expired {
+        txn.delete::<IdempotencyRecord>(edge.dest)?;
+    }
+    Ok(expired.len())
+}
+
+/// Forget keys older than `ttl`. Returns the number of keys removed.
+pub fn cleanup<T: Transactional>(
+    txn: &T,
+    ttl: Duration,
+) -> Result<usize, DatabaseError> {
+    expire_before(txn, now_micros().saturating_sub(ttl.as_micros() as u64))
+}
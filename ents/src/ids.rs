@@ -0,0 +1,137 @@
+//! Where new entity ids come from.
+//!
+//! Backends that assign ids themselves accept an [`IdProvider`] when they
+//! are set up. [`SnowflakeIds`] is the default of `ents-heed`: ids are
+//! roughly time ordered and unique across processes with distinct instance
+//! numbers. [`SequentialIds`] counts up from a starting id, and
+//! [`ScriptedIds`] hands out a fixed list so tests can rely on exact ids.
+//!
+//! ```ignore
+//! let env = HeedEnv::open(path, None)?
+//!     .with_id_provider(SequentialIds::new(1));
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::{DatabaseError, Id};
+
+/// A source of ids for new entities
+pub trait IdProvider: Send + Sync {
+    /// An id not returned by this provider before
+    fn next_id(&self) -> Result<Id, DatabaseError>;
+}
+
+/// Snowflake ids: a millisecond timestamp, an instance number and a
+/// per-millisecond sequence
+#[derive(Debug)]
+pub struct SnowflakeIds {
+    generator: snowflaked::sync::Generator,
+}
+
+impl SnowflakeIds {
+    /// Ids for instance `instance`, which must be below 1024. Processes
+    /// writing to the same store need distinct instances.
+    pub const fn new(instance: u16) -> Self {
+        Self {
+            generator: snowflaked::sync::Generator::new(instance),
+        }
+    }
+}
+
+impl Default for SnowflakeIds {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl IdProvider for SnowflakeIds {
+    fn next_id(&self) -> Result<Id, DatabaseError> {
+        Ok(self.generator.generate())
+    }
+}
+
+/// Consecutive ids counting up from a start. Reopening a store needs a
+/// start past its largest id.
+#[derive(Debug)]
+pub struct SequentialIds {
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    pub const fn new(start: Id) -> Self {
+        Self {
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl IdProvider for SequentialIds {
+    fn next_id(&self) -> Result<Id, DatabaseError> {
+        self.next
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| {
+                id.checked_add(1)
+            })
+            .map_err(|_| DatabaseError::EntCapacityReached)
+    }
+}
+
+/// A predetermined list of ids, for tests. Fails once the list runs out.
+#[derive(Debug, Default)]
+pub struct ScriptedIds {
+    ids: Mutex<VecDeque<Id>>,
+}
+
+impl ScriptedIds {
+    pub fn new(ids: impl IntoIterator<Item = Id>) -> Self {
+        Self {
+            ids: Mutex::new(ids.into_iter().collect()),
+        }
+    }
+}
+
+impl IdProvider for ScriptedIds {
+    fn next_id(&self) -> Result<Id, DatabaseError> {
+        let mut ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        ids.pop_front().ok_or(DatabaseError::EntCapacityReached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snowflake_ids_increase() {
+        let ids = SnowflakeIds::default();
+        let a = ids.next_id().unwrap();
+        let b = ids.next_id().unwrap();
+        assert!(b > a);
+    }
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIds::new(5);
+        assert_eq!(ids.next_id().unwrap(), 5);
+        assert_eq!(ids.next_id().unwrap(), 6);
+
+        let ids = SequentialIds::new(Id::MAX);
+        assert!(matches!(
+            ids.next_id(),
+            Err(DatabaseError::EntCapacityReached)
+        ));
+        assert!(matches!(
+            ids.next_id(),
+            Err(DatabaseError::EntCapacityReached)
+        ));
+    }
+
+    #[test]
+    fn test_scripted_ids() {
+        let ids = ScriptedIds::new([30, 10]);
+        assert_eq!(ids.next_id().unwrap(), 30);
+        assert_eq!(ids.next_id().unwrap(), 10);
+        assert!(ids.next_id().is_err());
+    }
+}
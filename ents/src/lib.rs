@@ -1,13 +1,59 @@
+pub mod acyclic;
+pub mod as_of;
+pub mod bloom;
+pub mod branch;
+pub mod bulk;
+pub mod cascade;
+pub mod cdc;
+pub mod clock;
+pub mod closure;
+pub mod dangling;
+pub mod decode;
+pub mod decorate;
 pub mod edge_provider;
+pub mod ent_types;
+pub mod feed;
+pub mod fixtures;
+pub mod format;
+pub mod geo;
+pub mod hash;
+pub mod history;
+pub mod idempotency;
+pub mod ids;
+pub mod lint;
+pub mod metrics;
+pub mod migrate;
+pub mod namespace;
+pub mod ndjson;
+pub mod observe;
 pub mod query_edge;
+pub mod rate_limit;
+pub mod registry;
+pub mod saga;
+pub mod sample;
+pub mod stats;
+pub mod timeline;
+pub mod tree;
+pub mod unique;
+pub mod watch;
+pub mod workflow;
 
 use std::any::Any;
 
+#[doc(hidden)]
+pub use inventory;
+
 pub use edge_provider::{
-    DraftError, EdgeDraft, EdgeProvider, EdgeValue, EntWithEdges,
-    NullEdgeDraft, NullEdgeProvider, Transactional,
+    DraftError, EdgeDraft, EdgeProvider, EdgeValue, EntWithEdges, EntityCursor,
+    EntityPage, NullEdgeDraft, NullEdgeProvider, ReadTransactional,
+    Transactional,
+};
+pub use hash::ent_hash;
+pub use ndjson::{dump, restore};
+pub use query_edge::{
+    Edge, EdgeCursor, EdgeCursorOwned, EdgeQuery, InvalidCursorToken,
+    QueryEdge, SortOrder, DEFAULT_EDGE_LIMIT,
 };
-pub use query_edge::{Edge, EdgeCursor, EdgeQuery, QueryEdge, SortOrder};
 
 /// Unique identifier for an entity
 pub type Id = u64;
@@ -17,6 +63,40 @@ pub type Id = u64;
 pub enum DatabaseError {
     #[error("Entity capacity reached")]
     EntCapacityReached,
+    #[error("Store is frozen")]
+    Frozen,
+    #[error("Store is overloaded: {reason}")]
+    Overloaded { reason: String },
+    #[error("Edge '{name}' from {from} to {to} would create a cycle")]
+    CycleDetected { name: String, from: Id, to: Id },
+    #[error("Unique key '{name}' is already held by entity {existing}")]
+    UniqueViolation {
+        name: String,
+        value: Vec<u8>,
+        existing: Id,
+    },
+    #[error("Entity {id} already exists")]
+    AlreadyExists { id: Id },
+    #[error("Entity type '{name}' is not registered")]
+    UnknownType { name: String },
+    #[error(
+        "Entity {id} was updated concurrently: expected last_updated \
+         {expected}, found {actual}"
+    )]
+    Conflict { id: Id, expected: u64, actual: u64 },
+    #[error("{operation} is not allowed in a read-only transaction")]
+    ReadOnlyViolation { operation: String },
+    #[error(
+        "Store format version {found} is not supported (expected {supported}){}",
+        if found > supported {
+            ": it was written by a newer version"
+        } else {
+            ": upgrade it with upgrade_store"
+        }
+    )]
+    UnsupportedFormat { found: u32, supported: u32 },
+    #[error("Writer lease {token} was superseded by lease {current}")]
+    Fenced { token: u64, current: u64 },
     #[error("Other error: {source}")]
     Other {
         #[from]
@@ -43,6 +123,13 @@ pub trait Ent: Any + dyn_clone::DynClone + Send + Sync {
 
 dyn_clone::clone_trait_object!(Ent);
 
+impl dyn Ent {
+    /// The entity as a `T`, if it is one
+    pub fn downcast_ref<T: Ent>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref::<T>()
+    }
+}
+
 pub trait EntExt {
     fn is<T: Ent>(&self) -> bool;
 
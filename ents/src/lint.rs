@@ -0,0 +1,183 @@
+//! Checks of the registered entity types and edge schemas.
+//!
+//! Registrations are scattered across crates and only meet in the final
+//! binary, so mistakes such as an edge declared towards a type that was
+//! registered under another name go unnoticed until data is written.
+//! [`lint_schema`] looks for them at startup:
+//!
+//! - errors: an edge endpoint that is not a registered entity type
+//! - warnings: a type or edge schema registered twice, and an edge name
+//!   declared from one source type towards several destination types
+//!
+//! Whether unique keys are declared cannot be checked here, as
+//! `unique_keys` is only known for entity values.
+//!
+//! ```ignore
+//! for issue in ents::lint::lint_schema() {
+//!     eprintln!("{}", issue);
+//! }
+//! assert!(!ents::lint::has_errors(&ents::lint::lint_schema()));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::registry::{self, EdgeSchema, EntRegistration};
+
+/// How serious a [`LintIssue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+/// A problem found in the registrations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub severity: LintSeverity,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Lint everything registered in the final binary
+pub fn lint_schema() -> Vec<LintIssue> {
+    let ents: Vec<_> = registry::registered_ents().collect();
+    let edges: Vec<_> = registry::registered_edges().collect();
+    lint(&ents, &edges)
+}
+
+/// Whether `issues` contains an error
+pub fn has_errors(issues: &[LintIssue]) -> bool {
+    issues.iter().any(|i| i.severity == LintSeverity::Error)
+}
+
+/// Lint the given registrations, errors first
+pub fn lint(
+    ents: &[&EntRegistration],
+    edges: &[&EdgeSchema],
+) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut warn = |message: String| {
+        issues.push(LintIssue {
+            severity: LintSeverity::Warning,
+            message,
+        })
+    };
+
+    let mut types = BTreeSet::new();
+    for ent in ents {
+        if !types.insert(ent.type_name) {
+            warn(format!("entity type {} is registered twice", ent.type_name));
+        }
+    }
+
+    let mut schemas = BTreeSet::new();
+    let mut dests: BTreeMap<(&str, &[u8]), BTreeSet<&str>> = BTreeMap::new();
+    for edge in edges {
+        let key = (edge.source_type, edge.name, edge.dest_type);
+        if !schemas.insert(key) {
+            warn(format!("edge {} is registered twice", describe(edge)));
+        }
+        dests
+            .entry((edge.source_type, edge.name))
+            .or_default()
+            .insert(edge.dest_type);
+    }
+    for ((source, name), dests) in dests {
+        if dests.len() > 1 {
+            let dests: Vec<_> = dests.into_iter().collect();
+            warn(format!(
+                "edge {} from {} points at several types: {}",
+                String::from_utf8_lossy(name),
+                source,
+                dests.join(", ")
+            ));
+        }
+    }
+
+    for edge in edges {
+        for (end, type_name) in [
+            ("source", edge.source_type),
+            ("destination", edge.dest_type),
+        ] {
+            if !types.contains(type_name) {
+                issues.push(LintIssue {
+                    severity: LintSeverity::Error,
+                    message: format!(
+                        "{} type {} of edge {} is not a registered entity type",
+                        end,
+                        type_name,
+                        describe(edge)
+                    ),
+                });
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| std::cmp::Reverse(i.severity));
+    issues
+}
+
+fn describe(edge: &EdgeSchema) -> String {
+    format!(
+        "{} -[{}]-> {}",
+        edge.source_type,
+        String::from_utf8_lossy(edge.name),
+        edge.dest_type
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: EntRegistration = EntRegistration::new("User");
+    const POST: EntRegistration = EntRegistration::new("Post");
+    const AUTHOR: EdgeSchema = EdgeSchema::new(b"author", "Post", "User");
+
+    #[test]
+    fn test_clean_schema() {
+        assert!(lint(&[&USER, &POST], &[&AUTHOR]).is_empty());
+        assert!(!has_errors(&lint_schema()));
+    }
+
+    #[test]
+    fn test_unregistered_endpoint() {
+        // Registered under a custom name, declared by identifier
+        let blog_post = EntRegistration::new("BlogPost");
+        let issues = lint(&[&USER, &blog_post], &[&AUTHOR]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, LintSeverity::Error);
+        assert_eq!(
+            issues[0].to_string(),
+            "error: source type Post of edge Post -[author]-> User is not a \
+             registered entity type"
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let to_post = EdgeSchema::new(b"author", "Post", "Post");
+        let issues =
+            lint(&[&USER, &POST, &USER], &[&AUTHOR, &to_post, &AUTHOR]);
+        assert!(!has_errors(&issues));
+        let messages: Vec<_> = issues.iter().map(|i| &i.message).collect();
+        assert_eq!(
+            messages,
+            [
+                "entity type User is registered twice",
+                "edge Post -[author]-> User is registered twice",
+                "edge author from Post points at several types: Post, User",
+            ]
+        );
+    }
+}
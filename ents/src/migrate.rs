@@ -0,0 +1,157 @@
+//! Copying a whole store into another, possibly of a different backend.
+//!
+//! [`migrate_batch`] copies the entities of the types registered with
+//! [`register_ent!`](crate::register_ent), type by type in id order, each
+//! with its ids kept and followed by the edges leaving it; the edges of the
+//! system id 0 come last. Every call copies at most `batch_size` entities
+//! and returns a [`MigrationCheckpoint`] to continue from, so each batch
+//! can be committed on its own and an interrupted migration resumed from
+//! the last saved checkpoint. Entities that already exist in the
+//! destination are skipped, so repeating a batch is harmless.
+//!
+//! ```ignore
+//! let types = EntTypes::new().with_type::<User>().with_type::<Post>();
+//! let mut checkpoint = MigrationCheckpoint::default();
+//! while !checkpoint.done {
+//!     let src = sqlite_txn(&pool)?;
+//!     let dst = env.write_txn()?;
+//!     checkpoint = migrate_batch(&src, &dst, &types, &checkpoint, 1000)?;
+//!     dst.commit()?;
+//!     save(&checkpoint)?;
+//! }
+//! ```
+//!
+//! Entities are created through `create_with_id`, so edge providers run and
+//! unique keys are claimed; their types must be listed in the [`EntTypes`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::ent_types::EntTypes;
+use crate::ndjson::{for_each_edge, put_edge};
+use crate::registry;
+use crate::{DatabaseError, EdgeValue, Id, Transactional};
+
+/// How far a migration got
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationCheckpoint {
+    /// The type being copied, None before the first batch
+    pub type_name: Option<String>,
+    /// The last id of `type_name` copied
+    pub after: Option<Id>,
+    /// Whether everything has been copied
+    pub done: bool,
+    /// Entities copied so far
+    pub entities: u64,
+    /// Edges copied so far
+    pub edges: u64,
+}
+
+/// Copy everything from `src` to `dst` in one go
+pub fn migrate<S, D>(
+    src: &S,
+    dst: &D,
+    types: &EntTypes<D>,
+) -> Result<MigrationCheckpoint, DatabaseError>
+where
+    S: Transactional,
+    D: Transactional,
+{
+    migrate_batch(src, dst, types, &MigrationCheckpoint::default(), usize::MAX)
+}
+
+/// Copy up to `batch_size` entities with their edges from `src` to `dst`,
+/// continuing after `checkpoint`
+pub fn migrate_batch<S, D>(
+    src: &S,
+    dst: &D,
+    types: &EntTypes<D>,
+    checkpoint: &MigrationCheckpoint,
+    batch_size: usize,
+) -> Result<MigrationCheckpoint, DatabaseError>
+where
+    S: Transactional,
+    D: Transactional,
+{
+    let mut checkpoint = checkpoint.clone();
+    if checkpoint.done || batch_size == 0 {
+        return Ok(checkpoint);
+    }
+
+    let mut names: Vec<&str> =
+        registry::registered_ents().map(|r| r.type_name).collect();
+    names.sort_unstable();
+    names.dedup();
+    let mut index = match &checkpoint.type_name {
+        None => 0,
+        Some(name) => names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| DatabaseError::UnknownType { name: name.clone() })?,
+    };
+
+    let mut remaining = batch_size;
+    while let Some(&name) = names.get(index) {
+        checkpoint.type_name = Some(name.to_string());
+        let ids = src.list_ids_by_type(name, checkpoint.after, remaining)?;
+        for &id in &ids {
+            copy_entity(src, dst, types, id, &mut checkpoint)?;
+        }
+        if ids.len() < remaining {
+            index += 1;
+            checkpoint.after = None;
+            remaining -= ids.len();
+        } else {
+            checkpoint.after = ids.last().copied();
+            return Ok(checkpoint);
+        }
+    }
+
+    copy_edges(src, dst, 0, &mut checkpoint)?;
+    checkpoint.type_name = None;
+    checkpoint.done = true;
+    Ok(checkpoint)
+}
+
+fn copy_entity<S, D>(
+    src: &S,
+    dst: &D,
+    types: &EntTypes<D>,
+    id: Id,
+    checkpoint: &mut MigrationCheckpoint,
+) -> Result<(), DatabaseError>
+where
+    S: Transactional,
+    D: Transactional,
+{
+    let Some(ent) = src.get(id)? else {
+        return Ok(());
+    };
+    match types.create(dst, ent, Some(id)) {
+        Ok(_) => checkpoint.entities += 1,
+        // Copied by an earlier run of this batch
+        Err(DatabaseError::AlreadyExists { .. }) => {}
+        Err(e) => return Err(e),
+    }
+    copy_edges(src, dst, id, checkpoint)
+}
+
+fn copy_edges<S, D>(
+    src: &S,
+    dst: &D,
+    source: Id,
+    checkpoint: &mut MigrationCheckpoint,
+) -> Result<(), DatabaseError>
+where
+    S: Transactional,
+    D: Transactional,
+{
+    for_each_edge(src, source, |edge| {
+        let value =
+            EdgeValue::new(edge.source, edge.sort_key.clone(), edge.dest)
+                .with_discriminator(edge.discriminator)
+                .with_payload(edge.payload.clone());
+        put_edge(dst, value, edge.hidden)?;
+        checkpoint.edges += 1;
+        Ok(())
+    })
+}
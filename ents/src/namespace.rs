@@ -0,0 +1,337 @@
+//! Edge-name namespaces for application modules sharing one store.
+//!
+//! A [`Namespace`] prefixes edge names with `<namespace>/`, so a `member`
+//! edge of the billing module is stored as `billing/member` and never
+//! collides with the `member` edge of another module. Wrapping a transaction
+//! with [`Namespace::wrap`] applies the prefix transparently: edges are
+//! written with prefixed names, queries match prefixed names, and results
+//! come back with the prefix stripped. Queries through the wrapper only ever
+//! see edges of their own namespace.
+//!
+//! Edges created by an entity's [`EdgeProvider`](crate::EdgeProvider) during
+//! `create` are written by the underlying transaction, so providers of
+//! namespaced modules build their names with [`Namespace::edge_name`].
+//!
+//! ```ignore
+//! const BILLING: Namespace = Namespace::new("billing");
+//!
+//! let txn = BILLING.wrap(env.write_txn()?);
+//! txn.create_edge(EdgeValue::new(account, b"member".to_vec(), user))?;
+//! let members = txn.find_edges(account, EdgeQuery::asc(&[b"member"]))?;
+//! txn.commit()?;
+//! ```
+
+use std::borrow::BorrowMut;
+
+use crate::unique::UniqueKey;
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, Ent, EntWithEdges,
+    EntityCursor, EntityPage, Id, QueryEdge, ReadTransactional, Transactional,
+};
+
+/// Separates the namespace from the edge name
+pub const NAMESPACE_SEPARATOR: u8 = b'/';
+
+/// A prefix applied to the edge names of one application module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Namespace {
+    name: &'static str,
+}
+
+impl Namespace {
+    /// Create a namespace. The name must be non-empty and must not contain
+    /// the separator `/`.
+    pub const fn new(name: &'static str) -> Self {
+        let bytes = name.as_bytes();
+        assert!(!bytes.is_empty(), "namespace must not be empty");
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(
+                bytes[i] != NAMESPACE_SEPARATOR,
+                "namespace must not contain '/'"
+            );
+            i += 1;
+        }
+        Self { name }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The stored name of edge `name` in this namespace
+    pub fn edge_name(&self, name: &[u8]) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.name.len() + 1 + name.len());
+        key.extend_from_slice(self.name.as_bytes());
+        key.push(NAMESPACE_SEPARATOR);
+        key.extend_from_slice(name);
+        key
+    }
+
+    /// The edge name within this namespace of a stored name, or None if the
+    /// stored name belongs to another namespace
+    pub fn strip<'k>(&self, key: &'k [u8]) -> Option<&'k [u8]> {
+        key.strip_prefix(self.name.as_bytes())?
+            .strip_prefix(&[NAMESPACE_SEPARATOR])
+    }
+
+    /// Whether a stored edge name belongs to this namespace
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.strip(key).is_some()
+    }
+
+    /// Build an edge in this namespace
+    pub fn edge(&self, source: Id, name: &[u8], dest: Id) -> EdgeValue {
+        EdgeValue::new(source, self.edge_name(name), dest)
+    }
+
+    /// View `txn` through this namespace
+    pub fn wrap<T>(self, txn: T) -> Namespaced<T> {
+        Namespaced {
+            namespace: self,
+            txn,
+        }
+    }
+
+    fn prefix_value(&self, edge: &EdgeValue) -> EdgeValue {
+        EdgeValue {
+            sort_key: self.edge_name(&edge.sort_key),
+            ..edge.clone()
+        }
+    }
+}
+
+/// A transaction whose edge names are confined to a [`Namespace`]
+pub struct Namespaced<T> {
+    namespace: Namespace,
+    txn: T,
+}
+
+impl<T> Namespaced<T> {
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// The underlying transaction, which sees every namespace
+    pub fn inner(&self) -> &T {
+        &self.txn
+    }
+
+    pub fn into_inner(self) -> T {
+        self.txn
+    }
+}
+
+impl<T: QueryEdge> Namespaced<T> {
+    /// Run `query` through `fetch` with namespaced names, paging until a
+    /// full page of this namespace's edges is collected. `endpoint` picks
+    /// the id the backend orders results by after the sort key.
+    fn query_namespaced<F>(
+        &self,
+        query: EdgeQuery,
+        endpoint: fn(&Edge) -> Id,
+        fetch: F,
+    ) -> Result<Vec<Edge>, DatabaseError>
+    where
+        F: Fn(EdgeQuery) -> Result<Vec<Edge>, DatabaseError>,
+    {
+        if query.max_edges() == 0 {
+            return Ok(Vec::new());
+        }
+        let ns = self.namespace;
+        let names: Vec<Vec<u8>> =
+            query.edge_names.iter().map(|n| ns.edge_name(n)).collect();
+        let name_refs: Vec<&[u8]> = names.iter().map(Vec::as_slice).collect();
+        let mut cursor = query.cursor.as_ref().map(|c| {
+            (ns.edge_name(c.sort_key), c.destination, c.discriminator)
+        });
+
+        // Without a name filter the backend returns edges of every
+        // namespace, so keep paging until a full page of ours is collected.
+        let mut edges = Vec::new();
+        loop {
+            let mut inner = EdgeQuery {
+                edge_names: &name_refs,
+                order: query.order,
+                cursor: None,
+                include_hidden: query.include_hidden,
+                limit: query.limit,
+            };
+            if let Some((sort_key, id, disc)) = &cursor {
+                inner = inner.with_cursor(
+                    EdgeCursor::new(sort_key, *id).with_discriminator(*disc),
+                );
+            }
+            let page = fetch(inner)?;
+            let exhausted = page.len() < query.max_edges();
+            cursor = page
+                .last()
+                .map(|e| (e.sort_key.clone(), endpoint(e), e.discriminator));
+
+            for mut edge in page {
+                if let Some(name) = ns.strip(&edge.sort_key) {
+                    edge.sort_key = name.to_vec();
+                    edges.push(edge);
+                    if edges.len() >= query.max_edges() {
+                        return Ok(edges);
+                    }
+                }
+            }
+            if exhausted {
+                return Ok(edges);
+            }
+        }
+    }
+}
+
+impl<T: QueryEdge> QueryEdge for Namespaced<T> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.query_namespaced(
+            query,
+            |e| e.dest,
+            |q| self.txn.find_edges(source, q),
+        )
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.query_namespaced(
+            query,
+            |e| e.source,
+            |q| self.txn.find_edges_to(dest, q),
+        )
+    }
+}
+
+impl<T: Transactional> ReadTransactional for Namespaced<T> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.txn.get(id)
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.txn.get_deleted(id)
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.txn.list_deleted(deleted_before, after, limit)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.txn.list_ids_by_type(type_name, after, limit)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        self.txn.find_unique(key)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        self.txn.scan_entities(cursor, limit)
+    }
+}
+
+impl<T: Transactional> Transactional for Namespaced<T> {
+    fn create<E: EntWithEdges>(&self, ent: E) -> Result<Id, DatabaseError> {
+        self.txn.create(ent)
+    }
+
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        self.txn.create_with_id(id, ent)
+    }
+
+    fn delete<E: EntWithEdges>(&self, id: Id) -> Result<(), DatabaseError> {
+        self.txn.delete::<E>(id)
+    }
+
+    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        self.txn.create_edge(self.namespace.prefix_value(&edge))
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.txn.hide_edge(&self.namespace.prefix_value(edge))
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.txn.restore_edge(&self.namespace.prefix_value(edge))
+    }
+
+    fn update<E, F, B>(&self, ent: B, mutator: F) -> Result<bool, DatabaseError>
+    where
+        E: EntWithEdges,
+        F: FnOnce(&mut E),
+        B: BorrowMut<E>,
+    {
+        self.txn.update(ent, mutator)
+    }
+
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.txn.touch::<E>(id)
+    }
+
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        self.txn.soft_delete::<E>(id)
+    }
+
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.txn.restore::<E>(id)
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        self.txn.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BILLING: Namespace = Namespace::new("billing");
+
+    #[test]
+    fn test_edge_name() {
+        assert_eq!(BILLING.edge_name(b"member"), b"billing/member");
+        assert_eq!(BILLING.strip(b"billing/member"), Some(&b"member"[..]));
+        assert_eq!(BILLING.strip(b"billing"), None);
+        assert_eq!(BILLING.strip(b"billingx/member"), None);
+        assert!(!BILLING.contains(b"chat/member"));
+    }
+
+    #[test]
+    #[should_panic(expected = "must not contain")]
+    fn test_separator_rejected() {
+        Namespace::new("a/b");
+    }
+}
@@ -0,0 +1,260 @@
+//! Exporting and importing a whole store as newline-delimited JSON.
+//!
+//! [`dump`] writes one record per line: first every entity of the types
+//! registered with [`register_ent!`](crate::register_ent), then the edges
+//! leaving those entities and the system id 0, hidden ones included.
+//!
+//! ```text
+//! {"entity":{"type":"User","username":"ada","id":7,"last_updated":1}}
+//! {"edge":{"source":7,"name":"follows","dest":9}}
+//! {"edge":{"source":9,"name":[0,255],"dest":7,"discriminator":2,"hidden":true}}
+//! ```
+//!
+//! Edge names that are not UTF-8 are written as byte arrays. [`restore`]
+//! reads such a stream into any store, keeping entity ids. Entities are
+//! created through `create_with_id`, so edge providers run and unique keys
+//! are claimed; the types in the stream must be listed in the [`EntTypes`]
+//! passed to it. Both report their progress after every record.
+//!
+//! ```ignore
+//! ents::dump(&txn, BufWriter::new(File::create(path)?), |p| {
+//!     eprint!("\r{} entities, {} edges", p.entities, p.edges)
+//! })?;
+//!
+//! let types = EntTypes::new().with_type::<User>();
+//! ents::restore(&txn, BufReader::new(File::open(path)?), &types, |_| {})?;
+//! txn.commit()?;
+//! ```
+
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ent_types::EntTypes;
+use crate::registry;
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, Ent, Id,
+    Transactional,
+};
+
+/// Number of ids or edges fetched per query
+const PAGE_SIZE: usize = 1000;
+
+/// Records written or read so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NdjsonProgress {
+    pub entities: u64,
+    pub edges: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Entity(Box<dyn Ent>),
+    Edge(EdgeRecord),
+}
+
+#[derive(Serialize, Deserialize)]
+struct EdgeRecord {
+    source: Id,
+    name: EdgeName,
+    dest: Id,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    discriminator: u64,
+    #[serde(default, skip_serializing_if = "is_false")]
+    hidden: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    payload: Vec<u8>,
+}
+
+/// An edge name, as text when it is UTF-8
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum EdgeName {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl EdgeName {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => EdgeName::Text(text),
+            Err(e) => EdgeName::Bytes(e.into_bytes()),
+        }
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        match self {
+            EdgeName::Text(text) => text.into_bytes(),
+            EdgeName::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Write the entities of registered types and their edges to `out`
+pub fn dump<T, W, P>(
+    txn: &T,
+    mut out: W,
+    mut progress: P,
+) -> Result<NdjsonProgress, DatabaseError>
+where
+    T: Transactional,
+    W: Write,
+    P: FnMut(&NdjsonProgress),
+{
+    let mut done = NdjsonProgress::default();
+    let mut sources: Vec<Id> = vec![0];
+    for registration in registry::registered_ents() {
+        let mut after = None;
+        loop {
+            let ids =
+                txn.list_ids_by_type(registration.type_name, after, PAGE_SIZE)?;
+            for &id in &ids {
+                if let Some(ent) = txn.get(id)? {
+                    write_record(&mut out, &Record::Entity(ent))?;
+                    done.entities += 1;
+                    progress(&done);
+                    sources.push(id);
+                }
+            }
+            if ids.len() < PAGE_SIZE {
+                break;
+            }
+            after = ids.last().copied();
+        }
+    }
+
+    for source in sources {
+        for_each_edge(txn, source, |edge| {
+            let record = EdgeRecord {
+                source: edge.source,
+                name: EdgeName::new(edge.sort_key.clone()),
+                dest: edge.dest,
+                discriminator: edge.discriminator,
+                hidden: edge.hidden,
+                payload: edge.payload.clone(),
+            };
+            write_record(&mut out, &Record::Edge(record))?;
+            done.edges += 1;
+            progress(&done);
+            Ok(())
+        })?;
+    }
+
+    out.flush().map_err(io_error)?;
+    Ok(done)
+}
+
+/// Create the entities and edges of a stream written by [`dump`]
+pub fn restore<T, R, P>(
+    txn: &T,
+    input: R,
+    types: &EntTypes<T>,
+    mut progress: P,
+) -> Result<NdjsonProgress, DatabaseError>
+where
+    T: Transactional,
+    R: BufRead,
+    P: FnMut(&NdjsonProgress),
+{
+    let mut done = NdjsonProgress::default();
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record =
+            serde_json::from_str(&line).map_err(|e| DatabaseError::Other {
+                source: format!("line {}: {}", n + 1, e).into(),
+            })?;
+        match record {
+            Record::Entity(ent) => {
+                let id = ent.id();
+                types.create(txn, ent, Some(id))?;
+                done.entities += 1;
+            }
+            Record::Edge(record) => {
+                let edge = EdgeValue::new(
+                    record.source,
+                    record.name.into_bytes(),
+                    record.dest,
+                )
+                .with_discriminator(record.discriminator)
+                .with_payload(record.payload);
+                put_edge(txn, edge, record.hidden)?;
+                done.edges += 1;
+            }
+        }
+        progress(&done);
+    }
+    Ok(done)
+}
+
+/// Call `f` with every edge leaving `source`, hidden ones included
+pub(crate) fn for_each_edge<T, F>(
+    txn: &T,
+    source: Id,
+    mut f: F,
+) -> Result<(), DatabaseError>
+where
+    T: Transactional,
+    F: FnMut(&Edge) -> Result<(), DatabaseError>,
+{
+    let mut last: Option<Edge> = None;
+    loop {
+        let query = EdgeQuery::asc(&[])
+            .include_hidden()
+            .with_limit(PAGE_SIZE)
+            .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+        let page = txn.find_edges(source, query)?;
+        for edge in &page {
+            f(edge)?;
+        }
+        if page.len() < PAGE_SIZE {
+            return Ok(());
+        }
+        last = page.last().cloned();
+    }
+}
+
+/// Create `edge`, hiding it if `hidden` and otherwise restoring it, since
+/// re-creating an edge keeps it hidden or visible as it was
+pub(crate) fn put_edge<T: Transactional>(
+    txn: &T,
+    edge: EdgeValue,
+    hidden: bool,
+) -> Result<(), DatabaseError> {
+    txn.create_edge(edge.clone())?;
+    if hidden {
+        txn.hide_edge(&edge)?;
+    } else {
+        txn.restore_edge(&edge)?;
+    }
+    Ok(())
+}
+
+fn write_record<W: Write>(
+    out: &mut W,
+    record: &Record,
+) -> Result<(), DatabaseError> {
+    serde_json::to_writer(&mut *out, record).map_err(|e| {
+        DatabaseError::Other {
+            source: Box::new(e),
+        }
+    })?;
+    out.write_all(b"\n").map_err(io_error)
+}
+
+fn io_error(e: std::io::Error) -> DatabaseError {
+    DatabaseError::Other {
+        source: Box::new(e),
+    }
+}
@@ -0,0 +1,342 @@
+//! Lifecycle hooks run around entity mutations.
+//!
+//! An [`EntObserver`] is called before and after each create, update and
+//! delete made through an [`ObservedTxn`]. The `before_*` calls run before
+//! the backend is touched and fail the mutation by returning an error,
+//! which makes them a place for validation; the `after_*` calls see the
+//! entity as written, for audit logs or derived data. Both get the
+//! underlying transaction, so observers can read and write in the same
+//! transaction as the mutation. Their own writes bypass the observers.
+//!
+//! Observers are registered once in an [`Observers`] registry, which wraps
+//! every transaction the application opens, whatever the backend.
+//!
+//! ```ignore
+//! struct NoEmptyNames;
+//!
+//! impl<T: Transactional> EntObserver<T> for NoEmptyNames {
+//!     fn before_create(&self, _: &T, ent: &dyn Ent) -> Result<(), DatabaseError> {
+//!         match ent.downcast_ref::<User>() {
+//!             Some(user) if user.name.is_empty() => Err(/* ... */),
+//!             _ => Ok(()),
+//!         }
+//!     }
+//! }
+//!
+//! let observers = Observers::new().with(NoEmptyNames);
+//! let txn = observers.wrap(env.write_txn()?);
+//! txn.create(user)?;
+//! txn.commit()?;
+//! ```
+//!
+//! Updates are observed with the entity before and after the mutator ran.
+//! Deletes load the entity first so observers can see it; deleting an id
+//! that does not exist calls no observer. Soft deletes and restores call
+//! none either; purging a soft-deleted entity is observed as a delete.
+
+use std::borrow::BorrowMut;
+use std::sync::Arc;
+
+use crate::unique::UniqueKey;
+use crate::{
+    DatabaseError, Edge, EdgeQuery, EdgeValue, Ent, EntWithEdges, EntityCursor,
+    EntityPage, Id, QueryEdge, ReadTransactional, Transactional,
+};
+
+/// Callbacks around the entity mutations of a transaction `T`
+pub trait EntObserver<T>: Send + Sync {
+    fn before_create(
+        &self,
+        _txn: &T,
+        _ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    /// `ent` carries its assigned id
+    fn after_create(
+        &self,
+        _txn: &T,
+        _ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    fn before_update(
+        &self,
+        _txn: &T,
+        _old: &dyn Ent,
+        _new: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    /// Called once the update was written or found to change nothing
+    fn after_update(
+        &self,
+        _txn: &T,
+        _ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    fn before_delete(
+        &self,
+        _txn: &T,
+        _ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    fn after_delete(
+        &self,
+        _txn: &T,
+        _ent: &dyn Ent,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+}
+
+/// The observers of an application, applied to its transactions
+pub struct Observers<T> {
+    observers: Vec<Arc<dyn EntObserver<T>>>,
+}
+
+impl<T> Default for Observers<T> {
+    fn default() -> Self {
+        Self {
+            observers: Vec::new(),
+        }
+    }
+}
+
+impl<T> Clone for Observers<T> {
+    fn clone(&self) -> Self {
+        Self {
+            observers: self.observers.clone(),
+        }
+    }
+}
+
+impl<T> Observers<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `observer`, called after the ones added before it
+    pub fn with(mut self, observer: impl EntObserver<T> + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Run these observers around the mutations of `txn`
+    pub fn wrap(&self, txn: T) -> ObservedTxn<T> {
+        ObservedTxn {
+            observers: self.clone(),
+            txn,
+        }
+    }
+
+    fn each(
+        &self,
+        f: impl Fn(&dyn EntObserver<T>) -> Result<(), DatabaseError>,
+    ) -> Result<(), DatabaseError> {
+        self.observers.iter().try_for_each(|o| f(o.as_ref()))
+    }
+}
+
+/// A transaction calling [`EntObserver`]s around its entity mutations
+pub struct ObservedTxn<T> {
+    observers: Observers<T>,
+    txn: T,
+}
+
+impl<T> ObservedTxn<T> {
+    /// The underlying transaction, which bypasses the observers
+    pub fn inner(&self) -> &T {
+        &self.txn
+    }
+
+    pub fn into_inner(self) -> T {
+        self.txn
+    }
+}
+
+impl<T: Transactional> ObservedTxn<T> {
+    fn observe_create(
+        &self,
+        ent: &dyn Ent,
+        create: impl FnOnce() -> Result<Id, DatabaseError>,
+    ) -> Result<Id, DatabaseError> {
+        if self.observers.is_empty() {
+            return create();
+        }
+        let txn = &self.txn;
+        self.observers.each(|o| o.before_create(txn, ent))?;
+        let id = create()?;
+        if let Some(created) = txn.get(id)? {
+            self.observers
+                .each(|o| o.after_create(txn, created.as_ref()))?;
+        }
+        Ok(id)
+    }
+}
+
+impl<T: QueryEdge> QueryEdge for ObservedTxn<T> {
+    fn find_edges(
+        &self,
+        source: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.txn.find_edges(source, query)
+    }
+
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError> {
+        self.txn.find_edges_to(dest, query)
+    }
+}
+
+impl<T: Transactional> ReadTransactional for ObservedTxn<T> {
+    fn get(&self, id: Id) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.txn.get(id)
+    }
+
+    fn get_deleted(
+        &self,
+        id: Id,
+    ) -> Result<Option<Box<dyn Ent>>, DatabaseError> {
+        self.txn.get_deleted(id)
+    }
+
+    fn list_deleted(
+        &self,
+        deleted_before: u64,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.txn.list_deleted(deleted_before, after, limit)
+    }
+
+    fn list_ids_by_type(
+        &self,
+        type_name: &str,
+        after: Option<Id>,
+        limit: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        self.txn.list_ids_by_type(type_name, after, limit)
+    }
+
+    fn find_unique(
+        &self,
+        key: &UniqueKey,
+    ) -> Result<Option<Id>, DatabaseError> {
+        self.txn.find_unique(key)
+    }
+
+    fn scan_entities(
+        &self,
+        cursor: Option<EntityCursor>,
+        limit: usize,
+    ) -> Result<EntityPage, DatabaseError> {
+        self.txn.scan_entities(cursor, limit)
+    }
+}
+
+impl<T: Transactional> Transactional for ObservedTxn<T> {
+    fn create<E: EntWithEdges>(&self, ent: E) -> Result<Id, DatabaseError> {
+        let copy = dyn_clone::clone(&ent);
+        self.observe_create(&copy, || self.txn.create(ent))
+    }
+
+    fn create_with_id<E: EntWithEdges>(
+        &self,
+        id: Id,
+        ent: E,
+    ) -> Result<Id, DatabaseError> {
+        let mut copy = dyn_clone::clone(&ent);
+        copy.set_id(id);
+        self.observe_create(&copy, || self.txn.create_with_id(id, ent))
+    }
+
+    fn delete<E: EntWithEdges>(&self, id: Id) -> Result<(), DatabaseError> {
+        if self.observers.is_empty() {
+            return self.txn.delete::<E>(id);
+        }
+        let txn = &self.txn;
+        let Some(ent) = txn.get(id)? else {
+            return txn.delete::<E>(id);
+        };
+        self.observers
+            .each(|o| o.before_delete(txn, ent.as_ref()))?;
+        txn.delete::<E>(id)?;
+        self.observers.each(|o| o.after_delete(txn, ent.as_ref()))
+    }
+
+    fn create_edge(&self, edge: EdgeValue) -> Result<(), DatabaseError> {
+        self.txn.create_edge(edge)
+    }
+
+    fn hide_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.txn.hide_edge(edge)
+    }
+
+    fn restore_edge(&self, edge: &EdgeValue) -> Result<bool, DatabaseError> {
+        self.txn.restore_edge(edge)
+    }
+
+    fn update<E, F, B>(
+        &self,
+        mut ent: B,
+        mutator: F,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: EntWithEdges,
+        F: FnOnce(&mut E),
+        B: BorrowMut<E>,
+    {
+        if self.observers.is_empty() {
+            return self.txn.update(ent, mutator);
+        }
+        let txn = &self.txn;
+        let ent = ent.borrow_mut();
+        let mut new = dyn_clone::clone(&*ent);
+        mutator(&mut new);
+        self.observers.each(|o| o.before_update(txn, &*ent, &new))?;
+        if !txn.update(&mut *ent, |e: &mut E| *e = new)? {
+            return Ok(false);
+        }
+        self.observers.each(|o| o.after_update(txn, &*ent))?;
+        Ok(true)
+    }
+
+    fn touch<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.txn.touch::<E>(id)
+    }
+
+    fn soft_delete<E: EntWithEdges>(
+        &self,
+        id: Id,
+    ) -> Result<bool, DatabaseError> {
+        self.txn.soft_delete::<E>(id)
+    }
+
+    fn restore<E: EntWithEdges>(&self, id: Id) -> Result<bool, DatabaseError> {
+        self.txn.restore::<E>(id)
+    }
+
+    fn commit(self) -> Result<(), DatabaseError> {
+        self.txn.commit()
+    }
+}
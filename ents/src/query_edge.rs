@@ -1,5 +1,12 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
 use crate::{DatabaseError, Id};
 
+/// Number of edges a query returns when it sets no limit
+pub const DEFAULT_EDGE_LIMIT: usize = 100;
+
 /// Sort order for edge queries
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
@@ -9,13 +16,15 @@ pub enum SortOrder {
     Desc,
 }
 
-/// Cursor for pagination combining sort key and destination
+/// Cursor for pagination combining sort key, destination and discriminator
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EdgeCursor<'a> {
     /// The sort key value at the cursor position
     pub sort_key: &'a [u8],
     /// The destination ID at the cursor position
     pub destination: Id,
+    /// The discriminator at the cursor position (0 for plain edges)
+    pub discriminator: u64,
 }
 
 impl<'a> EdgeCursor<'a> {
@@ -24,11 +33,118 @@ impl<'a> EdgeCursor<'a> {
         Self {
             sort_key,
             destination,
+            discriminator: 0,
+        }
+    }
+
+    /// Create a cursor positioned at the given edge
+    pub fn from_edge(edge: &'a Edge) -> Self {
+        Self {
+            sort_key: &edge.sort_key,
+            destination: edge.dest,
+            discriminator: edge.discriminator,
+        }
+    }
+
+    /// Create a cursor positioned at an edge returned by
+    /// [`QueryEdge::find_edges_to`], where results are ordered by source
+    /// instead of destination
+    pub fn from_incoming_edge(edge: &'a Edge) -> Self {
+        Self {
+            sort_key: &edge.sort_key,
+            destination: edge.source,
+            discriminator: edge.discriminator,
+        }
+    }
+
+    /// Set the discriminator at the cursor position
+    pub fn with_discriminator(mut self, discriminator: u64) -> Self {
+        self.discriminator = discriminator;
+        self
+    }
+
+    /// Copy the cursor into an owned one that outlives the sort key
+    pub fn into_owned(self) -> EdgeCursorOwned {
+        EdgeCursorOwned {
+            sort_key: self.sort_key.to_vec(),
+            destination: self.destination,
+            discriminator: self.discriminator,
+        }
+    }
+}
+
+/// Error returned when a page token cannot be decoded
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid edge cursor token")]
+pub struct InvalidCursorToken;
+
+/// An [`EdgeCursor`] owning its sort key, so it can be stored or handed to
+/// an API client as an opaque page token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EdgeCursorOwned {
+    pub sort_key: Vec<u8>,
+    pub destination: Id,
+    pub discriminator: u64,
+}
+
+impl EdgeCursorOwned {
+    /// Create an owned cursor positioned at the given edge
+    pub fn from_edge(edge: &Edge) -> Self {
+        EdgeCursor::from_edge(edge).into_owned()
+    }
+
+    /// Create an owned cursor positioned at an edge returned by
+    /// [`QueryEdge::find_edges_to`]
+    pub fn from_incoming_edge(edge: &Edge) -> Self {
+        EdgeCursor::from_incoming_edge(edge).into_owned()
+    }
+
+    /// Borrow as a cursor for [`EdgeQuery::with_cursor`]
+    pub fn as_cursor(&self) -> EdgeCursor<'_> {
+        EdgeCursor {
+            sort_key: &self.sort_key,
+            destination: self.destination,
+            discriminator: self.discriminator,
+        }
+    }
+
+    /// Encode as a URL-safe page token: the destination and discriminator
+    /// big-endian followed by the sort key, in unpadded base64
+    pub fn to_token(&self) -> String {
+        let mut bytes = Vec::with_capacity(16 + self.sort_key.len());
+        bytes.extend_from_slice(&self.destination.to_be_bytes());
+        bytes.extend_from_slice(&self.discriminator.to_be_bytes());
+        bytes.extend_from_slice(&self.sort_key);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Decode a token produced by [`EdgeCursorOwned::to_token`]
+    pub fn from_token(token: &str) -> Result<Self, InvalidCursorToken> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|_| InvalidCursorToken)?;
+        if bytes.len() < 16 {
+            return Err(InvalidCursorToken);
         }
+        let (destination, rest) = bytes.split_at(8);
+        let (discriminator, sort_key) = rest.split_at(8);
+        Ok(Self {
+            sort_key: sort_key.to_vec(),
+            destination: Id::from_be_bytes(destination.try_into().unwrap()),
+            discriminator: u64::from_be_bytes(
+                discriminator.try_into().unwrap(),
+            ),
+        })
     }
 }
 
-/// Edge result containing all three properties
+impl From<EdgeCursor<'_>> for EdgeCursorOwned {
+    fn from(cursor: EdgeCursor<'_>) -> Self {
+        cursor.into_owned()
+    }
+}
+
+/// Edge result containing all stored properties
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Edge {
     /// Source entity ID
@@ -37,6 +153,12 @@ pub struct Edge {
     pub sort_key: Vec<u8>,
     /// Destination entity ID
     pub dest: Id,
+    /// Discriminator separating parallel edges (0 for plain edges)
+    pub discriminator: u64,
+    /// Whether the edge is hidden (soft-deleted)
+    pub hidden: bool,
+    /// Data stored with the edge (empty for plain edges)
+    pub payload: Vec<u8>,
 }
 
 impl Edge {
@@ -46,8 +168,29 @@ impl Edge {
             source,
             sort_key,
             dest,
+            discriminator: 0,
+            hidden: false,
+            payload: Vec::new(),
         }
     }
+
+    /// Set the discriminator
+    pub fn with_discriminator(mut self, discriminator: u64) -> Self {
+        self.discriminator = discriminator;
+        self
+    }
+
+    /// Mark the edge as hidden
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Set the payload
+    pub fn with_payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
 }
 
 /// Query parameters for edge enumeration
@@ -58,9 +201,13 @@ pub struct EdgeQuery<'a> {
     /// Sort order for results
     pub order: SortOrder,
     /// Cursor for pagination:
-    /// - For Asc order: returns edges with (sort_key, destination) > cursor
-    /// - For Desc order: returns edges with (sort_key, destination) < cursor
+    /// - For Asc order: returns edges with (sort_key, destination, discriminator) > cursor
+    /// - For Desc order: returns edges with (sort_key, destination, discriminator) < cursor
     pub cursor: Option<EdgeCursor<'a>>,
+    /// Whether hidden (soft-deleted) edges are returned. Defaults to false.
+    pub include_hidden: bool,
+    /// Maximum number of edges returned. Defaults to [`DEFAULT_EDGE_LIMIT`].
+    pub limit: Option<usize>,
 }
 
 impl<'a> EdgeQuery<'a> {
@@ -70,6 +217,8 @@ impl<'a> EdgeQuery<'a> {
             edge_names,
             order: SortOrder::Asc,
             cursor: None,
+            include_hidden: false,
+            limit: None,
         }
     }
 
@@ -79,6 +228,8 @@ impl<'a> EdgeQuery<'a> {
             edge_names,
             order: SortOrder::Desc,
             cursor: None,
+            include_hidden: false,
+            limit: None,
         }
     }
 
@@ -92,6 +243,23 @@ impl<'a> EdgeQuery<'a> {
         self.cursor = cursor;
         self
     }
+
+    /// Include hidden (soft-deleted) edges in the results
+    pub fn include_hidden(mut self) -> Self {
+        self.include_hidden = true;
+        self
+    }
+
+    /// Return at most `limit` edges
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The number of edges the query returns at most
+    pub fn max_edges(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_EDGE_LIMIT)
+    }
 }
 
 pub trait QueryEdge {
@@ -101,12 +269,57 @@ pub trait QueryEdge {
     /// * `source` - The source entity ID
     /// * `query` - Query parameters specifying filters, ordering, and pagination
     ///
-    /// Returns up to 100 edges matching the query criteria, sorted by (sort_key, destination).
-    /// For ascending order, edges are returned where (sort_key, destination) > cursor.
-    /// For descending order, edges are returned where (sort_key, destination) < cursor.
+    /// Returns up to the query's limit (100 by default) of the edges matching
+    /// the query criteria, sorted by
+    /// (sort_key, destination, discriminator).
+    /// For ascending order, edges are returned where (sort_key, destination, discriminator) > cursor.
+    /// For descending order, edges are returned where (sort_key, destination, discriminator) < cursor.
+    /// Hidden edges are skipped unless the query includes them.
     fn find_edges(
         &self,
         source: Id,
         query: EdgeQuery,
     ) -> Result<Vec<Edge>, DatabaseError>;
+
+    /// Find edges pointing at `dest`, the mirror image of `find_edges`.
+    ///
+    /// Returns up to the query's limit of edges sorted by
+    /// (sort_key, source, discriminator).
+    /// The cursor's `destination` holds the source of the edge to continue
+    /// after; build it with [`EdgeCursor::from_incoming_edge`].
+    fn find_edges_to(
+        &self,
+        dest: Id,
+        query: EdgeQuery,
+    ) -> Result<Vec<Edge>, DatabaseError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_token_roundtrip() {
+        let edge = Edge::new(1, b"follows".to_vec(), 42).with_discriminator(7);
+        let cursor = EdgeCursorOwned::from_edge(&edge);
+        let token = cursor.to_token();
+        assert!(token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+
+        let decoded = EdgeCursorOwned::from_token(&token).unwrap();
+        assert_eq!(decoded, cursor);
+        assert_eq!(decoded.as_cursor(), EdgeCursor::from_edge(&edge));
+    }
+
+    #[test]
+    fn test_cursor_token_invalid() {
+        assert!(EdgeCursorOwned::from_token("not base64!").is_err());
+        assert!(EdgeCursorOwned::from_token("AAAA").is_err());
+        let empty_key = EdgeCursorOwned::from(EdgeCursor::new(b"", 3));
+        assert_eq!(
+            EdgeCursorOwned::from_token(&empty_key.to_token()).unwrap(),
+            empty_key
+        );
+    }
 }
@@ -0,0 +1,180 @@
+//! Rate limits counted in the store.
+//!
+//! [`check_and_increment`] counts an action under a key, such as a user id
+//! and the action name, and refuses it once `limit` actions fall into the
+//! last `window`. The count is kept in a [`RateCounter`] entity updated in
+//! the caller's transaction, so the action's writes and its count commit or
+//! roll back together.
+//!
+//! Counting uses a sliding window approximated from two fixed windows: the
+//! actions of the current window, plus those of the previous window
+//! weighted by how much of it still overlaps the last `window`.
+//!
+//! ```ignore
+//! let key = format!("post:{}", user_id);
+//! if !check_and_increment(&txn, &key, 10, Duration::from_secs(60))? {
+//!     return Err(TooManyRequests);
+//! }
+//! txn.create(post)?;
+//! txn.commit()?;
+//! ```
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::now_micros;
+use crate::unique::UniqueKey;
+use crate::{
+    DatabaseError, Ent, EntMutationError, EntWithEdges, Id, NullEdgeProvider,
+    Transactional,
+};
+
+/// Name of the unique key holding the counter of a rate limit key
+pub const RATE_LIMIT_KEY: &str = "ents::rate_limit";
+
+/// The counts of one rate limit key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateCounter {
+    pub key: String,
+    /// Start of the current window in microseconds since the epoch
+    pub window_start: u64,
+    /// Actions counted in the current window
+    pub count: u64,
+    /// Actions counted in the window before
+    pub previous_count: u64,
+    pub id: Id,
+    pub last_updated: u64,
+}
+
+#[typetag::serde(name = "ents::RateCounter")]
+impl Ent for RateCounter {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.last_updated
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        self.last_updated = now_micros();
+        Ok(())
+    }
+}
+
+impl EntWithEdges for RateCounter {
+    type EdgeProvider = NullEdgeProvider;
+
+    fn unique_keys(&self) -> Vec<UniqueKey> {
+        vec![UniqueKey::new(RATE_LIMIT_KEY, self.key.as_str())]
+    }
+}
+
+crate::register_ent!(RateCounter, name = "ents::RateCounter");
+
+impl RateCounter {
+    /// Move to the window starting at `start`
+    fn roll(&mut self, start: u64, window: u64) {
+        if self.window_start == start {
+            return;
+        }
+        self.previous_count = if self.window_start + window == start {
+            self.count
+        } else {
+            0
+        };
+        self.count = 0;
+        self.window_start = start;
+    }
+
+    /// Actions in the `window` before `now`, once rolled to its window
+    fn estimate(&self, now: u64, window: u64) -> u64 {
+        let overlap = window - (now - self.window_start);
+        self.count
+            + (self.previous_count as u128 * overlap as u128 / window as u128)
+                as u64
+    }
+}
+
+/// Count an action under `key` unless `limit` actions were already counted
+/// in the last `window`. Returns whether the action is allowed.
+///
+/// A lost race with a concurrent transaction fails with
+/// [`DatabaseError::Conflict`].
+pub fn check_and_increment<T: Transactional>(
+    txn: &T,
+    key: &str,
+    limit: u64,
+    window: Duration,
+) -> Result<bool, DatabaseError> {
+    let window = (window.as_micros() as u64).max(1);
+    let now = now_micros();
+    let start = now - now % window;
+    let (mut counter, _) =
+        txn.get_or_create(&UniqueKey::new(RATE_LIMIT_KEY, key), || {
+            RateCounter {
+                key: key.to_string(),
+                window_start: start,
+                count: 0,
+                previous_count: 0,
+                id: 0,
+                last_updated: 0,
+            }
+        })?;
+
+    let mut rolled = counter.clone();
+    rolled.roll(start, window);
+    if rolled.estimate(now, window) >= limit {
+        return Ok(false);
+    }
+    txn.try_update(&mut counter, |c: &mut RateCounter| {
+        c.roll(start, window);
+        c.count += 1;
+    })?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter(
+        window_start: u64,
+        count: u64,
+        previous_count: u64,
+    ) -> RateCounter {
+        RateCounter {
+            key: "k".to_string(),
+            window_start,
+            count,
+            previous_count,
+            id: 0,
+            last_updated: 0,
+        }
+    }
+
+    #[test]
+    fn test_roll() {
+        let mut c = counter(100, 4, 9);
+        c.roll(100, 100);
+        assert_eq!((c.count, c.previous_count), (4, 9));
+        c.roll(200, 100);
+        assert_eq!((c.window_start, c.count, c.previous_count), (200, 0, 4));
+        c.count = 3;
+        c.roll(400, 100);
+        assert_eq!((c.count, c.previous_count), (0, 0));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let c = counter(200, 2, 8);
+        assert_eq!(c.estimate(200, 100), 10);
+        assert_eq!(c.estimate(250, 100), 6);
+        assert_eq!(c.estimate(299, 100), 2);
+    }
+}
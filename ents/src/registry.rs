@@ -0,0 +1,159 @@
+//! Registration of entity types and edge schemas declared across crates.
+//!
+//! Applications often define entities in many crates. Each crate declares its
+//! types with [`register_ent!`](crate::register_ent) and its edges with
+//! [`register_edge!`](crate::register_edge); the declarations are collected at
+//! link time so tooling (servers, CLIs, linters) can enumerate them without a
+//! hand-maintained central list.
+
+/// A registered entity type.
+#[derive(Debug)]
+pub struct EntRegistration {
+    /// The typetag name the entity is serialized with
+    pub type_name: &'static str,
+}
+
+impl EntRegistration {
+    /// Create a new registration for the given typetag name
+    pub const fn new(type_name: &'static str) -> Self {
+        Self { type_name }
+    }
+}
+
+/// A registered edge schema, describing which entity types an edge connects.
+#[derive(Debug)]
+pub struct EdgeSchema {
+    /// The edge name (sort key) as stored in the database
+    pub name: &'static [u8],
+    /// Typetag name of the source entity type
+    pub source_type: &'static str,
+    /// Typetag name of the destination entity type
+    pub dest_type: &'static str,
+}
+
+impl EdgeSchema {
+    /// Create a new edge schema
+    pub const fn new(
+        name: &'static [u8],
+        source_type: &'static str,
+        dest_type: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            source_type,
+            dest_type,
+        }
+    }
+}
+
+inventory::collect!(EntRegistration);
+inventory::collect!(EdgeSchema);
+
+/// Iterate over all entity types registered in the final binary.
+pub fn registered_ents() -> impl Iterator<Item = &'static EntRegistration> {
+    inventory::iter::<EntRegistration>.into_iter()
+}
+
+/// Iterate over all edge schemas registered in the final binary.
+pub fn registered_edges() -> impl Iterator<Item = &'static EdgeSchema> {
+    inventory::iter::<EdgeSchema>.into_iter()
+}
+
+/// Look up the registration of an entity type by its typetag name.
+pub fn find_ent(type_name: &str) -> Option<&'static EntRegistration> {
+    registered_ents().find(|r| r.type_name == type_name)
+}
+
+/// Iterate over edge schemas whose source is the given entity type.
+pub fn edges_from(
+    type_name: &str,
+) -> impl Iterator<Item = &'static EdgeSchema> + '_ {
+    registered_edges().filter(move |s| s.source_type == type_name)
+}
+
+/// Register an entity type so it can be enumerated at runtime.
+///
+/// The type must implement [`Ent`](crate::Ent). By default the registered name
+/// is the type's identifier, matching typetag's default; pass `name = "..."`
+/// when the entity uses a custom typetag name.
+///
+/// ```ignore
+/// ents::register_ent!(User);
+/// ents::register_ent!(Post, name = "BlogPost");
+/// ```
+#[macro_export]
+macro_rules! register_ent {
+    ($ty:ident) => {
+        $crate::register_ent!($ty, name = stringify!($ty));
+    };
+    ($ty:ty, name = $name:expr) => {
+        const _: () = {
+            fn assert_ent<T: $crate::Ent>() {}
+            let _ = assert_ent::<$ty>;
+        };
+        $crate::inventory::submit! {
+            $crate::registry::EntRegistration::new($name)
+        }
+    };
+}
+
+/// Register an edge schema between two entity types.
+///
+/// ```ignore
+/// ents::register_edge!(Post => User, b"author");
+/// ```
+#[macro_export]
+macro_rules! register_edge {
+    ($source:ident => $dest:ident, $name:expr) => {
+        $crate::inventory::submit! {
+            $crate::registry::EdgeSchema::new(
+                $name,
+                stringify!($source),
+                stringify!($dest),
+            )
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntMutationError, Id};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Widget {
+        id: Id,
+        last_updated: u64,
+    }
+
+    #[typetag::serde]
+    impl crate::Ent for Widget {
+        fn id(&self) -> Id {
+            self.id
+        }
+        fn set_id(&mut self, id: Id) {
+            self.id = id;
+        }
+        fn last_updated(&self) -> u64 {
+            self.last_updated
+        }
+        fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+            Ok(())
+        }
+    }
+
+    crate::register_ent!(Widget);
+    crate::register_edge!(Widget => Widget, b"parent");
+
+    #[test]
+    fn test_registered_ents() {
+        assert!(find_ent("Widget").is_some());
+        assert!(find_ent("Gadget").is_none());
+
+        let edges: Vec<_> = edges_from("Widget").collect();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].name, b"parent");
+        assert_eq!(edges[0].dest_type, "Widget");
+    }
+}
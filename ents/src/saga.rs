@@ -0,0 +1,162 @@
+//! Sagas: multi-step operations with compensation.
+//!
+//! Each step runs in its own transaction and has a compensation that undoes
+//! it. When a step fails, the compensations of the already committed steps
+//! run in reverse order. Progress is persisted in the workflow log (see
+//! [`crate::workflow`]) under the key `saga:<key>`, so re-running a saga with
+//! the same key after a crash resumes where it stopped, whether it was moving
+//! forward or compensating.
+
+use crate::workflow::{self, WorkflowRecord};
+use crate::{DatabaseError, Transactional};
+
+type StepFn<'a, T> = Box<dyn FnMut(&T) -> Result<(), DatabaseError> + 'a>;
+
+struct SagaStep<'a, T> {
+    name: String,
+    action: StepFn<'a, T>,
+    compensate: StepFn<'a, T>,
+}
+
+/// How a saga ended
+#[derive(Debug)]
+pub enum SagaOutcome {
+    /// Every step committed
+    Completed,
+    /// A step failed and the committed steps were compensated
+    Compensated {
+        failed_step: String,
+        /// The step's error; None when resuming a saga whose failure was
+        /// recorded by an earlier run
+        error: Option<DatabaseError>,
+    },
+}
+
+/// A sequence of steps with compensations, identified by a key
+pub struct Saga<'a, T> {
+    key: String,
+    steps: Vec<SagaStep<'a, T>>,
+}
+
+impl<'a, T: Transactional> Saga<'a, T> {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Append a step. Step names must be unique within the saga.
+    pub fn step<A, C>(
+        mut self,
+        name: impl Into<String>,
+        action: A,
+        compensate: C,
+    ) -> Self
+    where
+        A: FnMut(&T) -> Result<(), DatabaseError> + 'a,
+        C: FnMut(&T) -> Result<(), DatabaseError> + 'a,
+    {
+        self.steps.push(SagaStep {
+            name: name.into(),
+            action: Box::new(action),
+            compensate: Box::new(compensate),
+        });
+        self
+    }
+
+    /// Run (or resume) the saga, taking a fresh transaction from `begin` for
+    /// every step.
+    ///
+    /// Returns an error only if the log cannot be written or a compensation
+    /// fails; running the saga again then resumes compensating.
+    pub fn run<B>(mut self, mut begin: B) -> Result<SagaOutcome, DatabaseError>
+    where
+        B: FnMut() -> Result<T, DatabaseError>,
+    {
+        let txn = begin()?;
+        let mut wf =
+            workflow::begin_workflow(&txn, &format!("saga:{}", self.key))?;
+        txn.commit()?;
+
+        if let Some(failed_step) = failed_step(&wf) {
+            if !wf.completed {
+                self.compensate(&mut begin, &mut wf)?;
+            }
+            return Ok(SagaOutcome::Compensated {
+                failed_step,
+                error: None,
+            });
+        }
+
+        for step in self.steps.iter_mut() {
+            let done = format!("do:{}", step.name);
+            if wf.is_done(&done) {
+                continue;
+            }
+
+            let txn = begin()?;
+            match (step.action)(&txn) {
+                Ok(()) => {
+                    workflow::checkpoint(&txn, &mut wf, &done)?;
+                    txn.commit()?;
+                }
+                Err(error) => {
+                    drop(txn);
+                    let failed_step = step.name.clone();
+                    let txn = begin()?;
+                    workflow::checkpoint(
+                        &txn,
+                        &mut wf,
+                        &format!("failed:{}", failed_step),
+                    )?;
+                    txn.commit()?;
+
+                    self.compensate(&mut begin, &mut wf)?;
+                    return Ok(SagaOutcome::Compensated {
+                        failed_step,
+                        error: Some(error),
+                    });
+                }
+            }
+        }
+
+        let txn = begin()?;
+        workflow::complete(&txn, &mut wf)?;
+        txn.commit()?;
+        Ok(SagaOutcome::Completed)
+    }
+
+    /// Undo every committed step that has not been undone, newest first
+    fn compensate<B>(
+        &mut self,
+        begin: &mut B,
+        wf: &mut WorkflowRecord,
+    ) -> Result<(), DatabaseError>
+    where
+        B: FnMut() -> Result<T, DatabaseError>,
+    {
+        for step in self.steps.iter_mut().rev() {
+            let undone = format!("undo:{}", step.name);
+            if !wf.is_done(&format!("do:{}", step.name)) || wf.is_done(&undone)
+            {
+                continue;
+            }
+
+            let txn = begin()?;
+            (step.compensate)(&txn)?;
+            workflow::checkpoint(&txn, wf, &undone)?;
+            txn.commit()?;
+        }
+
+        let txn = begin()?;
+        workflow::complete(&txn, wf)?;
+        txn.commit()
+    }
+}
+
+fn failed_step(wf: &WorkflowRecord) -> Option<String> {
+    wf.completed_steps
+        .iter()
+        .find_map(|s| s.strip_prefix("failed:").map(str::to_string))
+}
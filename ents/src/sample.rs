@@ -0,0 +1,101 @@
+//! Reservoir sampling used to pick representative entities and edges.
+//!
+//! Backends stream a full scan through a [`Reservoir`], which keeps a uniform
+//! random sample of fixed size without knowing the total count up front. The
+//! generator is seeded, so the same seed over the same data yields the same
+//! sample — handy when building test fixtures.
+
+/// Fixed-size uniform sample over a stream of items (Algorithm R)
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    capacity: usize,
+    seen: u64,
+    items: Vec<T>,
+    rng: SplitMix64,
+}
+
+impl<T> Reservoir<T> {
+    /// Create a reservoir keeping at most `capacity` items
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        Self {
+            capacity,
+            seen: 0,
+            items: Vec::with_capacity(capacity),
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Offer the next item of the stream
+    pub fn offer(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+            return;
+        }
+        let slot = self.rng.next_below(self.seen);
+        if (slot as usize) < self.capacity {
+            self.items[slot as usize] = item;
+        }
+    }
+
+    /// Number of items offered so far
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// The sampled items
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+/// Small, fast, seedable generator; not suitable for cryptographic use.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservoir_keeps_everything_when_small() {
+        let mut reservoir = Reservoir::new(10, 1);
+        for i in 0..5 {
+            reservoir.offer(i);
+        }
+        assert_eq!(reservoir.seen(), 5);
+        assert_eq!(reservoir.into_vec(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_reservoir_is_deterministic_and_spread() {
+        let sample = |seed| {
+            let mut reservoir = Reservoir::new(100, seed);
+            for i in 0..10_000 {
+                reservoir.offer(i);
+            }
+            reservoir.into_vec()
+        };
+
+        let a = sample(42);
+        assert_eq!(a.len(), 100);
+        assert_eq!(a, sample(42));
+        assert_ne!(a, sample(43));
+        // A uniform sample should reach well into the tail of the stream
+        assert!(a.iter().filter(|&&i| i >= 5_000).count() > 25);
+    }
+}
@@ -0,0 +1,167 @@
+//! Graph-wide statistics: degree distributions, per-edge-name counts and top
+//! hubs.
+//!
+//! Backends feed every entity and edge of a snapshot into a
+//! [`GraphStatsCollector`]; the resulting [`GraphStats`] report is
+//! serializable so it can be emitted as JSON by tooling.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Edge, Id};
+
+/// An entity with an unusually high number of edges
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hub {
+    pub id: Id,
+    pub out_degree: u64,
+    pub in_degree: u64,
+}
+
+/// Report produced by a statistics run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub entity_count: u64,
+    pub edge_count: u64,
+    /// Number of edges per edge name. See [`edge_name`] for how names are
+    /// derived from sort keys.
+    pub edges_by_name: BTreeMap<String, u64>,
+    /// Maps an out-degree to the number of entities with that out-degree
+    pub out_degree_histogram: BTreeMap<u64, u64>,
+    /// Maps an in-degree to the number of entities with that in-degree
+    pub in_degree_histogram: BTreeMap<u64, u64>,
+    /// Entities with the highest total degree, highest first
+    pub top_hubs: Vec<Hub>,
+}
+
+/// Derive a human-readable edge name from a sort key.
+///
+/// Sort keys frequently carry binary suffixes (e.g. timeline timestamps), so
+/// the name is the leading run of printable ASCII. Keys without such a prefix
+/// are rendered as hex.
+pub fn edge_name(sort_key: &[u8]) -> String {
+    let printable =
+        sort_key.iter().take_while(|b| b.is_ascii_graphic()).count();
+    if printable > 0 {
+        String::from_utf8_lossy(&sort_key[..printable]).into_owned()
+    } else {
+        sort_key.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Accumulates entities and edges into a [`GraphStats`] report
+#[derive(Debug, Default)]
+pub struct GraphStatsCollector {
+    entity_count: u64,
+    edge_count: u64,
+    edges_by_name: BTreeMap<String, u64>,
+    degrees: HashMap<Id, (u64, u64)>,
+}
+
+impl GraphStatsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an entity, so it counts towards the zero-degree buckets
+    pub fn observe_entity(&mut self, id: Id) {
+        self.entity_count += 1;
+        self.degrees.entry(id).or_default();
+    }
+
+    /// Record an edge
+    pub fn observe_edge(&mut self, edge: &Edge) {
+        self.edge_count += 1;
+        *self
+            .edges_by_name
+            .entry(edge_name(&edge.sort_key))
+            .or_default() += 1;
+        self.degrees.entry(edge.source).or_default().0 += 1;
+        self.degrees.entry(edge.dest).or_default().1 += 1;
+    }
+
+    /// Produce the report, keeping the `top_n` highest-degree hubs
+    pub fn finish(self, top_n: usize) -> GraphStats {
+        let mut out_degree_histogram = BTreeMap::new();
+        let mut in_degree_histogram = BTreeMap::new();
+        for (out_degree, in_degree) in self.degrees.values() {
+            *out_degree_histogram.entry(*out_degree).or_default() += 1;
+            *in_degree_histogram.entry(*in_degree).or_default() += 1;
+        }
+
+        let mut hubs: Vec<Hub> = self
+            .degrees
+            .into_iter()
+            .map(|(id, (out_degree, in_degree))| Hub {
+                id,
+                out_degree,
+                in_degree,
+            })
+            .collect();
+        hubs.sort_by(|a, b| {
+            (b.out_degree + b.in_degree)
+                .cmp(&(a.out_degree + a.in_degree))
+                .then(a.id.cmp(&b.id))
+        });
+        hubs.truncate(top_n);
+
+        GraphStats {
+            entity_count: self.entity_count,
+            edge_count: self.edge_count,
+            edges_by_name: self.edges_by_name,
+            out_degree_histogram,
+            in_degree_histogram,
+            top_hubs: hubs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::timeline_key;
+
+    #[test]
+    fn test_edge_name() {
+        assert_eq!(edge_name(b"follows"), "follows");
+        assert_eq!(edge_name(&timeline_key(b"feed", 10)), "feed");
+        assert_eq!(edge_name(&[0x00, 0xff]), "00ff");
+    }
+
+    #[test]
+    fn test_collector() {
+        let mut collector = GraphStatsCollector::new();
+        for id in 1..=4 {
+            collector.observe_entity(id);
+        }
+        for dest in 2..=4 {
+            collector.observe_edge(&Edge::new(1, b"follows".to_vec(), dest));
+        }
+        collector.observe_edge(&Edge::new(2, b"likes".to_vec(), 3));
+
+        let stats = collector.finish(2);
+        assert_eq!(stats.entity_count, 4);
+        assert_eq!(stats.edge_count, 4);
+        assert_eq!(stats.edges_by_name["follows"], 3);
+        assert_eq!(stats.edges_by_name["likes"], 1);
+        assert_eq!(stats.out_degree_histogram[&0], 2);
+        assert_eq!(stats.out_degree_histogram[&3], 1);
+        assert_eq!(stats.in_degree_histogram[&2], 1);
+        assert_eq!(
+            stats.top_hubs,
+            vec![
+                Hub {
+                    id: 1,
+                    out_degree: 3,
+                    in_degree: 0,
+                },
+                Hub {
+                    id: 2,
+                    out_degree: 1,
+                    in_degree: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Helpers for time-ordered edges such as activity feeds.
+//!
+//! A timeline edge uses a sort key of `name || reverse_timestamp`, where the
+//! reverse timestamp is `u64::MAX - timestamp` encoded big-endian. Ascending
+//! edge order therefore yields the newest entries first, and a time range maps
+//! to a contiguous range of sort keys.
+
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, Id, QueryEdge,
+};
+
+/// Build the sort key of a timeline edge
+pub fn timeline_key(name: &[u8], timestamp: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(name.len() + 8);
+    key.extend_from_slice(name);
+    key.extend_from_slice(&(u64::MAX - timestamp).to_be_bytes());
+    key
+}
+
+/// Extract the timestamp from a timeline sort key.
+///
+/// Returns None if the key does not belong to the named timeline.
+pub fn parse_timeline_key(name: &[u8], key: &[u8]) -> Option<u64> {
+    if key.len() != name.len() + 8 || !key.starts_with(name) {
+        return None;
+    }
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&key[name.len()..]);
+    Some(u64::MAX - u64::from_be_bytes(buf))
+}
+
+/// Build a timeline edge from `source` to `dest` at the given timestamp
+pub fn timeline_edge(
+    source: Id,
+    name: &[u8],
+    timestamp: u64,
+    dest: Id,
+) -> EdgeValue {
+    EdgeValue::new(source, timeline_key(name, timestamp), dest)
+}
+
+/// Return the `n` most recent edges of the named timeline, newest first.
+pub fn latest<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    n: usize,
+) -> Result<Vec<Edge>, DatabaseError> {
+    scan_range(txn, source, name, u64::MAX, 0, n)
+}
+
+/// Return edges of the named timeline with `from <= timestamp <= to`, newest
+/// first.
+pub fn between<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    from: u64,
+    to: u64,
+) -> Result<Vec<Edge>, DatabaseError> {
+    if from > to {
+        return Ok(Vec::new());
+    }
+    scan_range(txn, source, name, to, from, usize::MAX)
+}
+
+/// Return up to `limit` edges of the named timeline older than `cursor`,
+/// newest first. Use the last returned edge as the next cursor.
+pub fn page<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    cursor: Option<&Edge>,
+    limit: usize,
+) -> Result<Vec<Edge>, DatabaseError> {
+    let mut start = match cursor {
+        Some(edge) => edge.clone(),
+        None => timeline_start(name, u64::MAX),
+    };
+    collect(txn, source, name, &mut start, 0, limit)
+}
+
+fn scan_range<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    newest: u64,
+    oldest: u64,
+    limit: usize,
+) -> Result<Vec<Edge>, DatabaseError> {
+    let mut start = timeline_start(name, newest);
+    collect(txn, source, name, &mut start, oldest, limit)
+}
+
+/// A position sorting immediately before every edge of the timeline that is
+/// at or older than `newest`.
+fn timeline_start(name: &[u8], newest: u64) -> Edge {
+    let reverse = u64::MAX - newest;
+    let sort_key = match reverse.checked_sub(1) {
+        Some(prev) => {
+            let mut key = name.to_vec();
+            key.extend_from_slice(&prev.to_be_bytes());
+            key
+        }
+        // The bare name sorts before any key it prefixes
+        None => name.to_vec(),
+    };
+    Edge::new(0, sort_key, Id::MAX).with_discriminator(u64::MAX)
+}
+
+fn collect<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+    last: &mut Edge,
+    oldest: u64,
+    limit: usize,
+) -> Result<Vec<Edge>, DatabaseError> {
+    let end = timeline_key(name, oldest);
+    let mut results = Vec::new();
+
+    while results.len() < limit {
+        let query =
+            EdgeQuery::asc(&[]).with_cursor(EdgeCursor::from_edge(last));
+        let page = txn.find_edges(source, query)?;
+        let Some(tail) = page.last().cloned() else {
+            break;
+        };
+
+        for edge in page {
+            if edge.sort_key > end {
+                return Ok(results);
+            }
+            if parse_timeline_key(name, &edge.sort_key).is_some() {
+                results.push(edge);
+                if results.len() >= limit {
+                    break;
+                }
+            }
+        }
+        *last = tail;
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeline_key_roundtrip() {
+        let key = timeline_key(b"posted", 1_700_000_000);
+        assert_eq!(parse_timeline_key(b"posted", &key), Some(1_700_000_000));
+        assert_eq!(parse_timeline_key(b"liked", &key), None);
+        assert_eq!(parse_timeline_key(b"posted", b"posted"), None);
+    }
+
+    #[test]
+    fn test_timeline_key_ordering() {
+        // Newer entries sort first
+        assert!(timeline_key(b"posted", 20) < timeline_key(b"posted", 10));
+        assert!(timeline_key(b"posted", u64::MAX) < timeline_key(b"posted", 0));
+    }
+}
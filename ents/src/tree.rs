@@ -0,0 +1,282 @@
+//! Trees over parent/child edges, for categories, org charts and comment
+//! threads.
+//!
+//! A [`Tree`] links each attached node to its parent with a
+//! `tree:<name>:parent` edge and the parent back with a `tree:<name>:child`
+//! edge. It also maintains a materialized path per node: an edge from the
+//! system id 0 whose sort key is `tree:<name>:path:` followed by the
+//! big-endian ids from the root down to the node. The paths of a subtree
+//! share a prefix and sort contiguously, so [`Tree::descendants`] is a single
+//! range scan in depth-first order.
+//!
+//! A node without a parent is a root. Moving a subtree rewrites the paths of
+//! every node in it; the replaced edges are hidden rather than removed.
+//!
+//! ```ignore
+//! const CATEGORIES: Tree = Tree::new("category");
+//!
+//! CATEGORIES.attach(&txn, laptops, computers)?;
+//! let crumbs = CATEGORIES.ancestors(&txn, laptops)?;
+//! let all = CATEGORIES.descendants(&txn, computers, usize::MAX)?;
+//! ```
+
+use crate::ndjson::put_edge;
+use crate::{
+    DatabaseError, Edge, EdgeCursor, EdgeQuery, EdgeValue, Id, QueryEdge,
+    ReadTransactional, Transactional, DEFAULT_EDGE_LIMIT,
+};
+
+/// A named hierarchy of entities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tree {
+    name: &'static str,
+}
+
+impl Tree {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn edge_name(&self, kind: &str) -> Vec<u8> {
+        format!("tree:{}:{}", self.name, kind).into_bytes()
+    }
+
+    fn path_key(&self, path: &[Id]) -> Vec<u8> {
+        let mut key = self.edge_name("path:");
+        for id in path {
+            key.extend_from_slice(&id.to_be_bytes());
+        }
+        key
+    }
+
+    /// Parent of `id`, or None for a root
+    pub fn parent<Q: ReadTransactional>(
+        &self,
+        txn: &Q,
+        id: Id,
+    ) -> Result<Option<Id>, DatabaseError> {
+        let name = self.edge_name("parent");
+        let edges = txn.find_edges(id, EdgeQuery::asc(&[&name]))?;
+        Ok(edges.first().map(|e| e.dest))
+    }
+
+    /// Direct children of `id` in id order
+    pub fn children<Q: ReadTransactional>(
+        &self,
+        txn: &Q,
+        id: Id,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let name = self.edge_name("child");
+        Ok(all_edges(txn, id, &name)?.iter().map(|e| e.dest).collect())
+    }
+
+    /// Ancestors of `id`, nearest first
+    pub fn ancestors<Q: ReadTransactional>(
+        &self,
+        txn: &Q,
+        id: Id,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let mut ancestors = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.parent(txn, current)? {
+            if parent == id || ancestors.contains(&parent) {
+                return Err(DatabaseError::Other {
+                    source: format!(
+                        "tree '{}' has a cycle through {}",
+                        self.name, parent
+                    )
+                    .into(),
+                });
+            }
+            ancestors.push(parent);
+            current = parent;
+        }
+        Ok(ancestors)
+    }
+
+    /// Ids from the root of `id`'s tree down to `id` itself
+    pub fn path<Q: ReadTransactional>(
+        &self,
+        txn: &Q,
+        id: Id,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let mut path = self.ancestors(txn, id)?;
+        path.reverse();
+        path.push(id);
+        Ok(path)
+    }
+
+    /// Descendants of `id` at most `depth` levels below it, in depth-first
+    /// order with siblings in id order. A depth of 1 returns the children.
+    pub fn descendants<Q: ReadTransactional>(
+        &self,
+        txn: &Q,
+        id: Id,
+        depth: usize,
+    ) -> Result<Vec<Id>, DatabaseError> {
+        let prefix = self.path_key(&self.path(txn, id)?);
+        Ok(subtree(txn, &prefix)?
+            .into_iter()
+            .filter(|e| (e.sort_key.len() - prefix.len()) / 8 <= depth)
+            .map(|e| e.dest)
+            .collect())
+    }
+
+    /// Attach the root `id`, with its subtree, under `parent`
+    pub fn attach<T: Transactional>(
+        &self,
+        txn: &T,
+        id: Id,
+        parent: Id,
+    ) -> Result<(), DatabaseError> {
+        if let Some(current) = self.parent(txn, id)? {
+            return Err(DatabaseError::Other {
+                source: format!(
+                    "{} is already attached under {} in tree '{}'",
+                    id, current, self.name
+                )
+                .into(),
+            });
+        }
+        self.move_subtree(txn, id, Some(parent))
+    }
+
+    /// Detach `id` from its parent, making it the root of its subtree
+    pub fn detach<T: Transactional>(
+        &self,
+        txn: &T,
+        id: Id,
+    ) -> Result<(), DatabaseError> {
+        self.move_subtree(txn, id, None)
+    }
+
+    /// Move `id` and its subtree under `new_parent`, or make it a root.
+    /// Fails if `new_parent` is `id` or one of its descendants.
+    pub fn move_subtree<T: Transactional>(
+        &self,
+        txn: &T,
+        id: Id,
+        new_parent: Option<Id>,
+    ) -> Result<(), DatabaseError> {
+        let new_path = match new_parent {
+            Some(parent) => {
+                let mut path = self.path(txn, parent)?;
+                if path.contains(&id) {
+                    return Err(DatabaseError::Other {
+                        source: format!(
+                            "moving {} under {} would create a cycle in tree \
+                             '{}'",
+                            id, parent, self.name
+                        )
+                        .into(),
+                    });
+                }
+                path.push(id);
+                path
+            }
+            None => vec![id],
+        };
+        let old_parent = self.parent(txn, id)?;
+        if old_parent == new_parent {
+            return Ok(());
+        }
+
+        let old_prefix = self.path_key(&self.path(txn, id)?);
+        let new_prefix = self.path_key(&new_path);
+        let descendants = subtree(txn, &old_prefix)?;
+
+        let parent_name = self.edge_name("parent");
+        let child_name = self.edge_name("child");
+        if let Some(parent) = old_parent {
+            txn.hide_edge(&EdgeValue::new(id, parent_name.clone(), parent))?;
+            txn.hide_edge(&EdgeValue::new(parent, child_name.clone(), id))?;
+            txn.hide_edge(&EdgeValue::new(0, old_prefix.clone(), id))?;
+        }
+        if let Some(parent) = new_parent {
+            put_edge(txn, EdgeValue::new(id, parent_name, parent), false)?;
+            put_edge(txn, EdgeValue::new(parent, child_name, id), false)?;
+            put_edge(txn, EdgeValue::new(0, new_prefix.clone(), id), false)?;
+        }
+
+        for edge in descendants {
+            let mut key = new_prefix.clone();
+            key.extend_from_slice(&edge.sort_key[old_prefix.len()..]);
+            txn.hide_edge(&EdgeValue::new(0, edge.sort_key, edge.dest))?;
+            put_edge(txn, EdgeValue::new(0, key, edge.dest), false)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every edge named `name` from `source`
+fn all_edges<Q: QueryEdge>(
+    txn: &Q,
+    source: Id,
+    name: &[u8],
+) -> Result<Vec<Edge>, DatabaseError> {
+    let names = [name];
+    let mut edges = Vec::new();
+    let mut last: Option<Edge> = None;
+    loop {
+        let query = EdgeQuery::asc(&names)
+            .with_cursor_opt(last.as_ref().map(EdgeCursor::from_edge));
+        let page = txn.find_edges(source, query)?;
+        let done = page.len() < DEFAULT_EDGE_LIMIT;
+        last = page.last().cloned();
+        edges.extend(page);
+        if done {
+            return Ok(edges);
+        }
+    }
+}
+
+/// The path edges strictly below the path `prefix`
+fn subtree<Q: QueryEdge>(
+    txn: &Q,
+    prefix: &[u8],
+) -> Result<Vec<Edge>, DatabaseError> {
+    // Positioned after the node's own path edge; it is skipped below as
+    // well, since not every backend orders the maximal id last
+    let mut last =
+        Edge::new(0, prefix.to_vec(), Id::MAX).with_discriminator(u64::MAX);
+    let mut edges = Vec::new();
+    loop {
+        let query =
+            EdgeQuery::asc(&[]).with_cursor(EdgeCursor::from_edge(&last));
+        let page = txn.find_edges(0, query)?;
+        let done = page.len() < DEFAULT_EDGE_LIMIT;
+        for edge in &page {
+            if !edge.sort_key.starts_with(prefix) {
+                return Ok(edges);
+            }
+            if edge.sort_key.len() > prefix.len() {
+                edges.push(edge.clone());
+            }
+        }
+        match page.last() {
+            Some(tail) if !done => last = tail.clone(),
+            _ => return Ok(edges),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_key_ordering() {
+        let tree = Tree::new("category");
+        let parent = tree.path_key(&[1, 2]);
+        assert_eq!(&parent[..19], b"tree:category:path:");
+        let child = tree.path_key(&[1, 2, 3]);
+        assert!(child.starts_with(&parent));
+        // A subtree sorts before the next sibling of its root
+        assert!(child < tree.path_key(&[1, 3]));
+        assert!(tree.path_key(&[1, 2, u64::MAX]) < tree.path_key(&[1, 3]));
+    }
+}
@@ -0,0 +1,84 @@
+//! Unique keys enforced by the backends.
+//!
+//! An entity type declares the keys it must hold exclusively by overriding
+//! [`EntWithEdges::unique_keys`](crate::EntWithEdges::unique_keys). Backends
+//! claim the keys in `create`, move them in `update` and release them in
+//! `delete`, failing with [`DatabaseError::UniqueViolation`] when another
+//! entity already holds one. Keys are stored encoded as the key name, a zero
+//! byte and the value, so keys of different names never collide.
+//!
+//! ```ignore
+//! impl EntWithEdges for User {
+//!     type EdgeProvider = NullEdgeProvider;
+//!
+//!     fn unique_keys(&self) -> Vec<UniqueKey> {
+//!         vec![UniqueKey::new("user_email", self.email.to_lowercase())]
+//!     }
+//! }
+//!
+//! let owner = txn.find_unique(&UniqueKey::new("user_email", "a@b.c"))?;
+//! ```
+
+use crate::{DatabaseError, Id, Transactional};
+
+/// A value that at most one entity may hold under a name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UniqueKey {
+    pub name: &'static str,
+    pub value: Vec<u8>,
+}
+
+impl UniqueKey {
+    pub fn new(name: &'static str, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name,
+            value: value.into(),
+        }
+    }
+
+    /// The stored form of the key
+    pub fn encode(&self) -> Vec<u8> {
+        let mut key =
+            Vec::with_capacity(self.name.len() + 1 + self.value.len());
+        key.extend_from_slice(self.name.as_bytes());
+        key.push(0);
+        key.extend_from_slice(&self.value);
+        key
+    }
+}
+
+/// Fail with [`DatabaseError::UniqueViolation`] if an entity other than
+/// `owner` holds one of `keys`
+pub fn check_available<T: Transactional>(
+    txn: &T,
+    keys: &[UniqueKey],
+    owner: Option<Id>,
+) -> Result<(), DatabaseError> {
+    for key in keys {
+        match txn.find_unique(key)? {
+            Some(existing) if Some(existing) != owner => {
+                return Err(DatabaseError::UniqueViolation {
+                    name: key.name.to_string(),
+                    value: key.value.clone(),
+                    existing,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(UniqueKey::new("email", "a@b").encode(), b"email\0a@b");
+        assert_ne!(
+            UniqueKey::new("ab", "c").encode(),
+            UniqueKey::new("a", "bc").encode()
+        );
+    }
+}
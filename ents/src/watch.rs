@@ -0,0 +1,327 @@
+//! Change notifications for committed writes.
+//!
+//! Backends record the entity changes of a transaction in a [`ChangeLog`]
+//! and hand them to a [`WatchHub`] once the transaction commits, so watchers
+//! never observe writes that were rolled back. Subscriptions are plain
+//! channels: [`WatchHub::watch`] follows one entity, [`WatchHub::watch_type`]
+//! every entity of a type, and [`WatchHub::watch_edges`] the edges of one
+//! name leaving a source. Dropping the receiver ends the subscription.
+//!
+//! Hiding an edge is reported as a removal and restoring it as an addition,
+//! matching what `find_edges` returns by default.
+//!
+//! ```ignore
+//! let changes = env.watch_type::<User>();
+//! // ... writes commit elsewhere ...
+//! for change in changes.try_iter() {
+//!     cache.invalidate(change.id);
+//! }
+//! ```
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use crate::cdc::CapturedChange;
+use crate::{EdgeValue, Ent, Id};
+
+/// What happened to an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A committed change to one entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityChange {
+    pub id: Id,
+    pub kind: ChangeKind,
+    /// Rust type name of the entity, for diagnostics
+    pub type_name: &'static str,
+    type_id: TypeId,
+}
+
+impl EntityChange {
+    pub fn new<E: Ent>(id: Id, kind: ChangeKind) -> Self {
+        Self {
+            id,
+            kind,
+            type_name: std::any::type_name::<E>(),
+            type_id: TypeId::of::<E>(),
+        }
+    }
+
+    /// Whether the changed entity is of type `E`
+    pub fn is<E: Ent>(&self) -> bool {
+        self.type_id == TypeId::of::<E>()
+    }
+}
+
+/// A committed change to the edges of a source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeChange {
+    Added(EdgeValue),
+    Removed(EdgeValue),
+}
+
+impl EdgeChange {
+    pub fn edge(&self) -> &EdgeValue {
+        match self {
+            EdgeChange::Added(edge) | EdgeChange::Removed(edge) => edge,
+        }
+    }
+}
+
+struct EdgeFilter {
+    source: Id,
+    name: Vec<u8>,
+}
+
+impl EdgeFilter {
+    fn matches(&self, change: &EdgeChange) -> bool {
+        let edge = change.edge();
+        edge.source == self.source && edge.sort_key == self.name
+    }
+}
+
+enum EntityFilter {
+    Id(Id),
+    Type(TypeId),
+}
+
+impl EntityFilter {
+    fn matches(&self, change: &EntityChange) -> bool {
+        match self {
+            EntityFilter::Id(id) => change.id == *id,
+            EntityFilter::Type(type_id) => change.type_id == *type_id,
+        }
+    }
+}
+
+/// Subscribers to committed changes of one store
+#[derive(Default)]
+pub struct WatchHub {
+    entity_watchers: Mutex<Vec<(EntityFilter, Sender<EntityChange>)>>,
+    edge_watchers: Mutex<Vec<(EdgeFilter, Sender<EdgeChange>)>>,
+}
+
+impl WatchHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to changes of the entity `id`
+    pub fn watch(&self, id: Id) -> Receiver<EntityChange> {
+        self.subscribe(EntityFilter::Id(id))
+    }
+
+    /// Subscribe to changes of every entity of type `E`
+    pub fn watch_type<E: Ent>(&self) -> Receiver<EntityChange> {
+        self.subscribe(EntityFilter::Type(TypeId::of::<E>()))
+    }
+
+    /// Subscribe to additions and removals of `name` edges from `source`
+    pub fn watch_edges(&self, source: Id, name: &[u8]) -> Receiver<EdgeChange> {
+        let (tx, rx) = channel();
+        let filter = EdgeFilter {
+            source,
+            name: name.to_vec(),
+        };
+        self.edge_watchers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((filter, tx));
+        rx
+    }
+
+    fn subscribe(&self, filter: EntityFilter) -> Receiver<EntityChange> {
+        let (tx, rx) = channel();
+        self.entity_watchers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((filter, tx));
+        rx
+    }
+
+    /// Deliver committed entity changes, dropping subscriptions whose
+    /// receiver is gone
+    pub fn publish(&self, changes: &[EntityChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut watchers = self
+            .entity_watchers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        watchers.retain(|(filter, tx)| {
+            changes
+                .iter()
+                .filter(|c| filter.matches(c))
+                .all(|c| tx.send(c.clone()).is_ok())
+        });
+    }
+
+    /// Deliver committed edge changes, dropping subscriptions whose receiver
+    /// is gone
+    pub fn publish_edges(&self, changes: &[EdgeChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut watchers =
+            self.edge_watchers.lock().unwrap_or_else(|e| e.into_inner());
+        watchers.retain(|(filter, tx)| {
+            changes
+                .iter()
+                .filter(|c| filter.matches(c))
+                .all(|c| tx.send(c.clone()).is_ok())
+        });
+    }
+}
+
+/// A change recorded by a [`ChangeLog`]
+#[derive(Debug)]
+enum Logged {
+    Entity(EntityChange),
+    Edge(EdgeChange),
+}
+
+/// Changes made by an uncommitted transaction, in the order they were made
+#[derive(Debug, Default)]
+pub struct ChangeLog {
+    changes: RefCell<Vec<Logged>>,
+}
+
+impl ChangeLog {
+    pub fn record(&self, change: EntityChange) {
+        self.changes.borrow_mut().push(Logged::Entity(change));
+    }
+
+    pub fn record_edge(&self, change: EdgeChange) {
+        self.changes.borrow_mut().push(Logged::Edge(change));
+    }
+
+    /// The recorded changes in the form kept by change capture
+    pub fn captured(&self) -> Vec<CapturedChange> {
+        self.changes
+            .borrow()
+            .iter()
+            .map(|change| match change {
+                Logged::Entity(change) => change.into(),
+                Logged::Edge(change) => change.into(),
+            })
+            .collect()
+    }
+
+    /// Publish the recorded changes; call after the transaction committed
+    pub fn publish_to(self, hub: &WatchHub) {
+        let mut entities = Vec::new();
+        let mut edges = Vec::new();
+        for change in self.changes.into_inner() {
+            match change {
+                Logged::Entity(change) => entities.push(change),
+                Logged::Edge(change) => edges.push(change),
+            }
+        }
+        hub.publish(&entities);
+        hub.publish_edges(&edges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntMutationError;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Note {
+        id: Id,
+    }
+
+    #[typetag::serde]
+    impl Ent for Note {
+        fn id(&self) -> Id {
+            self.id
+        }
+        fn set_id(&mut self, id: Id) {
+            self.id = id;
+        }
+        fn last_updated(&self) -> u64 {
+            0
+        }
+        fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_publish_filters() {
+        let hub = WatchHub::new();
+        let by_id = hub.watch(2);
+        let by_type = hub.watch_type::<Note>();
+        let dropped = hub.watch(1);
+        drop(dropped);
+
+        let log = ChangeLog::default();
+        log.record(EntityChange::new::<Note>(1, ChangeKind::Created));
+        log.record(EntityChange::new::<Note>(2, ChangeKind::Updated));
+        log.publish_to(&hub);
+
+        let changes: Vec<_> = by_id.try_iter().collect();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Updated);
+        assert!(changes[0].is::<Note>());
+        assert_eq!(by_type.try_iter().count(), 2);
+        assert_eq!(hub.entity_watchers.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_publish_edges() {
+        let hub = WatchHub::new();
+        let follows = hub.watch_edges(1, b"follows");
+
+        let log = ChangeLog::default();
+        let edge = EdgeValue::new(1, b"follows".to_vec(), 2);
+        log.record_edge(EdgeChange::Added(edge.clone()));
+        log.record_edge(EdgeChange::Added(EdgeValue::new(
+            1,
+            b"likes".to_vec(),
+            2,
+        )));
+        log.record_edge(EdgeChange::Removed(edge.clone()));
+        log.publish_to(&hub);
+
+        let changes: Vec<_> = follows.try_iter().collect();
+        assert_eq!(
+            changes,
+            vec![EdgeChange::Added(edge.clone()), EdgeChange::Removed(edge)]
+        );
+    }
+
+    #[test]
+    fn test_captured_keeps_order() {
+        let log = ChangeLog::default();
+        log.record(EntityChange::new::<Note>(1, ChangeKind::Created));
+        log.record_edge(EdgeChange::Added(EdgeValue::new(
+            1,
+            b"likes".to_vec(),
+            2,
+        )));
+        log.record(EntityChange::new::<Note>(2, ChangeKind::Deleted));
+
+        let captured = log.captured();
+        assert_eq!(captured.len(), 3);
+        assert!(
+            matches!(&captured[0], CapturedChange::Created(e) if e.id == 1)
+        );
+        assert!(
+            matches!(&captured[1], CapturedChange::EdgeAdded(e) if e.dest == 2)
+        );
+        assert!(
+            matches!(&captured[2], CapturedChange::Deleted(e) if e.id == 2)
+        );
+    }
+}
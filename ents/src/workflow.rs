@@ -0,0 +1,209 @@
+//! Durable step log for operations spanning several transactions.
+//!
+//! A workflow is identified by a caller-chosen key and stored as a
+//! [`WorkflowRecord`] entity, indexed by a `workflow:<key>` edge from the
+//! system id 0. Each step's writes and its [`checkpoint`] go into the same
+//! transaction, so after a crash [`begin_workflow`] returns the record with
+//! exactly the committed steps, and the caller skips them when resuming.
+//!
+//! ```ignore
+//! let txn = env.write_txn()?;
+//! let mut wf = workflow::begin_workflow(&txn, "migrate-v2")?;
+//! txn.commit()?;
+//!
+//! for batch in batches {
+//!     let step = format!("batch-{}", batch.index);
+//!     if wf.is_done(&step) {
+//!         continue;
+//!     }
+//!     let txn = env.write_txn()?;
+//!     migrate(&txn, batch)?;
+//!     workflow::checkpoint(&txn, &mut wf, &step)?;
+//!     txn.commit()?;
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DatabaseError, DraftError, EdgeDraft, EdgeProvider, EdgeQuery, EdgeValue,
+    Ent, EntExt, EntMutationError, EntWithEdges, Id, Transactional,
+};
+
+/// Prefix of the index edges from the system id 0 to workflow records
+pub const WORKFLOW_EDGE_PREFIX: &[u8] = b"workflow:";
+
+/// Persisted state of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowRecord {
+    pub key: String,
+    /// Steps committed so far, in order
+    pub completed_steps: Vec<String>,
+    pub completed: bool,
+    pub id: Id,
+    pub last_updated: u64,
+}
+
+impl WorkflowRecord {
+    /// Whether `step` has already been checkpointed
+    pub fn is_done(&self, step: &str) -> bool {
+        self.completed_steps.iter().any(|s| s == step)
+    }
+
+    /// The most recently checkpointed step
+    pub fn last_step(&self) -> Option<&str> {
+        self.completed_steps.last().map(String::as_str)
+    }
+}
+
+#[typetag::serde(name = "ents::Workflow")]
+impl Ent for WorkflowRecord {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn last_updated(&self) -> u64 {
+        self.last_updated
+    }
+
+    fn mark_updated(&mut self) -> Result<(), EntMutationError> {
+        self.last_updated = crate::clock::now_micros();
+        Ok(())
+    }
+}
+
+impl EntWithEdges for WorkflowRecord {
+    type EdgeProvider = WorkflowEdgeProvider;
+}
+
+crate::register_ent!(WorkflowRecord, name = "ents::Workflow");
+
+/// Maintains the `workflow:<key>` index edge of a workflow record
+pub struct WorkflowEdgeProvider;
+
+/// Draft of the index edge of a workflow record
+#[derive(PartialEq)]
+pub struct WorkflowEdgeDraft {
+    id: Id,
+    key: String,
+}
+
+impl EdgeDraft for WorkflowEdgeDraft {
+    fn check<T: Transactional>(
+        self,
+        _txn: &T,
+    ) -> Result<Vec<EdgeValue>, DraftError> {
+        Ok(vec![EdgeValue::new(0, workflow_edge(&self.key), self.id)])
+    }
+}
+
+impl EdgeProvider<WorkflowRecord> for WorkflowEdgeProvider {
+    type Draft = WorkflowEdgeDraft;
+
+    fn draft(ent: &WorkflowRecord) -> Self::Draft {
+        WorkflowEdgeDraft {
+            id: ent.id,
+            key: ent.key.clone(),
+        }
+    }
+}
+
+fn workflow_edge(key: &str) -> Vec<u8> {
+    let mut name = WORKFLOW_EDGE_PREFIX.to_vec();
+    name.extend_from_slice(key.as_bytes());
+    name
+}
+
+/// Look up the workflow with the given key
+pub fn find_workflow<T: Transactional>(
+    txn: &T,
+    key: &str,
+) -> Result<Option<WorkflowRecord>, DatabaseError> {
+    let name = workflow_edge(key);
+    let edges = txn.find_edges(0, EdgeQuery::asc(&[&name]))?;
+    for edge in edges {
+        if let Some(record) = txn
+            .get(edge.dest)?
+            .and_then(|e| e.into_ent::<WorkflowRecord>())
+        {
+            return Ok(Some(record));
+        }
+    }
+    Ok(None)
+}
+
+/// Start the workflow with the given key, or return its persisted state if
+/// it was started before
+pub fn begin_workflow<T: Transactional>(
+    txn: &T,
+    key: &str,
+) -> Result<WorkflowRecord, DatabaseError> {
+    if let Some(record) = find_workflow(txn, key)? {
+        return Ok(record);
+    }
+
+    let mut record = WorkflowRecord {
+        key: key.to_string(),
+        completed_steps: Vec::new(),
+        completed: false,
+        id: 0,
+        last_updated: 0,
+    };
+    record.id = txn.create(record.clone())?;
+    Ok(txn
+        .get(record.id)?
+        .and_then(|e| e.into_ent())
+        .unwrap_or(record))
+}
+
+/// Record `step` as done. Call it in the transaction that performs the step.
+///
+/// Checkpointing a step that is already recorded is a no-op, which keeps
+/// replays idempotent.
+pub fn checkpoint<T: Transactional>(
+    txn: &T,
+    workflow: &mut WorkflowRecord,
+    step: &str,
+) -> Result<(), DatabaseError> {
+    if workflow.is_done(step) {
+        return Ok(());
+    }
+    save(txn, workflow, |record| {
+        record.completed_steps.push(step.to_string())
+    })
+}
+
+/// Mark the workflow as finished
+pub fn complete<T: Transactional>(
+    txn: &T,
+    workflow: &mut WorkflowRecord,
+) -> Result<(), DatabaseError> {
+    save(txn, workflow, |record| record.completed = true)
+}
+
+fn save<T, F>(
+    txn: &T,
+    workflow: &mut WorkflowRecord,
+    mutate: F,
+) -> Result<(), DatabaseError>
+where
+    T: Transactional,
+    F: FnOnce(&mut WorkflowRecord),
+{
+    let mut updated = workflow.clone();
+    if !txn.update(&mut updated, mutate)? {
+        return Err(DatabaseError::Other {
+            source: format!(
+                "workflow '{}' was modified concurrently",
+                workflow.key
+            )
+            .into(),
+        });
+    }
+    *workflow = updated;
+    Ok(())
+}